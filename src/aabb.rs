@@ -1,33 +1,55 @@
 use std::ops::Add;
 
+use crate::float::{vec3, Float, Vec3};
 use crate::ray::Ray;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Aabb {
-    pub min: glam::DVec3,
-    pub max: glam::DVec3,
+    pub min: Vec3,
+    pub max: Vec3,
 }
 
 impl Aabb {
-    pub fn new(min: glam::DVec3, max: glam::DVec3) -> Self {
+    /// A box containing nothing, which is the identity for `+`.
+    pub const EMPTY: Self = Self {
+        min: Vec3::splat(Float::INFINITY),
+        max: Vec3::splat(Float::NEG_INFINITY),
+    };
+
+    pub fn new(min: Vec3, max: Vec3) -> Self {
         Self { min, max }
     }
 
-    pub fn hit(&self, r: Ray, mut t_min: f64, mut t_max: f64) -> bool {
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn surface_area(&self) -> Float {
+        let d = self.max - self.min;
+        if d.min_element() < 0.0 {
+            return 0.0;
+        }
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Slab test that counts touching the boundary as a hit, so boxes may have zero thickness.
+    pub fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.clip(r, t_min, t_max).is_some()
+    }
+
+    /// The part of `[t_min, t_max]` where `r` is inside the box, if any.
+    pub fn clip(&self, r: Ray, mut t_min: Float, mut t_max: Float) -> Option<(Float, Float)> {
+        let bounds = [self.min, self.max];
         for a in 0..3 {
-            let inv_dir = 1.0 / r.direction[a];
-            let mut t0 = (self.min[a] - r.origin[a]) * inv_dir;
-            let mut t1 = (self.max[a] - r.origin[a]) * inv_dir;
-            if inv_dir < 0.0 {
-                (t0, t1) = (t1, t0);
-            }
-            t_min = if t0 > t_min { t0 } else { t_min };
-            t_max = if t1 < t_max { t1 } else { t_max };
-            if t_max <= t_min {
-                return false;
-            }
+            let t_near = (bounds[r.sign[a]][a] - r.origin[a]) * r.inv_direction[a];
+            let t_far = (bounds[1 - r.sign[a]][a] - r.origin[a]) * r.inv_direction[a];
+            // A ray parallel to a slab that starts on one of its planes gives 0 * inf = NaN.
+            // Float::max and Float::min ignore NaN arguments, which treats that axis as no
+            // constraint instead of a miss.
+            t_min = t_min.max(t_near);
+            t_max = t_max.min(t_far);
         }
-        true
+        (t_min <= t_max).then_some((t_min, t_max))
     }
 }
 
@@ -35,18 +57,100 @@ impl Add for Aabb {
     type Output = Aabb;
 
     fn add(self, rhs: Self) -> Self::Output {
-        let small = glam::dvec3(
-            f64::min(self.min.x, rhs.min.x),
-            f64::min(self.min.y, rhs.min.y),
-            f64::min(self.min.z, rhs.min.z),
+        let small = vec3(
+            Float::min(self.min.x, rhs.min.x),
+            Float::min(self.min.y, rhs.min.y),
+            Float::min(self.min.z, rhs.min.z),
         );
 
-        let big = glam::dvec3(
-            f64::max(self.max.x, rhs.max.x),
-            f64::max(self.max.y, rhs.max.y),
-            f64::max(self.max.z, rhs.max.z),
+        let big = vec3(
+            Float::max(self.max.x, rhs.max.x),
+            Float::max(self.max.y, rhs.max.y),
+            Float::max(self.max.z, rhs.max.z),
         );
 
         Aabb::new(small, big)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INF: Float = Float::INFINITY;
+
+    fn unit() -> Aabb {
+        Aabb::new(Vec3::ZERO, Vec3::ONE)
+    }
+
+    fn clip(aabb: Aabb, origin: Vec3, direction: Vec3) -> Option<(Float, Float)> {
+        aabb.clip(Ray::new(origin, direction, 0.0), 0.0, INF)
+    }
+
+    #[test]
+    fn ray_in_a_face_plane_touches_the_box() {
+        let x = vec3(1.0, 0.0, 0.0);
+        assert_eq!(clip(unit(), vec3(-1.0, 0.5, 0.0), x), Some((1.0, 2.0)));
+        assert_eq!(clip(unit(), vec3(-1.0, 0.5, 1.0), x), Some((1.0, 2.0)));
+        // Along an edge, in two face planes at once.
+        assert_eq!(clip(unit(), vec3(-1.0, 1.0, 1.0), x), Some((1.0, 2.0)));
+        assert_eq!(clip(unit(), vec3(-1.0, 0.5, 1.001), x), None);
+        assert_eq!(clip(unit(), vec3(-1.0, 0.5, -0.001), x), None);
+    }
+
+    #[test]
+    fn zero_thickness_box_is_hit() {
+        let flat = Aabb::new(Vec3::ZERO, vec3(1.0, 0.0, 1.0));
+        assert_eq!(
+            clip(flat, vec3(0.5, 1.0, 0.5), vec3(0.0, -1.0, 0.0)),
+            Some((1.0, 1.0))
+        );
+        assert_eq!(
+            clip(flat, vec3(0.5, -2.0, 0.5), vec3(0.0, 1.0, 0.0)),
+            Some((2.0, 2.0))
+        );
+        assert_eq!(
+            clip(flat, vec3(-1.0, 0.0, 0.5), vec3(1.0, 0.0, 0.0)),
+            Some((1.0, 2.0))
+        );
+        assert_eq!(clip(flat, vec3(2.0, 1.0, 0.5), vec3(0.0, -1.0, 0.0)), None);
+
+        let point = Aabb::new(Vec3::splat(0.5), Vec3::splat(0.5));
+        assert_eq!(
+            clip(point, vec3(0.5, 0.5, -1.0), vec3(0.0, 0.0, 1.0)),
+            Some((1.5, 1.5))
+        );
+    }
+
+    #[test]
+    fn negative_zero_direction_clips_like_positive_zero() {
+        for origin in [
+            vec3(-1.0, 0.5, 0.5),
+            vec3(-1.0, 0.0, 0.5),
+            vec3(-1.0, 1.0, 0.5),
+            vec3(-1.0, 1.5, 0.5),
+        ] {
+            let positive = clip(unit(), origin, vec3(1.0, 0.0, 0.0));
+            let negative = clip(unit(), origin, vec3(1.0, -0.0, 0.0));
+            assert_eq!(positive, negative, "from {origin}");
+            assert_eq!(positive.is_some(), origin.y <= 1.0);
+        }
+        assert_eq!(
+            clip(unit(), vec3(0.5, 0.5, 2.0), vec3(-0.0, -0.0, -1.0)),
+            Some((1.0, 2.0))
+        );
+    }
+
+    #[test]
+    fn origin_on_a_slab_boundary() {
+        let x = vec3(1.0, 0.0, 0.0);
+        assert_eq!(clip(unit(), vec3(0.0, 0.5, 0.5), x), Some((0.0, 1.0)));
+        assert_eq!(clip(unit(), vec3(1.0, 0.5, 0.5), x), Some((0.0, 0.0)));
+        assert_eq!(clip(unit(), vec3(1.0, 0.5, 0.5), -x), Some((0.0, 1.0)));
+        assert_eq!(clip(unit(), vec3(0.0, 0.5, 0.5), -x), Some((0.0, 0.0)));
+        assert_eq!(clip(unit(), Vec3::ZERO, Vec3::ONE), Some((0.0, 1.0)));
+        assert_eq!(clip(unit(), Vec3::ONE, Vec3::ONE), Some((0.0, 0.0)));
+        // Leaving through a corner.
+        assert_eq!(clip(unit(), Vec3::ZERO, -Vec3::ONE), Some((0.0, 0.0)));
+    }
+}
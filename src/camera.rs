@@ -1,39 +1,43 @@
 use rand::{thread_rng, Rng};
 
-use crate::{math, ray::Ray};
+use crate::{
+    float::{Float, Vec3},
+    math,
+    ray::Ray,
+};
 
 #[derive(Debug, Default)]
 pub struct Camera {
-    origin: glam::DVec3,
-    horizontal: glam::DVec3,
-    vertical: glam::DVec3,
-    lower_left_corner: glam::DVec3,
+    origin: Vec3,
+    horizontal: Vec3,
+    vertical: Vec3,
+    lower_left_corner: Vec3,
     #[allow(dead_code)]
-    w: glam::DVec3,
-    v: glam::DVec3,
-    u: glam::DVec3,
-    len_radius: f64,
-    time0: f64,
-    time1: f64,
+    w: Vec3,
+    v: Vec3,
+    u: Vec3,
+    len_radius: Float,
+    time0: Float,
+    time1: Float,
 }
 
 impl Camera {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        lookfrom: glam::DVec3,
-        lookat: glam::DVec3,
-        vup: glam::DVec3,
-        vfov: f64,
-        aspect_ratio: f64,
-        aperture: f64,
-        focus_dist: f64,
-        time0: f64,
-        time1: f64,
+        lookfrom: Vec3,
+        lookat: Vec3,
+        vup: Vec3,
+        vfov: Float,
+        aspect_ratio: Float,
+        aperture: Float,
+        focus_dist: Float,
+        time0: Float,
+        time1: Float,
     ) -> Camera {
         let theta = vfov.to_radians();
         let h = (theta / 2.0).tan();
-        let viewport_height: f64 = 2.0 * h;
-        let viewport_width: f64 = aspect_ratio * viewport_height;
+        let viewport_height: Float = 2.0 * h;
+        let viewport_width: Float = aspect_ratio * viewport_height;
 
         let w = (lookfrom - lookat).normalize();
         let u = vup.cross(w).normalize();
@@ -58,7 +62,12 @@ impl Camera {
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+    pub fn set_shutter(&mut self, time0: Float, time1: Float) {
+        self.time0 = time0;
+        self.time1 = time1;
+    }
+
+    pub fn get_ray(&self, s: Float, t: Float) -> Ray {
         let rd = math::random_point_in_unit_disk() * self.len_radius;
         let offset = self.u * rd.x + self.v * rd.y;
         Ray::new(
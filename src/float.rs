@@ -0,0 +1,84 @@
+//! The scalar and vector types all geometry and color is computed with: `f64` by default, `f32`
+//! with the `f32` feature, which halves the memory taken by meshes and BVH nodes.
+
+#[cfg(not(feature = "f32"))]
+mod types {
+    pub use glam::{
+        dvec2 as vec2, dvec3 as vec3, DAffine3 as Affine3, DMat3 as Mat3, DMat4 as Mat4,
+        DVec2 as Vec2, DVec3 as Vec3,
+    };
+    pub use std::f64::consts;
+
+    pub type Float = f64;
+}
+
+#[cfg(feature = "f32")]
+mod types {
+    pub use glam::{vec2, vec3, Affine3A as Affine3, Mat3, Mat4, Vec2, Vec3};
+    pub use std::f32::consts;
+
+    pub type Float = f32;
+}
+
+pub use types::*;
+
+/// The linear part and the translation of an affine map. In single precision glam stores them
+/// as its SIMD-aligned types, which these convert back.
+#[allow(clippy::useless_conversion)]
+pub fn affine_parts(affine: Affine3) -> (Mat3, Vec3) {
+    (affine.matrix3.into(), affine.translation.into())
+}
+
+/// The scale factor of `affine` if it is a similarity, a rotation combined with a uniform scale
+/// (and maybe a reflection), which keeps round shapes round.
+pub fn similarity_scale(affine: Affine3) -> Option<Float> {
+    let m = affine_parts(affine).0;
+    let scale = m.x_axis.length();
+    let gram = m.transpose() * m;
+    gram.abs_diff_eq(
+        Mat3::from_diagonal(Vec3::splat(scale * scale)),
+        Float::EPSILON.sqrt() * scale * scale,
+    )
+    .then_some(scale)
+}
+
+/// Bounds the relative error of `n` consecutive roundings, as in pbrt: a result computed with `n`
+/// floating-point operations lies within a factor of `1 ± gamma(n)` of the exact one.
+pub const fn gamma(n: u32) -> Float {
+    let n_epsilon = n as Float * (Float::EPSILON / 2.0);
+    n_epsilon / (1.0 - n_epsilon)
+}
+
+/// Bounds the error of `matrix * point + translation`, given the error already in `point`.
+pub fn transformed_error(matrix: Mat3, translation: Vec3, point: Vec3, error: Vec3) -> Vec3 {
+    let abs = Mat3::from_cols(
+        matrix.x_axis.abs(),
+        matrix.y_axis.abs(),
+        matrix.z_axis.abs(),
+    );
+    gamma(3) * (abs * point.abs() + translation.abs()) + (1.0 + gamma(3)) * (abs * error)
+}
+
+/// Moves `point`, which lies within `error` of a surface with the given normal, off it to the
+/// side `direction` leaves on. The point is pushed out of its error box along the normal and then
+/// one more ulp, to make up for the rounding of the move itself, so a ray starting there can't
+/// hit the surface again.
+pub fn offset_ray_origin(point: Vec3, error: Vec3, normal: Vec3, direction: Vec3) -> Vec3 {
+    let normal = if direction.dot(normal) < 0.0 {
+        -normal
+    } else {
+        normal
+    };
+    // A point exactly on a plane through the origin has no error, and one ulp away from zero is
+    // so small that the ray parameter back to the plane underflows. Step off by a normal number.
+    let distance = normal.abs().dot(error).max(Float::MIN_POSITIVE);
+    let mut origin = point + distance * normal;
+    for a in 0..3 {
+        if normal[a] > 0.0 {
+            origin[a] = origin[a].next_up();
+        } else if normal[a] < 0.0 {
+            origin[a] = origin[a].next_down();
+        }
+    }
+    origin
+}
@@ -2,22 +2,33 @@ use std::rc::Rc;
 
 use crate::{
     aabb::Aabb,
+    float::{gamma, vec3, Float, Vec3},
     hittable::{HitRecord, Hittable},
     material::Material,
     ray::Ray,
 };
 
+/// The point at `t` along `r` on the plane where coordinate `axis` is `k`, and its error bound.
+/// That coordinate is exact, the others carry the rounding of `origin + t * direction`.
+fn plane_point(r: Ray, t: Float, axis: usize, k: Float) -> (Vec3, Vec3) {
+    let mut point = r.at(t);
+    let mut error = gamma(3) * (r.origin.abs() + (t * r.direction).abs());
+    point[axis] = k;
+    error[axis] = 0.0;
+    (point, error)
+}
+
 pub struct XYRect {
-    pub x0: f64,
-    pub x1: f64,
-    pub y0: f64,
-    pub y1: f64,
-    pub k: f64,
+    pub x0: Float,
+    pub x1: Float,
+    pub y0: Float,
+    pub y1: Float,
+    pub k: Float,
     pub mp: Rc<dyn Material>,
 }
 
 impl XYRect {
-    pub fn new(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, mp: Rc<dyn Material>) -> Self {
+    pub fn new(x0: Float, x1: Float, y0: Float, y1: Float, k: Float, mp: Rc<dyn Material>) -> Self {
         Self {
             x0,
             x1,
@@ -27,10 +38,9 @@ impl XYRect {
             mp,
         }
     }
-}
 
-impl Hittable for XYRect {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    /// Returns the ray parameter and the in-plane coordinates of the hit point.
+    fn intersect(&self, r: Ray, t_min: Float, t_max: Float) -> Option<(Float, Float, Float)> {
         let t = (self.k - r.origin.z) / r.direction.z;
         if t < t_min || t > t_max {
             return None;
@@ -41,13 +51,22 @@ impl Hittable for XYRect {
         if x < self.x0 || x > self.x1 || y < self.y0 || y > self.y1 {
             return None;
         }
+        Some((t, x, y))
+    }
+}
+
+impl Hittable for XYRect {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (t, x, y) = self.intersect(r, t_min, t_max)?;
 
         let u = (x - self.x0) / (self.x1 - self.x0);
         let v = (y - self.y0) / (self.y1 - self.y0);
 
-        let outward_normal = glam::DVec3::Z;
+        let (point, point_error) = plane_point(r, t, 2, self.k);
+        let outward_normal = Vec3::Z;
         let rec = HitRecord {
-            point: r.at(t),
+            point,
+            point_error,
             mat: Some(self.mp.clone()),
             t,
             u,
@@ -59,26 +78,30 @@ impl Hittable for XYRect {
         Some(rec)
     }
 
-    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
         let output_box = Aabb::new(
-            glam::dvec3(self.x0, self.y0, self.k - 0.0001),
-            glam::dvec3(self.x1, self.y1, self.k + 0.0001),
+            vec3(self.x0, self.y0, self.k),
+            vec3(self.x1, self.y1, self.k),
         );
         Some(output_box)
     }
 }
 
 pub struct XZRect {
-    pub x0: f64,
-    pub x1: f64,
-    pub z0: f64,
-    pub z1: f64,
-    pub k: f64,
+    pub x0: Float,
+    pub x1: Float,
+    pub z0: Float,
+    pub z1: Float,
+    pub k: Float,
     pub mp: Rc<dyn Material>,
 }
 
 impl XZRect {
-    pub fn new(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, mp: Rc<dyn Material>) -> Self {
+    pub fn new(x0: Float, x1: Float, z0: Float, z1: Float, k: Float, mp: Rc<dyn Material>) -> Self {
         Self {
             x0,
             x1,
@@ -88,10 +111,9 @@ impl XZRect {
             mp,
         }
     }
-}
 
-impl Hittable for XZRect {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    /// Returns the ray parameter and the in-plane coordinates of the hit point.
+    fn intersect(&self, r: Ray, t_min: Float, t_max: Float) -> Option<(Float, Float, Float)> {
         let t = (self.k - r.origin.y) / r.direction.y;
         if t < t_min || t > t_max {
             return None;
@@ -102,13 +124,22 @@ impl Hittable for XZRect {
         if x < self.x0 || x > self.x1 || z < self.z0 || z > self.z1 {
             return None;
         }
+        Some((t, x, z))
+    }
+}
+
+impl Hittable for XZRect {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (t, x, z) = self.intersect(r, t_min, t_max)?;
 
         let u = (x - self.x0) / (self.x1 - self.x0);
         let v = (z - self.z0) / (self.z1 - self.z0);
 
-        let outward_normal = glam::DVec3::Y;
+        let (point, point_error) = plane_point(r, t, 1, self.k);
+        let outward_normal = Vec3::Y;
         let rec = HitRecord {
-            point: r.at(t),
+            point,
+            point_error,
             mat: Some(self.mp.clone()),
             t,
             u,
@@ -120,26 +151,30 @@ impl Hittable for XZRect {
         Some(rec)
     }
 
-    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
         let output_box = Aabb::new(
-            glam::dvec3(self.x0, self.k - 0.0001, self.z0),
-            glam::dvec3(self.x1, self.k + 0.0001, self.z1),
+            vec3(self.x0, self.k, self.z0),
+            vec3(self.x1, self.k, self.z1),
         );
         Some(output_box)
     }
 }
 
 pub struct YZRect {
-    pub y0: f64,
-    pub y1: f64,
-    pub z0: f64,
-    pub z1: f64,
-    pub k: f64,
+    pub y0: Float,
+    pub y1: Float,
+    pub z0: Float,
+    pub z1: Float,
+    pub k: Float,
     pub mp: Rc<dyn Material>,
 }
 
 impl YZRect {
-    pub fn new(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, mp: Rc<dyn Material>) -> Self {
+    pub fn new(y0: Float, y1: Float, z0: Float, z1: Float, k: Float, mp: Rc<dyn Material>) -> Self {
         Self {
             y0,
             y1,
@@ -149,10 +184,9 @@ impl YZRect {
             mp,
         }
     }
-}
 
-impl Hittable for YZRect {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    /// Returns the ray parameter and the in-plane coordinates of the hit point.
+    fn intersect(&self, r: Ray, t_min: Float, t_max: Float) -> Option<(Float, Float, Float)> {
         let t = (self.k - r.origin.x) / r.direction.x;
         if t < t_min || t > t_max {
             return None;
@@ -163,13 +197,22 @@ impl Hittable for YZRect {
         if y < self.y0 || y > self.y1 || z < self.z0 || z > self.z1 {
             return None;
         }
+        Some((t, y, z))
+    }
+}
+
+impl Hittable for YZRect {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (t, y, z) = self.intersect(r, t_min, t_max)?;
 
         let u = (y - self.y0) / (self.y1 - self.y0);
         let v = (z - self.z0) / (self.z1 - self.z0);
 
-        let outward_normal = glam::DVec3::X;
+        let (point, point_error) = plane_point(r, t, 0, self.k);
+        let outward_normal = Vec3::X;
         let rec = HitRecord {
-            point: r.at(t),
+            point,
+            point_error,
             mat: Some(self.mp.clone()),
             t,
             u,
@@ -181,10 +224,14 @@ impl Hittable for YZRect {
         Some(rec)
     }
 
-    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
         let output_box = Aabb::new(
-            glam::dvec3(self.k - 0.0001, self.y0, self.z0),
-            glam::dvec3(self.k + 0.0001, self.y1, self.z1),
+            vec3(self.k, self.y0, self.z0),
+            vec3(self.k, self.y1, self.z1),
         );
         Some(output_box)
     }
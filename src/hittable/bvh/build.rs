@@ -0,0 +1,358 @@
+//! Binned surface area heuristic construction, shared by every BVH layout.
+
+use std::fmt;
+
+use rayon::prelude::*;
+
+use crate::{
+    aabb::Aabb,
+    float::{Float, Vec3},
+};
+
+/// Cost of visiting a node, relative to intersecting one primitive.
+pub const TRAVERSAL_COST: Float = 0.125;
+pub const INTERSECTION_COST: Float = 1.0;
+
+/// Nodes deeper than this are turned into leaves, so traversal can use a fixed-size stack.
+pub const MAX_TREE_DEPTH: usize = 64;
+
+/// Nodes with fewer primitives than this are built on the current thread, since splitting the
+/// work further costs more than it saves.
+const PARALLEL_THRESHOLD: usize = 4096;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BvhBuilder {
+    bins: usize,
+    max_leaf_size: usize,
+    parallel: bool,
+}
+
+impl Default for BvhBuilder {
+    fn default() -> Self {
+        Self {
+            bins: 16,
+            max_leaf_size: 4,
+            parallel: true,
+        }
+    }
+}
+
+/// The bounds of one primitive, as seen by the builder.
+#[derive(Debug, Clone, Copy)]
+struct PrimitiveInfo {
+    index: usize,
+    aabb: Aabb,
+    centroid: Vec3,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bin {
+    count: usize,
+    aabb: Aabb,
+}
+
+impl Default for Bin {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            aabb: Aabb::EMPTY,
+        }
+    }
+}
+
+/// A node of the tree produced by [`BvhBuilder::build`]. Leaves refer to a range of
+/// [`BuildTree::order`].
+#[derive(Debug, PartialEq)]
+pub enum BuildNode {
+    Leaf {
+        aabb: Aabb,
+        first: usize,
+        count: usize,
+    },
+    Interior {
+        aabb: Aabb,
+        axis: usize,
+        children: Box<[BuildNode; 2]>,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BuildTree {
+    pub root: BuildNode,
+    /// Primitive indices in the order the leaves refer to them.
+    pub order: Vec<usize>,
+}
+
+impl BvhBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of buckets the centroids are sorted into along each axis.
+    pub fn with_bins(mut self, bins: usize) -> Self {
+        self.bins = bins.max(2);
+        self
+    }
+
+    /// Largest number of primitives a leaf may hold.
+    pub fn with_max_leaf_size(mut self, max_leaf_size: usize) -> Self {
+        self.max_leaf_size = max_leaf_size.max(1);
+        self
+    }
+
+    /// Whether to build independent subtrees and bin large nodes on the rayon thread pool. The
+    /// result is the same either way.
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Builds a tree over primitives with the given bounds. The result only depends on the
+    /// bounds and their order, so the same scene always produces the same tree.
+    pub fn build(&self, bounds: &[Aabb]) -> BuildTree {
+        assert!(!bounds.is_empty(), "Cannot build a BVH over no primitives.");
+        let mut prims: Vec<_> = bounds
+            .iter()
+            .enumerate()
+            .map(|(index, &aabb)| PrimitiveInfo {
+                index,
+                aabb,
+                centroid: aabb.centroid(),
+            })
+            .collect();
+        let root = self.build_recursive(&mut prims, 0, 1);
+        BuildTree {
+            root,
+            order: prims.iter().map(|p| p.index).collect(),
+        }
+    }
+
+    fn build_recursive(
+        &self,
+        prims: &mut [PrimitiveInfo],
+        offset: usize,
+        depth: usize,
+    ) -> BuildNode {
+        let (aabb, centroid_bounds) = self.bounds(prims);
+        let count = prims.len();
+        let leaf = BuildNode::Leaf {
+            aabb,
+            first: offset,
+            count,
+        };
+        if count == 1 || depth >= MAX_TREE_DEPTH {
+            return leaf;
+        }
+
+        let (axis, mid) = match self.best_split(prims, aabb, centroid_bounds) {
+            Some((cost, axis, split)) => {
+                let leaf_cost = INTERSECTION_COST * count as Float;
+                if count <= self.max_leaf_size && leaf_cost <= cost {
+                    return leaf;
+                }
+                let mid = partition(prims, |p| {
+                    self.bin_index(p.centroid[axis], &centroid_bounds, axis) <= split
+                });
+                // Rounding can leave one side empty; fall back to an even split.
+                if mid == 0 || mid == count {
+                    (axis, count / 2)
+                } else {
+                    (axis, mid)
+                }
+            }
+            // All centroids coincide, so there is nothing to separate them by.
+            None if count <= self.max_leaf_size => return leaf,
+            None => (0, count / 2),
+        };
+
+        let (left, right) = prims.split_at_mut(mid);
+        let mut build_left = || self.build_recursive(left, offset, depth + 1);
+        let mut build_right = || self.build_recursive(right, offset + mid, depth + 1);
+        let children = if self.parallel && count >= PARALLEL_THRESHOLD {
+            rayon::join(build_left, build_right)
+        } else {
+            (build_left(), build_right())
+        };
+        BuildNode::Interior {
+            aabb,
+            axis,
+            children: Box::new([children.0, children.1]),
+        }
+    }
+
+    /// Returns the bounds of the primitives and of their centroids.
+    fn bounds(&self, prims: &[PrimitiveInfo]) -> (Aabb, Aabb) {
+        let fold = |prims: &[PrimitiveInfo]| {
+            prims.iter().fold((Aabb::EMPTY, Aabb::EMPTY), |(b, c), p| {
+                (b + p.aabb, c + Aabb::new(p.centroid, p.centroid))
+            })
+        };
+        if self.parallel && prims.len() >= PARALLEL_THRESHOLD {
+            prims
+                .par_chunks(PARALLEL_THRESHOLD)
+                .map(fold)
+                .reduce(|| (Aabb::EMPTY, Aabb::EMPTY), |a, b| (a.0 + b.0, a.1 + b.1))
+        } else {
+            fold(prims)
+        }
+    }
+
+    /// Counts and bounds the primitives falling into each bucket along `axis`.
+    fn bin(&self, prims: &[PrimitiveInfo], axis: usize, centroid_bounds: &Aabb) -> Vec<Bin> {
+        let fill = |prims: &[PrimitiveInfo]| {
+            let mut bins = vec![Bin::default(); self.bins];
+            for p in prims {
+                let bin = &mut bins[self.bin_index(p.centroid[axis], centroid_bounds, axis)];
+                bin.count += 1;
+                bin.aabb = bin.aabb + p.aabb;
+            }
+            bins
+        };
+        // Merging bins only takes sums and min/max, so the result doesn't depend on how the
+        // work was split up.
+        if self.parallel && prims.len() >= PARALLEL_THRESHOLD {
+            prims.par_chunks(PARALLEL_THRESHOLD).map(fill).reduce(
+                || vec![Bin::default(); self.bins],
+                |mut a, b| {
+                    for (a, b) in a.iter_mut().zip(b) {
+                        a.count += b.count;
+                        a.aabb = a.aabb + b.aabb;
+                    }
+                    a
+                },
+            )
+        } else {
+            fill(prims)
+        }
+    }
+
+    /// Finds the cheapest bucket boundary over all three axes. Returns the cost, the axis and
+    /// the last bucket of the left side.
+    fn best_split(
+        &self,
+        prims: &[PrimitiveInfo],
+        aabb: Aabb,
+        centroid_bounds: Aabb,
+    ) -> Option<(Float, usize, usize)> {
+        let area = aabb.surface_area();
+        let mut best: Option<(Float, usize, usize)> = None;
+
+        for axis in 0..3 {
+            if centroid_bounds.max[axis] <= centroid_bounds.min[axis] {
+                continue;
+            }
+
+            let bins = self.bin(prims, axis, &centroid_bounds);
+
+            // Sweep from the right to get the area and count of everything after each boundary.
+            let mut right = vec![(0usize, 0.0); self.bins - 1];
+            let (mut n, mut b) = (0, Aabb::EMPTY);
+            for i in (1..self.bins).rev() {
+                n += bins[i].count;
+                b = b + bins[i].aabb;
+                right[i - 1] = (n, b.surface_area());
+            }
+
+            let (mut n, mut b) = (0, Aabb::EMPTY);
+            for (i, &(right_count, right_area)) in right.iter().enumerate() {
+                n += bins[i].count;
+                b = b + bins[i].aabb;
+                if n == 0 || right_count == 0 {
+                    continue;
+                }
+                let cost = TRAVERSAL_COST
+                    + INTERSECTION_COST
+                        * (n as Float * b.surface_area() + right_count as Float * right_area)
+                        / area;
+                if best.is_none_or(|(best_cost, ..)| cost < best_cost) {
+                    best = Some((cost, axis, i));
+                }
+            }
+        }
+
+        best
+    }
+
+    fn bin_index(&self, c: Float, centroid_bounds: &Aabb, axis: usize) -> usize {
+        let min = centroid_bounds.min[axis];
+        let extent = centroid_bounds.max[axis] - min;
+        (((c - min) / extent * self.bins as Float) as usize).min(self.bins - 1)
+    }
+}
+
+/// Moves the elements matching `pred` to the front, returning how many there are.
+fn partition(prims: &mut [PrimitiveInfo], pred: impl Fn(&PrimitiveInfo) -> bool) -> usize {
+    let mut mid = 0;
+    for i in 0..prims.len() {
+        if pred(&prims[i]) {
+            prims.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+/// Shape and expected cost of a built hierarchy.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BvhStats {
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub depth: usize,
+    pub primitive_count: usize,
+    /// Expected cost of tracing a ray against the tree, in units of primitive intersections.
+    pub sah_cost: Float,
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes ({} leaves, {} primitives), depth {}, SAH cost {:.2}",
+            self.node_count, self.leaf_count, self.primitive_count, self.depth, self.sah_cost
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn random_boxes(count: usize) -> Vec<Aabb> {
+        let mut rng = StdRng::seed_from_u64(31);
+        (0..count)
+            .map(|_| {
+                let min = 100.0 * rng.gen::<Vec3>();
+                Aabb::new(min, min + rng.gen::<Vec3>())
+            })
+            .collect()
+    }
+
+    fn assert_parallel_matches_sequential(count: usize) {
+        let bounds = random_boxes(count);
+        // Several threads even on a single core machine, so that subtrees really are stolen.
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let parallel = pool.install(|| BvhBuilder::new().with_parallel(true).build(&bounds));
+        let sequential = BvhBuilder::new().with_parallel(false).build(&bounds);
+        assert_eq!(parallel, sequential);
+
+        let mut order = parallel.order.clone();
+        order.sort_unstable();
+        assert!(order.into_iter().eq(0..count));
+    }
+
+    /// Large enough that the root and its first few levels are split and binned on the pool.
+    #[test]
+    fn parallel_build_matches_sequential() {
+        assert_parallel_matches_sequential(4 * PARALLEL_THRESHOLD + 123);
+    }
+
+    #[test]
+    fn small_build_matches_sequential() {
+        assert_parallel_matches_sequential(PARALLEL_THRESHOLD / 8);
+        assert_parallel_matches_sequential(1);
+    }
+}
@@ -0,0 +1,792 @@
+//! A BVH flattened into an array of nodes in depth-first order.
+
+use std::{array, fmt, rc::Rc};
+
+use crate::{
+    aabb::Aabb,
+    float::{Float, Vec3},
+    hittable::{HitRecord, Hittable, HittableList},
+    ray::Ray,
+};
+
+use super::build::{
+    BuildNode, BuildTree, BvhBuilder, BvhStats, INTERSECTION_COST, MAX_TREE_DEPTH, TRAVERSAL_COST,
+};
+#[cfg(feature = "simd")]
+use super::quad::QuadBvh;
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct LinearNode {
+    pub(super) aabb: Aabb,
+    /// For leaves the first primitive, for interior nodes the second child. The first child of
+    /// an interior node always directly follows it.
+    pub(super) offset: u32,
+    /// Number of primitives in a leaf, 0 for interior nodes.
+    pub(super) count: u32,
+    /// Split axis of an interior node.
+    axis: u8,
+}
+
+/// The node array of a flattened BVH. It only knows primitives by their position in the
+/// [`BuildTree::order`] it was built from, so the same traversal serves any kind of primitive.
+pub struct LinearBvh {
+    pub(super) nodes: Vec<LinearNode>,
+    /// SAH cost right after the tree was built, to measure refits against.
+    build_cost: Float,
+    /// The same tree collapsed to four children per node, which is what gets traversed.
+    #[cfg(feature = "simd")]
+    wide: QuadBvh,
+}
+
+/// Number of rays traced together by [`LinearBvh::closest_hit_packet`], enough for a 2x2 block of
+/// pixels.
+pub const PACKET_SIZE: usize = 4;
+
+/// Bounds on the origins and inverse directions of a packet of rays. Interval arithmetic on
+/// them gives a single slab test that no ray of the packet can pass without it passing too.
+struct PacketInterval {
+    origin_min: Vec3,
+    origin_max: Vec3,
+    inv_direction_min: Vec3,
+    inv_direction_max: Vec3,
+    sign: [usize; 3],
+}
+
+impl PacketInterval {
+    /// Returns `None` unless all rays point into the same octant, without being parallel to an
+    /// axis. Otherwise the inverse directions would span infinity.
+    fn new(rays: &[Ray; PACKET_SIZE]) -> Option<Self> {
+        let sign = rays[0].sign;
+        if rays
+            .iter()
+            .any(|r| r.sign != sign || !r.inv_direction.is_finite())
+        {
+            return None;
+        }
+
+        let mut interval = Self {
+            origin_min: rays[0].origin,
+            origin_max: rays[0].origin,
+            inv_direction_min: rays[0].inv_direction,
+            inv_direction_max: rays[0].inv_direction,
+            sign,
+        };
+        for r in &rays[1..] {
+            interval.origin_min = interval.origin_min.min(r.origin);
+            interval.origin_max = interval.origin_max.max(r.origin);
+            interval.inv_direction_min = interval.inv_direction_min.min(r.inv_direction);
+            interval.inv_direction_max = interval.inv_direction_max.max(r.inv_direction);
+        }
+        Some(interval)
+    }
+
+    fn hit(&self, aabb: &Aabb, mut t_min: Float, mut t_max: Float) -> bool {
+        let bounds = [aabb.min, aabb.max];
+        for a in 0..3 {
+            let near = bounds[self.sign[a]][a];
+            let far = bounds[1 - self.sign[a]][a];
+            let products = |plane: Float| {
+                [
+                    (plane - self.origin_min[a]) * self.inv_direction_min[a],
+                    (plane - self.origin_min[a]) * self.inv_direction_max[a],
+                    (plane - self.origin_max[a]) * self.inv_direction_min[a],
+                    (plane - self.origin_max[a]) * self.inv_direction_max[a],
+                ]
+            };
+            // The earliest any ray could enter the slab, and the latest it could leave.
+            let enter = products(near).into_iter().fold(Float::INFINITY, Float::min);
+            let exit = products(far)
+                .into_iter()
+                .fold(Float::NEG_INFINITY, Float::max);
+            t_min = t_min.max(enter);
+            t_max = t_max.min(exit);
+        }
+        t_min <= t_max
+    }
+}
+
+/// How much worse a refitted tree may get, relative to a fresh build, before rebuilding it pays
+/// off.
+const REBUILD_THRESHOLD: Float = 1.5;
+
+/// The outcome of [`LinearBvh::refit`].
+#[derive(Debug, Clone, Copy)]
+pub struct RefitReport {
+    pub build_cost: Float,
+    pub sah_cost: Float,
+}
+
+impl RefitReport {
+    /// Ratio of the refitted tree's SAH cost to the cost it had when built.
+    pub fn degradation(&self) -> Float {
+        if self.build_cost > 0.0 {
+            self.sah_cost / self.build_cost
+        } else {
+            // There was no tree to refit.
+            1.0
+        }
+    }
+
+    pub fn needs_rebuild(&self) -> bool {
+        self.degradation() > REBUILD_THRESHOLD
+    }
+}
+
+impl fmt::Display for RefitReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "SAH cost {:.2}, {:.2}x the cost when built",
+            self.sah_cost,
+            self.degradation()
+        )
+    }
+}
+
+impl LinearBvh {
+    pub fn new(tree: &BuildTree) -> Self {
+        let mut nodes = Vec::new();
+        flatten(&tree.root, &mut nodes);
+        let mut bvh = Self {
+            #[cfg(feature = "simd")]
+            wide: QuadBvh::new(&nodes),
+            nodes,
+            build_cost: 0.0,
+        };
+        bvh.build_cost = bvh.stats().sah_cost;
+        bvh
+    }
+
+    /// Recomputes every node's bounds from the primitive bounds returned by `bounds`, keeping
+    /// the topology.
+    pub fn refit(&mut self, mut bounds: impl FnMut(usize) -> Aabb) -> RefitReport {
+        // Children always come after their parent, so a reverse sweep visits them first.
+        for index in (0..self.nodes.len()).rev() {
+            let node = self.nodes[index];
+            self.nodes[index].aabb = if node.count > 0 {
+                (node.offset..node.offset + node.count)
+                    .fold(Aabb::EMPTY, |acc, i| acc + bounds(i as usize))
+            } else {
+                self.nodes[index + 1].aabb + self.nodes[node.offset as usize].aabb
+            };
+        }
+        #[cfg(feature = "simd")]
+        {
+            self.wide = QuadBvh::new(&self.nodes);
+        }
+
+        RefitReport {
+            build_cost: self.build_cost,
+            sah_cost: self.stats().sah_cost,
+        }
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        self.nodes[0].aabb
+    }
+
+    /// Finds the closest primitive hit. `hit` is called with a primitive position and the
+    /// current `t_max`, and returns what was hit along with its distance.
+    pub fn closest_hit<T>(
+        &self,
+        r: Ray,
+        t_min: Float,
+        t_max: Float,
+        hit: impl FnMut(usize, Float) -> Option<(T, Float)>,
+    ) -> Option<T> {
+        #[cfg(feature = "simd")]
+        let closest = self.wide.closest_hit(r, t_min, t_max, hit);
+        #[cfg(not(feature = "simd"))]
+        let closest = self.binary_closest_hit(r, t_min, t_max, hit);
+        closest
+    }
+
+    /// Whether any primitive is hit, stopping at the first one `hit` reports.
+    pub fn any_hit(
+        &self,
+        r: Ray,
+        t_min: Float,
+        t_max: Float,
+        hit: impl FnMut(usize) -> bool,
+    ) -> bool {
+        #[cfg(feature = "simd")]
+        let found = self.wide.any_hit(r, t_min, t_max, hit);
+        #[cfg(not(feature = "simd"))]
+        let found = self.binary_any_hit(r, t_min, t_max, hit);
+        found
+    }
+
+    /// Finds the closest primitive hit for each ray of a packet, like
+    /// [`closest_hit`](Self::closest_hit). `hit` is also given the index of the ray.
+    ///
+    /// Nodes are culled for the whole packet at once where possible, which pays off for coherent
+    /// rays such as those of neighbouring pixels. Rays that have left a subtree are not tested
+    /// against it any further, so a packet that diverges costs little more than tracing its rays
+    /// one by one, and rays that point different ways are traced one by one from the start.
+    pub fn closest_hit_packet<T>(
+        &self,
+        rays: &[Ray; PACKET_SIZE],
+        t_min: Float,
+        t_max: Float,
+        mut hit: impl FnMut(usize, usize, Float) -> Option<(T, Float)>,
+    ) -> [Option<T>; PACKET_SIZE] {
+        let Some(interval) = PacketInterval::new(rays) else {
+            return array::from_fn(|ray| {
+                self.closest_hit(rays[ray], t_min, t_max, |i, t_max| hit(ray, i, t_max))
+            });
+        };
+
+        let mut closest = array::from_fn(|_| None);
+        let mut t_maxes = [t_max; PACKET_SIZE];
+        // Each entry holds a node and the first ray that may still hit it.
+        let mut stack = [(0u32, 0); MAX_TREE_DEPTH];
+        let mut stack_len = 0;
+        let (mut current, mut first_ray) = (0, 0);
+
+        loop {
+            let node = &self.nodes[current as usize];
+            // For coherent rays the first one tried usually hits. When it doesn't, one interval
+            // test may still reject the node for all the others at once.
+            let hits = |ray: usize| node.aabb.hit(rays[ray], t_min, t_maxes[ray]);
+            let first_hit = if hits(first_ray) {
+                Some(first_ray)
+            } else if first_ray + 1 < PACKET_SIZE
+                && interval.hit(
+                    &node.aabb,
+                    t_min,
+                    t_maxes.into_iter().fold(t_min, Float::max),
+                )
+            {
+                (first_ray + 1..PACKET_SIZE).find(|&ray| hits(ray))
+            } else {
+                None
+            };
+
+            if let Some(first_hit) = first_hit {
+                if node.count > 0 {
+                    for ray in first_hit..PACKET_SIZE {
+                        if ray > first_hit && !node.aabb.hit(rays[ray], t_min, t_maxes[ray]) {
+                            continue;
+                        }
+                        for i in node.offset..node.offset + node.count {
+                            if let Some((rec, t)) = hit(ray, i as usize, t_maxes[ray]) {
+                                t_maxes[ray] = t;
+                                closest[ray] = Some(rec);
+                            }
+                        }
+                    }
+                } else {
+                    // All rays share the same direction signs, so they agree on the near side.
+                    let (near, far) = if interval.sign[node.axis as usize] == 1 {
+                        (node.offset, current + 1)
+                    } else {
+                        (current + 1, node.offset)
+                    };
+                    stack[stack_len] = (far, first_hit);
+                    stack_len += 1;
+                    (current, first_ray) = (near, first_hit);
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            (current, first_ray) = stack[stack_len];
+        }
+
+        closest
+    }
+
+    #[cfg_attr(feature = "simd", allow(dead_code))]
+    pub(super) fn binary_closest_hit<T>(
+        &self,
+        r: Ray,
+        t_min: Float,
+        mut t_max: Float,
+        mut hit: impl FnMut(usize, Float) -> Option<(T, Float)>,
+    ) -> Option<T> {
+        let mut closest = None;
+        let mut stack = [0u32; MAX_TREE_DEPTH];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current as usize];
+            if node.aabb.hit(r, t_min, t_max) {
+                if node.count > 0 {
+                    for i in node.offset..node.offset + node.count {
+                        if let Some((rec, t)) = hit(i as usize, t_max) {
+                            t_max = t;
+                            closest = Some(rec);
+                        }
+                    }
+                } else {
+                    // Visit the child on the near side of the split first, so hits there can
+                    // cull the far one.
+                    let (near, far) = if r.sign[node.axis as usize] == 1 {
+                        (node.offset, current + 1)
+                    } else {
+                        (current + 1, node.offset)
+                    };
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    current = near;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+
+        closest
+    }
+
+    #[cfg_attr(feature = "simd", allow(dead_code))]
+    pub(super) fn binary_any_hit(
+        &self,
+        r: Ray,
+        t_min: Float,
+        t_max: Float,
+        mut hit: impl FnMut(usize) -> bool,
+    ) -> bool {
+        let mut stack = [0u32; MAX_TREE_DEPTH];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current as usize];
+            if node.aabb.hit(r, t_min, t_max) {
+                if node.count > 0 {
+                    if (node.offset..node.offset + node.count).any(|i| hit(i as usize)) {
+                        return true;
+                    }
+                } else {
+                    // Any order will do, since there is no closest hit to look for.
+                    stack[stack_len] = node.offset;
+                    stack_len += 1;
+                    current += 1;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                return false;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats::default();
+        stats.sah_cost = self.collect_stats(0, 1, &mut stats);
+        stats
+    }
+
+    /// Accumulates counts into `stats` and returns the SAH cost of the subtree at `index`.
+    fn collect_stats(&self, index: usize, depth: usize, stats: &mut BvhStats) -> Float {
+        let node = &self.nodes[index];
+        stats.node_count += 1;
+        stats.depth = stats.depth.max(depth);
+        if node.count > 0 {
+            stats.leaf_count += 1;
+            stats.primitive_count += node.count as usize;
+            return INTERSECTION_COST * node.count as Float;
+        }
+
+        let area = node.aabb.surface_area();
+        let mut cost = TRAVERSAL_COST;
+        for child in [index + 1, node.offset as usize] {
+            let child_cost = self.collect_stats(child, depth + 1, stats);
+            let probability = if area > 0.0 {
+                self.nodes[child].aabb.surface_area() / area
+            } else {
+                1.0
+            };
+            cost += probability * child_cost;
+        }
+        cost
+    }
+}
+
+fn flatten(node: &BuildNode, nodes: &mut Vec<LinearNode>) {
+    let index = nodes.len();
+    match node {
+        BuildNode::Leaf { aabb, first, count } => nodes.push(LinearNode {
+            aabb: *aabb,
+            offset: *first as u32,
+            count: *count as u32,
+            axis: 0,
+        }),
+        BuildNode::Interior {
+            aabb,
+            axis,
+            children,
+        } => {
+            nodes.push(LinearNode {
+                aabb: *aabb,
+                offset: 0,
+                count: 0,
+                axis: *axis as u8,
+            });
+            flatten(&children[0], nodes);
+            nodes[index].offset = nodes.len() as u32;
+            flatten(&children[1], nodes);
+        }
+    }
+}
+
+/// A flattened BVH over arbitrary hittables.
+pub struct Bvh {
+    /// `None` when no object has a bounding box to build it from.
+    tree: Option<LinearBvh>,
+    objects: Vec<Rc<dyn Hittable>>,
+    /// Objects without a bounding box, such as planes, tested against every ray.
+    unbounded: HittableList,
+}
+
+impl Bvh {
+    pub fn from_hittable_list(list: HittableList, time0: Float, time1: Float) -> Self {
+        Self::with_builder(&list.objects, time0, time1, &BvhBuilder::new())
+    }
+
+    pub fn with_builder(
+        objects: &[Rc<dyn Hittable>],
+        time0: Float,
+        time1: Float,
+        builder: &BvhBuilder,
+    ) -> Self {
+        let (objects, bounds, unbounded) = super::partition_bounded(objects, time0, time1);
+        if bounds.is_empty() {
+            return Self {
+                tree: None,
+                objects,
+                unbounded,
+            };
+        }
+        let tree = builder.build(&bounds);
+        Self {
+            objects: tree.order.iter().map(|&i| objects[i].clone()).collect(),
+            tree: Some(LinearBvh::new(&tree)),
+            unbounded,
+        }
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        self.tree
+            .as_ref()
+            .map_or_else(BvhStats::default, LinearBvh::stats)
+    }
+
+    /// Like [`Hittable::hit`], for a packet of coherent rays such as the primary rays of a 2x2
+    /// block of pixels.
+    pub fn hit_packet(
+        &self,
+        rays: &[Ray; PACKET_SIZE],
+        t_min: Float,
+        t_max: Float,
+    ) -> [Option<HitRecord>; PACKET_SIZE] {
+        let hits = match &self.tree {
+            Some(tree) => tree.closest_hit_packet(rays, t_min, t_max, |ray, i, t_max| {
+                self.objects[i].hit(rays[ray], t_min, t_max).map(|rec| {
+                    let t = rec.t;
+                    (rec, t)
+                })
+            }),
+            None => array::from_fn(|_| None),
+        };
+        if self.unbounded.is_empty() {
+            return hits;
+        }
+        let mut k = 0;
+        hits.map(|hit| {
+            let t_max = hit.as_ref().map_or(t_max, |hit| hit.t);
+            let unbounded = self.unbounded.hit(rays[k], t_min, t_max);
+            k += 1;
+            unbounded.or(hit)
+        })
+    }
+
+    /// Updates the bounds for a new time interval without rebuilding. Objects are not
+    /// re-sorted, so trees over moving objects slowly get worse; see
+    /// [`RefitReport::needs_rebuild`].
+    ///
+    /// An object that has no bounding box for the new interval can't stay in the tree. The tree
+    /// is then left as it was and the report asks for a rebuild, which moves it to the unbounded
+    /// objects.
+    pub fn refit(&mut self, time0: Float, time1: Float) -> RefitReport {
+        let Some(tree) = &mut self.tree else {
+            return RefitReport {
+                build_cost: 0.0,
+                sah_cost: 0.0,
+            };
+        };
+        let bounds: Option<Vec<_>> = self
+            .objects
+            .iter()
+            .map(|object| object.bounding_box(time0, time1))
+            .collect();
+        match bounds {
+            Some(bounds) => tree.refit(|i| bounds[i]),
+            None => RefitReport {
+                build_cost: tree.build_cost,
+                sah_cost: Float::INFINITY,
+            },
+        }
+    }
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        // Planes tend to be big, so testing them first gives the tree a closer t_max to cull with.
+        let unbounded = self.unbounded.hit(r, t_min, t_max);
+        let t_max = unbounded.as_ref().map_or(t_max, |rec| rec.t);
+        let Some(tree) = &self.tree else {
+            return unbounded;
+        };
+        tree.closest_hit(r, t_min, t_max, |i, t_max| {
+            self.objects[i].hit(r, t_min, t_max).map(|rec| {
+                let t = rec.t;
+                (rec, t)
+            })
+        })
+        .or(unbounded)
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.unbounded.occluded(r, t_min, t_max)
+            || self.tree.as_ref().is_some_and(|tree| {
+                tree.any_hit(r, t_min, t_max, |i| {
+                    self.objects[i].occluded(r, t_min, t_max)
+                })
+            })
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.tree.as_ref().map(LinearBvh::aabb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        float::vec3,
+        hittable::{
+            sphere::{MovingSphere, Sphere},
+            Plane,
+        },
+        material::Lambertian,
+    };
+
+    /// 500 spheres of assorted sizes in a 100 unit cube, and optionally a plane through it.
+    fn world(rng: &mut StdRng, plane: bool) -> Bvh {
+        let mat = Rc::new(Lambertian::from_color(Vec3::splat(0.5)));
+        let mut list = HittableList::default();
+        for _ in 0..500 {
+            let center = 100.0 * rng.gen::<Vec3>();
+            list.add(Rc::new(Sphere::new(
+                center,
+                rng.gen_range(0.5..3.0),
+                mat.clone(),
+            )));
+        }
+        if plane {
+            list.add(Rc::new(Plane::new(
+                vec3(0.0, 30.0, 0.0),
+                vec3(0.1, 1.0, 0.2),
+                mat,
+            )));
+        }
+        Bvh::from_hittable_list(list, 0.0, 1.0)
+    }
+
+    /// A packet of rays from a block of neighbouring origins towards a random point in the
+    /// cloud, spread apart by `spread`.
+    fn packet(rng: &mut StdRng, spread: Float) -> [Ray; PACKET_SIZE] {
+        let origin = vec3(-30.0, 120.0, -40.0) + 20.0 * rng.gen::<Vec3>();
+        let direction = 100.0 * rng.gen::<Vec3>() - origin;
+        array::from_fn(|k| {
+            let offset = 0.1 * vec3((k % 2) as Float, (k / 2) as Float, 0.0);
+            let jitter = spread * direction.length() * (2.0 * rng.gen::<Vec3>() - Vec3::ONE);
+            Ray::new(origin + offset, direction + jitter, 0.0)
+        })
+    }
+
+    /// The packet traversal, with its interval culling and per-ray bookkeeping, finds exactly
+    /// the hits that tracing each ray on its own does.
+    #[test]
+    fn packets_hit_what_single_rays_hit() {
+        let mut rng = StdRng::seed_from_u64(36);
+        for plane in [false, true] {
+            let bvh = world(&mut rng, plane);
+            let (mut hits, mut shared_octant) = (0, 0);
+            for n in 0..2000 {
+                let mut rays = match n % 4 {
+                    // Coherent, as for neighbouring pixels.
+                    0 | 1 => packet(&mut rng, 0.01),
+                    // Diverging, into different octants more often than not.
+                    2 => packet(&mut rng, 1.0),
+                    // Any which way.
+                    _ => array::from_fn(|_| {
+                        let origin = 100.0 * rng.gen::<Vec3>();
+                        Ray::new(origin, 2.0 * rng.gen::<Vec3>() - Vec3::ONE, 0.0)
+                    }),
+                };
+                // Some parallel to an axis, with zeros of either sign.
+                if n % 7 == 0 {
+                    let k = rng.gen_range(0..PACKET_SIZE);
+                    let mut direction = rays[k].direction;
+                    direction[rng.gen_range(0..3)] = if rng.gen() { 0.0 } else { -0.0 };
+                    rays[k] = Ray::new(rays[k].origin, direction, 0.0);
+                }
+                shared_octant += PacketInterval::new(&rays).is_some() as usize;
+
+                let t_max = if n % 5 == 0 { 60.0 } else { Float::INFINITY };
+                let packet = bvh.hit_packet(&rays, 0.001, t_max);
+                for (r, rec) in rays.iter().zip(packet) {
+                    let expected = bvh.hit(*r, 0.001, t_max).map(|rec| rec.t);
+                    assert_eq!(rec.map(|rec| rec.t), expected, "{r:?}");
+                    hits += expected.is_some() as usize;
+                }
+            }
+            assert!(hits > 2000, "{hits}");
+            assert!((500..1500).contains(&shared_octant), "{shared_octant}");
+        }
+    }
+
+    fn contains(outer: Aabb, inner: Aabb) -> bool {
+        outer.min.cmple(inner.min).all() && inner.max.cmple(outer.max).all()
+    }
+
+    /// Checks that every node's box contains its children's, or in a leaf its objects' boxes
+    /// over `time0..time1`.
+    fn assert_nodes_bound_their_objects(bvh: &Bvh, time0: Float, time1: Float) {
+        let nodes = &bvh.tree.as_ref().unwrap().nodes;
+        for (index, node) in nodes.iter().enumerate() {
+            if node.count > 0 {
+                for i in node.offset..node.offset + node.count {
+                    let bounds = bvh.objects[i as usize].bounding_box(time0, time1).unwrap();
+                    assert!(contains(node.aabb, bounds), "leaf {index}, object {i}");
+                }
+            } else {
+                for child in [index + 1, node.offset as usize] {
+                    assert!(contains(node.aabb, nodes[child].aabb), "node {index}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn refits_bound_moving_objects_until_a_rebuild_pays_off() {
+        let mut rng = StdRng::seed_from_u64(32);
+        let mat = Rc::new(Lambertian::from_color(Vec3::splat(0.5)));
+        let objects: Vec<Rc<dyn Hittable>> = (0..300)
+            .map(|_| {
+                let center = 100.0 * rng.gen::<Vec3>();
+                let velocity = 10.0 * (2.0 * rng.gen::<Vec3>() - Vec3::ONE);
+                let radius = rng.gen_range(0.5..3.0);
+                Rc::new(MovingSphere::new(
+                    center,
+                    center + velocity,
+                    0.0,
+                    1.0,
+                    radius,
+                    mat.clone(),
+                )) as Rc<dyn Hittable>
+            })
+            .collect();
+        let mut bvh = Bvh::with_builder(&objects, 0.0, 1.0, &BvhBuilder::new());
+
+        let mut reports = Vec::new();
+        for frame in 1..20 {
+            let (time0, time1) = (frame as Float, frame as Float + 1.0);
+            reports.push(bvh.refit(time0, time1));
+            assert_nodes_bound_their_objects(&bvh, time0, time1);
+
+            // The refitted tree still finds what the objects themselves do.
+            for _ in 0..100 {
+                let time = rng.gen_range(time0..time1);
+                let origin = 400.0 * rng.gen::<Vec3>() - Vec3::splat(150.0);
+                let r = Ray::new(origin, 2.0 * rng.gen::<Vec3>() - Vec3::ONE, time);
+                let expected = objects
+                    .iter()
+                    .filter_map(|object| object.hit(r, 0.001, Float::INFINITY))
+                    .map(|rec| rec.t)
+                    .min_by(Float::total_cmp);
+                assert_eq!(
+                    bvh.hit(r, 0.001, Float::INFINITY).map(|rec| rec.t),
+                    expected
+                );
+            }
+        }
+
+        // Objects drift apart from the ones they share nodes with, and the tree gets worse.
+        assert!(!reports[0].needs_rebuild(), "{}", reports[0]);
+        let last = reports.last().unwrap();
+        assert!(last.needs_rebuild(), "{last}");
+        let flipped = reports.iter().position(RefitReport::needs_rebuild).unwrap();
+        assert!(reports[flipped..].iter().all(RefitReport::needs_rebuild));
+    }
+
+    /// A sphere without a bounding box from time 1 on, as if it had become unbounded.
+    struct Vanishing(Sphere);
+
+    impl Hittable for Vanishing {
+        fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+            self.0.hit(r, t_min, t_max)
+        }
+
+        fn bounding_box(&self, time0: Float, time1: Float) -> Option<Aabb> {
+            if time1 > 1.0 {
+                None
+            } else {
+                self.0.bounding_box(time0, time1)
+            }
+        }
+    }
+
+    #[test]
+    fn refitting_objects_without_bounds_asks_for_a_rebuild() {
+        let mut rng = StdRng::seed_from_u64(32);
+        let mat = Rc::new(Lambertian::from_color(Vec3::splat(0.5)));
+        let objects: Vec<Rc<dyn Hittable>> = (0..50)
+            .map(|i| {
+                let sphere = Sphere::new(100.0 * rng.gen::<Vec3>(), 2.0, mat.clone());
+                if i == 17 {
+                    Rc::new(Vanishing(sphere)) as Rc<dyn Hittable>
+                } else {
+                    Rc::new(sphere)
+                }
+            })
+            .collect();
+        let mut bvh = Bvh::with_builder(&objects, 0.0, 1.0, &BvhBuilder::new());
+        let aabbs = |bvh: &Bvh| -> Vec<Aabb> {
+            let nodes = &bvh.tree.as_ref().unwrap().nodes;
+            nodes.iter().map(|node| node.aabb).collect()
+        };
+        let built = aabbs(&bvh);
+
+        assert!(!bvh.refit(0.0, 0.5).needs_rebuild());
+        assert_eq!(aabbs(&bvh), built);
+        let report = bvh.refit(1.0, 2.0);
+        assert!(report.needs_rebuild(), "{report}");
+        // Left as it was, so it still bounds everything as of the last refit.
+        assert_eq!(aabbs(&bvh), built);
+
+        let rebuilt = Bvh::with_builder(&objects, 1.0, 2.0, &BvhBuilder::new());
+        assert_eq!(rebuilt.objects.len(), 49);
+        assert_eq!(rebuilt.unbounded.objects.len(), 1);
+    }
+}
@@ -0,0 +1,365 @@
+use std::rc::Rc;
+
+use rand::prelude::*;
+
+use crate::{
+    aabb::Aabb,
+    float::Float,
+    hittable::{HitRecord, Hittable, HittableList},
+    ray::Ray,
+};
+
+mod build;
+mod linear;
+#[cfg(feature = "simd")]
+mod quad;
+
+pub use build::{BvhBuilder, BvhStats};
+pub use linear::{Bvh, LinearBvh, PACKET_SIZE};
+
+use build::{BuildNode, INTERSECTION_COST, TRAVERSAL_COST};
+
+pub struct BvhNode {
+    left: BvhChild,
+    right: BvhChild,
+    aabb: Aabb,
+    /// Objects without a bounding box, tested against every ray. Only ever set at the root.
+    unbounded: HittableList,
+}
+
+enum BvhChild {
+    Node(Box<BvhNode>),
+    Leaf {
+        objects: Vec<Rc<dyn Hittable>>,
+        aabb: Aabb,
+    },
+}
+
+impl BvhNode {
+    #[allow(dead_code)]
+    pub fn from_hittable_list(list: HittableList, time0: Float, time1: Float) -> Self {
+        Self::from_slice(&list.objects[..], time0, time1)
+    }
+
+    #[allow(dead_code)]
+    pub fn from_slice(objects: &[Rc<dyn Hittable>], time0: Float, time1: Float) -> Self {
+        Self::with_builder(objects, time0, time1, &BvhBuilder::new())
+    }
+
+    pub fn with_builder(
+        objects: &[Rc<dyn Hittable>],
+        time0: Float,
+        time1: Float,
+        builder: &BvhBuilder,
+    ) -> Self {
+        let (objects, bounds, unbounded) = partition_bounded(objects, time0, time1);
+        let mut node = if bounds.is_empty() {
+            Self::leaf(Vec::new(), Aabb::EMPTY)
+        } else {
+            let tree = builder.build(&bounds);
+            match BvhChild::from_build_node(&tree.root, &tree.order, &objects) {
+                BvhChild::Node(node) => *node,
+                BvhChild::Leaf { objects, aabb } => Self::leaf(objects, aabb),
+            }
+        };
+        node.unbounded = unbounded;
+        node
+    }
+
+    /// The original builder: splits at the median along a random axis, one object per leaf.
+    /// Objects without a bounding box are kept aside at the root, as [`BvhNode::with_builder`]
+    /// does.
+    pub fn from_slice_median(objects: &[Rc<dyn Hittable>], time0: Float, time1: Float) -> Self {
+        let (objects, bounds, unbounded) = partition_bounded(objects, time0, time1);
+        let mut objects: Vec<_> = objects.into_iter().zip(bounds).collect();
+        let mut node = if objects.is_empty() {
+            Self::leaf(Vec::new(), Aabb::EMPTY)
+        } else {
+            Self::median_split(&mut objects)
+        };
+        node.unbounded = unbounded;
+        node
+    }
+
+    /// A node with a single leaf holding all of `objects`.
+    fn leaf(objects: Vec<Rc<dyn Hittable>>, aabb: Aabb) -> Self {
+        Self {
+            aabb,
+            left: BvhChild::Leaf { objects, aabb },
+            right: BvhChild::Leaf {
+                objects: Vec::new(),
+                aabb: Aabb::EMPTY,
+            },
+            unbounded: HittableList::default(),
+        }
+    }
+
+    fn median_split(objects: &mut [(Rc<dyn Hittable>, Aabb)]) -> Self {
+        // Boxes that are NaN somewhere still sort, wherever they end up.
+        let axis = thread_rng().gen_range(0..3);
+        objects.sort_by(|(_, a), (_, b)| a.min[axis].total_cmp(&b.min[axis]));
+
+        let leaf = |(object, aabb): &(Rc<dyn Hittable>, Aabb)| BvhChild::Leaf {
+            objects: vec![object.clone()],
+            aabb: *aabb,
+        };
+        let (left, right) = match objects {
+            [object] => (leaf(object), leaf(object)),
+            [a, b] => (leaf(a), leaf(b)),
+            _ => {
+                let (left, right) = objects.split_at_mut(objects.len() / 2);
+                (
+                    BvhChild::Node(Box::new(Self::median_split(left))),
+                    BvhChild::Node(Box::new(Self::median_split(right))),
+                )
+            }
+        };
+        let aabb = left.aabb() + right.aabb();
+
+        Self {
+            left,
+            right,
+            aabb,
+            unbounded: HittableList::default(),
+        }
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats::default();
+        stats.sah_cost = self.collect_stats(&mut stats, 1);
+        stats
+    }
+
+    /// Accumulates counts into `stats` and returns the SAH cost of this subtree.
+    fn collect_stats(&self, stats: &mut BvhStats, depth: usize) -> Float {
+        stats.node_count += 1;
+        stats.depth = stats.depth.max(depth);
+
+        let area = self.aabb.surface_area();
+        let mut cost = TRAVERSAL_COST;
+        for child in [&self.left, &self.right] {
+            let child_cost = match child {
+                BvhChild::Node(node) => node.collect_stats(stats, depth + 1),
+                BvhChild::Leaf { objects, .. } => {
+                    stats.node_count += 1;
+                    stats.leaf_count += 1;
+                    stats.depth = stats.depth.max(depth + 1);
+                    stats.primitive_count += objects.len();
+                    INTERSECTION_COST * objects.len() as Float
+                }
+            };
+            let probability = if area > 0.0 {
+                child.aabb().surface_area() / area
+            } else {
+                1.0
+            };
+            cost += probability * child_cost;
+        }
+        cost
+    }
+}
+
+impl BvhChild {
+    fn from_build_node(node: &BuildNode, order: &[usize], objects: &[Rc<dyn Hittable>]) -> Self {
+        match node {
+            BuildNode::Leaf { aabb, first, count } => BvhChild::Leaf {
+                objects: order[*first..*first + *count]
+                    .iter()
+                    .map(|&i| objects[i].clone())
+                    .collect(),
+                aabb: *aabb,
+            },
+            BuildNode::Interior { aabb, children, .. } => BvhChild::Node(Box::new(BvhNode {
+                left: Self::from_build_node(&children[0], order, objects),
+                right: Self::from_build_node(&children[1], order, objects),
+                aabb: *aabb,
+                unbounded: HittableList::default(),
+            })),
+        }
+    }
+
+    fn aabb(&self) -> Aabb {
+        match self {
+            BvhChild::Node(node) => node.aabb,
+            BvhChild::Leaf { aabb, .. } => *aabb,
+        }
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        match self {
+            BvhChild::Node(node) => node.occluded(r, t_min, t_max),
+            BvhChild::Leaf { objects, .. } => objects
+                .iter()
+                .any(|object| object.occluded(r, t_min, t_max)),
+        }
+    }
+
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        match self {
+            BvhChild::Node(node) => node.hit(r, t_min, t_max),
+            BvhChild::Leaf { objects, .. } => {
+                let mut closest = None;
+                let mut closest_t = t_max;
+                for object in objects {
+                    if let Some(rec) = object.hit(r, t_min, closest_t) {
+                        closest_t = rec.t;
+                        closest = Some(rec);
+                    }
+                }
+                closest
+            }
+        }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let mut hit = self.unbounded.hit(r, t_min, t_max);
+        let t_max = hit.as_ref().map_or(t_max, |hit| hit.t);
+        if !self.aabb.hit(r, t_min, t_max) {
+            return hit;
+        }
+
+        hit = self.left.hit(r, t_min, t_max).or(hit);
+        let next_hit_t_max = match &hit {
+            Some(hit) => hit.t,
+            None => t_max,
+        };
+        hit = self.right.hit(r, t_min, next_hit_t_max).or(hit);
+        hit
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.unbounded.occluded(r, t_min, t_max)
+            || self.aabb.hit(r, t_min, t_max)
+                && (self.left.occluded(r, t_min, t_max) || self.right.occluded(r, t_min, t_max))
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        self.unbounded.is_empty().then_some(self.aabb)
+    }
+}
+
+/// Splits `objects` into those with a bounding box, returned along with their boxes, and those
+/// without, such as planes, which no tree can cull.
+fn partition_bounded(
+    objects: &[Rc<dyn Hittable>],
+    time0: Float,
+    time1: Float,
+) -> (Vec<Rc<dyn Hittable>>, Vec<Aabb>, HittableList) {
+    let mut bounded = Vec::new();
+    let mut bounds = Vec::new();
+    let mut unbounded = HittableList::default();
+    for object in objects {
+        match object.bounding_box(time0, time1) {
+            Some(aabb) => {
+                bounded.push(object.clone());
+                bounds.push(aabb);
+            }
+            None => unbounded.add(object.clone()),
+        }
+    }
+    (bounded, bounds, unbounded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        float::Vec3,
+        hittable::{sphere::Sphere, Plane},
+        material::Lambertian,
+        test_scenes::{self, Scene},
+        testing,
+    };
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn median_tree_hits_what_a_list_hits() {
+        let mut rng = StdRng::seed_from_u64(29);
+        let mat = Rc::new(Lambertian::from_color(Vec3::splat(0.5)));
+        let mut list = HittableList::default();
+        for _ in 0..200 {
+            let center = 20.0 * rng.gen::<Vec3>() - Vec3::splat(10.0);
+            list.add(Rc::new(Sphere::new(
+                center,
+                rng.gen_range(0.1..1.0),
+                mat.clone(),
+            )));
+        }
+        // A plane has no bounds, so it stays at the root.
+        list.add(Rc::new(Plane::new(Vec3::ZERO, Vec3::Y, mat.clone())));
+
+        let tree = BvhNode::from_slice_median(&list.objects, 0.0, 1.0);
+        assert!(tree.bounding_box(0.0, 1.0).is_none());
+        for _ in 0..10_000 {
+            let origin = 30.0 * rng.gen::<Vec3>() - Vec3::splat(15.0);
+            let r = Ray::new(origin, 2.0 * rng.gen::<Vec3>() - Vec3::ONE, 0.0);
+            let expected = list.hit(r, 0.001, Float::INFINITY).map(|rec| rec.t);
+            assert_eq!(
+                tree.hit(r, 0.001, Float::INFINITY).map(|rec| rec.t),
+                expected
+            );
+            assert_eq!(tree.occluded(r, 0.001, Float::INFINITY), expected.is_some());
+        }
+
+        // Bounds that are NaN still sort.
+        list.add(Rc::new(Sphere::new(Vec3::NAN, 1.0, mat)));
+        BvhNode::from_slice_median(&list.objects, 0.0, 1.0);
+    }
+
+    /// Primary rays through a `side` x `side` grid of pixels and, where they hit something, a
+    /// diffuse bounce from there, all the same from run to run.
+    fn scene_rays(scene: &Scene, side: u32) -> Vec<Ray> {
+        let mut rng = StdRng::seed_from_u64(30);
+        let mut rays = Vec::new();
+        for j in 0..side {
+            for i in 0..side {
+                let r = scene.cam.get_ray(
+                    (i as Float + 0.5) / side as Float,
+                    (j as Float + 0.5) / side as Float,
+                );
+                let r = Ray::new(r.origin, r.direction, 0.5);
+                rays.push(r);
+                if let Some(rec) = scene.world.hit(r, 0.0, Float::INFINITY) {
+                    let bounce = loop {
+                        let v = 2.0 * rng.gen::<Vec3>() - Vec3::ONE;
+                        if v.length_squared() < 1.0 && (rec.normal + v).length_squared() > 1e-6 {
+                            break rec.normal + v;
+                        }
+                    };
+                    rays.push(Ray::new(rec.point, bounce, 0.5));
+                }
+            }
+        }
+        rays
+    }
+
+    /// Times the pointer-based tree against the flattened one at the top level of the final
+    /// scene, as `--bench` does, but on a fixed set of rays. Run with
+    /// `cargo test --release -- --ignored --nocapture bench_`.
+    #[test]
+    #[ignore]
+    fn bench_bvh_layouts_on_final_scene() {
+        let scene = test_scenes::final_scene();
+        let rays = scene_rays(&scene, 400);
+        let objects = &scene.world.objects;
+        let tree = BvhNode::from_slice(objects, scene.time0, scene.time1);
+        let flat = Bvh::with_builder(objects, scene.time0, scene.time1, &BvhBuilder::new());
+
+        let trace = |world: &dyn Hittable| {
+            rays.iter()
+                .filter_map(|&r| world.hit(r, 0.001, Float::INFINITY))
+                .count()
+        };
+        let (tree_hits, tree_time) = testing::fastest(10, || trace(&tree));
+        let (flat_hits, flat_time) = testing::fastest(10, || trace(&flat));
+        println!(
+            "{} rays: BvhNode {tree_time:.2?}, Bvh {flat_time:.2?} ({:.2}x)",
+            rays.len(),
+            tree_time.as_secs_f64() / flat_time.as_secs_f64()
+        );
+        // The fog filling the scene scatters at random, so hits may differ a little.
+        assert!(tree_hits.abs_diff(flat_hits) < rays.len() / 100);
+    }
+}
@@ -0,0 +1,369 @@
+//! A 4-wide BVH, collapsed from the binary one, whose nodes test all four child boxes at once.
+
+#[cfg(feature = "f32")]
+use wide::f32x4 as FloatX4;
+#[cfg(not(feature = "f32"))]
+use wide::f64x4 as FloatX4;
+use wide::CmpLe;
+
+use crate::{float::Float, ray::Ray};
+
+use super::{build::MAX_TREE_DEPTH, linear::LinearNode};
+
+#[derive(Debug, Clone, Copy)]
+struct QuadNode {
+    /// Child bounds per axis, one lane per child. Unused lanes hold an empty box, which no ray
+    /// hits.
+    min: [FloatX4; 3],
+    max: [FloatX4; 3],
+    /// For leaf children the first primitive, for interior children the node index.
+    offset: [u32; 4],
+    /// Number of primitives of leaf children, 0 for interior ones.
+    count: [u32; 4],
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct StackEntry {
+    offset: u32,
+    count: u32,
+    t_near: Float,
+}
+
+/// The ray, broadcast to all four lanes.
+struct WideRay {
+    origin: [FloatX4; 3],
+    inv_direction: [FloatX4; 3],
+    sign: [usize; 3],
+}
+
+impl WideRay {
+    fn new(r: Ray) -> Self {
+        Self {
+            origin: [0, 1, 2].map(|a| FloatX4::splat(r.origin[a])),
+            inv_direction: [0, 1, 2].map(|a| FloatX4::splat(r.inv_direction[a])),
+            sign: r.sign,
+        }
+    }
+}
+
+impl QuadNode {
+    /// The same slab test as `Aabb::hit`, for four boxes. Returns a bit mask of the children
+    /// that were hit and the distance at which the ray enters each of them.
+    fn intersect(&self, r: &WideRay, t_min: Float, t_max: Float) -> (i32, [Float; 4]) {
+        let mut t0 = FloatX4::splat(t_min);
+        let mut t1 = FloatX4::splat(t_max);
+        for a in 0..3 {
+            let (near, far) = if r.sign[a] == 0 {
+                (self.min[a], self.max[a])
+            } else {
+                (self.max[a], self.min[a])
+            };
+            // Like Float::max and Float::min, these ignore NaN lanes.
+            t0 = t0.max((near - r.origin[a]) * r.inv_direction[a]);
+            t1 = t1.min((far - r.origin[a]) * r.inv_direction[a]);
+        }
+        (t0.cmp_le(t1).move_mask(), t0.to_array())
+    }
+}
+
+pub struct QuadBvh {
+    nodes: Vec<QuadNode>,
+}
+
+/// At most three more entries per level, since a node replaces itself with up to four children.
+const STACK_SIZE: usize = 3 * MAX_TREE_DEPTH + 1;
+
+impl QuadBvh {
+    /// Collapses a binary tree, given as the nodes of a [`LinearBvh`](super::LinearBvh), by
+    /// pulling grandchildren up into each node. Primitive positions stay the same.
+    pub fn new(binary: &[LinearNode]) -> Self {
+        let mut nodes = Vec::new();
+        collapse(binary, &[0], &mut nodes);
+        Self { nodes }
+    }
+
+    pub fn closest_hit<T>(
+        &self,
+        r: Ray,
+        t_min: Float,
+        mut t_max: Float,
+        mut hit: impl FnMut(usize, Float) -> Option<(T, Float)>,
+    ) -> Option<T> {
+        let wide_ray = WideRay::new(r);
+        let mut closest = None;
+        let mut stack = [StackEntry::default(); STACK_SIZE];
+        stack[0].t_near = t_min;
+        let mut stack_len = 1;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let entry = stack[stack_len];
+            // Something closer was found since this entry was pushed.
+            if entry.t_near > t_max {
+                continue;
+            }
+
+            if entry.count > 0 {
+                for i in entry.offset..entry.offset + entry.count {
+                    if let Some((rec, t)) = hit(i as usize, t_max) {
+                        t_max = t;
+                        closest = Some(rec);
+                    }
+                }
+                continue;
+            }
+
+            let node = &self.nodes[entry.offset as usize];
+            let (mask, t_near) = node.intersect(&wide_ray, t_min, t_max);
+            let mut hits = [(0.0, 0); 4];
+            let mut hit_count = 0;
+            for (lane, &t) in t_near.iter().enumerate() {
+                if mask & (1 << lane) != 0 {
+                    hits[hit_count] = (t, lane);
+                    hit_count += 1;
+                }
+            }
+            // Push the farthest child first, so the nearest one is visited next.
+            hits[..hit_count].sort_unstable_by(|a, b| b.0.total_cmp(&a.0));
+            for &(t_near, lane) in &hits[..hit_count] {
+                stack[stack_len] = StackEntry {
+                    offset: node.offset[lane],
+                    count: node.count[lane],
+                    t_near,
+                };
+                stack_len += 1;
+            }
+        }
+
+        closest
+    }
+
+    pub fn any_hit(
+        &self,
+        r: Ray,
+        t_min: Float,
+        t_max: Float,
+        mut hit: impl FnMut(usize) -> bool,
+    ) -> bool {
+        let wide_ray = WideRay::new(r);
+        let mut stack = [StackEntry::default(); STACK_SIZE];
+        let mut stack_len = 1;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let entry = stack[stack_len];
+            if entry.count > 0 {
+                if (entry.offset..entry.offset + entry.count).any(|i| hit(i as usize)) {
+                    return true;
+                }
+                continue;
+            }
+
+            let node = &self.nodes[entry.offset as usize];
+            let (mask, _) = node.intersect(&wide_ray, t_min, t_max);
+            for lane in 0..4 {
+                if mask & (1 << lane) != 0 {
+                    stack[stack_len] = StackEntry {
+                        offset: node.offset[lane],
+                        count: node.count[lane],
+                        t_near: 0.0,
+                    };
+                    stack_len += 1;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+/// Adds a node holding the given binary nodes as children, opening up interior ones until
+/// there are four. Returns the new node's index.
+fn collapse(binary: &[LinearNode], children: &[usize], nodes: &mut Vec<QuadNode>) -> u32 {
+    let mut children = children.to_vec();
+    while children.len() < 4 {
+        // Opening the largest child removes the most overlap from the traversal.
+        let Some(i) = (0..children.len())
+            .filter(|&i| binary[children[i]].count == 0)
+            .max_by(|&a, &b| {
+                let area = |i: usize| binary[children[i]].aabb.surface_area();
+                area(a).total_cmp(&area(b))
+            })
+        else {
+            break;
+        };
+        let index = children[i];
+        children.splice(i..=i, [index + 1, binary[index].offset as usize]);
+    }
+
+    let quad_index = nodes.len();
+    let mut min = [[Float::INFINITY; 4]; 3];
+    let mut max = [[Float::NEG_INFINITY; 4]; 3];
+    let mut offset = [0; 4];
+    let mut count = [0; 4];
+    // Reserve the slot, so that the children pushed below come after their parent.
+    nodes.push(QuadNode {
+        min: [FloatX4::ZERO; 3],
+        max: [FloatX4::ZERO; 3],
+        offset,
+        count,
+    });
+
+    for (lane, &index) in children.iter().enumerate() {
+        let node = &binary[index];
+        for a in 0..3 {
+            min[a][lane] = node.aabb.min[a];
+            max[a][lane] = node.aabb.max[a];
+        }
+        if node.count > 0 {
+            offset[lane] = node.offset;
+            count[lane] = node.count;
+        } else {
+            offset[lane] = collapse(binary, &[index], nodes);
+        }
+    }
+
+    nodes[quad_index] = QuadNode {
+        min: min.map(FloatX4::new),
+        max: max.map(FloatX4::new),
+        offset,
+        count,
+    };
+    quad_index as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aabb::Aabb,
+        float::{vec3, Vec3},
+        hittable::bvh::{build::BvhBuilder, LinearBvh},
+        testing,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    struct Spheres {
+        centers: Vec<Vec3>,
+        radius: Float,
+    }
+
+    impl Spheres {
+        fn random(rng: &mut StdRng, count: usize, radius: Float) -> Self {
+            Self {
+                centers: (0..count).map(|_| 100.0 * rng.gen::<Vec3>()).collect(),
+                radius,
+            }
+        }
+
+        fn bounds(&self) -> Vec<Aabb> {
+            let r = Vec3::splat(self.radius);
+            self.centers
+                .iter()
+                .map(|&c| Aabb::new(c - r, c + r))
+                .collect()
+        }
+
+        /// The nearest hit of `r` with sphere `i` in `(t_min, t_max)`, along with `i`.
+        fn hit(&self, i: usize, r: Ray, t_min: Float, t_max: Float) -> Option<(usize, Float)> {
+            let oc = r.origin - self.centers[i];
+            let a = r.direction.length_squared();
+            let half_b = oc.dot(r.direction);
+            let c = oc.length_squared() - self.radius * self.radius;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let sqrtd = discriminant.sqrt();
+            [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a]
+                .into_iter()
+                .find(|&t| t_min < t && t < t_max)
+                .map(|t| (i, t))
+        }
+
+        /// The `hit` argument of the traversals, for spheres in the given order.
+        fn closest_hit<'a>(
+            &'a self,
+            order: &'a [usize],
+            r: Ray,
+        ) -> impl FnMut(usize, Float) -> Option<((usize, Float), Float)> + 'a {
+            move |i, t_max| self.hit(order[i], r, 0.001, t_max).map(|hit| (hit, hit.1))
+        }
+    }
+
+    fn random_ray(rng: &mut StdRng) -> Ray {
+        let origin = vec3(-50.0, -50.0, -50.0) + 200.0 * rng.gen::<Vec3>();
+        let mut direction = 2.0 * rng.gen::<Vec3>() - Vec3::ONE;
+        // Some rays parallel to one or two axes, with zeros of either sign.
+        match rng.gen_range(0..8) {
+            0 => direction.x = 0.0,
+            1 => direction.y = -0.0,
+            2 => (direction.x, direction.z) = (-0.0, 0.0),
+            _ => {}
+        }
+        Ray::new(origin, direction, 0.0)
+    }
+
+    /// The 4-wide traversal finds exactly the hits the binary one does.
+    #[test]
+    fn quad_bvh_matches_binary() {
+        let mut rng = StdRng::seed_from_u64(35);
+        for (count, radius) in [(1, 10.0), (3, 10.0), (100, 5.0), (5000, 1.0)] {
+            let spheres = Spheres::random(&mut rng, count, radius);
+            let tree = BvhBuilder::new().build(&spheres.bounds());
+            let binary = LinearBvh::new(&tree);
+            let quad = QuadBvh::new(&binary.nodes);
+            let hit = |r| spheres.closest_hit(&tree.order, r);
+
+            let mut hits = 0;
+            for _ in 0..5000 {
+                let r = random_ray(&mut rng);
+                let expected = binary.binary_closest_hit(r, 0.001, Float::INFINITY, hit(r));
+                assert_eq!(
+                    quad.closest_hit(r, 0.001, Float::INFINITY, hit(r)),
+                    expected
+                );
+                hits += expected.is_some() as usize;
+
+                let t_max = expected.map_or(Float::INFINITY, |(_, t)| 0.5 * t);
+                let occluded = |i: usize| spheres.hit(tree.order[i], r, 0.001, t_max).is_some();
+                assert_eq!(
+                    quad.any_hit(r, 0.001, t_max, occluded),
+                    binary.binary_any_hit(r, 0.001, t_max, occluded)
+                );
+            }
+            assert!(hits > 0, "no ray hit any of {count} spheres");
+        }
+    }
+
+    /// Times both traversals over 200,000 small spheres. Run with
+    /// `cargo test --release --features simd -- --ignored --nocapture bench_`.
+    #[test]
+    #[ignore]
+    fn bench_quad_bvh_against_binary() {
+        let mut rng = StdRng::seed_from_u64(35);
+        let spheres = Spheres::random(&mut rng, 200_000, 0.2);
+        let tree = BvhBuilder::new().build(&spheres.bounds());
+        let binary = LinearBvh::new(&tree);
+        let quad = QuadBvh::new(&binary.nodes);
+        let rays: Vec<_> = (0..500_000).map(|_| random_ray(&mut rng)).collect();
+        let hit = |r| spheres.closest_hit(&tree.order, r);
+
+        let (binary_hits, binary_time) = testing::fastest(5, || {
+            rays.iter()
+                .filter_map(|&r| binary.binary_closest_hit(r, 0.001, Float::INFINITY, hit(r)))
+                .count()
+        });
+        let (quad_hits, quad_time) = testing::fastest(5, || {
+            rays.iter()
+                .filter_map(|&r| quad.closest_hit(r, 0.001, Float::INFINITY, hit(r)))
+                .count()
+        });
+        println!(
+            "{} rays: binary {binary_time:.2?}, quad {quad_time:.2?} ({:.2}x)",
+            rays.len(),
+            binary_time.as_secs_f64() / quad_time.as_secs_f64()
+        );
+        assert_eq!(binary_hits, quad_hits);
+    }
+}
@@ -1,71 +1,60 @@
-use std::rc::Rc;
+use std::{iter, rc::Rc};
 
 use rand::random;
 
 use crate::{
     aabb::Aabb,
+    float::{Float, Vec3},
     hittable::{HitRecord, Hittable},
     material::{Isotropic, Material},
     ray::Ray,
-    texture::Texture,
 };
 
 pub struct ConstantMedium {
     pub boundary: Rc<dyn Hittable>,
-    pub neg_inv_density: f64,
+    pub neg_inv_density: Float,
     pub phasing_function: Rc<dyn Material>,
 }
 
 impl ConstantMedium {
-    #[allow(dead_code)]
-    pub fn from_texture(b: Rc<dyn Hittable>, d: f64, a: Rc<dyn Texture>) -> Self {
+    /// A medium that scatters with `phasing_function`, such as an [`Isotropic`] or an
+    /// [`Anisotropic`](crate::material::Anisotropic) material.
+    pub fn new(b: Rc<dyn Hittable>, d: Float, phasing_function: Rc<dyn Material>) -> Self {
         Self {
             boundary: b,
             neg_inv_density: -1.0 / d,
-            phasing_function: Rc::new(Isotropic::from_texture(a)),
+            phasing_function,
         }
     }
 
-    pub fn from_color(b: Rc<dyn Hittable>, d: f64, c: glam::DVec3) -> Self {
-        Self {
-            boundary: b,
-            neg_inv_density: -1.0 / d,
-            phasing_function: Rc::new(Isotropic::from_color(c)),
-        }
+    pub fn from_color(b: Rc<dyn Hittable>, d: Float, c: Vec3) -> Self {
+        Self::new(b, d, Rc::new(Isotropic::from_color(c)))
     }
 }
 
 impl Hittable for ConstantMedium {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
         const ENABLE_DEBUG: bool = false;
-        let debugging = ENABLE_DEBUG && random::<f64>() < 0.00001;
-
-        let mut rec1 = self.boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY)?;
-        let mut rec2 = self.boundary.hit(r, rec1.t + 0.0001, f64::INFINITY)?;
-
-        if debugging {
-            println!("t_min={}, t_max={}", rec1.t, rec2.t);
-        }
-
-        rec1.t = rec1.t.max(t_min);
-        rec2.t = rec2.t.min(t_max);
-
-        if rec1.t >= rec2.t {
-            return None;
-        }
-
-        rec1.t = rec1.t.max(0.0);
+        let debugging = ENABLE_DEBUG && random::<Float>() < 0.00001;
 
+        // The boundary may be entered and left several times, like a shape with a bite taken
+        // out of it. Each stretch inside gets its own chance to scatter, which is the same as
+        // sampling one distance over all of them since the exponential distribution has no
+        // memory.
         let ray_length = r.direction.length();
-        let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
-        // WARN
-        let hit_distance = self.neg_inv_density * random::<f64>().ln();
-
-        if hit_distance > distance_inside_boundary {
-            return None;
-        }
-
-        let rec_t = rec1.t + hit_distance / ray_length;
+        let (t_enter, hit_distance) =
+            inside(&*self.boundary, r, t_min, t_max).find_map(|(t_enter, t_exit)| {
+                if debugging {
+                    println!("t_min={}, t_max={}", t_enter, t_exit);
+                }
+
+                let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+                // WARN
+                let hit_distance = self.neg_inv_density * random::<Float>().ln();
+                (hit_distance <= distance_inside_boundary).then_some((t_enter, hit_distance))
+            })?;
+
+        let rec_t = t_enter + hit_distance / ray_length;
         let rec_p = r.at(rec_t);
 
         if debugging {
@@ -76,15 +65,45 @@ impl Hittable for ConstantMedium {
 
         Some(HitRecord {
             point: rec_p,
-            normal: glam::DVec3::Y, // arbitrary
-            front_face: true,       // also arbitrary
+            normal: Vec3::Y,  // arbitrary
+            front_face: true, // also arbitrary
             mat: Some(self.phasing_function.clone()),
             t: rec_t,
             ..Default::default()
         })
     }
 
-    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<Aabb> {
         self.boundary.bounding_box(time0, time1)
     }
 }
+
+/// The stretches of `r` within `[t_min, t_max]` that lie inside `boundary`, in order, as the ray
+/// parameters where they start and end.
+pub(super) fn inside(
+    boundary: &dyn Hittable,
+    r: Ray,
+    t_min: Float,
+    t_max: Float,
+) -> impl Iterator<Item = (Float, Float)> + '_ {
+    let mut search = Some((r, Float::NEG_INFINITY, 0.0));
+    iter::from_fn(move || loop {
+        let (ray, search_min, offset) = search.take()?;
+        let mut enter = boundary.hit(ray, search_min, Float::INFINITY)?;
+        enter.t += offset;
+        if enter.t >= t_max {
+            return None;
+        }
+        // Look for the exit from just past the entry point. The spawned ray starts where the
+        // original one is at enter.t, give or take the entry point's error bound.
+        let mut exit = boundary.hit(enter.spawn_ray(r.direction, r.time), 0.0, Float::INFINITY)?;
+        exit.t += enter.t;
+        search = Some((exit.spawn_ray(r.direction, r.time), 0.0, exit.t));
+
+        let t_enter = enter.t.max(t_min);
+        let t_exit = exit.t.min(t_max);
+        if t_enter < t_exit {
+            return Some((t_enter.max(0.0), t_exit));
+        }
+    })
+}
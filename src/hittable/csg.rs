@@ -0,0 +1,329 @@
+use std::{iter, rc::Rc};
+
+use crate::{
+    aabb::Aabb,
+    float::Float,
+    hittable::{HitRecord, Hittable},
+    ray::Ray,
+};
+
+/// How a [`Csg`] node combines the solids bounded by its two children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOperation {
+    /// Everything inside either child.
+    Union,
+    /// Everything inside both children.
+    Intersection,
+    /// Everything inside the left child but not the right one.
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(self, inside_left: bool, inside_right: bool) -> bool {
+        match self {
+            Self::Union => inside_left || inside_right,
+            Self::Intersection => inside_left && inside_right,
+            Self::Difference => inside_left && !inside_right,
+        }
+    }
+}
+
+/// A boolean combination of two closed shapes, like a box with a spherical bite taken out of it.
+///
+/// Every surface of the result is a piece of one child's surface and keeps that child's material,
+/// so the bite out of a box is lined with the material of the sphere. Normals always face out of
+/// the combined solid, and since the result is closed again it can be the boundary of a
+/// [`super::ConstantMedium`] or part of another `Csg`.
+///
+/// The inside of a child is found by following a ray from infinitely far behind its origin and
+/// counting entries and exits, so the children have to be closed. A lone [`super::Plane`] also
+/// works, as the half-space behind it.
+pub struct Csg {
+    pub operation: CsgOperation,
+    pub left: Rc<dyn Hittable>,
+    pub right: Rc<dyn Hittable>,
+}
+
+/// Stops following a ray through a shape with this many surfaces along it, which guards against
+/// getting stuck on a surface that keeps being hit again.
+const MAX_CROSSINGS: usize = 64;
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Rc<dyn Hittable>, right: Rc<dyn Hittable>) -> Self {
+        Self {
+            operation,
+            left,
+            right,
+        }
+    }
+
+    pub fn union(left: Rc<dyn Hittable>, right: Rc<dyn Hittable>) -> Self {
+        Self::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(left: Rc<dyn Hittable>, right: Rc<dyn Hittable>) -> Self {
+        Self::new(CsgOperation::Intersection, left, right)
+    }
+
+    pub fn difference(left: Rc<dyn Hittable>, right: Rc<dyn Hittable>) -> Self {
+        Self::new(CsgOperation::Difference, left, right)
+    }
+}
+
+/// The crossings of the whole line along `r` with the surface of `object`, nearest first and
+/// with `t` measured along `r`, found one at a time as they are needed. Whether `front_face` is
+/// set tells entries from exits.
+fn crossings(object: &dyn Hittable, r: Ray) -> impl Iterator<Item = HitRecord> + '_ {
+    iter::successors(
+        object.hit(r, Float::NEG_INFINITY, Float::INFINITY),
+        move |rec| {
+            // Continue from just past this crossing, like ConstantMedium looks for its exit.
+            let next = object.hit(rec.spawn_ray(r.direction, r.time), 0.0, Float::INFINITY)?;
+            Some(HitRecord {
+                t: rec.t + next.t,
+                ..next
+            })
+        },
+    )
+    .take(MAX_CROSSINGS)
+}
+
+impl Csg {
+    /// The first crossing of `r` with the surface of the result within `[t_min, t_max]`, and
+    /// whether the ray is inside the result after it.
+    fn surface(&self, r: Ray, t_min: Float, t_max: Float) -> Option<(HitRecord, bool)> {
+        let mut left = crossings(self.left.as_ref(), r).peekable();
+        let mut right = crossings(self.right.as_ref(), r).peekable();
+
+        // A line that first leaves a child started out inside it.
+        let mut inside_left = left.peek().is_some_and(|rec| !rec.front_face);
+        let mut inside_right = right.peek().is_some_and(|rec| !rec.front_face);
+        let mut inside = self.operation.contains(inside_left, inside_right);
+
+        // Walk both children's crossings in order of t, up to `t_max`. Only crossings that
+        // change whether the line is inside the result are on its surface.
+        loop {
+            let from_left = match (left.peek(), right.peek()) {
+                (Some(a), Some(b)) => a.t <= b.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return None,
+            };
+            let rec = if from_left {
+                let rec = left.next()?;
+                inside_left = rec.front_face;
+                rec
+            } else {
+                let rec = right.next()?;
+                inside_right = rec.front_face;
+                rec
+            };
+            if rec.t > t_max {
+                return None;
+            }
+
+            let now_inside = self.operation.contains(inside_left, inside_right);
+            if now_inside != inside && rec.t >= t_min {
+                return Some((rec, now_inside));
+            }
+            inside = now_inside;
+        }
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (rec, now_inside) = self.surface(r, t_min, t_max)?;
+        // The normal already faces against the ray, so only which side of the result it is on
+        // can change, e.g. leaving the bite means entering the bitten box.
+        Some(HitRecord {
+            front_face: now_inside,
+            ..rec
+        })
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.surface(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<Aabb> {
+        let left = self.left.bounding_box(time0, time1);
+        let right = self.right.bounding_box(time0, time1);
+        match self.operation {
+            CsgOperation::Union => Some(left? + right?),
+            CsgOperation::Intersection => match (left, right) {
+                (Some(a), Some(b)) => Some(Aabb::new(a.min.max(b.min), a.max.min(b.max))),
+                (a, b) => a.or(b),
+            },
+            CsgOperation::Difference => left,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+    use crate::{
+        float::{vec3, Vec3},
+        hittable::{sphere::Sphere, ConstantMedium, GeometricBox},
+        material::{Lambertian, Material},
+    };
+
+    fn same(mat: &Option<Rc<dyn Material>>, expected: &Rc<dyn Material>) -> bool {
+        mat.as_ref()
+            .is_some_and(|mat| ptr::addr_eq(Rc::as_ptr(mat), Rc::as_ptr(expected)))
+    }
+
+    /// A box from -1 to 1, and a sphere taking a bite out of the middle of its +x face.
+    fn box_and_sphere() -> (Rc<GeometricBox>, Rc<Sphere>, [Rc<dyn Material>; 2]) {
+        let stone: Rc<dyn Material> = Rc::new(Lambertian::from_color(Vec3::splat(0.5)));
+        let moss: Rc<dyn Material> = Rc::new(Lambertian::from_color(vec3(0.1, 0.5, 0.1)));
+        let cube = Rc::new(GeometricBox::new(-Vec3::ONE, Vec3::ONE, stone.clone()));
+        let sphere = Rc::new(Sphere::new(vec3(1.0, 0.0, 0.0), 0.5, moss.clone()));
+        (cube, sphere, [stone, moss])
+    }
+
+    #[test]
+    fn surfaces_of_the_result_keep_their_childrens_materials() {
+        let (cube, sphere, [stone, moss]) = box_and_sphere();
+        let difference = Csg::difference(cube.clone(), sphere.clone());
+        let union = Csg::union(cube.clone(), sphere.clone());
+        let intersection = Csg::intersection(cube, sphere);
+
+        let x = vec3(1.0, 0.0, 0.0);
+        for (name, csg, origin, direction, expected) in [
+            (
+                "away from the bite",
+                &difference,
+                -5.0 * x,
+                x,
+                Some((4.0, true, -x, &stone)),
+            ),
+            (
+                "into the bite",
+                &difference,
+                5.0 * x,
+                -x,
+                Some((4.5, true, x, &moss)),
+            ),
+            (
+                "from inside the bite",
+                &difference,
+                0.8 * x,
+                -x,
+                Some((0.3, true, x, &moss)),
+            ),
+            (
+                "from inside the box",
+                &difference,
+                Vec3::ZERO,
+                x,
+                Some((0.5, false, x, &moss)),
+            ),
+            (
+                "out of the box",
+                &difference,
+                Vec3::ZERO,
+                -x,
+                Some((1.0, false, -x, &stone)),
+            ),
+            (
+                "through the bite",
+                &difference,
+                vec3(1.3, 0.0, 5.0),
+                -Vec3::Z,
+                None,
+            ),
+            ("union", &union, 5.0 * x, -x, Some((3.5, true, x, &moss))),
+            (
+                "union from inside",
+                &union,
+                Vec3::ZERO,
+                x,
+                Some((1.5, false, x, &moss)),
+            ),
+            (
+                "intersection",
+                &intersection,
+                5.0 * x,
+                -x,
+                Some((4.0, true, x, &stone)),
+            ),
+            (
+                "intersection",
+                &intersection,
+                -5.0 * x,
+                x,
+                Some((5.5, true, -x, &moss)),
+            ),
+            (
+                "outside the intersection",
+                &intersection,
+                -5.0 * x,
+                Vec3::Y,
+                None,
+            ),
+        ] {
+            let r = Ray::new(origin, direction, 0.0);
+            let rec = csg.hit(r, 0.0, Float::INFINITY);
+            assert_eq!(
+                csg.occluded(r, 0.0, Float::INFINITY),
+                rec.is_some(),
+                "{name}"
+            );
+            match (rec, expected) {
+                (Some(rec), Some((t, front_face, outward, mat))) => {
+                    assert!((rec.t - t).abs() < 1e-6, "{name}: {} != {t}", rec.t);
+                    assert_eq!(rec.front_face, front_face, "{name}");
+                    // Normals face against the ray, and out of the result on front faces.
+                    assert!(rec.normal.dot(direction) < 0.0, "{name}");
+                    let normal = if front_face { outward } else { -outward };
+                    assert!(
+                        rec.normal.abs_diff_eq(normal, 1e-6),
+                        "{name}: {}",
+                        rec.normal
+                    );
+                    assert!(same(&rec.mat, mat), "{name}");
+
+                    // Stopping short of the surface misses it.
+                    let short = t - 0.01;
+                    assert!(csg.hit(r, 0.0, short).is_none(), "{name}");
+                    assert!(!csg.occluded(r, 0.0, short), "{name}");
+                }
+                (None, None) => {}
+                (rec, _) => panic!("{name}: {:?}", rec.map(|rec| rec.t)),
+            }
+        }
+    }
+
+    #[test]
+    fn csg_bounds_a_medium() {
+        let (cube, sphere, _) = box_and_sphere();
+        let bitten = Rc::new(Csg::difference(cube, sphere));
+        // So dense that rays scatter right where they enter.
+        let fog = ConstantMedium::from_color(bitten, 1e6, Vec3::ONE);
+
+        let x = vec3(1.0, 0.0, 0.0);
+        for (name, origin, direction, expected) in [
+            ("into the bite", 5.0 * x, -x, Some(4.5)),
+            ("from inside the bite", 0.8 * x, -x, Some(0.3)),
+            ("from inside the box", Vec3::ZERO, x, Some(0.0)),
+            ("through the bite", vec3(1.3, 0.0, 5.0), -Vec3::Z, None),
+        ] {
+            let r = Ray::new(origin, direction, 0.0);
+            let t = fog.hit(r, 0.0, Float::INFINITY).map(|rec| rec.t);
+            match (t, expected) {
+                (Some(t), Some(expected)) => {
+                    assert!(
+                        (expected - 1e-5..expected + 1e-4).contains(&t),
+                        "{name}: {t}"
+                    )
+                }
+                (None, None) => {}
+                _ => panic!("{name}: {t:?}"),
+            }
+        }
+    }
+}
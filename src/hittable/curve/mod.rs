@@ -0,0 +1,593 @@
+//! Thin curves for hair and fur.
+//!
+//! A curve is a cubic Bézier swept out to a width that changes linearly along it. Rays are
+//! intersected with it in a space where the ray runs down the z axis, by subdividing the curve
+//! until its pieces are close enough to straight lines, as in pbrt. Strands of hair take
+//! millions of them, so [`Curves`] keeps them in plain arrays under a BVH of its own instead of
+//! making each one a separate [`Hittable`].
+
+mod strand;
+
+pub use strand::{load_strands, Fur, Strand};
+
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb,
+    float::{consts, gamma, vec3, Float, Vec3},
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+};
+
+use super::bvh::{BvhBuilder, LinearBvh};
+
+/// How the width of a curve is turned into a surface.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CurveKind {
+    /// A flat strip that always faces the ray.
+    Flat,
+    /// A flat strip that faces the ray like [`CurveKind::Flat`], but whose normal turns across
+    /// its width as if it were round. Cheaper than a real tube and much the same from afar.
+    Tube,
+    /// A strip with a fixed orientation, whose normal turns from the first to the second given
+    /// one along each curve, like a blade of grass. Holds one pair of normals per curve.
+    Ribbon(Vec<[Vec3; 2]>),
+}
+
+/// A cubic Bézier from `points[0]` to `points[3]`, `widths[0]` wide at the start and
+/// `widths[1]` at the end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Curve {
+    pub points: [Vec3; 4],
+    pub widths: [Float; 2],
+}
+
+impl Curve {
+    pub fn new(points: [Vec3; 4], widths: [Float; 2]) -> Self {
+        Self { points, widths }
+    }
+
+    fn width(&self, u: Float) -> Float {
+        lerp(u, self.widths[0], self.widths[1])
+    }
+
+    /// How many pieces to bound separately. Long thin curves running diagonally fill only a
+    /// sliver of their boxes, which splitting them up shrinks.
+    fn pieces(&self) -> usize {
+        let [p0, p1, p2, p3] = self.points;
+        let length = (p1 - p0).length() + (p2 - p1).length() + (p3 - p2).length();
+        let width = self.widths[0].max(self.widths[1]);
+        ((length / (MAX_PIECE_ASPECT * width)).ceil() as usize).clamp(1, MAX_PIECES)
+    }
+
+    /// Bounds the part of the curve from `u[0]` to `u[1]`.
+    fn bounds(&self, u: [Float; 2]) -> Aabb {
+        let points = segment(&self.points, u);
+        let half_width = 0.5 * self.width(u[0]).max(self.width(u[1]));
+        let (min, max) = points
+            .iter()
+            .fold((points[0], points[0]), |(min, max), &p| {
+                (min.min(p), max.max(p))
+            });
+        Aabb::new(min - Vec3::splat(half_width), max + Vec3::splat(half_width))
+    }
+}
+
+/// Longest a piece of a curve may be, in multiples of its width, before it is split up.
+const MAX_PIECE_ASPECT: Float = 16.0;
+const MAX_PIECES: usize = 8;
+
+/// Subdivisions deep enough for the pieces to be within 5% of the width from straight lines,
+/// but never more than this.
+const MAX_DEPTH: u32 = 10;
+
+/// Many curves sharing a material, such as all the hairs on a head.
+///
+/// The curves are cut into pieces with tight bounds, which go into a BVH of their own, so each
+/// piece takes a few numbers instead of a separate object and millions of them still fit.
+pub struct Curves {
+    curves: Vec<Curve>,
+    kind: CurveKind,
+    /// The pieces in the order of the BVH leaves.
+    pieces: Vec<Piece>,
+    /// `None` when there are no curves to build it from.
+    bvh: Option<LinearBvh>,
+    pub mat: Rc<dyn Material>,
+}
+
+/// The part of curve `curve` from `u[0]` to `u[1]`.
+#[derive(Debug, Clone, Copy)]
+struct Piece {
+    curve: u32,
+    u: [Float; 2],
+}
+
+/// Where a ray hits a curve, with `u` along it and `v` across it.
+#[derive(Debug, Clone, Copy)]
+struct CurveHit {
+    t: Float,
+    u: Float,
+    v: Float,
+    /// The width of the curve at the hit, as seen along the ray.
+    width: Float,
+    /// The normal of a ribbon at the hit.
+    normal: Vec3,
+}
+
+impl Curves {
+    pub fn new(curves: Vec<Curve>, kind: CurveKind, mat: Rc<dyn Material>) -> Self {
+        if let CurveKind::Ribbon(normals) = &kind {
+            assert_eq!(
+                normals.len(),
+                curves.len(),
+                "ribbons need a pair of normals for every curve"
+            );
+        }
+
+        let mut pieces = Vec::new();
+        let mut bounds = Vec::new();
+        for (index, curve) in curves.iter().enumerate() {
+            let count = curve.pieces();
+            for i in 0..count {
+                let u = [
+                    i as Float / count as Float,
+                    (i + 1) as Float / count as Float,
+                ];
+                bounds.push(curve.bounds(u));
+                pieces.push(Piece {
+                    curve: index as u32,
+                    u,
+                });
+            }
+        }
+
+        let bvh = if bounds.is_empty() {
+            None
+        } else {
+            let tree = BvhBuilder::new().build(&bounds);
+            pieces = tree.order.iter().map(|&i| pieces[i]).collect();
+            Some(LinearBvh::new(&tree))
+        };
+        Self {
+            curves,
+            kind,
+            pieces,
+            bvh,
+            mat,
+        }
+    }
+
+    /// Intersects `r` with one piece of a curve.
+    fn intersect(&self, piece: Piece, r: Ray, t_min: Float, t_max: Float) -> Option<CurveHit> {
+        let curve = &self.curves[piece.curve as usize];
+        let frame = RayFrame::new(r, &curve.points);
+        let points = segment(&curve.points, piece.u).map(|p| frame.to_local(p));
+
+        // Subdivide until the pieces are close enough to straight, going by how far the
+        // control points are from lying on a line.
+        let bend = (0..2)
+            .map(|i| {
+                (points[i] - 2.0 * points[i + 1] + points[i + 2])
+                    .abs()
+                    .max_element()
+            })
+            .fold(0.0, Float::max);
+        let epsilon = 0.05 * curve.widths[0].max(curve.widths[1]);
+        let ratio = consts::SQRT_2 * 6.0 * bend / (8.0 * epsilon);
+        let depth = if ratio >= 1.0 {
+            (ratio.log2() as u32 / 2).min(MAX_DEPTH)
+        } else {
+            0
+        };
+
+        let normals = match &self.kind {
+            CurveKind::Ribbon(normals) => Some(normals[piece.curve as usize]),
+            _ => None,
+        };
+        let length = r.direction.length();
+        let search = Search {
+            curve,
+            normals,
+            direction: r.direction / length,
+            z_min: t_min * length,
+        };
+        search
+            .recurse(&points, piece.u, depth, t_max * length)
+            .map(|hit| CurveHit {
+                t: hit.t / length,
+                ..hit
+            })
+    }
+
+    fn hit_record(&self, piece: Piece, hit: CurveHit, r: Ray) -> HitRecord {
+        let curve = &self.curves[piece.curve as usize];
+        let (_, dpdu) = evaluate(&curve.points, hit.u);
+        let frame = RayFrame::new(r, &curve.points);
+
+        // The direction across the curve, which `v` increases along.
+        let across = match self.kind {
+            CurveKind::Ribbon(_) => hit.normal.cross(dpdu),
+            CurveKind::Flat | CurveKind::Tube => {
+                let local = frame.to_local_vector(dpdu);
+                frame.to_world_vector(vec3(-local.y, local.x, 0.0))
+            }
+        }
+        .try_normalize()
+        .unwrap_or(frame.x);
+
+        let point = r.at(hit.t);
+        let mut rec = HitRecord {
+            point,
+            // The hit is only known to be somewhere across the width of the curve, and the ray
+            // parameter to it is rounded on top of that.
+            point_error: Vec3::splat(2.0 * hit.width)
+                + gamma(3) * (r.origin.abs() + (hit.t * r.direction).abs()),
+            mat: Some(self.mat.clone()),
+            tangent: dpdu.normalize(),
+            t: hit.t,
+            u: hit.u,
+            v: hit.v,
+            ..Default::default()
+        }
+        .with_face_normal(r, dpdu.cross(across).normalize());
+
+        if matches!(self.kind, CurveKind::Tube) {
+            // The normal of a round tube seen from along the ray, at the same offset from its
+            // middle.
+            let h = 2.0 * hit.v - 1.0;
+            rec.normal = h * across + (1.0 - h * h).max(0.0).sqrt() * rec.normal;
+        }
+        rec
+    }
+}
+
+impl Hittable for Curves {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (piece, hit) = self
+            .bvh
+            .as_ref()?
+            .closest_hit(r, t_min, t_max, |i, t_max| {
+                let piece = self.pieces[i];
+                self.intersect(piece, r, t_min, t_max)
+                    .map(|hit| ((piece, hit), hit.t))
+            })?;
+        Some(self.hit_record(piece, hit, r))
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.bvh.as_ref().is_some_and(|bvh| {
+            bvh.any_hit(r, t_min, t_max, |i| {
+                self.intersect(self.pieces[i], r, t_min, t_max).is_some()
+            })
+        })
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        self.bvh.as_ref().map(LinearBvh::aabb)
+    }
+}
+
+/// A frame with the ray's origin at its origin and its direction along z, in which a curve hit
+/// by the ray passes within half its width of the z axis.
+struct RayFrame {
+    origin: Vec3,
+    x: Vec3,
+    y: Vec3,
+    z: Vec3,
+}
+
+impl RayFrame {
+    /// Lines x up with the curve from its start to its end where possible, which keeps the
+    /// bounds of its pieces tight.
+    fn new(r: Ray, points: &[Vec3; 4]) -> Self {
+        let z = r.direction.normalize();
+        let chord = points[3] - points[0];
+        let x = (chord - chord.dot(z) * z)
+            .try_normalize()
+            .unwrap_or_else(|| z.any_orthonormal_pair().0);
+        Self {
+            origin: r.origin,
+            x,
+            y: z.cross(x),
+            z,
+        }
+    }
+
+    fn to_local(&self, p: Vec3) -> Vec3 {
+        self.to_local_vector(p - self.origin)
+    }
+
+    fn to_local_vector(&self, v: Vec3) -> Vec3 {
+        vec3(v.dot(self.x), v.dot(self.y), v.dot(self.z))
+    }
+
+    fn to_world_vector(&self, v: Vec3) -> Vec3 {
+        v.x * self.x + v.y * self.y + v.z * self.z
+    }
+}
+
+/// What stays the same while subdividing one curve for one ray. Distances along the ray are
+/// in world units here, as `z` in its [`RayFrame`].
+struct Search<'a> {
+    curve: &'a Curve,
+    normals: Option<[Vec3; 2]>,
+    /// The unit direction of the ray.
+    direction: Vec3,
+    z_min: Float,
+}
+
+impl Search<'_> {
+    /// Finds the nearest hit up to `z_max` of the part of the curve from `u[0]` to `u[1]`,
+    /// whose control points in the ray frame are `points`. The returned `t` is still a `z`.
+    fn recurse(
+        &self,
+        points: &[Vec3; 4],
+        u: [Float; 2],
+        depth: u32,
+        z_max: Float,
+    ) -> Option<CurveHit> {
+        // Skip pieces whose widened bounds miss the ray.
+        let half_width = 0.5 * self.curve.width(u[0]).max(self.curve.width(u[1]));
+        let (min, max) = points
+            .iter()
+            .fold((points[0], points[0]), |(min, max), &p| {
+                (min.min(p), max.max(p))
+            });
+        if max.x + half_width < 0.0
+            || min.x - half_width > 0.0
+            || max.y + half_width < 0.0
+            || min.y - half_width > 0.0
+            || max.z + half_width < self.z_min
+            || min.z - half_width > z_max
+        {
+            return None;
+        }
+
+        if depth > 0 {
+            let split = subdivide(points);
+            let middle = 0.5 * (u[0] + u[1]);
+            let first = [split[0], split[1], split[2], split[3]];
+            let second = [split[3], split[4], split[5], split[6]];
+            let near = self.recurse(&first, [u[0], middle], depth - 1, z_max);
+            let z_max = near.map_or(z_max, |hit| hit.t);
+            return self
+                .recurse(&second, [middle, u[1]], depth - 1, z_max)
+                .or(near);
+        }
+        self.leaf(points, u, z_max)
+    }
+
+    /// Intersects a piece that is close enough to a straight line.
+    fn leaf(&self, points: &[Vec3; 4], u: [Float; 2], z_max: Float) -> Option<CurveHit> {
+        let [p0, p1, p2, p3] = *points;
+        // The ray has to pass between the lines through the ends perpendicular to the curve,
+        // or it would hit the next piece instead.
+        if (p1.y - p0.y) * -p0.y + p0.x * (p0.x - p1.x) < 0.0
+            || (p2.y - p3.y) * -p3.y + p3.x * (p3.x - p2.x) < 0.0
+        {
+            return None;
+        }
+
+        // The closest point to the ray on the line from start to end.
+        let (dx, dy) = (p3.x - p0.x, p3.y - p0.y);
+        let denominator = dx * dx + dy * dy;
+        if denominator == 0.0 {
+            return None;
+        }
+        let w = (-p0.x * dx - p0.y * dy) / denominator;
+        let hit_u = lerp(w, u[0], u[1]).clamp(u[0], u[1]);
+        let mut width = self.curve.width(hit_u);
+
+        let mut normal = Vec3::ZERO;
+        if let Some(normals) = self.normals {
+            normal = slerp(hit_u, normals);
+            // A ribbon seen edge-on is narrower.
+            width *= normal.dot(self.direction).abs();
+        }
+
+        let (point, derivative) = evaluate(points, w.clamp(0.0, 1.0));
+        let distance_squared = point.x * point.x + point.y * point.y;
+        if distance_squared > 0.25 * width * width || point.z < self.z_min || point.z > z_max {
+            return None;
+        }
+
+        // Which side of the curve the ray passes on decides which half of [0, 1] `v` is in.
+        let distance = distance_squared.sqrt();
+        let side = derivative.x * -point.y + point.x * derivative.y;
+        let v = if side > 0.0 {
+            0.5 + distance / width
+        } else {
+            0.5 - distance / width
+        };
+        Some(CurveHit {
+            t: point.z,
+            u: hit_u,
+            v,
+            width,
+            normal,
+        })
+    }
+}
+
+fn lerp(t: Float, a: Float, b: Float) -> Float {
+    (1.0 - t) * a + t * b
+}
+
+/// Interpolates between two unit normals at a constant angular speed.
+fn slerp(t: Float, [n0, n1]: [Vec3; 2]) -> Vec3 {
+    let angle = n0.dot(n1).clamp(-1.0, 1.0).acos();
+    if angle < 1e-4 {
+        return n0;
+    }
+    let sin = angle.sin();
+    ((1.0 - t) * angle).sin() / sin * n0 + (t * angle).sin() / sin * n1
+}
+
+/// The polar form of a cubic Bézier, which is a point for three parameters and the curve itself
+/// when they are equal.
+fn blossom(points: &[Vec3; 4], u0: Float, u1: Float, u2: Float) -> Vec3 {
+    let lerp = |t: Float, a: Vec3, b: Vec3| (1.0 - t) * a + t * b;
+    let a = [
+        lerp(u0, points[0], points[1]),
+        lerp(u0, points[1], points[2]),
+        lerp(u0, points[2], points[3]),
+    ];
+    let b = [lerp(u1, a[0], a[1]), lerp(u1, a[1], a[2])];
+    lerp(u2, b[0], b[1])
+}
+
+/// The control points of the part of a curve from `u[0]` to `u[1]`.
+fn segment(points: &[Vec3; 4], [u0, u1]: [Float; 2]) -> [Vec3; 4] {
+    [
+        blossom(points, u0, u0, u0),
+        blossom(points, u0, u0, u1),
+        blossom(points, u0, u1, u1),
+        blossom(points, u1, u1, u1),
+    ]
+}
+
+/// Splits a curve in half, into the control points `0..=3` and `3..=6` of the result.
+fn subdivide(p: &[Vec3; 4]) -> [Vec3; 7] {
+    [
+        p[0],
+        (p[0] + p[1]) / 2.0,
+        (p[0] + 2.0 * p[1] + p[2]) / 4.0,
+        (p[0] + 3.0 * p[1] + 3.0 * p[2] + p[3]) / 8.0,
+        (p[1] + 2.0 * p[2] + p[3]) / 4.0,
+        (p[2] + p[3]) / 2.0,
+        p[3],
+    ]
+}
+
+/// The point at `u` and the derivative there.
+fn evaluate(points: &[Vec3; 4], u: Float) -> (Vec3, Vec3) {
+    let lerp = |a: Vec3, b: Vec3| (1.0 - u) * a + u * b;
+    let a = [
+        lerp(points[0], points[1]),
+        lerp(points[1], points[2]),
+        lerp(points[2], points[3]),
+    ];
+    let b = [lerp(a[0], a[1]), lerp(a[1], a[2])];
+    // Where the first or last two control points coincide the derivative vanishes at that
+    // end, but the curve still heads towards the far end.
+    let derivative = if (b[1] - b[0]).length_squared() > 0.0 {
+        3.0 * (b[1] - b[0])
+    } else {
+        points[3] - points[0]
+    };
+    (lerp(b[0], b[1]), derivative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Hair, testing};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// `count` drooping strands of fur, three curves each, on the unit sphere.
+    fn fur(count: usize) -> Curves {
+        let curves = Fur::new(Vec3::ZERO, 1.0, count, 0.3, 0.004)
+            .with_droop(0.5)
+            .with_seed(45)
+            .strands()
+            .iter()
+            .flat_map(Strand::curves)
+            .collect();
+        Curves::new(
+            curves,
+            CurveKind::Tube,
+            Rc::new(Hair::from_melanin(1.3, 0.0)),
+        )
+    }
+
+    /// Rays from all around the fur aimed at the middle of it, where strands are densest.
+    fn rays_into_fur(rng: &mut StdRng, count: usize) -> Vec<Ray> {
+        let mut on_sphere = |radius: Float| {
+            let z: Float = rng.gen_range(-1.0..1.0);
+            let phi = consts::TAU * rng.gen::<Float>();
+            let r = (1.0 - z * z).sqrt();
+            radius * vec3(r * phi.cos(), r * phi.sin(), z)
+        };
+        (0..count)
+            .map(|_| {
+                let origin = on_sphere(3.0);
+                let target = on_sphere(1.15);
+                Ray::new(origin, target - origin, 0.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fur_bvh_finds_the_closest_curve() {
+        let curves = fur(4_000);
+        let mut rng = StdRng::seed_from_u64(45);
+        let mut hits = 0;
+        for r in rays_into_fur(&mut rng, 60) {
+            // Every piece whose bounds the ray passes through, without the tree.
+            let expected = curves
+                .pieces
+                .iter()
+                .filter(|piece| {
+                    let curve = &curves.curves[piece.curve as usize];
+                    curve.bounds(piece.u).hit(r, 0.0, Float::INFINITY)
+                })
+                .filter_map(|&piece| curves.intersect(piece, r, 0.0, Float::INFINITY))
+                .map(|hit| hit.t)
+                .min_by(Float::total_cmp);
+            let hit = curves.hit(r, 0.0, Float::INFINITY);
+            assert_eq!(hit.map(|rec| rec.t), expected);
+            assert_eq!(curves.occluded(r, 0.0, Float::INFINITY), expected.is_some());
+            hits += usize::from(expected.is_some());
+        }
+        assert!(hits > 20, "only {hits} rays hit the fur");
+    }
+
+    /// Fur ten times as dense takes ten times the memory and a tree only a few levels deeper,
+    /// and a ray still only meets a handful of the strands.
+    #[test]
+    fn fur_bvh_scales_to_many_strands() {
+        for count in [2_000, 20_000] {
+            let (curves, bytes) = testing::retained(|| fur(count));
+            let pieces = curves.pieces.len();
+            let stats = curves.bvh.as_ref().unwrap().stats();
+            assert!(pieces >= 3 * count);
+            assert!(bytes < 400 * pieces, "{bytes} bytes for {pieces} pieces");
+            assert!(
+                stats.depth as Float <= 2.0 * (pieces as Float).log2(),
+                "{stats}"
+            );
+            assert!(stats.sah_cost < pieces as Float / 500.0, "{stats}");
+        }
+    }
+
+    /// Grows a million strands of fur, three million curves, and traces a million rays into
+    /// them. Run with `cargo test --release -- --ignored --nocapture bench_`.
+    #[test]
+    #[ignore]
+    fn bench_million_strand_fur() {
+        let start = std::time::Instant::now();
+        let (curves, bytes) = testing::retained(|| fur(1_000_000));
+        let build_time = start.elapsed();
+        let stats = curves.bvh.as_ref().unwrap().stats();
+        println!(
+            "{} curves in {} pieces: built in {build_time:.2?}, {} MB, {stats}",
+            curves.curves.len(),
+            curves.pieces.len(),
+            bytes / 1_000_000
+        );
+
+        let mut rng = StdRng::seed_from_u64(45);
+        let rays = rays_into_fur(&mut rng, 1_000_000);
+        let (hits, time) = testing::fastest(3, || {
+            rays.iter()
+                .filter(|&&r| curves.hit(r, 0.0, Float::INFINITY).is_some())
+                .count()
+        });
+        println!(
+            "{} rays, {hits} hits in {time:.2?} ({:.2} µs per ray)",
+            rays.len(),
+            time.as_secs_f64() * 1e6 / rays.len() as f64
+        );
+    }
+}
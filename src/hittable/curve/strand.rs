@@ -0,0 +1,246 @@
+//! Strands of hair: reading them from files, growing them as fur and turning them into curves.
+
+use std::fs;
+
+use rand::prelude::*;
+
+use crate::float::{consts::TAU, vec3, Float, Vec3};
+
+use super::Curve;
+
+/// One hair, as points from its root to its tip with the width at each.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Strand {
+    pub points: Vec<Vec3>,
+    pub widths: Vec<Float>,
+}
+
+impl Strand {
+    /// One cubic Bézier from each point to the next, with the tangents of a Catmull-Rom spline
+    /// so that they join smoothly.
+    pub fn curves(&self) -> impl Iterator<Item = Curve> + '_ {
+        let p = &self.points;
+        let n = p.len();
+        let tangent = move |i: usize| {
+            if i == 0 {
+                p[1] - p[0]
+            } else if i == n - 1 {
+                p[n - 1] - p[n - 2]
+            } else {
+                0.5 * (p[i + 1] - p[i - 1])
+            }
+        };
+        (0..n.saturating_sub(1)).map(move |i| {
+            Curve::new(
+                [
+                    p[i],
+                    p[i] + tangent(i) / 3.0,
+                    p[i + 1] - tangent(i + 1) / 3.0,
+                    p[i + 1],
+                ],
+                [self.widths[i], self.widths[i + 1]],
+            )
+        })
+    }
+}
+
+/// Reads strands from a Cem Yuksel HAIR file, or otherwise from a text file with one strand per
+/// line, given as `x y z width` for each of its points. Lines starting with `#` are comments.
+pub fn load_strands(filename: &str) -> Result<Vec<Strand>, String> {
+    let bytes = fs::read(filename).map_err(|err| err.to_string())?;
+    if bytes.starts_with(b"HAIR") {
+        parse_hair(&bytes)
+    } else {
+        let src = String::from_utf8(bytes).map_err(|_| "not a HAIR or text file".to_owned())?;
+        parse_text(&src)
+    }
+}
+
+fn parse_text(src: &str) -> Result<Vec<Strand>, String> {
+    let mut strands = Vec::new();
+    for (index, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let numbers = line
+            .split_whitespace()
+            .map(|word| word.parse::<Float>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("line {}: {err}", index + 1))?;
+        if numbers.len() % 4 != 0 || numbers.len() < 8 {
+            return Err(format!(
+                "line {}: expected `x y z width` for at least 2 points",
+                index + 1
+            ));
+        }
+        let mut strand = Strand::default();
+        for point in numbers.chunks_exact(4) {
+            strand.points.push(vec3(point[0], point[1], point[2]));
+            strand.widths.push(point[3]);
+        }
+        strands.push(strand);
+    }
+    Ok(strands)
+}
+
+const HAS_SEGMENTS: u32 = 1 << 0;
+const HAS_POINTS: u32 = 1 << 1;
+const HAS_THICKNESS: u32 = 1 << 2;
+
+/// Parses the binary HAIR format: a 128 byte header followed by arrays of the segment count of
+/// every strand, then all points and their thickness, each of which may be left out in favor
+/// of a default from the header. Transparency and color arrays after those are ignored.
+fn parse_hair(bytes: &[u8]) -> Result<Vec<Strand>, String> {
+    let mut reader = Reader { bytes, offset: 4 };
+    let strand_count = reader.u32()? as usize;
+    let point_count = reader.u32()? as usize;
+    let flags = reader.u32()?;
+    let default_segments = reader.u32()? as usize;
+    let default_thickness = reader.f32()?;
+    if flags & HAS_POINTS == 0 {
+        return Err("the file has no points".to_owned());
+    }
+
+    reader.offset = 128;
+    let segments = if flags & HAS_SEGMENTS != 0 {
+        (0..strand_count)
+            .map(|_| reader.u16().map(usize::from))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        vec![default_segments; strand_count]
+    };
+    if segments.iter().map(|s| s + 1).sum::<usize>() > point_count {
+        return Err("the strands have more points than the file".to_owned());
+    }
+    let points = (0..point_count)
+        .map(|_| Ok(vec3(reader.f32()?, reader.f32()?, reader.f32()?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let thickness = if flags & HAS_THICKNESS != 0 {
+        (0..point_count)
+            .map(|_| reader.f32())
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        vec![default_thickness; point_count]
+    };
+
+    let mut start = 0;
+    let strands = segments
+        .into_iter()
+        .map(|segments| {
+            let range = start..start + segments + 1;
+            start = range.end;
+            Strand {
+                points: points[range.clone()].to_vec(),
+                widths: thickness[range].to_vec(),
+            }
+        })
+        .collect();
+    Ok(strands)
+}
+
+/// Reads little-endian numbers one after another.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset + N)
+            .ok_or("unexpected end of file")?;
+        self.offset += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Result<Float, String> {
+        self.take().map(|bytes| f32::from_le_bytes(bytes) as Float)
+    }
+}
+
+/// Strands scattered evenly over a sphere, each growing out of it and tapering to a tenth of
+/// its width at the tip.
+///
+/// The strands lean a little off the surface normal at random and bend down by `droop`, which
+/// is how much they turn towards -y over their length: 0 for straight hairs sticking out, around
+/// 1 for hairs that hang. The same `seed` always grows the same fur.
+pub struct Fur {
+    pub center: Vec3,
+    pub radius: Float,
+    pub count: usize,
+    pub length: Float,
+    pub width: Float,
+    pub droop: Float,
+    pub seed: u64,
+}
+
+/// Points along each strand of [`Fur`], which makes for three curves.
+const FUR_POINTS: usize = 4;
+
+impl Fur {
+    pub fn new(center: Vec3, radius: Float, count: usize, length: Float, width: Float) -> Self {
+        Self {
+            center,
+            radius,
+            count,
+            length,
+            width,
+            droop: 0.0,
+            seed: 0,
+        }
+    }
+
+    pub fn with_droop(mut self, droop: Float) -> Self {
+        self.droop = droop;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn strands(&self) -> Vec<Strand> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut random_unit_vector = move || {
+            let z: Float = rng.gen_range(-1.0..1.0);
+            let phi = TAU * rng.gen::<Float>();
+            let r = (1.0 - z * z).sqrt();
+            vec3(r * phi.cos(), r * phi.sin(), z)
+        };
+
+        (0..self.count)
+            .map(|_| {
+                let normal = random_unit_vector();
+                let mut direction = (normal + 0.3 * random_unit_vector())
+                    .try_normalize()
+                    .unwrap_or(normal);
+                let step = self.length / (FUR_POINTS - 1) as Float;
+                let bend = self.droop / (FUR_POINTS - 1) as Float;
+
+                let mut strand = Strand::default();
+                let mut point = self.center + self.radius * normal;
+                for i in 0..FUR_POINTS {
+                    let along = i as Float / (FUR_POINTS - 1) as Float;
+                    strand.points.push(point);
+                    strand.widths.push(self.width * (1.0 - 0.9 * along));
+                    point += step * direction;
+                    direction = (direction - bend * Vec3::Y)
+                        .try_normalize()
+                        .unwrap_or(direction);
+                }
+                strand
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,135 @@
+use std::rc::Rc;
+
+use rand::prelude::*;
+
+use crate::{
+    aabb::Aabb,
+    float::{
+        consts::{PI, TAU},
+        vec3, Float, Vec3,
+    },
+    hittable::{HitRecord, Hittable, Light},
+    material::Material,
+    ray::Ray,
+};
+
+use super::plane::{in_plane_point, plane_crossing};
+
+/// How far a disk with the given unit normal and radius reaches from its center along each axis.
+/// It reaches furthest along the axes the normal is most perpendicular to.
+pub(super) fn disk_extent(normal: Vec3, radius: Float) -> Vec3 {
+    let n = normal;
+    radius
+        * vec3(
+            (1.0 - n.x * n.x).max(0.0).sqrt(),
+            (1.0 - n.y * n.y).max(0.0).sqrt(),
+            (1.0 - n.z * n.z).max(0.0).sqrt(),
+        )
+}
+
+/// A flat disk facing along `normal`, or an annulus when `inner_radius` is not 0.
+///
+/// As in pbrt, `u` is the angle around the normal and `v` runs from 0 on the outer edge to 1 on
+/// the inner one.
+pub struct Disk {
+    pub center: Vec3,
+    pub normal: Vec3,
+    pub radius: Float,
+    pub inner_radius: Float,
+    pub mat: Rc<dyn Material>,
+    tangents: (Vec3, Vec3),
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: Float, mat: Rc<dyn Material>) -> Self {
+        let normal = normal.normalize();
+        Self {
+            center,
+            normal,
+            radius,
+            inner_radius: 0.0,
+            mat,
+            tangents: normal.any_orthonormal_pair(),
+        }
+    }
+
+    pub fn with_inner_radius(mut self, inner_radius: Float) -> Self {
+        self.inner_radius = inner_radius;
+        self
+    }
+
+    /// Returns the ray parameter and the coordinates of the hit point along the tangents.
+    fn intersect(&self, r: Ray, t_min: Float, t_max: Float) -> Option<(Float, Float, Float)> {
+        let t = plane_crossing(self.center, self.normal, r, t_min, t_max)?;
+
+        let local = r.at(t) - self.center;
+        let (x, y) = (self.tangents.0.dot(local), self.tangents.1.dot(local));
+        let distance_squared = x * x + y * y;
+        if distance_squared > self.radius * self.radius
+            || distance_squared < self.inner_radius * self.inner_radius
+        {
+            return None;
+        }
+        Some((t, x, y))
+    }
+
+    fn area(&self) -> Float {
+        PI * (self.radius * self.radius - self.inner_radius * self.inner_radius)
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (t, x, y) = self.intersect(r, t_min, t_max)?;
+
+        let (point, point_error) = in_plane_point(self.center, self.tangents, x, y);
+        let phi = y.atan2(x);
+        let phi = if phi < 0.0 { phi + TAU } else { phi };
+        let distance = (x * x + y * y).sqrt();
+        let rec = HitRecord {
+            point,
+            point_error,
+            mat: Some(self.mat.clone()),
+            t,
+            u: phi / TAU,
+            v: (self.radius - distance) / (self.radius - self.inner_radius),
+            ..Default::default()
+        }
+        .with_face_normal(r, self.normal);
+
+        Some(rec)
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        let extent = disk_extent(self.normal, self.radius);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+impl Light for Disk {
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> Float {
+        let Some((t, _, _)) =
+            self.intersect(Ray::new(origin, direction, 0.0), 0.0, Float::INFINITY)
+        else {
+            return 0.0;
+        };
+        let distance_squared = t * t * direction.length_squared();
+        let cosine = self.normal.dot(direction).abs() / direction.length();
+        distance_squared / (cosine * self.area())
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        let mut rng = thread_rng();
+        // Uniform in area: the squared distance from the center is uniform between the radii.
+        let inner_squared = self.inner_radius * self.inner_radius;
+        let r = (inner_squared + rng.gen::<Float>() * (self.radius * self.radius - inner_squared))
+            .sqrt();
+        let phi = TAU * rng.gen::<Float>();
+        let (x, y) = (r * phi.cos(), r * phi.sin());
+        self.center + x * self.tangents.0 + y * self.tangents.1 - origin
+    }
+}
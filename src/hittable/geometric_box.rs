@@ -2,6 +2,7 @@ use std::rc::Rc;
 
 use crate::{
     aabb::Aabb,
+    float::{Float, Vec3},
     hittable::{HitRecord, Hittable},
     hittable::{HittableList, XYRect, XZRect, YZRect},
     material::Material,
@@ -9,13 +10,13 @@ use crate::{
 };
 
 pub struct GeometricBox {
-    pub box_min: glam::DVec3,
-    pub box_max: glam::DVec3,
+    pub box_min: Vec3,
+    pub box_max: Vec3,
     pub sides: HittableList,
 }
 
 impl GeometricBox {
-    pub fn new(p0: glam::DVec3, p1: glam::DVec3, mat: Rc<dyn Material>) -> Self {
+    pub fn new(p0: Vec3, p1: Vec3, mat: Rc<dyn Material>) -> Self {
         let mut sides = HittableList::default();
 
         sides.add(Rc::new(XYRect {
@@ -81,11 +82,21 @@ impl GeometricBox {
 }
 
 impl Hittable for GeometricBox {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        self.sides.hit(r, t_min, t_max)
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let rec = self.sides.hit(r, t_min, t_max)?;
+        // Each side faces the same way as the opposite one, so tell which side of the box was
+        // hit to face the normal out of it, which glass and CSG rely on.
+        let axis = rec.geometric_normal.abs();
+        let center = 0.5 * (self.box_min + self.box_max);
+        let outward = axis * (rec.point - center).dot(axis).signum();
+        Some(rec.with_face_normal(r, outward))
     }
 
-    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.sides.occluded(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
         Some(Aabb::new(self.box_min, self.box_max))
     }
 }
@@ -0,0 +1,437 @@
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb,
+    float::{gamma, vec3, Float, Vec3},
+    hittable::{triangle, HitRecord, Hittable},
+    material::Material,
+    noise::Perlin,
+    ray::Ray,
+    texture::load_image,
+};
+
+/// A grid of heights in `[0, 1]`, `width` samples along x by `depth` along z, stored row by row
+/// along x.
+pub struct HeightMap {
+    pub width: usize,
+    pub depth: usize,
+    pub heights: Vec<Float>,
+}
+
+impl HeightMap {
+    /// Reads the brightness of a grayscale or color image. The top row of the image ends up at
+    /// the far end of z, so an image texture with the same UVs lines up with it.
+    pub fn load(filename: &str) -> Result<Self, String> {
+        let image = load_image(filename)?;
+        if image.width < 2 || image.height < 2 {
+            return Err("a heightmap needs at least 2x2 pixels".to_owned());
+        }
+
+        const SCALE: Float = 1.0 / 255.0;
+        let channels = image.depth;
+        let mut heights = Vec::with_capacity(image.width * image.height);
+        for row in (0..image.height).rev() {
+            for column in 0..image.width {
+                let pixel = &image.data[(row * image.width + column) * channels..][..channels];
+                let brightness = if channels >= 3 {
+                    0.2126 * pixel[0] as Float
+                        + 0.7152 * pixel[1] as Float
+                        + 0.0722 * pixel[2] as Float
+                } else {
+                    pixel[0] as Float
+                };
+                heights.push(brightness * SCALE);
+            }
+        }
+        Ok(Self {
+            width: image.width,
+            depth: image.height,
+            heights,
+        })
+    }
+
+    /// Samples turbulence over the unit square, `scale` noise cells across, and stretches the
+    /// result to cover all of `[0, 1]`.
+    pub fn from_noise(noise: &Perlin, width: usize, depth: usize, scale: Float) -> Self {
+        let mut heights = Vec::with_capacity(width * depth);
+        for z in 0..depth {
+            for x in 0..width {
+                let p = vec3(
+                    x as Float / (width - 1) as Float,
+                    0.0,
+                    z as Float / (depth - 1) as Float,
+                );
+                heights.push(noise.turb(scale * p, 7));
+            }
+        }
+        let min = heights.iter().copied().fold(Float::INFINITY, Float::min);
+        let max = heights
+            .iter()
+            .copied()
+            .fold(Float::NEG_INFINITY, Float::max);
+        let range = if max > min { max - min } else { 1.0 };
+        for h in &mut heights {
+            *h = (*h - min) / range;
+        }
+        Self {
+            width,
+            depth,
+            heights,
+        }
+    }
+}
+
+/// Terrain over the box from `min` to `max`, with a height of 0 at `min.y` and 1 at `max.y`.
+///
+/// Every cell of the grid is split into two triangles, but those are only made up while a ray
+/// walks over the cells below it, so even large maps take little more memory than their heights.
+/// Shading normals are interpolated from the slopes at the grid points, and `u` and `v` run from
+/// 0 to 1 over x and z.
+pub struct Heightfield {
+    width: usize,
+    depth: usize,
+    /// The world-space height of every grid point.
+    heights: Vec<Float>,
+    normals: Vec<Vec3>,
+    cell_size: Vec3,
+    bbox: Aabb,
+    pub mat: Rc<dyn Material>,
+}
+
+impl Heightfield {
+    pub fn new(map: &HeightMap, min: Vec3, max: Vec3, mat: Rc<dyn Material>) -> Self {
+        let (width, depth) = (map.width, map.depth);
+        assert!(
+            width >= 2 && depth >= 2,
+            "heightfield needs at least 2x2 samples"
+        );
+        let heights: Vec<_> = map
+            .heights
+            .iter()
+            .map(|h| min.y + h * (max.y - min.y))
+            .collect();
+        let cell_size = vec3(
+            (max.x - min.x) / (width - 1) as Float,
+            0.0,
+            (max.z - min.z) / (depth - 1) as Float,
+        );
+
+        // The normal of the surface through the neighbors, which is one-sided along the edges.
+        let height = |x: usize, z: usize| heights[z * width + x];
+        let mut normals = Vec::with_capacity(heights.len());
+        for z in 0..depth {
+            for x in 0..width {
+                let (x0, x1) = (x.saturating_sub(1), (x + 1).min(width - 1));
+                let (z0, z1) = (z.saturating_sub(1), (z + 1).min(depth - 1));
+                let slope_x = (height(x1, z) - height(x0, z)) / ((x1 - x0) as Float * cell_size.x);
+                let slope_z = (height(x, z1) - height(x, z0)) / ((z1 - z0) as Float * cell_size.z);
+                normals.push(vec3(-slope_x, 1.0, -slope_z).normalize());
+            }
+        }
+
+        let lowest = heights.iter().copied().fold(Float::INFINITY, Float::min);
+        let highest = heights
+            .iter()
+            .copied()
+            .fold(Float::NEG_INFINITY, Float::max);
+        Self {
+            width,
+            depth,
+            heights,
+            normals,
+            cell_size,
+            bbox: Aabb::new(vec3(min.x, lowest, min.z), vec3(max.x, highest, max.z)),
+            mat,
+        }
+    }
+
+    fn grid_point(&self, x: usize, z: usize) -> Vec3 {
+        vec3(
+            self.bbox.min.x + x as Float * self.cell_size.x,
+            self.heights[z * self.width + x],
+            self.bbox.min.z + z as Float * self.cell_size.z,
+        )
+    }
+
+    /// The two triangles of the cell whose lowest corner is grid point `(x, z)`, both facing up,
+    /// as the indices of their grid points.
+    fn cell_triangles(&self, x: usize, z: usize) -> [[usize; 3]; 2] {
+        let p00 = z * self.width + x;
+        let (p10, p01, p11) = (p00 + 1, p00 + self.width, p00 + self.width + 1);
+        [[p00, p11, p10], [p00, p01, p11]]
+    }
+
+    fn vertex(&self, index: usize) -> Vec3 {
+        self.grid_point(index % self.width, index / self.width)
+    }
+
+    /// Walks the cells under `r` in order with a 2D DDA and returns the first triangle hit, as
+    /// its grid point indices, the ray parameter and the barycentric coordinates of its second
+    /// and third vertex.
+    fn intersect(
+        &self,
+        r: Ray,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<([usize; 3], Float, Float, Float)> {
+        let (t_enter, t_exit) = self.bbox.clip(r, t_min, t_max)?;
+        let (o, d) = (r.origin, r.direction);
+        let (min, size) = (self.bbox.min, self.cell_size);
+        let cells = [self.width - 1, self.depth - 1];
+
+        // The cell of the entry point, and where the ray crosses into the next one along x and
+        // along z.
+        let entry = r.at(t_enter);
+        let mut cell = [
+            (((entry.x - min.x) / size.x) as usize).min(cells[0] - 1),
+            (((entry.z - min.z) / size.z) as usize).min(cells[1] - 1),
+        ];
+        let mut t_next = [0.0; 2];
+        let mut t_delta = [0.0; 2];
+        let mut step = [0isize; 2];
+        let axes = [(o.x, d.x, min.x, size.x), (o.z, d.z, min.z, size.z)];
+        for (axis, (origin, direction, start, spacing)) in axes.into_iter().enumerate() {
+            if direction == 0.0 {
+                t_next[axis] = Float::INFINITY;
+                t_delta[axis] = Float::INFINITY;
+            } else {
+                let forward = direction > 0.0;
+                let boundary = start + (cell[axis] + forward as usize) as Float * spacing;
+                t_next[axis] = (boundary - origin) / direction;
+                t_delta[axis] = spacing / direction.abs();
+                step[axis] = if forward { 1 } else { -1 };
+            }
+        }
+
+        let mut t = t_enter;
+        loop {
+            let axis = if t_next[0] < t_next[1] { 0 } else { 1 };
+            let t_leave = t_next[axis].min(t_exit);
+
+            // Skip cells the ray passes entirely above or below.
+            let [x, z] = cell;
+            let corners = [
+                self.heights[z * self.width + x],
+                self.heights[z * self.width + x + 1],
+                self.heights[(z + 1) * self.width + x],
+                self.heights[(z + 1) * self.width + x + 1],
+            ];
+            let (y0, y1) = (r.at(t).y, r.at(t_leave).y);
+            // Both the ray parameters of the cell's sides and the heights at them are rounded.
+            let slack = gamma(8) * (o.y.abs() + d.y.abs() * t.abs().max(t_leave.abs()));
+            let ray_low = y0.min(y1) - slack;
+            let ray_high = y0.max(y1) + slack;
+            let cell_low = corners.iter().copied().fold(Float::INFINITY, Float::min);
+            let cell_high = corners
+                .iter()
+                .copied()
+                .fold(Float::NEG_INFINITY, Float::max);
+            if ray_high >= cell_low && ray_low <= cell_high {
+                let mut nearest = None;
+                let mut t_max = t_max;
+                for indices in self.cell_triangles(x, z) {
+                    let vertices = indices.map(|i| self.vertex(i));
+                    if let Some((t, b1, b2)) = triangle::intersect(r, vertices, t_min, t_max) {
+                        nearest = Some((indices, t, b1, b2));
+                        t_max = t;
+                    }
+                }
+                if nearest.is_some() {
+                    return nearest;
+                }
+            }
+
+            if t_next[axis] > t_exit {
+                return None;
+            }
+            let next = cell[axis] as isize + step[axis];
+            if next < 0 || next as usize >= cells[axis] {
+                return None;
+            }
+            cell[axis] = next as usize;
+            t = t_next[axis];
+            t_next[axis] += t_delta[axis];
+        }
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (indices, t, b1, b2) = self.intersect(r, t_min, t_max)?;
+        let [p0, p1, p2] = indices.map(|i| self.vertex(i));
+        let b0 = 1.0 - b1 - b2;
+
+        // Interpolating the vertices is more accurate than the ray parameter.
+        let point = b0 * p0 + b1 * p1 + b2 * p2;
+        let point_error = gamma(7) * ((b0 * p0).abs() + (b1 * p1).abs() + (b2 * p2).abs());
+        let extent = self.bbox.max - self.bbox.min;
+        let mut rec = HitRecord {
+            point,
+            point_error,
+            mat: Some(self.mat.clone()),
+            t,
+            u: ((point.x - self.bbox.min.x) / extent.x).clamp(0.0, 1.0),
+            v: ((point.z - self.bbox.min.z) / extent.z).clamp(0.0, 1.0),
+            ..Default::default()
+        }
+        .with_face_normal(r, (p1 - p0).cross(p2 - p0).normalize());
+
+        let [n0, n1, n2] = indices.map(|i| self.normals[i]);
+        let shading_normal = (b0 * n0 + b1 * n1 + b2 * n2).normalize();
+        // Keep the shading normal on the same side as the geometric one.
+        rec.normal = if shading_normal.dot(rec.normal) < 0.0 {
+            -shading_normal
+        } else {
+            shading_normal
+        };
+        Some(rec)
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::material::Lambertian;
+
+    const MIN: Vec3 = vec3(-2.0, -1.0, -3.0);
+    const MAX: Vec3 = vec3(3.0, 1.0, 2.0);
+
+    fn heightfield(
+        width: usize,
+        depth: usize,
+        height: impl Fn(usize, usize) -> Float,
+    ) -> Heightfield {
+        let map = HeightMap {
+            width,
+            depth,
+            heights: (0..depth)
+                .flat_map(|z| (0..width).map(move |x| (x, z)))
+                .map(|(x, z)| height(x, z))
+                .collect(),
+        };
+        Heightfield::new(&map, MIN, MAX, Rc::new(Lambertian::from_color(Vec3::ONE)))
+    }
+
+    /// The nearest hit of `r` with any triangle of any cell.
+    fn brute_force(field: &Heightfield, r: Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        let mut t_max = t_max;
+        let mut nearest = None;
+        for z in 0..field.depth - 1 {
+            for x in 0..field.width - 1 {
+                for indices in field.cell_triangles(x, z) {
+                    let vertices = indices.map(|i| field.vertex(i));
+                    if let Some((t, _, _)) = triangle::intersect(r, vertices, t_min, t_max) {
+                        (nearest, t_max) = (Some(t), t);
+                    }
+                }
+            }
+        }
+        nearest
+    }
+
+    fn assert_matches_brute_force(name: &str, field: &Heightfield, r: Ray) -> bool {
+        let expected = brute_force(field, r, 0.0, Float::INFINITY);
+        let t = field.hit(r, 0.0, Float::INFINITY).map(|rec| rec.t);
+        match (t, expected) {
+            (Some(t), Some(expected)) => {
+                assert!(
+                    (t - expected).abs() <= 1e-9 * expected.max(1.0),
+                    "{name}: {t} != {expected} for {r:?}"
+                )
+            }
+            (None, None) => {}
+            _ => panic!("{name}: {t:?} != {expected:?} for {r:?}"),
+        }
+        assert_eq!(
+            field.occluded(r, 0.0, Float::INFINITY),
+            expected.is_some(),
+            "{name}: {r:?}"
+        );
+        expected.is_some()
+    }
+
+    fn random_point(rng: &mut StdRng, min: Vec3, max: Vec3) -> Vec3 {
+        min + rng.gen::<Vec3>() * (max - min)
+    }
+
+    #[test]
+    fn grid_walk_finds_the_nearest_triangle() {
+        let mut rng = StdRng::seed_from_u64(44);
+        let heights: Vec<Float> = (0..9 * 7).map(|_| rng.gen()).collect();
+        let bumpy = heightfield(9, 7, |x, z| heights[z * 9 + x]);
+        let flat = heightfield(5, 4, |_, _| 0.5);
+        assert_eq!(flat.bbox.min.y, flat.bbox.max.y);
+
+        for (name, field) in [("bumpy", &bumpy), ("flat", &flat)] {
+            let (min, max) = (field.bbox.min, field.bbox.max);
+            let center = 0.5 * (min + max);
+            let mut hits = 0;
+            for n in 0..4000 {
+                let target = random_point(&mut rng, min, max);
+                let origin = match n % 8 {
+                    // From beyond each side, above and below.
+                    0 => vec3(min.x - 2.0, rng.gen_range(-2.0..2.0), target.z),
+                    1 => vec3(max.x + 2.0, rng.gen_range(-2.0..2.0), target.z),
+                    2 => vec3(target.x, rng.gen_range(-2.0..2.0), min.z - 2.0),
+                    3 => vec3(target.x, rng.gen_range(-2.0..2.0), max.z + 2.0),
+                    4 => center + vec3(0.0, 3.0, 0.0) + 4.0 * (rng.gen::<Vec3>() - 0.5),
+                    5 => center - vec3(0.0, 3.0, 0.0) + 4.0 * (rng.gen::<Vec3>() - 0.5),
+                    // From inside the box.
+                    _ => random_point(&mut rng, min, max),
+                };
+                let mut direction = target - origin;
+                // Some parallel to x or z, or vertical, with zeros of either sign.
+                match n % 5 {
+                    0 => direction.x = if rng.gen() { 0.0 } else { -0.0 },
+                    1 => direction.z = if rng.gen() { 0.0 } else { -0.0 },
+                    2 => (direction.x, direction.z) = (0.0, -0.0),
+                    _ => {}
+                }
+                if direction == Vec3::ZERO {
+                    continue;
+                }
+                let r = Ray::new(origin, direction, 0.0);
+                hits += assert_matches_brute_force(name, field, r) as usize;
+            }
+            assert!(hits > 1000, "{name}: {hits}");
+        }
+    }
+
+    #[test]
+    fn grazing_rays_along_a_ridge() {
+        // A ridge of height 1 along z in the middle of the map, falling off to 0 on both sides.
+        let field = heightfield(5, 6, |x, _| if x == 2 { 1.0 } else { 0.0 });
+        let ridge_x = 0.5 * (MIN.x + MAX.x);
+        let mut hits = 0;
+        for (y, direction) in [
+            // Along the ridge line, and descending onto it.
+            (MAX.y, Vec3::Z),
+            (MAX.y, -Vec3::Z),
+            (MAX.y + 0.05, vec3(0.0, -0.01, 1.0)),
+            (MAX.y + 0.05, vec3(0.0, -0.01, -1.0)),
+            // Just under the top, crossing it at a shallow angle.
+            (MAX.y - 1e-3, vec3(1e-3, 0.0, 1.0)),
+        ] {
+            for dx in [0.0, 1e-9, -1e-9, -2e-3] {
+                let z = if direction.z > 0.0 {
+                    MIN.z - 1.0
+                } else {
+                    MAX.z + 1.0
+                };
+                let r = Ray::new(vec3(ridge_x + dx, y, z), direction, 0.0);
+                hits += assert_matches_brute_force("ridge", &field, r) as usize;
+            }
+        }
+        assert!(hits > 8, "{hits}");
+    }
+}
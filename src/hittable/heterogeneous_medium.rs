@@ -0,0 +1,396 @@
+use std::{fs, path::Path, rc::Rc};
+
+use rand::random;
+
+use crate::{
+    aabb::Aabb,
+    float::{Float, Vec3},
+    hittable::{constant_medium::inside, HitRecord, Hittable},
+    material::Material,
+    noise::Perlin,
+    ray::Ray,
+};
+
+/// How dense a medium is at each point, along with a bound on that density.
+pub trait DensityField {
+    fn density(&self, point: Vec3) -> Float;
+
+    /// A density that no point exceeds. The closer it is to the real maximum, the fewer steps
+    /// tracking takes.
+    fn majorant(&self) -> Float;
+}
+
+/// Densities on a grid of voxels spanning a box, the unit cube unless given another, interpolated
+/// between the voxel centers and 0 outside the box.
+#[derive(Clone)]
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    /// Along x first, then y and then z.
+    densities: Vec<Float>,
+    bounds: Aabb,
+    majorant: Float,
+}
+
+impl VoxelGrid {
+    pub fn new(resolution: [usize; 3], densities: Vec<Float>) -> Self {
+        assert_eq!(
+            densities.len(),
+            resolution.iter().product(),
+            "voxel grid needs a density for every voxel"
+        );
+        let majorant = densities.iter().copied().fold(0.0, Float::max);
+        Self {
+            resolution,
+            densities,
+            bounds: Aabb::new(Vec3::ZERO, Vec3::ONE),
+            majorant,
+        }
+    }
+
+    /// Reads the densities of a grid with `resolution` voxels along each axis, ordered along x
+    /// first, then y and then z.
+    ///
+    /// A `.raw` file holds them as bytes, read as fractions of 255, or as little-endian 32-bit
+    /// floats, told apart by the size of the file. Any other file is text with the densities
+    /// separated by whitespace, where lines starting with `#` are comments.
+    pub fn load(filename: &str, resolution: [usize; 3]) -> Result<Self, String> {
+        let count: usize = resolution.iter().product();
+        let bytes = fs::read(filename).map_err(|err| err.to_string())?;
+        let densities = if Path::new(filename)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("raw"))
+        {
+            if bytes.len() == count {
+                bytes.iter().map(|&b| b as Float / 255.0).collect()
+            } else if bytes.len() == 4 * count {
+                bytes
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as Float)
+                    .collect()
+            } else {
+                return Err(format!(
+                    "expected {count} bytes or {count} floats of 4 bytes each, found {} bytes",
+                    bytes.len()
+                ));
+            }
+        } else {
+            let src = String::from_utf8(bytes).map_err(|_| "not a raw or text file".to_owned())?;
+            let densities = src
+                .lines()
+                .filter(|line| !line.trim_start().starts_with('#'))
+                .flat_map(str::split_whitespace)
+                .map(|word| word.parse::<Float>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| err.to_string())?;
+            if densities.len() != count {
+                return Err(format!(
+                    "expected {count} densities, found {}",
+                    densities.len()
+                ));
+            }
+            densities
+        };
+
+        if let Some(bad) = densities.iter().find(|d| !d.is_finite() || **d < 0.0) {
+            return Err(format!("density {bad} is not a non-negative number"));
+        }
+        Ok(Self::new(resolution, densities))
+    }
+
+    pub fn with_bounds(mut self, bounds: Aabb) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    /// Multiplies every density by `scale`.
+    pub fn scaled(mut self, scale: Float) -> Self {
+        for density in &mut self.densities {
+            *density *= scale;
+        }
+        self.majorant *= scale;
+        self
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> Float {
+        let [nx, ny, _] = self.resolution;
+        self.densities[(z * ny + y) * nx + x]
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, point: Vec3) -> Float {
+        let extent = self.bounds.max - self.bounds.min;
+        let local = (point - self.bounds.min) / extent;
+        if local.cmplt(Vec3::ZERO).any() || local.cmpgt(Vec3::ONE).any() {
+            return 0.0;
+        }
+
+        // Trilinear interpolation between the eight nearest voxel centers, holding the outer
+        // voxels' densities out to the faces of the box.
+        let mut lower = [0; 3];
+        let mut upper = [0; 3];
+        let mut weight = [0.0; 3];
+        for axis in 0..3 {
+            let n = self.resolution[axis];
+            let x = (local[axis] * n as Float - 0.5).clamp(0.0, (n - 1) as Float);
+            lower[axis] = (x as usize).min(n - 1);
+            upper[axis] = (lower[axis] + 1).min(n - 1);
+            weight[axis] = x - lower[axis] as Float;
+        }
+        let lerp = |a: Float, b: Float, t: Float| a + t * (b - a);
+        let along_x = |y: usize, z: usize| {
+            lerp(
+                self.voxel(lower[0], y, z),
+                self.voxel(upper[0], y, z),
+                weight[0],
+            )
+        };
+        let along_y = |z| lerp(along_x(lower[1], z), along_x(upper[1], z), weight[1]);
+        lerp(along_y(lower[2]), along_y(upper[2]), weight[2])
+    }
+
+    fn majorant(&self) -> Float {
+        self.majorant
+    }
+}
+
+/// Perlin turbulence, `scale` noise cells per unit and capped to at most `density`.
+pub struct NoiseDensity {
+    noise: Perlin,
+    scale: Float,
+    density: Float,
+}
+
+impl NoiseDensity {
+    pub fn new(density: Float) -> Self {
+        Self {
+            noise: Perlin::new(),
+            scale: 1.0,
+            density,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: Float) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+impl DensityField for NoiseDensity {
+    fn density(&self, point: Vec3) -> Float {
+        self.density * self.noise.turb(self.scale * point, 7).min(1.0)
+    }
+
+    fn majorant(&self) -> Float {
+        self.density
+    }
+}
+
+/// Smoke or fog whose density varies from point to point, filling a closed `boundary`.
+///
+/// Rays are tracked through it against a majorant, the highest density anywhere: distances are
+/// sampled as if the whole medium were that dense, and at each one a real collision happens with
+/// the ratio of the density there to the majorant, otherwise the ray carries on. That scatters
+/// rays exactly where a medium of the true density would (delta tracking). Shadow rays instead
+/// multiply up the chances of passing each of those points to estimate the transmittance (ratio
+/// tracking), and get through with that probability.
+pub struct HeterogeneousMedium {
+    pub boundary: Rc<dyn Hittable>,
+    pub density: Rc<dyn DensityField>,
+    pub phasing_function: Rc<dyn Material>,
+}
+
+impl HeterogeneousMedium {
+    /// A medium that scatters with `phasing_function`, such as an
+    /// [`Isotropic`](crate::material::Isotropic) or an
+    /// [`Anisotropic`](crate::material::Anisotropic) material.
+    pub fn new(
+        boundary: Rc<dyn Hittable>,
+        density: Rc<dyn DensityField>,
+        phasing_function: Rc<dyn Material>,
+    ) -> Self {
+        Self {
+            boundary,
+            density,
+            phasing_function,
+        }
+    }
+
+    /// Calls `collision` with each tentative collision along `r` inside the boundary, in order,
+    /// until it returns something. Tentative collisions are spaced as in a medium with the
+    /// density of the majorant.
+    fn track<T>(
+        &self,
+        r: Ray,
+        t_min: Float,
+        t_max: Float,
+        mut collision: impl FnMut(Float, Float) -> Option<T>,
+    ) -> Option<T> {
+        let majorant = self.density.majorant();
+        if majorant <= 0.0 {
+            return None;
+        }
+        let inv_majorant = 1.0 / (majorant * r.direction.length());
+        for (t_enter, t_exit) in inside(&*self.boundary, r, t_min, t_max) {
+            let mut t = t_enter;
+            loop {
+                t -= (1.0 - random::<Float>()).ln() * inv_majorant;
+                if t >= t_exit {
+                    break;
+                }
+                let density = self.density.density(r.at(t));
+                if let Some(result) = collision(t, density / majorant) {
+                    return Some(result);
+                }
+            }
+        }
+        None
+    }
+
+    /// An unbiased estimate of how much light gets through along `r` (ratio tracking).
+    fn transmittance(&self, r: Ray, t_min: Float, t_max: Float) -> Float {
+        let mut transmittance = 1.0;
+        self.track(r, t_min, t_max, |_, ratio| {
+            transmittance *= 1.0 - ratio;
+            // Stop with Russian roulette once barely anything gets through.
+            if transmittance < 0.1 {
+                if random::<Float>() < 0.5 {
+                    return Some(());
+                }
+                transmittance *= 2.0;
+            }
+            None
+        })
+        .map_or(transmittance, |()| 0.0)
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let t = self.track(r, t_min, t_max, |t, ratio| {
+            (random::<Float>() < ratio).then_some(t)
+        })?;
+        Some(HitRecord {
+            point: r.at(t),
+            normal: Vec3::Y,  // arbitrary
+            front_face: true, // also arbitrary
+            mat: Some(self.phasing_function.clone()),
+            t,
+            ..Default::default()
+        })
+    }
+
+    /// Blocks the ray with the probability that it would scatter before `t_max`, so averaged
+    /// over many shadow rays visibility comes out as the transmittance.
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        random::<Float>() >= self.transmittance(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<Aabb> {
+        self.boundary.bounding_box(time0, time1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        float::vec3,
+        hittable::GeometricBox,
+        material::{Isotropic, Lambertian},
+    };
+
+    /// The same density everywhere, under a majorant that may be higher than it needs to be.
+    struct Constant {
+        density: Float,
+        majorant: Float,
+    }
+
+    impl DensityField for Constant {
+        fn density(&self, _point: Vec3) -> Float {
+            self.density
+        }
+
+        fn majorant(&self) -> Float {
+            self.majorant
+        }
+    }
+
+    const SIGMA: Float = 1.5;
+
+    /// The unit cube filled with density `SIGMA`, once as a voxel grid, whose majorant is
+    /// exact, and then under majorants that leave most tentative collisions null.
+    fn media() -> Vec<HeterogeneousMedium> {
+        let grid = Rc::new(VoxelGrid::new([2, 2, 2], vec![SIGMA; 8]));
+        let loose = [2.0, 5.0].map(|factor| {
+            Rc::new(Constant {
+                density: SIGMA,
+                majorant: factor * SIGMA,
+            }) as Rc<dyn DensityField>
+        });
+        let boundary = Rc::new(GeometricBox::new(
+            Vec3::ZERO,
+            Vec3::ONE,
+            Rc::new(Lambertian::from_color(Vec3::ONE)),
+        ));
+        let phase = Rc::new(Isotropic::from_color(Vec3::ONE));
+        [grid as Rc<dyn DensityField>]
+            .into_iter()
+            .chain(loose)
+            .map(|density| HeterogeneousMedium::new(boundary.clone(), density, phase.clone()))
+            .collect()
+    }
+
+    /// Enters the cube at t = 0.5 and leaves it at t = 1, after a distance of 1.
+    fn ray_through() -> Ray {
+        Ray::new(vec3(-1.0, 0.5, 0.5), vec3(2.0, 0.0, 0.0), 0.0)
+    }
+
+    const SAMPLES: usize = 100_000;
+
+    #[test]
+    fn ratio_tracking_converges_to_beers_law() {
+        let r = ray_through();
+        for medium in media() {
+            // All the way through, and stopping halfway.
+            for (t_max, distance) in [(2.0, 1.0), (0.75, 0.5)] {
+                let expected = (-SIGMA * distance).exp();
+                let transmittance = (0..SAMPLES)
+                    .map(|_| medium.transmittance(r, 0.0, t_max))
+                    .sum::<Float>()
+                    / SAMPLES as Float;
+                assert!((transmittance - expected).abs() < 0.01, "{transmittance}");
+
+                let unoccluded = (0..SAMPLES)
+                    .filter(|_| !medium.occluded(r, 0.0, t_max))
+                    .count() as Float
+                    / SAMPLES as Float;
+                assert!((unoccluded - expected).abs() < 0.01, "{unoccluded}");
+            }
+        }
+    }
+
+    /// The distance a ray goes into the medium before it collides is exponentially distributed,
+    /// whatever the majorant.
+    #[test]
+    fn delta_tracking_flies_freely_for_exponential_distances() {
+        let r = ray_through();
+        let length = r.direction.length();
+        for medium in media() {
+            let distances: Vec<_> = (0..SAMPLES)
+                .map(|_| {
+                    medium
+                        .hit(r, 0.0, Float::INFINITY)
+                        .map_or(Float::INFINITY, |rec| (rec.t - 0.5) * length)
+                })
+                .collect();
+            assert!(distances.iter().all(|&d| d >= 0.0));
+            for s in [0.1, 0.25, 0.5, 0.75, 1.0] {
+                let collided = distances.iter().filter(|&&d| d <= s).count() as Float;
+                let fraction = collided / SAMPLES as Float;
+                let expected = 1.0 - (-SIGMA * s).exp();
+                assert!((fraction - expected).abs() < 0.01, "{s}: {fraction}");
+            }
+        }
+    }
+}
@@ -2,6 +2,7 @@ use std::rc::Rc;
 
 use crate::{
     aabb::Aabb,
+    float::Float,
     hittable::{HitRecord, Hittable},
     ray::Ray,
 };
@@ -27,7 +28,7 @@ impl HittableList {
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
         let mut rec = None;
         let mut closest_so_far = t_max;
 
@@ -40,7 +41,13 @@ impl Hittable for HittableList {
         rec
     }
 
-    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.objects
+            .iter()
+            .any(|object| object.occluded(r, t_min, t_max))
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<Aabb> {
         if self.is_empty() {
             return None;
         }
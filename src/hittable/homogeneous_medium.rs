@@ -0,0 +1,270 @@
+use std::rc::Rc;
+
+use rand::prelude::*;
+
+use crate::{
+    float::{Float, Vec3},
+    hittable::{constant_medium::inside, Hittable},
+    material::{IsotropicPhase, PhaseFunction},
+    ray::Ray,
+};
+
+/// Water, gas or glass of uniform color filling a closed `boundary`, which absorbs and scatters
+/// each color channel at its own rate and may glow.
+///
+/// `sigma_a` and `sigma_s` are the chances per unit of length of light being absorbed and
+/// scattered, so murky water absorbs red more than blue, and a medium that only absorbs tints
+/// what is behind it without clouding it. `emission` is the radiance the medium glows with where
+/// it is thick enough to be opaque.
+///
+/// Unlike other media these aren't objects in the world, since how a ray that gets through
+/// should be weighted depends on how far it goes in them before hitting something else. The
+/// renderer instead follows each path through all of them with a [`MediaPath`].
+pub struct HomogeneousMedium {
+    pub boundary: Rc<dyn Hittable>,
+    pub sigma_a: Vec3,
+    pub sigma_s: Vec3,
+    pub emission: Vec3,
+    pub phase: Rc<dyn PhaseFunction>,
+}
+
+impl HomogeneousMedium {
+    /// A medium that doesn't glow and scatters equally in all directions.
+    pub fn new(boundary: Rc<dyn Hittable>, sigma_a: Vec3, sigma_s: Vec3) -> Self {
+        assert!(
+            sigma_a.min_element() >= 0.0 && sigma_s.min_element() >= 0.0,
+            "absorption and scattering coefficients must not be negative"
+        );
+        Self {
+            boundary,
+            sigma_a,
+            sigma_s,
+            emission: Vec3::ZERO,
+            phase: Rc::new(IsotropicPhase),
+        }
+    }
+
+    pub fn with_emission(mut self, emission: Vec3) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    pub fn with_phase(mut self, phase: Rc<dyn PhaseFunction>) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    fn sigma_t(&self) -> Vec3 {
+        self.sigma_a + self.sigma_s
+    }
+
+    /// Where `r` would collide with the medium before `t_max` if only `channel` counted.
+    fn sample_collision(&self, r: Ray, t_max: Float, channel: usize) -> Option<Float> {
+        // Infinite when the channel doesn't interact with the medium.
+        let distance = -(1.0 - random::<Float>()).ln() / self.sigma_t()[channel];
+
+        // As with constant media, one distance is spread over all stretches inside.
+        let ray_length = r.direction.length();
+        let mut remaining = distance;
+        inside(&*self.boundary, r, 0.0, t_max).find_map(|(t_enter, t_exit)| {
+            let length = (t_exit - t_enter) * ray_length;
+            if remaining < length {
+                Some(t_enter + remaining / ray_length)
+            } else {
+                remaining -= length;
+                None
+            }
+        })
+    }
+
+    /// How much light gets through the medium along `r` up to `t_max`.
+    fn transmittance(&self, r: Ray, t_max: Float) -> Vec3 {
+        let stretches = inside(&*self.boundary, r, 0.0, t_max);
+        let distance = r.direction.length()
+            * stretches
+                .map(|(t_enter, t_exit)| t_exit - t_enter)
+                .sum::<Float>();
+        (-self.sigma_t() * distance).exp()
+    }
+}
+
+/// One path from the camera through a scene's homogeneous media.
+///
+/// Distances to collisions are sampled with the extinction of one color channel, the path's
+/// hero, picked at random for the whole path. The other channels are weighted by how likely
+/// the path was with the hero and how likely it would have been with each of them (one-sample
+/// MIS), which keeps channels that a medium treats very differently from each other from
+/// turning into fireflies after a few collisions.
+#[derive(Clone, Copy)]
+pub struct MediaPath<'a> {
+    media: &'a [HomogeneousMedium],
+    hero: usize,
+    /// How many times likelier the path so far would have been with each channel as the hero.
+    pdf_ratios: Vec3,
+}
+
+/// What happens to a ray in the media before it reaches whatever it hits.
+pub enum MediumEvent {
+    /// The ray gets there, and the light from there is multiplied by `weight`.
+    Passed { weight: Vec3 },
+    /// The ray collides on the way, emitting `emitted` and scattering along `scattered_ray`,
+    /// with the light from there multiplied by `attenuation`.
+    Collided {
+        emitted: Vec3,
+        attenuation: Vec3,
+        scattered_ray: Ray,
+    },
+}
+
+impl<'a> MediaPath<'a> {
+    pub fn new(media: &'a [HomogeneousMedium]) -> Self {
+        Self {
+            media,
+            hero: thread_rng().gen_range(0..3),
+            pdf_ratios: Vec3::ONE,
+        }
+    }
+
+    /// What to multiply light emitted at the end of the path so far by, to weigh the channels
+    /// against each other.
+    pub fn mis_weight(&self) -> Float {
+        3.0 / (self.pdf_ratios.x + self.pdf_ratios.y + self.pdf_ratios.z)
+    }
+
+    /// Follows `r` through the media up to `t_max`, where it hits something, and extends the
+    /// path by what happens on the way.
+    pub fn travel(&mut self, r: Ray, t_max: Float) -> MediumEvent {
+        if self.media.is_empty() {
+            return MediumEvent::Passed { weight: Vec3::ONE };
+        }
+        let hero = self.hero;
+        let collision = self
+            .media
+            .iter()
+            .filter_map(|medium| Some((medium, medium.sample_collision(r, t_max, hero)?)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        let t_end = collision.map_or(t_max, |(_, t)| t);
+        let transmittance = self.media.iter().fold(Vec3::ONE, |product, medium| {
+            product * medium.transmittance(r, t_end)
+        });
+
+        match collision {
+            None => {
+                // The hero got through, so its transmittance is the probability of that.
+                if transmittance[hero] <= 0.0 {
+                    return MediumEvent::Passed { weight: Vec3::ZERO };
+                }
+                let weight = transmittance / transmittance[hero];
+                self.pdf_ratios *= weight;
+                MediumEvent::Passed { weight }
+            }
+            Some((medium, t)) => {
+                let pdf = medium.sigma_t() * transmittance;
+                let weight = transmittance / pdf[hero];
+                self.pdf_ratios *= pdf / pdf[hero];
+                MediumEvent::Collided {
+                    emitted: weight * medium.sigma_a * medium.emission,
+                    attenuation: weight * medium.sigma_s,
+                    scattered_ray: Ray::new(
+                        r.at(t),
+                        medium.phase.sample(r.direction.normalize()),
+                        r.time,
+                    ),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{float::vec3, hittable::GeometricBox, material::Lambertian};
+
+    /// The unit cube filled with a medium.
+    fn cube(sigma_a: Vec3, sigma_s: Vec3) -> HomogeneousMedium {
+        let boundary = Rc::new(GeometricBox::new(
+            Vec3::ZERO,
+            Vec3::ONE,
+            Rc::new(Lambertian::from_color(Vec3::ONE)),
+        ));
+        HomogeneousMedium::new(boundary, sigma_a, sigma_s)
+    }
+
+    /// Enters the cube at t = 0.5 and leaves it at t = 1, after a distance of 1.
+    fn ray_through() -> Ray {
+        Ray::new(vec3(-1.0, 0.5, 0.5), vec3(2.0, 0.0, 0.0), 0.0)
+    }
+
+    const SAMPLES: usize = 100_000;
+
+    #[test]
+    fn absorbing_media_follow_beers_law() {
+        let sigma_a = vec3(0.2, 1.0, 3.0);
+        let media = [cube(sigma_a, Vec3::ZERO)];
+        let r = ray_through();
+        // All the way through, and stopping halfway.
+        for (t_max, distance) in [(2.0, 1.0), (0.75, 0.5)] {
+            let expected = (-sigma_a * distance).exp();
+            for hero in 0..3 {
+                let mut passed = 0;
+                let mut total = Vec3::ZERO;
+                for _ in 0..SAMPLES {
+                    let mut path = MediaPath {
+                        media: &media,
+                        hero,
+                        pdf_ratios: Vec3::ONE,
+                    };
+                    match path.travel(r, t_max) {
+                        MediumEvent::Passed { weight } => {
+                            let exact = expected / expected[hero];
+                            assert!((weight - exact).abs().max_element() < 1e-5, "{weight}");
+                            passed += 1;
+                            total += weight;
+                        }
+                        // Nothing is scattered or emitted, so the path ends there.
+                        MediumEvent::Collided {
+                            emitted,
+                            attenuation,
+                            ..
+                        } => assert_eq!((emitted, attenuation), (Vec3::ZERO, Vec3::ZERO)),
+                    }
+                }
+
+                // Getting through is a coin flip, so allow four of its standard deviations,
+                // relative to the chance of it.
+                let p = expected[hero];
+                let tolerance = 4.0 * ((1.0 - p) / (p * SAMPLES as Float)).sqrt();
+                let fraction = passed as Float / SAMPLES as Float;
+                assert!((fraction / p - 1.0).abs() < tolerance, "{hero}: {fraction}");
+                let transmittance = total / SAMPLES as Float;
+                let error = ((transmittance - expected) / expected).abs().max_element();
+                assert!(error < tolerance, "{hero}: {transmittance}");
+            }
+        }
+    }
+
+    /// With a random hero and MIS, paths that get through carry each channel's own
+    /// transmittance on average, however differently the channels are attenuated.
+    #[test]
+    fn mis_weighted_paths_converge_to_beers_law() {
+        let (sigma_a, sigma_s) = (vec3(0.05, 0.5, 1.0), vec3(0.0, 0.5, 5.0));
+        let media = [cube(sigma_a, sigma_s)];
+        let r = ray_through();
+        for (t_max, distance) in [(2.0, 1.0), (0.75, 0.5)] {
+            let expected = (-(sigma_a + sigma_s) * distance).exp();
+            let transmittance = (0..SAMPLES)
+                .map(|_| {
+                    let mut path = MediaPath::new(&media);
+                    match path.travel(r, t_max) {
+                        MediumEvent::Passed { weight } => weight * path.mis_weight(),
+                        MediumEvent::Collided { .. } => Vec3::ZERO,
+                    }
+                })
+                .fold(Vec3::ZERO, |sum, weight| sum + weight)
+                / SAMPLES as Float;
+            let error = ((transmittance - expected) / expected).abs().max_element();
+            assert!(error < 0.05, "{transmittance} vs {expected}");
+        }
+    }
+}
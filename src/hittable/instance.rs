@@ -1,32 +1,48 @@
-use std::rc::Rc;
+use std::{ptr, rc::Rc};
 
 use crate::{
     aabb::Aabb,
+    float::{self, gamma, vec3, Affine3, Float, Mat3, Vec3},
     hittable::{HitRecord, Hittable},
+    material::Material,
     ray::Ray,
 };
 
 pub struct Translate {
     pub ptr: Rc<dyn Hittable>,
-    pub offset: glam::DVec3,
+    pub offset: Vec3,
 }
 
 impl Translate {
-    pub fn new(ptr: Rc<dyn Hittable>, offset: glam::DVec3) -> Self {
+    pub fn new(ptr: Rc<dyn Hittable>, offset: Vec3) -> Self {
         Self { ptr, offset }
     }
+
+    /// The ray in object space, and `t_min` raised past the rounding error of its origin.
+    fn object_ray(&self, r: Ray, t_min: Float) -> (Ray, Float) {
+        let object_ray = Ray::new(r.origin - self.offset, r.direction, r.time);
+        let error = gamma(1) * object_ray.origin.abs();
+        (object_ray, object_ray.clear_t_min(t_min, error))
+    }
 }
 
 impl Hittable for Translate {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let moved_r = Ray::new(r.origin - self.offset, r.direction, r.time);
-        let mut rec = self.ptr.hit(moved_r, t_min, t_max)?;
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (object_ray, t_min) = self.object_ray(r, t_min);
+        let mut rec = self.ptr.hit(object_ray, t_min, t_max)?;
+        // Moving the surface leaves its normals, and the side the ray is on, as they were.
+        rec.point_error =
+            float::transformed_error(Mat3::IDENTITY, self.offset, rec.point, rec.point_error);
         rec.point += self.offset;
-        rec.set_face_normal(moved_r, rec.normal);
         Some(rec)
     }
 
-    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        let (object_ray, t_min) = self.object_ray(r, t_min);
+        self.ptr.occluded(object_ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<Aabb> {
         let ptr_box = self.ptr.bounding_box(time0, time1)?;
         Some(Aabb::new(
             ptr_box.min + self.offset,
@@ -37,33 +53,32 @@ impl Hittable for Translate {
 
 pub struct RotateY {
     pub ptr: Rc<dyn Hittable>,
-    pub sin_theta: f64,
-    pub cos_theta: f64,
+    pub sin_theta: Float,
+    pub cos_theta: Float,
     pub bbox: Option<Aabb>,
 }
 
 impl RotateY {
-    pub fn new(ptr: Rc<dyn Hittable>, angle: f64) -> Self {
+    pub fn new(ptr: Rc<dyn Hittable>, angle: Float) -> Self {
         let radians = angle.to_radians();
         let sin_theta = radians.sin();
         let cos_theta = radians.cos();
-        let bbox = ptr.bounding_box(0.0, 1.0);
-
-        let mut min = glam::DVec3::splat(f64::INFINITY);
-        let mut max = glam::DVec3::splat(f64::NEG_INFINITY);
+        // Objects without bounds, such as planes, stay without them.
+        let bbox = ptr.bounding_box(0.0, 1.0).map(|bbox| {
+            let mut min = Vec3::splat(Float::INFINITY);
+            let mut max = Vec3::splat(Float::NEG_INFINITY);
 
-        if let Some(bbox) = bbox {
             for i in 0..2 {
                 for j in 0..2 {
                     for k in 0..2 {
-                        let x = i as f64 * bbox.max.x + (1 - i) as f64 * bbox.min.x;
-                        let y = j as f64 * bbox.max.y + (1 - j) as f64 * bbox.min.y;
-                        let z = k as f64 * bbox.max.z + (1 - k) as f64 * bbox.min.z;
+                        let x = i as Float * bbox.max.x + (1 - i) as Float * bbox.min.x;
+                        let y = j as Float * bbox.max.y + (1 - j) as Float * bbox.min.y;
+                        let z = k as Float * bbox.max.z + (1 - k) as Float * bbox.min.z;
 
                         let newx = cos_theta * x + sin_theta * z;
                         let newz = -sin_theta * x + cos_theta * z;
 
-                        let tester = glam::dvec3(newx, y, newz);
+                        let tester = vec3(newx, y, newz);
 
                         for c in 0..3 {
                             min[c] = min[c].min(tester[c]);
@@ -72,19 +87,27 @@ impl RotateY {
                     }
                 }
             }
-        }
+            Aabb::new(min, max)
+        });
 
         Self {
             ptr,
             sin_theta,
             cos_theta,
-            bbox: Some(Aabb::new(min, max)),
+            bbox,
         }
     }
-}
 
-impl Hittable for RotateY {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn object_to_world(&self) -> Mat3 {
+        Mat3::from_cols(
+            vec3(self.cos_theta, 0.0, -self.sin_theta),
+            Vec3::Y,
+            vec3(self.sin_theta, 0.0, self.cos_theta),
+        )
+    }
+
+    /// The ray in object space, and `t_min` raised past the rounding error of its origin.
+    fn object_ray(&self, r: Ray, t_min: Float) -> (Ray, Float) {
         let mut origin = r.origin;
         let mut direction = r.direction;
 
@@ -94,26 +117,293 @@ impl Hittable for RotateY {
         direction[0] = self.cos_theta * r.direction[0] - self.sin_theta * r.direction[2];
         direction[2] = self.sin_theta * r.direction[0] + self.cos_theta * r.direction[2];
 
-        let rotated_r = Ray::new(origin, direction, r.time);
+        let object_ray = Ray::new(origin, direction, r.time);
+        let error = float::transformed_error(
+            self.object_to_world().transpose(),
+            Vec3::ZERO,
+            r.origin,
+            Vec3::ZERO,
+        );
+        (object_ray, object_ray.clear_t_min(t_min, error))
+    }
+}
+
+impl Hittable for RotateY {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (object_ray, t_min) = self.object_ray(r, t_min);
+        let mut rec = self.ptr.hit(object_ray, t_min, t_max)?;
+
+        // A rotation keeps the normals facing against the rotated ray, so front_face stays valid.
+        let object_to_world = self.object_to_world();
+        rec.point_error =
+            float::transformed_error(object_to_world, Vec3::ZERO, rec.point, rec.point_error);
+        rec.point = object_to_world * rec.point;
+        rec.normal = object_to_world * rec.normal;
+        rec.geometric_normal = object_to_world * rec.geometric_normal;
+        rec.tangent = object_to_world * rec.tangent;
+
+        Some(rec)
+    }
 
-        let mut rec = self.ptr.hit(rotated_r, t_min, t_max)?;
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        let (object_ray, t_min) = self.object_ray(r, t_min);
+        self.ptr.occluded(object_ray, t_min, t_max)
+    }
 
-        let mut p = rec.point;
-        let mut normal = rec.normal;
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        self.bbox
+    }
+}
 
-        p[0] = self.cos_theta * rec.point[0] + self.sin_theta * rec.point[2];
-        p[2] = -self.sin_theta * rec.point[0] + self.cos_theta * rec.point[2];
+/// An arbitrary affine transformation of another hittable.
+pub struct Transform {
+    pub ptr: Rc<dyn Hittable>,
+    pub object_to_world: Affine3,
+    pub world_to_object: Affine3,
+    pub normal_to_world: Mat3,
+}
 
-        normal[0] = self.cos_theta * rec.normal[0] + self.sin_theta * rec.normal[2];
-        normal[2] = -self.sin_theta * rec.normal[0] + self.cos_theta * rec.normal[2];
+impl Transform {
+    pub fn new(ptr: Rc<dyn Hittable>, object_to_world: Affine3) -> Self {
+        let world_to_object = object_to_world.inverse();
+        Self {
+            ptr,
+            object_to_world,
+            world_to_object,
+            normal_to_world: float::affine_parts(world_to_object).0.transpose(),
+        }
+    }
 
-        rec.point = p;
-        rec.set_face_normal(rotated_r, normal);
+    /// The ray in object space, and `t_min` raised past the rounding error of its origin.
+    fn object_ray(&self, r: Ray, t_min: Float) -> (Ray, Float) {
+        // Taking the origin relative to the object's own first keeps the rounding error down to
+        // the size of the object, however far from the world's origin it stands.
+        let (linear, _) = float::affine_parts(self.world_to_object);
+        let (_, position) = float::affine_parts(self.object_to_world);
+        let offset = r.origin - position;
+        let object_ray = Ray::new(linear * offset, linear * r.direction, r.time);
+        let error = float::transformed_error(linear, Vec3::ZERO, offset, gamma(1) * offset.abs());
+        (object_ray, object_ray.clear_t_min(t_min, error))
+    }
+}
 
+impl Hittable for Transform {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        // An affine map preserves the ray parameter, so t needs no conversion.
+        let (object_ray, t_min) = self.object_ray(r, t_min);
+        let mut rec = self.ptr.hit(object_ray, t_min, t_max)?;
+        let (matrix, translation) = float::affine_parts(self.object_to_world);
+        rec.point_error = float::transformed_error(matrix, translation, rec.point, rec.point_error);
+        rec.point = self.object_to_world.transform_point3(rec.point);
+        // The inverse transpose also preserves the sign of dot(normal, direction), so the normal
+        // still faces the ray and front_face stays valid.
+        rec.normal = (self.normal_to_world * rec.normal).normalize();
+        rec.geometric_normal = (self.normal_to_world * rec.geometric_normal).normalize();
+        rec.tangent = self.object_to_world.transform_vector3(rec.tangent);
         Some(rec)
     }
 
-    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
-        self.bbox
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        let (object_ray, t_min) = self.object_ray(r, t_min);
+        self.ptr.occluded(object_ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<Aabb> {
+        let bbox = self.ptr.bounding_box(time0, time1)?;
+        let mut min = Vec3::splat(Float::INFINITY);
+        let mut max = Vec3::splat(Float::NEG_INFINITY);
+        for i in 0..8 {
+            let corner = vec3(
+                if i & 1 == 0 { bbox.min.x } else { bbox.max.x },
+                if i & 2 == 0 { bbox.min.y } else { bbox.max.y },
+                if i & 4 == 0 { bbox.min.z } else { bbox.max.z },
+            );
+            let corner = self.object_to_world.transform_point3(corner);
+            min = min.min(corner);
+            max = max.max(corner);
+        }
+        Some(Aabb::new(min, max))
+    }
+}
+
+/// Materials that an [`Instance`] uses in place of those of its prototype. Materials are matched
+/// by identity, so the prototype has to share its `Rc`s with the table.
+#[derive(Clone, Default)]
+pub struct MaterialVariants {
+    replacements: Vec<(Rc<dyn Material>, Rc<dyn Material>)>,
+}
+
+impl MaterialVariants {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces `original` with `replacement`.
+    pub fn with(mut self, original: Rc<dyn Material>, replacement: Rc<dyn Material>) -> Self {
+        self.replacements.push((original, replacement));
+        self
+    }
+
+    fn get(&self, mat: &Rc<dyn Material>) -> Option<&Rc<dyn Material>> {
+        self.replacements
+            .iter()
+            .find(|(original, _)| ptr::addr_eq(Rc::as_ptr(original), Rc::as_ptr(mat)))
+            .map(|(_, replacement)| replacement)
+    }
+}
+
+enum Materials {
+    Prototype,
+    Override(Rc<dyn Material>),
+    Variants(Rc<MaterialVariants>),
+}
+
+/// A placement of a shared prototype, such as one of many trees in a forest.
+///
+/// The prototype, along with any BVH inside it, is built once and shared by all of its instances,
+/// each of which only adds a transform and the materials it swaps in. That keeps thousands of
+/// copies of a detailed model down to a few hundred bytes each.
+pub struct Instance {
+    transform: Transform,
+    materials: Materials,
+}
+
+impl Instance {
+    pub fn new(prototype: Rc<dyn Hittable>, object_to_world: Affine3) -> Self {
+        Self {
+            transform: Transform::new(prototype, object_to_world),
+            materials: Materials::Prototype,
+        }
+    }
+
+    /// Uses `mat` for every surface of the prototype.
+    pub fn with_material(mut self, mat: Rc<dyn Material>) -> Self {
+        self.materials = Materials::Override(mat);
+        self
+    }
+
+    /// Swaps some materials of the prototype for others. Instances that look the same can share
+    /// one table.
+    pub fn with_variants(mut self, variants: Rc<MaterialVariants>) -> Self {
+        self.materials = Materials::Variants(variants);
+        self
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let mut rec = self.transform.hit(r, t_min, t_max)?;
+        match &self.materials {
+            Materials::Prototype => {}
+            Materials::Override(mat) => rec.mat = Some(mat.clone()),
+            Materials::Variants(variants) => {
+                if let Some(mat) = rec.mat.as_ref().and_then(|mat| variants.get(mat)) {
+                    rec.mat = Some(mat.clone());
+                }
+            }
+        }
+        Some(rec)
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.transform.occluded(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<Aabb> {
+        self.transform.bounding_box(time0, time1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        float::consts,
+        hittable::{sphere::Sphere, Bvh, HittableList, TriangleMesh},
+        material::Lambertian,
+        testing,
+    };
+
+    const SIDE: usize = 100;
+
+    fn same(mat: &Option<Rc<dyn Material>>, expected: &Rc<dyn Material>) -> bool {
+        mat.as_ref()
+            .is_some_and(|mat| ptr::addr_eq(Rc::as_ptr(mat), Rc::as_ptr(expected)))
+    }
+
+    /// 10,000 instances of a tree, a triangle mesh trunk under a sphere of leaves, share one
+    /// prototype, cost a bounded amount of memory each, and hit with the materials they swap in.
+    #[test]
+    fn instances_share_their_prototype() {
+        let bark: Rc<dyn Material> = Rc::new(Lambertian::from_color(vec3(0.3, 0.2, 0.1)));
+        let leaves: Rc<dyn Material> = Rc::new(Lambertian::from_color(vec3(0.1, 0.4, 0.1)));
+        let autumn: Rc<dyn Material> = Rc::new(Lambertian::from_color(vec3(0.6, 0.2, 0.0)));
+        let snow: Rc<dyn Material> = Rc::new(Lambertian::from_color(Vec3::ONE));
+
+        // A cone of 1,000 triangles, detailed enough that copies of it would show.
+        let segments = 500;
+        let mut positions = vec![Vec3::ZERO, vec3(0.0, 1.5, 0.0)];
+        let mut indices = Vec::new();
+        for k in 0..segments {
+            let phi = k as Float / segments as Float * consts::TAU;
+            positions.push(vec3(0.2 * phi.cos(), 0.0, 0.2 * phi.sin()));
+            let (a, b) = (k + 2, (k + 1) % segments + 2);
+            indices.push([a, b, 1]);
+            indices.push([b, a, 0]);
+        }
+        let mesh = Rc::new(TriangleMesh::new(positions, indices, bark.clone()));
+        let mut tree = HittableList::default();
+        for triangle in mesh.triangles() {
+            tree.add(Rc::new(triangle));
+        }
+        tree.add(Rc::new(Sphere::new(
+            vec3(0.0, 2.0, 0.0),
+            0.5,
+            leaves.clone(),
+        )));
+        let prototype: Rc<dyn Hittable> = Rc::new(Bvh::from_hittable_list(tree, 0.0, 1.0));
+        let mesh_count = Rc::strong_count(&mesh);
+
+        let variants = Rc::new(MaterialVariants::new().with(leaves.clone(), autumn.clone()));
+        let (world, retained) = testing::retained(|| {
+            let mut instances = HittableList::default();
+            for i in 0..SIDE * SIDE {
+                let offset = vec3(2.0 * (i % SIDE) as Float, 0.0, 2.0 * (i / SIDE) as Float);
+                let instance = Instance::new(prototype.clone(), Affine3::from_translation(offset));
+                let instance = match i % 3 {
+                    0 => instance,
+                    1 => instance.with_variants(variants.clone()),
+                    _ => instance.with_material(snow.clone()),
+                };
+                instances.add(Rc::new(instance));
+            }
+            Bvh::from_hittable_list(instances, 0.0, 1.0)
+        });
+
+        assert_eq!(Rc::strong_count(&prototype), SIDE * SIDE + 1);
+        assert_eq!(Rc::strong_count(&mesh), mesh_count);
+        // The instances, their BVH and the list they were built from, but no copies of the tree.
+        assert!(
+            retained < SIDE * SIDE * 1024,
+            "10,000 instances retained {retained} bytes"
+        );
+
+        for i in [0, 1, 2, 4242, 5000, SIDE * SIDE - 1] {
+            let (x, z) = (2.0 * (i % SIDE) as Float, 2.0 * (i / SIDE) as Float);
+            let expected = [&leaves, &autumn, &snow][i % 3];
+            let down = Ray::new(vec3(x, 10.0, z), -Vec3::Y, 0.0);
+            let rec = world.hit(down, 0.0, Float::INFINITY).unwrap();
+            assert!((rec.point - vec3(x, 2.5, z)).length() < 1e-3);
+            assert!(same(&rec.mat, expected), "instance {i}");
+
+            // Only overridden instances replace the bark of the trunk.
+            let expected = if i % 3 == 2 { &snow } else { &bark };
+            let sideways = Ray::new(vec3(x, 0.5, z - 1.0), Vec3::Z, 0.0);
+            let rec = world.hit(sideways, 0.0, Float::INFINITY).unwrap();
+            assert!(same(&rec.mat, expected), "instance {i}");
+        }
+
+        let between = Ray::new(vec3(1.0, 10.0, 1.0), -Vec3::Y, 0.0);
+        assert!(world.hit(between, 0.0, Float::INFINITY).is_none());
     }
 }
@@ -0,0 +1,124 @@
+use std::rc::Rc;
+
+use rand::prelude::*;
+
+use crate::float::{Float, Vec3};
+
+/// A shape that rays can be aimed at directly, because it emits light.
+///
+/// Lights are also objects in the world, where rays that happen to hit them see what they emit.
+/// This only tells how to send rays towards them, so that a diffuse surface finds a small light
+/// far sooner than by bouncing at random.
+pub trait Light {
+    /// The density, per unit solid angle, with which [`Light::random`] picks `direction` from
+    /// `origin`.
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> Float;
+
+    /// A random direction from `origin` towards a point on the light, spread uniformly over its
+    /// area.
+    fn random(&self, origin: Vec3) -> Vec3;
+}
+
+/// The lights of a scene, each of them picked with the same probability.
+#[derive(Default)]
+pub struct LightList {
+    pub lights: Vec<Rc<dyn Light>>,
+}
+
+impl LightList {
+    pub fn add(&mut self, light: Rc<dyn Light>) {
+        self.lights.push(light);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn new(light: Rc<dyn Light>) -> Self {
+        let mut lights = Self::default();
+        lights.add(light);
+        lights
+    }
+
+    /// The density with which [`LightList::random`] picks `direction` from `origin`, which is
+    /// 0 for every direction when there are no lights.
+    pub fn pdf_value(&self, origin: Vec3, direction: Vec3) -> Float {
+        let weight = 1.0 / self.lights.len() as Float;
+        self.lights
+            .iter()
+            .map(|light| weight * light.pdf_value(origin, direction))
+            .sum()
+    }
+
+    /// A random direction from `origin` towards one of the lights, or `None` when there are
+    /// none.
+    pub fn random(&self, origin: Vec3) -> Option<Vec3> {
+        let light = self.lights.choose(&mut thread_rng())?;
+        Some(light.random(origin))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        float::{consts::PI, vec3},
+        hittable::{sphere::Sphere, Disk, Quad},
+        material::DiffuseLight,
+        math::random_unit_vec,
+    };
+
+    fn lights() -> LightList {
+        let mat = Rc::new(DiffuseLight::from_color(Vec3::ONE));
+        let mut lights = LightList::new(Rc::new(Quad::new(
+            vec3(-1.0, 1.0, -1.0),
+            vec3(2.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 2.0),
+            mat.clone(),
+        )));
+        lights.add(Rc::new(
+            Disk::new(vec3(0.0, 0.0, -2.0), vec3(0.0, 1.0, 1.0), 1.5, mat.clone())
+                .with_inner_radius(0.5),
+        ));
+        lights.add(Rc::new(Sphere::new(vec3(2.0, -1.0, 1.0), 1.0, mat)));
+        lights
+    }
+
+    /// Integrates the density of `lights` over all directions from `origin`.
+    fn pdf_integral(lights: &LightList, origin: Vec3) -> Float {
+        let samples = 200_000;
+        (0..samples)
+            .map(|_| lights.pdf_value(origin, random_unit_vec()))
+            .sum::<Float>()
+            * 4.0
+            * PI
+            / samples as Float
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let integral = pdf_integral(&lights(), Vec3::ZERO);
+        assert!((integral - 1.0).abs() < 0.03, "{integral}");
+
+        // From inside a sphere every direction reaches it.
+        let mat = Rc::new(DiffuseLight::from_color(Vec3::ONE));
+        let sphere = LightList::new(Rc::new(Sphere::new(Vec3::ZERO, 1.0, mat)));
+        let integral = pdf_integral(&sphere, vec3(0.0, 0.5, 0.0));
+        assert!((integral - 1.0).abs() < 0.01, "{integral}");
+    }
+
+    #[test]
+    fn sampled_directions_have_a_density() {
+        let lights = lights();
+        for _ in 0..10_000 {
+            let direction = lights.random(Vec3::ZERO).unwrap();
+            assert!(lights.pdf_value(Vec3::ZERO, direction) > 0.0, "{direction}");
+        }
+    }
+
+    #[test]
+    fn empty_list_has_nothing_to_sample() {
+        let lights = LightList::default();
+        assert!(lights.random(Vec3::ZERO).is_none());
+    }
+}
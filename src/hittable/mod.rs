@@ -1,50 +1,488 @@
 mod aarect;
 mod bvh;
 mod constant_medium;
+mod csg;
+mod curve;
+mod disk;
 mod geometric_box;
+mod heightfield;
+mod heterogeneous_medium;
 mod hittable_list;
+mod homogeneous_medium;
 mod instance;
+mod light;
+mod plane;
+mod quad;
+mod quadric;
+pub mod sdf;
 pub mod sphere;
+mod torus;
+mod triangle;
 
 use std::rc::Rc;
 
-use crate::{aabb::Aabb, material::Material, ray::Ray};
+use crate::{
+    aabb::Aabb,
+    float::{self, Float, Vec3},
+    material::Material,
+    ray::Ray,
+};
 
 #[derive(Default, Clone)]
 pub struct HitRecord {
-    pub point: glam::DVec3,
-    pub normal: glam::DVec3,
+    pub point: Vec3,
+    pub normal: Vec3,
     pub mat: Option<Rc<dyn Material>>,
-    pub t: f64,
-    pub u: f64,
-    pub v: f64,
+    /// Bounds the absolute error of each coordinate of `point`.
+    pub point_error: Vec3,
+    /// The normal of the surface itself, on the same side as `normal`. Unlike `normal` it is
+    /// never replaced by an interpolated shading normal.
+    pub geometric_normal: Vec3,
+    /// The direction `u` increases in along the surface, for shapes whose materials depend on it,
+    /// such as the strands of hair that [`Curves`] are made of. Zero for all others.
+    pub tangent: Vec3,
+    /// The color of the shape itself at the point, such as the per-point colors of a
+    /// [`SphereCloud`](sphere::SphereCloud), which tints what the material reflects.
+    pub color: Option<Vec3>,
+    pub t: Float,
+    pub u: Float,
+    pub v: Float,
     pub front_face: bool,
 }
 
 impl HitRecord {
-    pub fn with_face_normal(mut self, r: Ray, outward_normal: glam::DVec3) -> Self {
+    pub fn with_face_normal(mut self, r: Ray, outward_normal: Vec3) -> Self {
         self.set_face_normal(r, outward_normal);
         self
     }
 
-    pub fn set_face_normal(&mut self, r: Ray, outward_normal: glam::DVec3) {
+    /// A ray leaving the hit point in `direction`, starting far enough off the surface that it
+    /// can't hit it again, so it can be traced from `t_min = 0`.
+    pub fn spawn_ray(&self, direction: Vec3, time: Float) -> Ray {
+        let origin = float::offset_ray_origin(
+            self.point,
+            self.point_error,
+            self.geometric_normal,
+            direction,
+        );
+        Ray::new(origin, direction, time)
+    }
+
+    /// `albedo` tinted by the shape's own color, if it has one.
+    pub fn tint(&self, albedo: Vec3) -> Vec3 {
+        self.color.map_or(albedo, |color| color * albedo)
+    }
+
+    /// Sets both the shading and the geometric normal to face against `r`.
+    pub fn set_face_normal(&mut self, r: Ray, outward_normal: Vec3) {
         self.front_face = r.direction.dot(outward_normal) < 0.0;
         self.normal = if self.front_face {
             outward_normal
         } else {
             -outward_normal
         };
+        self.geometric_normal = self.normal;
     }
 }
 
 pub trait Hittable {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
-    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb>;
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord>;
+
+    /// Whether anything is hit within `[t_min, t_max]`. Unlike `hit` this may stop at the first
+    /// intersection it finds and never builds a `HitRecord`, which is all a visibility test
+    /// needs.
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.hit(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<Aabb>;
 }
 
 pub use aarect::{XYRect, XZRect, YZRect};
-pub use bvh::BvhNode;
+pub use bvh::{Bvh, BvhBuilder, BvhNode, PACKET_SIZE};
 pub use constant_medium::ConstantMedium;
+pub use csg::Csg;
+pub use curve::{load_strands, Curve, CurveKind, Curves, Fur, Strand};
+pub use disk::Disk;
 pub use geometric_box::GeometricBox;
+pub use heightfield::{HeightMap, Heightfield};
+pub use heterogeneous_medium::{DensityField, HeterogeneousMedium, NoiseDensity, VoxelGrid};
 pub use hittable_list::HittableList;
-pub use instance::{RotateY, Translate};
+pub use homogeneous_medium::{HomogeneousMedium, MediaPath, MediumEvent};
+pub use instance::{Instance, MaterialVariants, RotateY, Transform, Translate};
+pub use light::{Light, LightList};
+pub use plane::Plane;
+pub use quad::Quad;
+pub use quadric::{Capsule, Cone, Cylinder};
+pub use torus::Torus;
+pub use triangle::TriangleMesh;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        float::{vec3, Affine3, Mat3},
+        material::{Isotropic, Lambertian, Material},
+        pbrt, scene_file,
+        test_scenes::{self, Scene},
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use sdf::{RoundedCuboid, SdfHittable};
+    use sphere::{MovingSphere, PointCloud, Sphere, SphereCloud};
+
+    /// Whether `again` is the hit `rec` was spawned from, found a second time: the two points
+    /// are closer than their rounding errors can tell apart, and on surfaces facing the same
+    /// way. Where two surfaces meet at an edge, a ray may hit the other one right away.
+    fn hit_again(rec: &HitRecord, again: &HitRecord) -> bool {
+        let error = 2.0 * (rec.point_error + again.point_error);
+        let close = (again.point - rec.point)
+            .abs()
+            .cmple(error + Float::EPSILON * rec.point.abs())
+            .all();
+        close && again.geometric_normal.dot(rec.geometric_normal).abs() > 0.9
+    }
+
+    /// Traces primary rays through random pixels of `scene`, and from every surface they hit
+    /// spawns rays out the front, out the back and just grazing it, none of which may hit the
+    /// same point again.
+    fn assert_no_self_intersections(name: &str, scene: Scene) {
+        let world = Bvh::from_hittable_list(scene.world, scene.time0, scene.time1);
+        let mut rng = StdRng::seed_from_u64(37);
+        let (mut spawned, mut self_hits) = (0, 0);
+        for _ in 0..5000 {
+            let r = scene.cam.get_ray(rng.gen(), rng.gen());
+            let Some(rec) = world.hit(r, 0.0, Float::INFINITY) else {
+                continue;
+            };
+            // Points inside media have no surface, and no normal to spawn rays off of.
+            if rec.geometric_normal == Vec3::ZERO {
+                continue;
+            }
+            let n = rec.geometric_normal;
+            let d = loop {
+                let d = 2.0 * rng.gen::<Vec3>() - Vec3::ONE;
+                let along = d.dot(n);
+                if (0.01..1.0).contains(&d.length_squared()) && along.abs() < 0.99 * d.length() {
+                    break if along < 0.0 { -d } else { d };
+                }
+            };
+            let tangent = (d - d.dot(n) * n).normalize();
+            for direction in [d, -d, tangent + 1e-3 * n, tangent - 1e-3 * n] {
+                spawned += 1;
+                let spawned_ray = rec.spawn_ray(direction, r.time);
+                if let Some(again) = world.hit(spawned_ray, 0.0, Float::INFINITY) {
+                    // A ray may scatter in a medium right after it enters, which is no surface.
+                    let surface = again.geometric_normal != Vec3::ZERO;
+                    self_hits += (surface && hit_again(&rec, &again)) as usize;
+                }
+            }
+        }
+        assert!(spawned > 0, "{name}: no primary ray hit anything");
+        assert_eq!(
+            self_hits, 0,
+            "{name}: {self_hits} of {spawned} rays hit their origin"
+        );
+    }
+
+    #[test]
+    fn spawned_rays_dont_hit_their_origin() {
+        for (name, scene) in [
+            ("simple_scene", test_scenes::simple_scene as fn() -> Scene),
+            ("random_scene", test_scenes::random_scene),
+            ("two_spheres", test_scenes::two_spheres),
+            ("two_perlin_spheres", test_scenes::two_perlin_spheres),
+            ("simple_light", test_scenes::simple_light),
+            ("cornel_box", test_scenes::cornel_box),
+            ("cornel_smoke", test_scenes::cornel_smoke),
+            ("cornel_clouds", test_scenes::cornel_clouds),
+            ("final_scene", test_scenes::final_scene),
+            ("forest", test_scenes::forest),
+        ] {
+            assert_no_self_intersections(name, scene());
+        }
+        assert_no_self_intersections(
+            "instances.toml",
+            scene_file::load("scenes/instances.toml").unwrap(),
+        );
+        assert_no_self_intersections(
+            "cornell_box.pbrt",
+            pbrt::load("scenes/cornell_box.pbrt").unwrap().0,
+        );
+    }
+
+    /// One of every kind of object, all around the origin, followed by containers of them.
+    fn every_object() -> Vec<(&'static str, Rc<dyn Hittable>)> {
+        let mat: Rc<dyn Material> = Rc::new(Lambertian::from_color(Vec3::splat(0.5)));
+        let fog: Rc<dyn Material> = Rc::new(Isotropic::from_color(Vec3::ONE));
+        let sphere: Rc<dyn Hittable> = Rc::new(Sphere::new(Vec3::ZERO, 1.0, mat.clone()));
+        let cube: Rc<dyn Hittable> = Rc::new(GeometricBox::new(-Vec3::ONE, Vec3::ONE, mat.clone()));
+        let mesh = Rc::new(TriangleMesh::new(
+            vec![
+                vec3(-1.0, -1.0, 0.0),
+                vec3(1.0, -1.0, 0.5),
+                vec3(0.0, 1.0, -0.5),
+                vec3(1.2, 0.8, 1.0),
+            ],
+            vec![[0, 1, 2], [1, 3, 2]],
+            mat.clone(),
+        ));
+        let triangles: Vec<Rc<dyn Hittable>> = mesh
+            .triangles()
+            .map(|triangle| Rc::new(triangle) as Rc<dyn Hittable>)
+            .collect();
+        let cylinder: Rc<dyn Hittable> = Rc::new(
+            Cylinder::new(vec3(0.0, -1.0, 0.2), vec3(0.3, 1.0, -0.2), 0.7, mat.clone())
+                .with_sweep(270.0),
+        );
+        let torus: Rc<dyn Hittable> = Rc::new(Torus::new(
+            Vec3::ZERO,
+            vec3(0.3, 1.0, 0.1),
+            1.0,
+            0.35,
+            mat.clone(),
+        ));
+        let curve = Curve::new(
+            [
+                vec3(-1.0, 0.0, 0.0),
+                vec3(-0.3, 1.0, 0.2),
+                vec3(0.3, -1.0, -0.2),
+                vec3(1.0, 0.2, 0.0),
+            ],
+            [0.6, 0.4],
+        );
+        let cloud = PointCloud {
+            centers: (0..20)
+                .map(|i| vec3((i % 4) as Float - 1.5, (i / 4) as Float * 0.5 - 1.0, 0.0))
+                .collect(),
+            ..Default::default()
+        };
+        let heights = HeightMap {
+            width: 6,
+            depth: 5,
+            heights: (0..30).map(|i| ((i * 7) % 11) as Float / 10.0).collect(),
+        };
+        let densities =
+            VoxelGrid::new([2, 2, 2], vec![1e7; 8]).with_bounds(Aabb::new(-Vec3::ONE, Vec3::ONE));
+
+        let mut objects: Vec<(&'static str, Rc<dyn Hittable>)> = vec![
+            ("sphere", sphere.clone()),
+            (
+                "moving sphere",
+                Rc::new(MovingSphere::new(
+                    vec3(-0.5, 0.0, 0.0),
+                    vec3(0.5, 0.2, 0.0),
+                    0.0,
+                    1.0,
+                    0.8,
+                    mat.clone(),
+                )),
+            ),
+            (
+                "sphere cloud",
+                Rc::new(SphereCloud::new(&cloud, 0.3, mat.clone())),
+            ),
+            (
+                "xy rect",
+                Rc::new(XYRect::new(-1.0, 1.0, -0.5, 1.5, 0.3, mat.clone())),
+            ),
+            (
+                "xz rect",
+                Rc::new(XZRect::new(-1.0, 1.0, -0.5, 1.5, 0.3, mat.clone())),
+            ),
+            (
+                "yz rect",
+                Rc::new(YZRect::new(-1.0, 1.0, -0.5, 1.5, 0.3, mat.clone())),
+            ),
+            (
+                "quad",
+                Rc::new(Quad::new(
+                    vec3(-1.0, -1.0, 0.2),
+                    vec3(2.0, 0.3, 0.0),
+                    vec3(0.2, 1.5, 0.5),
+                    mat.clone(),
+                )),
+            ),
+            (
+                "disk",
+                Rc::new(
+                    Disk::new(Vec3::ZERO, vec3(1.0, 2.0, -1.0), 1.2, mat.clone())
+                        .with_inner_radius(0.4),
+                ),
+            ),
+            (
+                "plane",
+                Rc::new(Plane::new(
+                    vec3(0.0, 0.3, 0.0),
+                    vec3(0.2, 1.0, -0.3),
+                    mat.clone(),
+                )),
+            ),
+            ("box", cube.clone()),
+            ("triangle", triangles[0].clone()),
+            ("cylinder", cylinder.clone()),
+            (
+                "open cylinder",
+                Rc::new(
+                    Cylinder::new(vec3(0.0, -1.0, 0.0), vec3(0.0, 1.0, 0.0), 0.8, mat.clone())
+                        .with_caps(false),
+                ),
+            ),
+            (
+                "cone",
+                Rc::new(
+                    Cone::new(
+                        vec3(0.2, -1.0, 0.0),
+                        vec3(-0.2, 1.0, 0.1),
+                        1.0,
+                        0.3,
+                        mat.clone(),
+                    )
+                    .with_sweep(300.0),
+                ),
+            ),
+            (
+                "capsule",
+                Rc::new(
+                    Capsule::new(vec3(-0.8, 0.0, 0.0), vec3(0.8, 0.3, 0.0), 0.5, mat.clone())
+                        .with_sweep(200.0),
+                ),
+            ),
+            ("torus", torus.clone()),
+            (
+                "heightfield",
+                Rc::new(Heightfield::new(
+                    &heights,
+                    vec3(-1.5, -0.5, -1.5),
+                    vec3(1.5, 0.5, 1.5),
+                    mat.clone(),
+                )),
+            ),
+            (
+                "flat curves",
+                Rc::new(Curves::new(vec![curve], CurveKind::Flat, mat.clone())),
+            ),
+            (
+                "ribbon curves",
+                Rc::new(Curves::new(
+                    vec![curve],
+                    CurveKind::Ribbon(vec![[Vec3::Z, Vec3::X]]),
+                    mat.clone(),
+                )),
+            ),
+            (
+                "sdf",
+                Rc::new(SdfHittable::new(
+                    Rc::new(RoundedCuboid {
+                        half_extents: vec3(1.0, 0.6, 0.8),
+                        radius: 0.2,
+                    }),
+                    Aabb::new(vec3(-1.0, -0.6, -0.8), vec3(1.0, 0.6, 0.8)),
+                    mat.clone(),
+                )),
+            ),
+            // Dense enough that rays collide right where they enter.
+            (
+                "constant medium",
+                Rc::new(ConstantMedium::from_color(sphere.clone(), 1e7, Vec3::ONE)),
+            ),
+            (
+                "heterogeneous medium",
+                Rc::new(HeterogeneousMedium::new(
+                    cube.clone(),
+                    Rc::new(densities),
+                    fog,
+                )),
+            ),
+        ];
+
+        let all: Vec<_> = objects.iter().map(|(_, object)| object.clone()).collect();
+        let bounded: Vec<_> = all
+            .iter()
+            .filter(|object| object.bounding_box(0.0, 1.0).is_some())
+            .cloned()
+            .collect();
+        let rotation = Mat3::from_axis_angle(vec3(1.0, 2.0, 2.0) / 3.0, 0.7);
+        let object_to_world = Affine3::from_mat3_translation(
+            rotation * Mat3::from_diagonal(vec3(1.5, 0.5, 1.0)),
+            vec3(0.2, -0.3, 0.1),
+        );
+        let shifted_sphere: Rc<dyn Hittable> =
+            Rc::new(Sphere::new(vec3(0.8, 0.5, 0.0), 0.9, mat.clone()));
+        objects.extend([
+            (
+                "list",
+                Rc::new(HittableList {
+                    objects: all.clone(),
+                }) as Rc<dyn Hittable>,
+            ),
+            (
+                "bvh",
+                Rc::new(Bvh::with_builder(&all, 0.0, 1.0, &BvhBuilder::new())),
+            ),
+            ("bvh node", Rc::new(BvhNode::from_slice(&bounded, 0.0, 1.0))),
+            (
+                "mesh bvh",
+                Rc::new(Bvh::with_builder(&triangles, 0.0, 1.0, &BvhBuilder::new())),
+            ),
+            (
+                "translate",
+                Rc::new(Translate::new(torus.clone(), vec3(0.3, -0.2, 0.1))),
+            ),
+            ("rotate y", Rc::new(RotateY::new(cube.clone(), 30.0))),
+            (
+                "transform",
+                Rc::new(Transform::new(cylinder.clone(), object_to_world)),
+            ),
+            (
+                "instance",
+                Rc::new(Instance::new(torus.clone(), object_to_world)),
+            ),
+            (
+                "union",
+                Rc::new(Csg::union(cube.clone(), shifted_sphere.clone())),
+            ),
+            (
+                "intersection",
+                Rc::new(Csg::intersection(cube.clone(), shifted_sphere.clone())),
+            ),
+            (
+                "difference",
+                Rc::new(Csg::difference(cube.clone(), shifted_sphere)),
+            ),
+        ]);
+        objects
+    }
+
+    /// `occluded` is an early-out version of `hit`, so the two have to agree on whether there is
+    /// anything between `t_min` and `t_max`.
+    #[test]
+    fn occluded_agrees_with_hit() {
+        let mut rng = StdRng::seed_from_u64(33);
+        for (name, object) in every_object() {
+            let mut hits = 0;
+            for _ in 0..2000 {
+                let origin = 10.0 * rng.gen::<Vec3>() - Vec3::splat(5.0);
+                let target = 3.0 * rng.gen::<Vec3>() - Vec3::splat(1.5);
+                let r = Ray::new(origin, target - origin, rng.gen());
+                let t_min = match rng.gen_range(0..3) {
+                    0 => 0.0,
+                    1 => 0.001,
+                    _ => rng.gen_range(0.0..1.0),
+                };
+                let t_max = if rng.gen() {
+                    Float::INFINITY
+                } else {
+                    t_min + rng.gen_range(0.0..1.5)
+                };
+                let hit = object.hit(r, t_min, t_max);
+                assert_eq!(
+                    object.occluded(r, t_min, t_max),
+                    hit.is_some(),
+                    "{name}: {r:?} from {t_min} to {t_max}"
+                );
+                hits += hit.is_some() as usize;
+            }
+            assert!(hits > 50, "{name}: {hits}");
+        }
+    }
+}
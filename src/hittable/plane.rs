@@ -0,0 +1,94 @@
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb,
+    float::{gamma, Float, Vec3},
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+};
+
+/// The point at coordinates `(x, y)` in the plane through `origin` spanned by the unit vectors
+/// `tangent` and `bitangent`, and its error bound. Building it from its coordinates puts it
+/// exactly on the plane, up to the rounding of the sum.
+pub(super) fn in_plane_point(
+    origin: Vec3,
+    (tangent, bitangent): (Vec3, Vec3),
+    x: Float,
+    y: Float,
+) -> (Vec3, Vec3) {
+    let (a, b) = (x * tangent, y * bitangent);
+    let point = origin + a + b;
+    // The tangents are only unit and orthogonal to the normal up to rounding, which tilts the
+    // plane by a few ulps more.
+    (point, gamma(5) * (origin.abs() + a.abs() + b.abs()))
+}
+
+/// The ray parameter at which `r` crosses the plane through `point` with the given normal, if
+/// it is within `[t_min, t_max]`.
+pub(super) fn plane_crossing(
+    point: Vec3,
+    normal: Vec3,
+    r: Ray,
+    t_min: Float,
+    t_max: Float,
+) -> Option<Float> {
+    // A ray parallel to the plane gives an infinite or NaN t, which an unbounded range would
+    // still contain if it is infinite.
+    let t = normal.dot(point - r.origin) / normal.dot(r.direction);
+    (t.is_finite() && (t_min..=t_max).contains(&t)).then_some(t)
+}
+
+/// An infinite plane. It has no bounding box, so a BVH keeps it aside and tests it on its own.
+///
+/// The texture coordinates are the distances along the plane from `point`, in world units, so
+/// textures that repeat are the ones that make sense on it. Its area is infinite, so it can't
+/// be sampled as a light.
+pub struct Plane {
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub mat: Rc<dyn Material>,
+    tangents: (Vec3, Vec3),
+}
+
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3, mat: Rc<dyn Material>) -> Self {
+        let normal = normal.normalize();
+        Self {
+            point,
+            normal,
+            mat,
+            tangents: normal.any_orthonormal_pair(),
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let t = plane_crossing(self.point, self.normal, r, t_min, t_max)?;
+
+        let local = r.at(t) - self.point;
+        let (u, v) = (self.tangents.0.dot(local), self.tangents.1.dot(local));
+        let (point, point_error) = in_plane_point(self.point, self.tangents, u, v);
+        let rec = HitRecord {
+            point,
+            point_error,
+            mat: Some(self.mat.clone()),
+            t,
+            u,
+            v,
+            ..Default::default()
+        }
+        .with_face_normal(r, self.normal);
+
+        Some(rec)
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        plane_crossing(self.point, self.normal, r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        None
+    }
+}
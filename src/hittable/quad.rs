@@ -0,0 +1,119 @@
+use std::rc::Rc;
+
+use rand::prelude::*;
+
+use crate::{
+    aabb::Aabb,
+    float::{gamma, Float, Vec3},
+    hittable::{HitRecord, Hittable, Light},
+    material::Material,
+    ray::Ray,
+};
+
+/// A parallelogram spanned by the edges `u` and `v` from the corner `q`, in any orientation.
+pub struct Quad {
+    pub q: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub mat: Rc<dyn Material>,
+    normal: Vec3,
+    /// `normal.dot(p)` for every point `p` on the plane.
+    d: Float,
+    /// Turns an offset in the plane into its coordinates along `u` and `v`.
+    w: Vec3,
+    area: Float,
+}
+
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, mat: Rc<dyn Material>) -> Self {
+        let n = u.cross(v);
+        let normal = n.normalize();
+        Self {
+            q,
+            u,
+            v,
+            mat,
+            normal,
+            d: normal.dot(q),
+            w: n / n.dot(n),
+            area: n.length(),
+        }
+    }
+
+    /// Returns the ray parameter and the coordinates of the hit point along `u` and `v`.
+    fn intersect(&self, r: Ray, t_min: Float, t_max: Float) -> Option<(Float, Float, Float)> {
+        // A ray parallel to the plane gives an infinite or NaN t, which an unbounded range would
+        // still contain if it is infinite.
+        let t = (self.d - self.normal.dot(r.origin)) / self.normal.dot(r.direction);
+        if !t.is_finite() || !(t_min..=t_max).contains(&t) {
+            return None;
+        }
+
+        let planar = r.at(t) - self.q;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some((t, alpha, beta))
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (t, alpha, beta) = self.intersect(r, t_min, t_max)?;
+
+        // Rebuilding the point from its coordinates puts it back on the plane. Errors in alpha
+        // and beta only move it within the plane, which leaves the rounding of the sum.
+        let (a, b) = (alpha * self.u, beta * self.v);
+        let point = self.q + a + b;
+        let point_error = gamma(3) * (self.q.abs() + a.abs() + b.abs());
+        let rec = HitRecord {
+            point,
+            point_error,
+            mat: Some(self.mat.clone()),
+            t,
+            u: alpha,
+            v: beta,
+            ..Default::default()
+        }
+        .with_face_normal(r, self.normal);
+
+        Some(rec)
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        let corners = [
+            self.q,
+            self.q + self.u,
+            self.q + self.v,
+            self.q + self.u + self.v,
+        ];
+        Some(Aabb::new(
+            corners.into_iter().reduce(Vec3::min).unwrap(),
+            corners.into_iter().reduce(Vec3::max).unwrap(),
+        ))
+    }
+}
+
+impl Light for Quad {
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> Float {
+        let Some((t, _, _)) =
+            self.intersect(Ray::new(origin, direction, 0.0), 0.0, Float::INFINITY)
+        else {
+            return 0.0;
+        };
+        let distance_squared = t * t * direction.length_squared();
+        let cosine = self.normal.dot(direction).abs() / direction.length();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        let mut rng = thread_rng();
+        self.q + rng.gen::<Float>() * self.u + rng.gen::<Float>() * self.v - origin
+    }
+}
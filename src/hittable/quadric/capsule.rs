@@ -0,0 +1,157 @@
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb,
+    float::{gamma, vec3, Float, Vec3},
+    hittable::{sphere, HitRecord, Hittable},
+    material::Material,
+    polynomial::quadratic,
+    ray::Ray,
+};
+
+use super::{phi, sweep_radians, AxisFrame, LocalHit};
+
+/// All points within `radius` of the segment from `start` to `end`: a cylinder closed by a
+/// hemisphere at either end.
+///
+/// `u` runs around the axis over the swept angle and `v` along it, from the tip of the
+/// hemisphere at `start` to the tip of the one at `end`.
+pub struct Capsule {
+    frame: AxisFrame,
+    pub radius: Float,
+    /// The swept angle around the axis, in radians.
+    pub phi_max: Float,
+    pub mat: Rc<dyn Material>,
+}
+
+/// Which part of a capsule a ray hits.
+#[derive(Clone, Copy)]
+enum CapsulePart {
+    Side,
+    /// The hemisphere around `start`.
+    Start,
+    /// The hemisphere around `end`.
+    End,
+}
+
+impl Capsule {
+    pub fn new(start: Vec3, end: Vec3, radius: Float, mat: Rc<dyn Material>) -> Self {
+        Self {
+            frame: AxisFrame::new(start, end),
+            radius,
+            phi_max: sweep_radians(360.0),
+            mat,
+        }
+    }
+
+    /// Only keeps the part within `degrees` around the axis.
+    pub fn with_sweep(mut self, degrees: Float) -> Self {
+        self.phi_max = sweep_radians(degrees);
+        self
+    }
+
+    /// Returns the nearest hit of the local ray `r` and the part it is on.
+    fn intersect(&self, r: Ray, t_min: Float, mut t_max: Float) -> Option<(Float, CapsulePart)> {
+        let (o, d) = (r.origin, r.direction);
+        let height = self.frame.height;
+        let radius_squared = self.radius * self.radius;
+        let mut nearest = None;
+
+        let side = quadratic(
+            d.x * d.x + d.y * d.y,
+            2.0 * (o.x * d.x + o.y * d.y),
+            o.x * o.x + o.y * o.y - radius_squared,
+        );
+        let start = quadratic(
+            d.length_squared(),
+            2.0 * o.dot(d),
+            o.length_squared() - radius_squared,
+        );
+        let end_center = vec3(0.0, 0.0, height);
+        let end = quadratic(
+            d.length_squared(),
+            2.0 * (o - end_center).dot(d),
+            (o - end_center).length_squared() - radius_squared,
+        );
+
+        // Each surface only counts over its own stretch of the axis.
+        let parts = [
+            (side, CapsulePart::Side),
+            (start, CapsulePart::Start),
+            (end, CapsulePart::End),
+        ];
+        for (roots, part) in parts {
+            let Some((t0, t1)) = roots else {
+                continue;
+            };
+            for t in [t0, t1] {
+                if !(t_min..=t_max).contains(&t) {
+                    continue;
+                }
+                let point = r.at(t);
+                let on_part = match part {
+                    CapsulePart::Side => (0.0..=height).contains(&point.z),
+                    CapsulePart::Start => point.z <= 0.0,
+                    CapsulePart::End => point.z >= height,
+                };
+                if on_part && phi(point) <= self.phi_max {
+                    nearest = Some((t, part));
+                    t_max = t;
+                    break;
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Moves a local point onto the hemisphere centered at height `z`, returning it with its
+    /// error bound and normal.
+    fn hemisphere_point(&self, point: Vec3, z: Float) -> (Vec3, Vec3, Vec3) {
+        let center = vec3(0.0, 0.0, z);
+        let (point, point_error) = sphere::surface_point(center, self.radius, point);
+        (point, point_error, (point - center) / self.radius)
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (local_ray, t_min) = self.frame.local_ray(r, t_min);
+        let (t, part) = self.intersect(local_ray, t_min, t_max)?;
+
+        let point = local_ray.at(t);
+        let (point, point_error, normal) = match part {
+            CapsulePart::Side => {
+                let scale = self.radius / (point.x * point.x + point.y * point.y).sqrt();
+                let point = vec3(point.x * scale, point.y * scale, point.z);
+                let point_error = gamma(3) * vec3(point.x.abs(), point.y.abs(), 0.0);
+                (
+                    point,
+                    point_error,
+                    vec3(point.x, point.y, 0.0) / self.radius,
+                )
+            }
+            CapsulePart::Start => self.hemisphere_point(point, 0.0),
+            CapsulePart::End => self.hemisphere_point(point, self.frame.height),
+        };
+        let hit = LocalHit {
+            t,
+            point,
+            point_error,
+            normal,
+            u: phi(point) / self.phi_max,
+            v: (point.z + self.radius) / (self.frame.height + 2.0 * self.radius),
+        };
+        Some(self.frame.hit_record(r, hit, &self.mat))
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        let (local_ray, t_min) = self.frame.local_ray(r, t_min);
+        self.intersect(local_ray, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        let (start, end) = (self.frame.start, self.frame.end());
+        let extent = Vec3::splat(self.radius);
+        Some(Aabb::new(start.min(end) - extent, start.max(end) + extent))
+    }
+}
@@ -0,0 +1,161 @@
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb,
+    float::{gamma, vec3, Float, Vec3},
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    polynomial::quadratic,
+    ray::Ray,
+};
+
+use super::{cap_crossing, cap_hit, phi, sweep_radians, AxisFrame, LocalHit, Part};
+
+/// A cone around the segment from `start` to `end`, whose radius changes linearly from
+/// `start_radius` to `end_radius`. With both radii above 0 it is a truncated cone, like a lamp
+/// shade.
+///
+/// `u` runs around the axis over the swept angle, `v` along the side from `start` to `end`, and
+/// on the caps from the axis out to the rim.
+pub struct Cone {
+    frame: AxisFrame,
+    pub start_radius: Float,
+    pub end_radius: Float,
+    pub caps: bool,
+    /// The swept angle around the axis, in radians.
+    pub phi_max: Float,
+    pub mat: Rc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(
+        start: Vec3,
+        end: Vec3,
+        start_radius: Float,
+        end_radius: Float,
+        mat: Rc<dyn Material>,
+    ) -> Self {
+        Self {
+            frame: AxisFrame::new(start, end),
+            start_radius,
+            end_radius,
+            caps: true,
+            phi_max: sweep_radians(360.0),
+            mat,
+        }
+    }
+
+    pub fn with_caps(mut self, caps: bool) -> Self {
+        self.caps = caps;
+        self
+    }
+
+    /// Only keeps the part within `degrees` around the axis.
+    pub fn with_sweep(mut self, degrees: Float) -> Self {
+        self.phi_max = sweep_radians(degrees);
+        self
+    }
+
+    /// How much the radius grows per unit along the axis.
+    fn slope(&self) -> Float {
+        (self.end_radius - self.start_radius) / self.frame.height
+    }
+
+    /// Returns the nearest hit of the local ray `r` and the part it is on.
+    fn intersect(&self, r: Ray, t_min: Float, mut t_max: Float) -> Option<(Float, Part)> {
+        let (o, d) = (r.origin, r.direction);
+        let k = self.slope();
+        let mut nearest = None;
+
+        // x^2 + y^2 = (start_radius + k z)^2 along the ray.
+        let radius_at_origin = self.start_radius + k * o.z;
+        let a = d.x * d.x + d.y * d.y - k * k * d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.y * d.y - k * d.z * radius_at_origin);
+        let c = o.x * o.x + o.y * o.y - radius_at_origin * radius_at_origin;
+        if let Some((t0, t1)) = quadratic(a, b, c) {
+            for t in [t0, t1] {
+                if !(t_min..=t_max).contains(&t) {
+                    continue;
+                }
+                // Between the ends also rules out the mirrored cone beyond the apex.
+                let point = r.at(t);
+                if (0.0..=self.frame.height).contains(&point.z) && phi(point) <= self.phi_max {
+                    nearest = Some((t, Part::Side));
+                    t_max = t;
+                    break;
+                }
+            }
+        }
+
+        if self.caps {
+            for (z, radius, part) in [
+                (0.0, self.start_radius, Part::BottomCap),
+                (self.frame.height, self.end_radius, Part::TopCap),
+            ] {
+                if radius == 0.0 {
+                    continue;
+                }
+                if let Some(t) = cap_crossing(r, z, radius, self.phi_max, t_min, t_max) {
+                    nearest = Some((t, part));
+                    t_max = t;
+                }
+            }
+        }
+        nearest
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (local_ray, t_min) = self.frame.local_ray(r, t_min);
+        let (t, part) = self.intersect(local_ray, t_min, t_max)?;
+
+        let hit = match part {
+            Part::Side => {
+                // Scaling the point to the radius at its height puts it on the surface up to a
+                // few roundings of its distance from the axis.
+                let k = self.slope();
+                let point = local_ray.at(t);
+                let radius = self.start_radius + k * point.z;
+                let distance = (point.x * point.x + point.y * point.y).sqrt();
+                let point = if distance > 0.0 {
+                    let scale = radius / distance;
+                    vec3(point.x * scale, point.y * scale, point.z)
+                } else {
+                    point
+                };
+                // The apex has no normal of its own; use the one of its tip.
+                let normal = vec3(point.x, point.y, -k * radius)
+                    .try_normalize()
+                    .unwrap_or(vec3(0.0, 0.0, -k.signum()));
+                LocalHit {
+                    t,
+                    point,
+                    point_error: gamma(7) * vec3(point.x.abs(), point.y.abs(), 0.0),
+                    normal,
+                    u: phi(point) / self.phi_max,
+                    v: point.z / self.frame.height,
+                }
+            }
+            Part::BottomCap => cap_hit(local_ray, t, 0.0, self.start_radius, self.phi_max, -1.0),
+            Part::TopCap => cap_hit(
+                local_ray,
+                t,
+                self.frame.height,
+                self.end_radius,
+                self.phi_max,
+                1.0,
+            ),
+        };
+        Some(self.frame.hit_record(r, hit, &self.mat))
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        let (local_ray, t_min) = self.frame.local_ray(r, t_min);
+        self.intersect(local_ray, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        Some(self.frame.bounding_box(self.start_radius, self.end_radius))
+    }
+}
@@ -0,0 +1,125 @@
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb,
+    float::{gamma, vec3, Float, Vec3},
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    polynomial::quadratic,
+    ray::Ray,
+};
+
+use super::{cap_crossing, cap_hit, phi, sweep_radians, AxisFrame, LocalHit, Part};
+
+/// A cylinder of the given radius around the segment from `start` to `end`.
+///
+/// `u` runs around the axis over the swept angle, `v` along the side from `start` to `end`, and
+/// on the caps from the axis out to the rim.
+pub struct Cylinder {
+    frame: AxisFrame,
+    pub radius: Float,
+    pub caps: bool,
+    /// The swept angle around the axis, in radians.
+    pub phi_max: Float,
+    pub mat: Rc<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(start: Vec3, end: Vec3, radius: Float, mat: Rc<dyn Material>) -> Self {
+        Self {
+            frame: AxisFrame::new(start, end),
+            radius,
+            caps: true,
+            phi_max: sweep_radians(360.0),
+            mat,
+        }
+    }
+
+    pub fn with_caps(mut self, caps: bool) -> Self {
+        self.caps = caps;
+        self
+    }
+
+    /// Only keeps the part within `degrees` around the axis.
+    pub fn with_sweep(mut self, degrees: Float) -> Self {
+        self.phi_max = sweep_radians(degrees);
+        self
+    }
+
+    /// Returns the nearest hit of the local ray `r` and the part it is on.
+    fn intersect(&self, r: Ray, t_min: Float, mut t_max: Float) -> Option<(Float, Part)> {
+        let (o, d) = (r.origin, r.direction);
+        let mut nearest = None;
+
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.y * d.y);
+        let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+        if let Some((t0, t1)) = quadratic(a, b, c) {
+            for t in [t0, t1] {
+                if !(t_min..=t_max).contains(&t) {
+                    continue;
+                }
+                let point = r.at(t);
+                if (0.0..=self.frame.height).contains(&point.z) && phi(point) <= self.phi_max {
+                    nearest = Some((t, Part::Side));
+                    t_max = t;
+                    break;
+                }
+            }
+        }
+
+        if self.caps {
+            for (z, part) in [(0.0, Part::BottomCap), (self.frame.height, Part::TopCap)] {
+                if let Some(t) = cap_crossing(r, z, self.radius, self.phi_max, t_min, t_max) {
+                    nearest = Some((t, part));
+                    t_max = t;
+                }
+            }
+        }
+        nearest
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (local_ray, t_min) = self.frame.local_ray(r, t_min);
+        let (t, part) = self.intersect(local_ray, t_min, t_max)?;
+
+        let hit = match part {
+            Part::Side => {
+                // Pushing the point back out to the radius puts it on the surface up to a few
+                // roundings of its distance from the axis. Moving along the axis keeps it there.
+                let point = local_ray.at(t);
+                let scale = self.radius / (point.x * point.x + point.y * point.y).sqrt();
+                let point = vec3(point.x * scale, point.y * scale, point.z);
+                LocalHit {
+                    t,
+                    point,
+                    point_error: gamma(3) * vec3(point.x.abs(), point.y.abs(), 0.0),
+                    normal: vec3(point.x, point.y, 0.0) / self.radius,
+                    u: phi(point) / self.phi_max,
+                    v: point.z / self.frame.height,
+                }
+            }
+            Part::BottomCap => cap_hit(local_ray, t, 0.0, self.radius, self.phi_max, -1.0),
+            Part::TopCap => cap_hit(
+                local_ray,
+                t,
+                self.frame.height,
+                self.radius,
+                self.phi_max,
+                1.0,
+            ),
+        };
+        Some(self.frame.hit_record(r, hit, &self.mat))
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        let (local_ray, t_min) = self.frame.local_ray(r, t_min);
+        self.intersect(local_ray, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        Some(self.frame.bounding_box(self.radius, self.radius))
+    }
+}
@@ -0,0 +1,428 @@
+//! Round shapes built around an axis segment: cylinders, cones and capsules.
+//!
+//! Each is intersected in a local frame where the axis runs along z from 0 to the segment's
+//! length, and can be swept through less than a full turn around it. Only full sweeps with caps
+//! are closed, so only those work as the boundary of a [`super::ConstantMedium`].
+
+mod capsule;
+mod cone;
+mod cylinder;
+
+pub use capsule::Capsule;
+pub use cone::Cone;
+pub use cylinder::Cylinder;
+
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb,
+    float::{self, consts::TAU, gamma, vec3, Float, Mat3, Vec3},
+    hittable::HitRecord,
+    material::Material,
+    ray::Ray,
+};
+
+use super::disk::disk_extent;
+
+/// An orthonormal frame whose z axis runs from `start` to `end` of a shape's axis.
+struct AxisFrame {
+    start: Vec3,
+    height: Float,
+    to_world: Mat3,
+    to_local: Mat3,
+}
+
+impl AxisFrame {
+    fn new(start: Vec3, end: Vec3) -> Self {
+        let axis = end - start;
+        let height = axis.length();
+        let w = axis / height;
+        let (t, b) = w.any_orthonormal_pair();
+        let to_world = Mat3::from_cols(t, b, w);
+        Self {
+            start,
+            height,
+            to_world,
+            to_local: to_world.transpose(),
+        }
+    }
+
+    fn axis(&self) -> Vec3 {
+        self.to_world.z_axis
+    }
+
+    fn end(&self) -> Vec3 {
+        self.start + self.height * self.axis()
+    }
+
+    /// The ray in local coordinates, and `t_min` raised past the rounding error of its origin.
+    /// The frame is orthonormal, so ray parameters carry over.
+    fn local_ray(&self, r: Ray, t_min: Float) -> (Ray, Float) {
+        let offset = r.origin - self.start;
+        let local_ray = Ray::new(self.to_local * offset, self.to_local * r.direction, r.time);
+        let error =
+            float::transformed_error(self.to_local, Vec3::ZERO, offset, gamma(1) * offset.abs());
+        (local_ray, local_ray.clear_t_min(t_min, error))
+    }
+
+    /// The box around the discs of the given radii at both ends, which bounds anything swept
+    /// around the axis between them.
+    fn bounding_box(&self, start_radius: Float, end_radius: Float) -> Aabb {
+        let (start, end) = (self.start, self.end());
+        let start_extent = disk_extent(self.axis(), start_radius);
+        let end_extent = disk_extent(self.axis(), end_radius);
+        Aabb::new(
+            (start - start_extent).min(end - end_extent),
+            (start + start_extent).max(end + end_extent),
+        )
+    }
+
+    /// Turns a hit found in local coordinates into a world-space record.
+    fn hit_record(&self, r: Ray, hit: LocalHit, mat: &Rc<dyn Material>) -> HitRecord {
+        HitRecord {
+            point: self.start + self.to_world * hit.point,
+            point_error: float::transformed_error(
+                self.to_world,
+                self.start,
+                hit.point,
+                hit.point_error,
+            ),
+            mat: Some(mat.clone()),
+            t: hit.t,
+            u: hit.u,
+            v: hit.v,
+            ..Default::default()
+        }
+        .with_face_normal(r, self.to_world * hit.normal)
+    }
+}
+
+/// A hit in the local frame of an [`AxisFrame`].
+struct LocalHit {
+    t: Float,
+    point: Vec3,
+    point_error: Vec3,
+    /// Unit length and pointing out of the shape.
+    normal: Vec3,
+    u: Float,
+    v: Float,
+}
+
+/// Which part of a shape a ray hits.
+#[derive(Clone, Copy)]
+enum Part {
+    Side,
+    /// The flat end at z = 0.
+    BottomCap,
+    /// The flat end at z = height.
+    TopCap,
+}
+
+/// The angle of a local point around the axis, in `[0, 2π)`.
+fn phi(point: Vec3) -> Float {
+    let phi = point.y.atan2(point.x);
+    if phi < 0.0 {
+        phi + TAU
+    } else {
+        phi
+    }
+}
+
+/// Where `r` crosses the flat cap at height `z`, if it is in range and within `radius` of the
+/// axis and `phi_max` of the sweep.
+fn cap_crossing(
+    r: Ray,
+    z: Float,
+    radius: Float,
+    phi_max: Float,
+    t_min: Float,
+    t_max: Float,
+) -> Option<Float> {
+    let t = (z - r.origin.z) / r.direction.z;
+    if !(t_min..=t_max).contains(&t) {
+        return None;
+    }
+    let point = r.at(t);
+    let inside = point.x * point.x + point.y * point.y <= radius * radius;
+    (inside && phi(point) <= phi_max).then_some(t)
+}
+
+/// The hit on a cap at height `z`, facing along `normal_z`, with `v` running from the axis out
+/// to the rim.
+fn cap_hit(r: Ray, t: Float, z: Float, radius: Float, phi_max: Float, normal_z: Float) -> LocalHit {
+    let mut point = r.at(t);
+    let mut point_error = gamma(3) * (r.origin.abs() + (t * r.direction).abs());
+    // Being on the cap only depends on z, which is exact.
+    point.z = z;
+    point_error.z = 0.0;
+    LocalHit {
+        t,
+        point,
+        point_error,
+        normal: vec3(0.0, 0.0, normal_z),
+        u: phi(point) / phi_max,
+        v: (point.x * point.x + point.y * point.y).sqrt() / radius,
+    }
+}
+
+/// Converts a sweep angle in degrees, clamping it to a full turn.
+fn sweep_radians(degrees: Float) -> Float {
+    degrees.to_radians().clamp(0.0, TAU)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        hittable::{ConstantMedium, Hittable},
+        material::Lambertian,
+        math::random_unit_vec,
+    };
+
+    /// A tilted axis of length 2, with the unit vectors along it and across it that the shapes
+    /// measure their sweep from.
+    struct Axis {
+        start: Vec3,
+        end: Vec3,
+        w: Vec3,
+        x: Vec3,
+        y: Vec3,
+    }
+
+    impl Axis {
+        fn new() -> Self {
+            let start = vec3(1.0, -1.0, 0.5);
+            let end = start + 2.0 * vec3(2.0, 1.0, -2.0) / 3.0;
+            let w = (end - start) / (end - start).length();
+            let (x, y) = w.any_orthonormal_pair();
+            Self {
+                start,
+                end,
+                w,
+                x,
+                y,
+            }
+        }
+
+        /// The point of the axis halfway along it.
+        fn middle(&self) -> Vec3 {
+            0.5 * (self.start + self.end)
+        }
+    }
+
+    fn mat() -> Rc<dyn Material> {
+        Rc::new(Lambertian::from_color(Vec3::splat(0.5)))
+    }
+
+    /// A cylinder and a capsule of radius 0.5, and a cone narrowing from 1 to 0.5, each with
+    /// its radius and slope halfway along the axis.
+    fn shapes(axis: &Axis, degrees: Float) -> [(&'static str, Rc<dyn Hittable>, Float, Float); 3] {
+        let (start, end) = (axis.start, axis.end);
+        [
+            (
+                "cylinder",
+                Rc::new(Cylinder::new(start, end, 0.5, mat()).with_sweep(degrees)),
+                0.5,
+                0.0,
+            ),
+            (
+                "cone",
+                Rc::new(Cone::new(start, end, 1.0, 0.5, mat()).with_sweep(degrees)),
+                0.75,
+                -0.25,
+            ),
+            (
+                "capsule",
+                Rc::new(Capsule::new(start, end, 0.5, mat()).with_sweep(degrees)),
+                0.5,
+                0.0,
+            ),
+        ]
+    }
+
+    /// Checks the hit of `object` along the ray against the expected `t` and normal pointing
+    /// out of the shape.
+    fn assert_hit(
+        name: &str,
+        object: &dyn Hittable,
+        origin: Vec3,
+        direction: Vec3,
+        expected: Option<(Float, Vec3)>,
+    ) {
+        let tolerance = 1e3 * Float::EPSILON;
+        let r = Ray::new(origin, direction, 0.0);
+        let rec = object.hit(r, 0.0, Float::INFINITY);
+        match (rec, expected) {
+            (Some(rec), Some((t, outward))) => {
+                assert!((rec.t - t).abs() < tolerance, "{name}: {} != {t}", rec.t);
+                let normal = if rec.front_face {
+                    rec.normal
+                } else {
+                    -rec.normal
+                };
+                assert!(normal.abs_diff_eq(outward, tolerance), "{name}: {normal}");
+                assert!(rec.point.abs_diff_eq(r.at(t), tolerance), "{name}");
+            }
+            (None, None) => {}
+            (rec, _) => panic!("{name}: {:?}", rec.map(|rec| rec.t)),
+        }
+    }
+
+    #[test]
+    fn hits_match_the_analytic_ones() {
+        let axis = Axis::new();
+        let Axis {
+            start, end, w, x, ..
+        } = axis;
+
+        // Straight at the side, halfway along the axis.
+        for (name, shape, radius, slope) in shapes(&axis, 360.0) {
+            let outward = (x - slope * w).normalize();
+            let origin = axis.middle() + 5.0 * x;
+            assert_hit(name, &*shape, origin, -x, Some((5.0 - radius, outward)));
+            // Not even a cone's side is hit from the axis beyond its ends.
+            assert_hit(name, &*shape, end + 5.0 * w + 5.0 * x, -x, None);
+        }
+
+        let cylinder = Cylinder::new(start, end, 0.5, mat());
+        let cone = Cone::new(start, end, 1.0, 0.5, mat());
+        let capsule = Capsule::new(start, end, 0.5, mat());
+        for (name, shape) in [("cylinder", &cylinder as &dyn Hittable), ("cone", &cone)] {
+            assert_hit(name, shape, end + 3.0 * w + 0.2 * x, -w, Some((3.0, w)));
+            assert_hit(name, shape, start - 3.0 * w + 0.2 * x, w, Some((3.0, -w)));
+        }
+        // Past the narrow end's rim but within the wide end's.
+        assert_hit(
+            "cone",
+            &cone,
+            end + 3.0 * w + 0.7 * x,
+            -w,
+            Some((3.8, (x - -0.25 * w).normalize())),
+        );
+        let uncapped = Cylinder::new(start, end, 0.5, mat()).with_caps(false);
+        assert_hit("uncapped", &uncapped, end + 3.0 * w, -w, None);
+
+        // The hemispheres, off their tips.
+        let hemisphere_normal = (0.3 * x + 0.4 * w) / 0.5;
+        assert_hit(
+            "capsule",
+            &capsule,
+            end + 3.0 * w + 0.3 * x,
+            -w,
+            Some((2.6, hemisphere_normal)),
+        );
+        assert_hit("capsule", &capsule, start - 3.0 * w, w, Some((2.5, -w)));
+        // From inside, the far side.
+        assert_hit("capsule", &capsule, axis.middle(), w, Some((1.5, w)));
+    }
+
+    #[test]
+    fn partial_sweeps_leave_out_a_wedge() {
+        let axis = Axis::new();
+        let Axis { start, w, x, y, .. } = axis;
+        // 270 degrees leave out the quarter between -y and x, around the bisector `b`.
+        let b = (x - y) / Float::sqrt(2.0);
+        let across = (x + y) / Float::sqrt(2.0);
+        let half_chord = Float::sqrt(1.0 - 0.81);
+        for (name, shape, radius, slope) in shapes(&axis, 270.0) {
+            // A chord of the removed quarter, and of the opposite one that is kept.
+            let origin = axis.middle() + 0.9 * radius * b - 5.0 * across;
+            assert_hit(name, &*shape, origin, across, None);
+            let origin = axis.middle() - 0.9 * radius * b - 5.0 * across;
+            let radial = -0.9 * b - half_chord * across;
+            let outward = (radial - slope * w).normalize();
+            let expected = Some((5.0 - half_chord * radius, outward));
+            assert_hit(name, &*shape, origin, across, expected);
+
+            // Along the axis within the wedge, through both its caps or hemispheres, and
+            // through the kept part of the start's.
+            assert_hit(name, &*shape, start - 3.0 * w + 0.25 * b, w, None);
+            let kept = if name == "capsule" {
+                let depth = Float::sqrt(0.25 - 0.0625);
+                (3.0 - depth, (-0.25 * b - depth * w) / 0.5)
+            } else {
+                (3.0, -w)
+            };
+            assert_hit(name, &*shape, start - 3.0 * w - 0.25 * b, w, Some(kept));
+        }
+    }
+
+    /// Random rays from all around `shape` that head somewhere near it.
+    fn rays_at(shape: &dyn Hittable, rng: &mut StdRng) -> Vec<Ray> {
+        let bbox = shape.bounding_box(0.0, 1.0).unwrap();
+        let (center, size) = (bbox.centroid(), (bbox.max - bbox.min).length());
+        (0..2000)
+            .map(|_| {
+                let origin = center + size * random_unit_vec();
+                let target = center + 0.5 * size * rng.gen::<Float>() * random_unit_vec();
+                Ray::new(origin, target - origin, 0.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn surface_coordinates_and_bounds() {
+        let axis = Axis::new();
+        let mut rng = StdRng::seed_from_u64(40);
+        for degrees in [360.0, 270.0, 90.0] {
+            for (name, shape, _, _) in shapes(&axis, degrees) {
+                let bbox = shape.bounding_box(0.0, 1.0).unwrap();
+                let (mut u_range, mut v_range) = ((1.0, 0.0), (1.0, 0.0));
+                let mut hits = 0;
+                for r in rays_at(&*shape, &mut rng) {
+                    let Some(rec) = shape.hit(r, 0.0, Float::INFINITY) else {
+                        continue;
+                    };
+                    hits += 1;
+                    let slack = Vec3::splat(1e-6);
+                    assert!(
+                        (bbox.min - slack).cmple(rec.point).all()
+                            && rec.point.cmple(bbox.max + slack).all(),
+                        "{name} {degrees}: {} outside {bbox:?}",
+                        rec.point
+                    );
+                    u_range = (rec.u.min(u_range.0), rec.u.max(u_range.1));
+                    v_range = (rec.v.min(v_range.0), rec.v.max(v_range.1));
+                }
+                assert!(hits > 200, "{name} {degrees}: {hits}");
+                // The ranges are covered, and never exceeded.
+                for (range, what) in [(u_range, "u"), (v_range, "v")] {
+                    assert!(
+                        0.0 <= range.0 && range.0 < 0.05 && 0.95 < range.1 && range.1 <= 1.0,
+                        "{name} {degrees}: {what} in {range:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn full_sweeps_bound_a_medium() {
+        let axis = Axis::new();
+        let Axis { x, .. } = axis;
+        for (name, shape, radius, _) in shapes(&axis, 360.0) {
+            // So dense that rays scatter right where they enter.
+            let fog = ConstantMedium::from_color(shape, 1e6, Vec3::ONE);
+            for (origin, direction, expected) in [
+                (axis.middle() + 5.0 * x, -x, Some(5.0 - radius)),
+                (axis.middle(), x, Some(0.0)),
+                (axis.middle() + 5.0 * x, axis.w, None),
+            ] {
+                let r = Ray::new(origin, direction, 0.0);
+                let t = fog.hit(r, 0.0, Float::INFINITY).map(|rec| rec.t);
+                match (t, expected) {
+                    (Some(t), Some(expected)) => {
+                        assert!(
+                            (expected - 1e-5..expected + 1e-4).contains(&t),
+                            "{name}: {t}"
+                        )
+                    }
+                    (None, None) => {}
+                    _ => panic!("{name}: {t:?}"),
+                }
+            }
+        }
+    }
+}
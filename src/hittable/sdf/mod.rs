@@ -0,0 +1,135 @@
+//! Shapes given by signed distance functions and rendered by sphere tracing.
+//!
+//! A ray is marched forward by the distance to the nearest surface at each step until it gets
+//! within a small epsilon of one. That works for any shape whose distance can be bounded, which
+//! takes in smooth blends and twisted or repeated shapes that have no analytic intersection.
+
+mod ops;
+mod shapes;
+
+pub use ops::{Repeat, SmoothUnion, Subtraction, Translate, Twist};
+pub use shapes::{Capsule, Cuboid, RoundedCuboid, Sphere, Torus};
+
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb,
+    float::{vec3, Float, Vec3},
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+};
+
+/// A signed distance function: negative inside a shape and positive outside. Its magnitude must
+/// never exceed the distance to the surface, since that is how far a ray may safely step.
+pub trait Sdf {
+    fn distance(&self, point: Vec3) -> Float;
+}
+
+impl<F: Fn(Vec3) -> Float> Sdf for F {
+    fn distance(&self, point: Vec3) -> Float {
+        self(point)
+    }
+}
+
+/// The surface where `sdf` is 0, within `bbox`.
+///
+/// The box only limits where rays are marched, so it has to contain the whole surface for none
+/// of it to be cut off. Rays that use up `max_steps` without getting within `epsilon` of the
+/// surface count as misses, which mostly happens when they graze it.
+pub struct SdfHittable {
+    pub sdf: Rc<dyn Sdf>,
+    pub bbox: Aabb,
+    pub max_steps: u32,
+    pub epsilon: Float,
+    pub mat: Rc<dyn Material>,
+}
+
+impl SdfHittable {
+    pub fn new(sdf: Rc<dyn Sdf>, bbox: Aabb, mat: Rc<dyn Material>) -> Self {
+        Self {
+            sdf,
+            bbox,
+            max_steps: 256,
+            epsilon: 1e-4,
+            mat,
+        }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// How close to the surface a ray has to get to hit it.
+    pub fn with_epsilon(mut self, epsilon: Float) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    /// Marches `r` through the box and returns where it first gets within `epsilon` of the
+    /// surface.
+    fn march(&self, r: Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        let (mut t, t_max) = self.bbox.clip(r, t_min, t_max)?;
+        // Distances are in world units and `t` in multiples of the direction.
+        let inv_length = 1.0 / r.direction.length();
+        for _ in 0..self.max_steps {
+            // From inside the shape the distance is negative, but just as safe a step.
+            let distance = self.sdf.distance(r.at(t)).abs();
+            if distance < self.epsilon {
+                return Some(t);
+            }
+            t += distance * inv_length;
+            if t > t_max {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// The gradient by finite differences over a tetrahedron around `point`, which takes four
+    /// evaluations where central differences take six.
+    fn normal(&self, point: Vec3) -> Vec3 {
+        let h = self.epsilon;
+        let offsets = [
+            vec3(1.0, -1.0, -1.0),
+            vec3(-1.0, -1.0, 1.0),
+            vec3(-1.0, 1.0, -1.0),
+            vec3(1.0, 1.0, 1.0),
+        ];
+        offsets
+            .iter()
+            .fold(Vec3::ZERO, |sum, &k| {
+                sum + k * self.sdf.distance(point + h * k)
+            })
+            .try_normalize()
+            .unwrap_or(Vec3::Y)
+    }
+}
+
+impl Hittable for SdfHittable {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let t = self.march(r, t_min, t_max)?;
+        let point = r.at(t);
+        Some(
+            HitRecord {
+                point,
+                // The surface is within epsilon of the point. Twice that keeps a spawned ray
+                // from ending its march where it starts, on either side of the surface.
+                point_error: Vec3::splat(2.0 * self.epsilon),
+                mat: Some(self.mat.clone()),
+                t,
+                ..Default::default()
+            }
+            .with_face_normal(r, self.normal(point)),
+        )
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.march(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
@@ -0,0 +1,158 @@
+//! Ways of building new distance functions out of others.
+
+use std::rc::Rc;
+
+use crate::float::{vec3, Float, Vec3};
+
+use super::Sdf;
+
+/// The union of two shapes, blended into each other over a distance of about `k`.
+pub struct SmoothUnion {
+    pub left: Rc<dyn Sdf>,
+    pub right: Rc<dyn Sdf>,
+    pub k: Float,
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, point: Vec3) -> Float {
+        let (a, b) = (self.left.distance(point), self.right.distance(point));
+        // The polynomial smooth minimum, which undercuts min(a, b) by at most k / 4 where the
+        // two are close and matches it elsewhere.
+        let h = (0.5 + 0.5 * (b - a) / self.k).clamp(0.0, 1.0);
+        b + (a - b) * h - self.k * h * (1.0 - h)
+    }
+}
+
+/// Everything inside `left` but not `right`.
+pub struct Subtraction {
+    pub left: Rc<dyn Sdf>,
+    pub right: Rc<dyn Sdf>,
+}
+
+impl Sdf for Subtraction {
+    fn distance(&self, point: Vec3) -> Float {
+        self.left.distance(point).max(-self.right.distance(point))
+    }
+}
+
+/// Copies of a shape on a grid with spacing `period`, one centered on each grid point.
+///
+/// Only the copy in the cell of the point is looked at, so the shape must fit within half a
+/// period of the origin to stay exact. The grid is endless; the bounding box of the
+/// [`super::SdfHittable`] decides how much of it is seen.
+pub struct Repeat {
+    pub shape: Rc<dyn Sdf>,
+    pub period: Vec3,
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, point: Vec3) -> Float {
+        let cell = (point / self.period).round();
+        self.shape.distance(point - cell * self.period)
+    }
+}
+
+/// A shape twisted around the y axis by `rate` radians per unit of height.
+///
+/// Twisting stretches space more the further it is from the axis, by at most `1 + |rate| r` at
+/// radius `r`. The distance is scaled down by that stretch at the furthest radius a step could
+/// reach, which keeps steps safe at the cost of taking more of them.
+pub struct Twist {
+    pub shape: Rc<dyn Sdf>,
+    pub rate: Float,
+}
+
+impl Sdf for Twist {
+    fn distance(&self, point: Vec3) -> Float {
+        let (sin, cos) = (self.rate * point.y).sin_cos();
+        let untwisted = vec3(
+            cos * point.x + sin * point.z,
+            point.y,
+            -sin * point.x + cos * point.z,
+        );
+        let radius = vec3(point.x, 0.0, point.z).length();
+        let distance = self.shape.distance(untwisted);
+        // A step no longer than the distance stays within `radius + |distance|` of the axis.
+        let stretch = 1.0 + self.rate.abs() * (radius + distance.abs());
+        distance / stretch
+    }
+}
+
+/// A shape moved by `offset`.
+pub struct Translate {
+    pub shape: Rc<dyn Sdf>,
+    pub offset: Vec3,
+}
+
+impl Sdf for Translate {
+    fn distance(&self, point: Vec3) -> Float {
+        self.shape.distance(point - self.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        aabb::Aabb,
+        hittable::{
+            sdf::{Cuboid, SdfHittable},
+            Hittable,
+        },
+        material::Lambertian,
+        math::random_unit_vec,
+        ray::Ray,
+    };
+
+    #[test]
+    fn twisted_thin_box_is_never_stepped_through() {
+        let twist = Rc::new(Twist {
+            shape: Rc::new(Cuboid {
+                half_extents: vec3(1.0, 1.5, 0.01),
+            }),
+            rate: 1.5,
+        });
+        let bbox = Aabb::new(vec3(-1.1, -1.6, -1.1), vec3(1.1, 1.6, 1.1));
+        let sdf = SdfHittable::new(
+            twist.clone(),
+            bbox,
+            Rc::new(Lambertian::from_color(Vec3::ONE)),
+        )
+        .with_max_steps(100_000);
+
+        let mut rng = StdRng::seed_from_u64(43);
+        let step = 2e-4;
+        let mut hits = 0;
+        for _ in 0..200 {
+            let origin = 3.0 * random_unit_vec();
+            let target = vec3(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.5..1.5),
+                rng.gen_range(-1.0..1.0),
+            );
+            let r = Ray::new(origin, (target - origin).normalize(), 0.0);
+
+            // The sign of the distance is exact even where its size isn't, so a dense search
+            // for where it first turns negative finds the surface.
+            let (t_enter, t_exit) = bbox.clip(r, 0.0, Float::INFINITY).unwrap();
+            let crossing = (0..)
+                .map(|i| t_enter + i as Float * step)
+                .take_while(|&t| t < t_exit)
+                .find(|&t| twist.distance(r.at(t)) <= 0.0);
+
+            match (sdf.hit(r, 0.0, Float::INFINITY), crossing) {
+                (Some(rec), Some(crossing)) => {
+                    hits += 1;
+                    assert!(rec.t <= crossing + step, "{} > {crossing}", rec.t);
+                }
+                (None, Some(crossing)) => panic!("stepped through the surface at {crossing}"),
+                // Rays may come within epsilon of the surface between two search steps.
+                (Some(rec), None) => assert!(twist.distance(rec.point).abs() < sdf.epsilon),
+                (None, None) => {}
+            }
+        }
+        assert!(hits > 50, "{hits}");
+    }
+}
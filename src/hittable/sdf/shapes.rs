@@ -0,0 +1,72 @@
+//! Exact distance functions of basic shapes, all centered on the origin.
+
+use crate::float::{vec3, Float, Vec3};
+
+use super::Sdf;
+
+pub struct Sphere {
+    pub radius: Float,
+}
+
+impl Sdf for Sphere {
+    fn distance(&self, point: Vec3) -> Float {
+        point.length() - self.radius
+    }
+}
+
+/// A box reaching `half_extents` out from the origin along each axis.
+pub struct Cuboid {
+    pub half_extents: Vec3,
+}
+
+impl Sdf for Cuboid {
+    fn distance(&self, point: Vec3) -> Float {
+        let q = point.abs() - self.half_extents;
+        // Outside, the distance to the nearest corner, edge or face; inside, to the nearest face.
+        q.max(Vec3::ZERO).length() + q.max_element().min(0.0)
+    }
+}
+
+/// A [`Cuboid`] with its edges and corners rounded off to `radius`, within the same extents.
+pub struct RoundedCuboid {
+    pub half_extents: Vec3,
+    pub radius: Float,
+}
+
+impl Sdf for RoundedCuboid {
+    fn distance(&self, point: Vec3) -> Float {
+        let core = Cuboid {
+            half_extents: self.half_extents - Vec3::splat(self.radius),
+        };
+        core.distance(point) - self.radius
+    }
+}
+
+/// A ring in the xz plane, around the y axis.
+pub struct Torus {
+    pub major_radius: Float,
+    pub minor_radius: Float,
+}
+
+impl Sdf for Torus {
+    fn distance(&self, point: Vec3) -> Float {
+        let ring = vec3(point.x, 0.0, point.z).length() - self.major_radius;
+        (ring * ring + point.y * point.y).sqrt() - self.minor_radius
+    }
+}
+
+/// All points within `radius` of the segment from `start` to `end`.
+pub struct Capsule {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub radius: Float,
+}
+
+impl Sdf for Capsule {
+    fn distance(&self, point: Vec3) -> Float {
+        let axis = self.end - self.start;
+        let offset = point - self.start;
+        let h = (offset.dot(axis) / axis.length_squared()).clamp(0.0, 1.0);
+        (offset - h * axis).length() - self.radius
+    }
+}
@@ -0,0 +1,360 @@
+use std::{fs, path::Path, rc::Rc};
+
+use crate::{
+    aabb::Aabb,
+    float::{vec3, Float, Vec3},
+    hittable::{
+        bvh::{BvhBuilder, LinearBvh},
+        HitRecord, Hittable,
+    },
+    material::Material,
+    ply,
+    ray::Ray,
+};
+
+use super::{nearest_root, surface_point, Sphere};
+
+/// Points with an optional radius and color each, such as the particles of a simulation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PointCloud {
+    pub centers: Vec<Vec3>,
+    pub radii: Option<Vec<Float>>,
+    /// In `[0, 1]`.
+    pub colors: Option<Vec<Vec3>>,
+}
+
+impl PointCloud {
+    /// Reads the vertices of a PLY file, with their `radius` and `red`, `green` and `blue`
+    /// properties if they have them, or otherwise a text file with one point per line.
+    ///
+    /// A line of text is `x y z`, optionally followed by a radius and then a color, so it has 3,
+    /// 4, 6 or 7 numbers, separated by whitespace or commas as in CSV. Every line has to have
+    /// the same number. Lines starting with `#` are comments and a first line that isn't
+    /// numbers is skipped as the header of a CSV file. Colors go from 0 to 1, unless any of them
+    /// is above 1, in which case they all go from 0 to 255.
+    pub fn load(filename: &str) -> Result<Self, String> {
+        let bytes = fs::read(filename).map_err(|err| err.to_string())?;
+        let cloud = if bytes.starts_with(b"ply") {
+            let mesh = ply::read(Path::new(filename))?;
+            Self {
+                centers: mesh.positions,
+                radii: mesh.radii,
+                colors: mesh.colors,
+            }
+        } else {
+            let src = String::from_utf8(bytes).map_err(|_| "not a PLY or text file".to_owned())?;
+            parse_text(&src)?
+        };
+
+        let radii = cloud.radii.iter().flatten();
+        if let Some((index, radius)) = radii.enumerate().find(|(_, r)| r.is_nan() || **r <= 0.0) {
+            return Err(format!("point {index} has a radius of {radius}"));
+        }
+        Ok(cloud)
+    }
+}
+
+fn parse_text(src: &str) -> Result<PointCloud, String> {
+    let mut cloud = PointCloud::default();
+    let mut radii = Vec::new();
+    let mut colors = Vec::new();
+    let mut columns = None;
+    let mut first = true;
+    for (index, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let header = first;
+        first = false;
+        let numbers = match line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.parse::<Float>())
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(numbers) => numbers,
+            Err(_) if header => continue,
+            Err(err) => return Err(format!("line {}: {err}", index + 1)),
+        };
+        if !matches!(numbers.len(), 3 | 4 | 6 | 7) {
+            return Err(format!(
+                "line {}: expected `x y z`, optionally followed by a radius and `r g b`",
+                index + 1
+            ));
+        }
+        if *columns.get_or_insert(numbers.len()) != numbers.len() {
+            return Err(format!(
+                "line {}: expected {} numbers like the lines before it",
+                index + 1,
+                columns.unwrap_or_default()
+            ));
+        }
+
+        cloud.centers.push(vec3(numbers[0], numbers[1], numbers[2]));
+        if numbers.len() % 3 == 1 {
+            radii.push(numbers[3]);
+        }
+        if numbers.len() >= 6 {
+            let rgb = &numbers[numbers.len() - 3..];
+            colors.push(vec3(rgb[0], rgb[1], rgb[2]));
+        }
+    }
+
+    if !radii.is_empty() {
+        cloud.radii = Some(radii);
+    }
+    if !colors.is_empty() {
+        if colors.iter().any(|color| color.max_element() > 1.0) {
+            for color in &mut colors {
+                *color /= 255.0;
+            }
+        }
+        cloud.colors = Some(colors);
+    }
+    Ok(cloud)
+}
+
+/// Many spheres sharing a material, such as the particles of a [`PointCloud`].
+///
+/// The centers, radii and colors are kept in flat arrays with a BVH of their own, so each sphere
+/// takes a few numbers instead of a separate object and millions of them still fit. The color of
+/// a sphere tints its material, which is why clouds are usually given a white one.
+pub struct SphereCloud {
+    /// The centers, radii and colors are in the order of the BVH leaves.
+    centers: Vec<Vec3>,
+    /// `None` when every sphere has the same `radius`.
+    radii: Option<Vec<Float>>,
+    radius: Float,
+    colors: Option<Vec<Vec3>>,
+    /// `None` when there are no points to build it from.
+    bvh: Option<LinearBvh>,
+    pub mat: Rc<dyn Material>,
+}
+
+impl SphereCloud {
+    /// Spheres around the points of `cloud`, with their own radius if they have one and with
+    /// `radius` otherwise.
+    pub fn new(cloud: &PointCloud, radius: Float, mat: Rc<dyn Material>) -> Self {
+        let count = cloud.centers.len();
+        for (name, len) in [
+            ("radii", cloud.radii.as_ref().map(Vec::len)),
+            ("colors", cloud.colors.as_ref().map(Vec::len)),
+        ] {
+            assert!(
+                len.is_none_or(|len| len == count),
+                "sphere cloud needs one of its {name} for every point"
+            );
+        }
+
+        let radius_of = |i: usize| cloud.radii.as_ref().map_or(radius, |radii| radii[i]);
+        let bounds: Vec<_> = cloud
+            .centers
+            .iter()
+            .enumerate()
+            .map(|(i, &center)| {
+                let r = Vec3::splat(radius_of(i));
+                Aabb::new(center - r, center + r)
+            })
+            .collect();
+
+        let (order, bvh) = if bounds.is_empty() {
+            (Vec::new(), None)
+        } else {
+            let tree = BvhBuilder::new().build(&bounds);
+            let bvh = LinearBvh::new(&tree);
+            (tree.order, Some(bvh))
+        };
+        Self {
+            centers: reorder(&cloud.centers, &order),
+            radii: cloud.radii.as_ref().map(|radii| reorder(radii, &order)),
+            radius,
+            colors: cloud.colors.as_ref().map(|colors| reorder(colors, &order)),
+            bvh,
+            mat,
+        }
+    }
+
+    fn radius(&self, i: usize) -> Float {
+        self.radii.as_ref().map_or(self.radius, |radii| radii[i])
+    }
+}
+
+fn reorder<T: Copy>(values: &[T], order: &[usize]) -> Vec<T> {
+    order.iter().map(|&i| values[i]).collect()
+}
+
+impl Hittable for SphereCloud {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (i, root) = self
+            .bvh
+            .as_ref()?
+            .closest_hit(r, t_min, t_max, |i, t_max| {
+                nearest_root(self.centers[i], self.radius(i), r, t_min, t_max)
+                    .map(|root| ((i, root), root))
+            })?;
+
+        let (center, radius) = (self.centers[i], self.radius(i));
+        let (point, point_error) = surface_point(center, radius, r.at(root));
+        let outward_normal = (point - center) / radius;
+        let (u, v) = Sphere::get_sphere_uv(outward_normal);
+        let rec = HitRecord {
+            point,
+            mat: Some(self.mat.clone()),
+            point_error,
+            color: self.colors.as_ref().map(|colors| colors[i]),
+            t: root,
+            u,
+            v,
+            ..Default::default()
+        }
+        .with_face_normal(r, outward_normal);
+        Some(rec)
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.bvh.as_ref().is_some_and(|bvh| {
+            bvh.any_hit(r, t_min, t_max, |i| {
+                nearest_root(self.centers[i], self.radius(i), r, t_min, t_max).is_some()
+            })
+        })
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        self.bvh.as_ref().map(LinearBvh::aabb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{hittable::HittableList, material::Lambertian};
+
+    #[test]
+    fn text_columns() {
+        let cloud = parse_text("# x y z\n1 2 3\n\n4,5,6\n").unwrap();
+        assert_eq!(cloud.centers, [vec3(1.0, 2.0, 3.0), vec3(4.0, 5.0, 6.0)]);
+        assert_eq!((cloud.radii, cloud.colors), (None, None));
+
+        let cloud = parse_text("x,y,z,radius\n1,2,3,0.5\n4,5,6,0.25\n").unwrap();
+        assert_eq!(cloud.radii, Some(vec![0.5, 0.25]));
+        assert_eq!(cloud.colors, None);
+
+        let cloud = parse_text("1 2 3 0 0.5 1\n4 5 6 1 1 1").unwrap();
+        assert_eq!(cloud.radii, None);
+        assert_eq!(cloud.colors, Some(vec![vec3(0.0, 0.5, 1.0), Vec3::ONE]));
+
+        let cloud = parse_text("1 2 3 0.5 0.1 0.2 0.3\n4 5 6 2 0 0 0").unwrap();
+        assert_eq!(cloud.centers[1], vec3(4.0, 5.0, 6.0));
+        assert_eq!(cloud.radii, Some(vec![0.5, 2.0]));
+        assert_eq!(cloud.colors, Some(vec![vec3(0.1, 0.2, 0.3), Vec3::ZERO]));
+    }
+
+    #[test]
+    fn colors_above_one_go_to_255() {
+        let cloud = parse_text("0 0 0 255 51 0\n1 1 1 1 0.5 0").unwrap();
+        assert_eq!(
+            cloud.colors,
+            Some(vec![vec3(1.0, 0.2, 0.0), vec3(1.0, 0.5, 0.0) / 255.0])
+        );
+    }
+
+    #[test]
+    fn malformed_text() {
+        for (src, line) in [
+            // Column counts that aren't 3, 4, 6 or 7, or that change.
+            ("1 2", 1),
+            ("1 2 3 4 5", 1),
+            ("1 2 3\n4 5 6 7", 2),
+            ("# comment\n1 2 3 4\n\n5 6 7 8 9 10", 4),
+            // Only the first line can be a header.
+            ("x y z\nr g b\n1 2 3", 2),
+            ("1 2 3\nx y z", 2),
+            ("# comment\nx y z\n1 2 3\n4 five 6", 4),
+        ] {
+            let err = parse_text(src).unwrap_err();
+            assert!(err.starts_with(&format!("line {line}:")), "{src:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn hits_match_separate_spheres() {
+        let mut rng = StdRng::seed_from_u64(46);
+        let mat: Rc<dyn Material> = Rc::new(Lambertian::from_color(Vec3::ONE));
+        let plain = PointCloud {
+            centers: (0..200).map(|_| 4.0 * (rng.gen::<Vec3>() - 0.5)).collect(),
+            ..Default::default()
+        };
+        let varied = PointCloud {
+            radii: Some((0..200).map(|_| rng.gen_range(0.05..0.4)).collect()),
+            colors: Some((0..200).map(|_| rng.gen()).collect()),
+            ..plain.clone()
+        };
+
+        let mut hits = 0;
+        for cloud in [&plain, &varied] {
+            let spheres = SphereCloud::new(cloud, 0.2, mat.clone());
+            let radius_of = |i: usize| cloud.radii.as_ref().map_or(0.2, |radii| radii[i]);
+            let mut list = HittableList::default();
+            for (i, &center) in cloud.centers.iter().enumerate() {
+                list.add(Rc::new(Sphere::new(center, radius_of(i), mat.clone())));
+            }
+
+            for _ in 0..2000 {
+                let origin = 8.0 * (rng.gen::<Vec3>() - 0.5);
+                let target = 4.0 * (rng.gen::<Vec3>() - 0.5);
+                let r = Ray::new(origin, target - origin, 0.0);
+                let t_min = if rng.gen() {
+                    0.0
+                } else {
+                    rng.gen_range(0.0..1.0)
+                };
+                let t_max = if rng.gen() {
+                    Float::INFINITY
+                } else {
+                    rng.gen_range(t_min..2.0)
+                };
+
+                assert_eq!(
+                    spheres.occluded(r, t_min, t_max),
+                    list.occluded(r, t_min, t_max)
+                );
+                let (rec, expected) =
+                    match (spheres.hit(r, t_min, t_max), list.hit(r, t_min, t_max)) {
+                        (None, None) => continue,
+                        (Some(rec), Some(expected)) => (rec, expected),
+                        (rec, expected) => panic!(
+                            "{r:?}: {:?} != {:?}",
+                            rec.map(|rec| rec.t),
+                            expected.map(|rec| rec.t)
+                        ),
+                    };
+                hits += 1;
+                assert_eq!(
+                    (rec.t, rec.point, rec.normal, rec.front_face, rec.u, rec.v),
+                    (
+                        expected.t,
+                        expected.point,
+                        expected.normal,
+                        expected.front_face,
+                        expected.u,
+                        expected.v
+                    )
+                );
+                assert_eq!(rec.point_error, expected.point_error);
+
+                // The color is that of the sphere the point is on.
+                let on_surface =
+                    |i: usize| ((rec.point - cloud.centers[i]).length() - radius_of(i)).abs();
+                let nearest = (0..cloud.centers.len())
+                    .min_by(|&i, &j| on_surface(i).total_cmp(&on_surface(j)))
+                    .unwrap();
+                let color = cloud.colors.as_ref().map(|colors| colors[nearest]);
+                assert_eq!(rec.color, color);
+            }
+        }
+        assert!(hits > 1000, "{hits}");
+    }
+}
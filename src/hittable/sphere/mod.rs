@@ -1,5 +1,46 @@
+mod cloud;
 mod moving;
 mod normal;
 
+pub use cloud::{PointCloud, SphereCloud};
 pub use moving::MovingSphere;
 pub use normal::Sphere;
+
+use crate::{
+    float::{gamma, Float, Vec3},
+    ray::Ray,
+};
+
+/// Returns the nearest intersection of `r` with a sphere that lies within `[t_min, t_max]`.
+fn nearest_root(center: Vec3, radius: Float, r: Ray, t_min: Float, t_max: Float) -> Option<Float> {
+    let oc = r.origin - center;
+    let a = r.direction.length_squared();
+    let half_b = oc.dot(r.direction);
+    let c = oc.length_squared() - radius * radius;
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    };
+
+    let sqrtd = discriminant.sqrt();
+    let mut root = (-half_b - sqrtd) / a;
+    if root < t_min || t_max < root {
+        root = (-half_b + sqrtd) / a;
+        if root < t_min || t_max < root {
+            return None;
+        }
+    }
+    Some(root)
+}
+
+/// Moves a point found by intersecting a sphere onto its surface, which is more accurate than the
+/// ray parameter it came from. Returns the point and its error bound.
+pub(super) fn surface_point(center: Vec3, radius: Float, point: Vec3) -> (Vec3, Vec3) {
+    let local = point - center;
+    let local = local * (radius / local.length());
+    let point = center + local;
+    // Reprojecting leaves an error of gamma(5) relative to the center, and adding the center
+    // back rounds once more.
+    (point, gamma(5) * local.abs() + gamma(1) * point.abs())
+}
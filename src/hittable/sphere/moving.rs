@@ -2,27 +2,31 @@ use std::rc::Rc;
 
 use crate::{
     aabb::Aabb,
+    float::{Float, Vec3},
     hittable::{HitRecord, Hittable},
     material::Material,
+    ray::Ray,
 };
 
+use super::{nearest_root, surface_point};
+
 #[derive(Clone)]
 pub struct MovingSphere {
-    pub center0: glam::DVec3,
-    pub center1: glam::DVec3,
-    pub time0: f64,
-    pub time1: f64,
-    pub radius: f64,
+    pub center0: Vec3,
+    pub center1: Vec3,
+    pub time0: Float,
+    pub time1: Float,
+    pub radius: Float,
     pub mat: Rc<dyn Material>,
 }
 
 impl MovingSphere {
     pub fn new(
-        center0: glam::DVec3,
-        center1: glam::DVec3,
-        time0: f64,
-        time1: f64,
-        radius: f64,
+        center0: Vec3,
+        center1: Vec3,
+        time0: Float,
+        time1: Float,
+        radius: Float,
         mat: Rc<dyn Material>,
     ) -> Self {
         Self {
@@ -35,39 +39,23 @@ impl MovingSphere {
         }
     }
 
-    pub fn center(&self, time: f64) -> glam::DVec3 {
+    pub fn center(&self, time: Float) -> Vec3 {
         self.center0
             + ((time - self.time0) / (self.time1 - self.time0)) * (self.center1 - self.center0)
     }
 }
 
 impl Hittable for MovingSphere {
-    fn hit(&self, r: crate::ray::Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
         let center = self.center(r.time);
-        let oc = r.origin - center;
-        let a = r.direction.length_squared();
-        let half_b = oc.dot(r.direction);
-        let c = oc.length_squared() - self.radius * self.radius;
-
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return None;
-        };
-
-        let sqrtd = discriminant.sqrt();
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None;
-            }
-        }
+        let root = nearest_root(center, self.radius, r, t_min, t_max)?;
 
-        let point = r.at(root);
+        let (point, point_error) = surface_point(center, self.radius, r.at(root));
         let outward_normal = (point - center) / self.radius;
         let rec = HitRecord {
             point,
-            normal: glam::DVec3::default(),
+            point_error,
+            normal: Vec3::default(),
             mat: Some(self.mat.clone()),
             t: root,
             front_face: false,
@@ -78,8 +66,12 @@ impl Hittable for MovingSphere {
         Some(rec)
     }
 
-    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
-        let center_to_edge = glam::DVec3::splat(self.radius);
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        nearest_root(self.center(r.time), self.radius, r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<Aabb> {
+        let center_to_edge = Vec3::splat(self.radius);
         let center0 = self.center(time0);
         let box0 = Aabb::new(center0 - center_to_edge, center0 + center_to_edge);
         let center1 = self.center(time1);
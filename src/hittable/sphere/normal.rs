@@ -1,24 +1,30 @@
-use std::{
-    f64::consts::{PI, TAU},
-    rc::Rc,
-};
+use std::rc::Rc;
+
+use rand::prelude::*;
 
 use crate::{
     aabb::Aabb,
-    hittable::{HitRecord, Hittable},
+    float::{
+        consts::{PI, TAU},
+        Float, Vec3,
+    },
+    hittable::{HitRecord, Hittable, Light},
     material::Material,
+    math::random_unit_vec,
     ray::Ray,
 };
 
+use super::{nearest_root, surface_point};
+
 #[derive(Clone)]
 pub struct Sphere {
-    pub center: glam::DVec3,
-    pub radius: f64,
+    pub center: Vec3,
+    pub radius: Float,
     pub mat: Rc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: glam::DVec3, radius: f64, mat: Rc<dyn Material>) -> Sphere {
+    pub fn new(center: Vec3, radius: Float, mat: Rc<dyn Material>) -> Sphere {
         Self {
             center,
             radius,
@@ -26,7 +32,7 @@ impl Sphere {
         }
     }
 
-    fn get_sphere_uv(p: glam::DVec3) -> (f64, f64) {
+    pub(super) fn get_sphere_uv(p: Vec3) -> (Float, Float) {
         // p: a given point on the sphere of radius one, centered at the origin.
         // u: returned value [0,1] of angle around the Y axis from X=-1.
         // v: returned value [0,1] of angle from Y=-1 to Y=+1.
@@ -41,33 +47,20 @@ impl Sphere {
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = r.origin - self.center;
-        let a = r.direction.length_squared();
-        let half_b = oc.dot(r.direction);
-        let c = oc.length_squared() - self.radius * self.radius;
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let root = nearest_root(self.center, self.radius, r, t_min, t_max)?;
 
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return None;
-        };
-
-        let sqrtd = discriminant.sqrt();
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None;
-            }
-        }
-
-        let point = r.at(root);
+        let (point, point_error) = surface_point(self.center, self.radius, r.at(root));
         let outward_normal = (point - self.center) / self.radius;
         let (u, v) = Self::get_sphere_uv(outward_normal);
         let rec = HitRecord {
             point,
-            normal: glam::DVec3::default(),
+            normal: Vec3::default(),
             mat: Some(self.mat.clone()),
+            point_error,
+            geometric_normal: Vec3::default(),
+            tangent: Vec3::default(),
+            color: None,
             t: root,
             u,
             v,
@@ -78,11 +71,58 @@ impl Hittable for Sphere {
         Some(rec)
     }
 
-    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
-        let center_to_edge = glam::DVec3::splat(self.radius);
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        nearest_root(self.center, self.radius, r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        let center_to_edge = Vec3::splat(self.radius);
         Some(Aabb::new(
             self.center - center_to_edge,
             self.center + center_to_edge,
         ))
     }
 }
+
+/// Samples the cone of directions in which the sphere is seen from outside, which is denser than
+/// spreading samples over its whole area, half of which faces away. From inside, every direction
+/// reaches the sphere.
+impl Light for Sphere {
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> Float {
+        let r = Ray::new(origin, direction, 0.0);
+        if nearest_root(self.center, self.radius, r, 0.0, Float::INFINITY).is_none() {
+            return 0.0;
+        }
+        let distance_squared = (self.center - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 1.0 / (4.0 * PI);
+        }
+        1.0 / (TAU * one_minus_cos_theta_max(radius_squared / distance_squared))
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        let to_center = self.center - origin;
+        let distance_squared = to_center.length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return random_unit_vec();
+        }
+        let mut rng = thread_rng();
+        let one_minus_cos_theta =
+            rng.gen::<Float>() * one_minus_cos_theta_max(radius_squared / distance_squared);
+        let cos_theta = 1.0 - one_minus_cos_theta;
+        let sin_theta = (one_minus_cos_theta * (1.0 + cos_theta)).max(0.0).sqrt();
+        let phi = TAU * rng.gen::<Float>();
+        let w = to_center / distance_squared.sqrt();
+        let (u, v) = w.any_orthonormal_pair();
+        sin_theta * (phi.cos() * u + phi.sin() * v) + cos_theta * w
+    }
+}
+
+/// `1 - cos θ`, for the half-angle θ of the cone that a sphere fills when its squared radius is
+/// `sin_squared` times the squared distance to its center. Computed this way it stays accurate
+/// for small and distant spheres.
+fn one_minus_cos_theta_max(sin_squared: Float) -> Float {
+    sin_squared / (1.0 + (1.0 - sin_squared).sqrt())
+}
@@ -0,0 +1,248 @@
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb,
+    float::{self, consts::TAU, gamma, vec3, Float, Mat3, Vec3},
+    hittable::{sphere, HitRecord, Hittable},
+    material::Material,
+    polynomial,
+    ray::Ray,
+};
+
+use super::disk::disk_extent;
+
+/// A ring around `center`, made by sweeping a circle of `minor_radius` along a circle of
+/// `major_radius` that faces along `normal`.
+///
+/// `u` runs around the normal and `v` around the tube, starting from its outer equator and
+/// heading towards `normal` first.
+pub struct Torus {
+    center: Vec3,
+    to_world: Mat3,
+    to_local: Mat3,
+    pub major_radius: Float,
+    pub minor_radius: Float,
+    pub mat: Rc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vec3,
+        normal: Vec3,
+        major_radius: Float,
+        minor_radius: Float,
+        mat: Rc<dyn Material>,
+    ) -> Self {
+        let w = normal.normalize();
+        let (t, b) = w.any_orthonormal_pair();
+        let to_world = Mat3::from_cols(t, b, w);
+        Self {
+            center,
+            to_world,
+            to_local: to_world.transpose(),
+            major_radius,
+            minor_radius,
+            mat,
+        }
+    }
+
+    /// The ray in local coordinates, and `t_min` raised past the rounding error of its origin.
+    /// The frame is orthonormal, so ray parameters carry over.
+    fn local_ray(&self, r: Ray, t_min: Float) -> (Ray, Float) {
+        let offset = r.origin - self.center;
+        let local_ray = Ray::new(self.to_local * offset, self.to_local * r.direction, r.time);
+        let error =
+            float::transformed_error(self.to_local, Vec3::ZERO, offset, gamma(1) * offset.abs());
+        (local_ray, local_ray.clear_t_min(t_min, error))
+    }
+
+    /// Returns the nearest hit of the local ray `r`.
+    fn intersect(&self, r: Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        let (major, minor) = (self.major_radius, self.minor_radius);
+        let d = r.direction;
+        let dd = d.length_squared();
+
+        // The quartic's coefficients cancel badly when the origin is far away, so solve it from
+        // the point of the ray closest to the center instead. That also bounds the search to
+        // where the ray is inside the bounding sphere.
+        let base = -r.origin.dot(d) / dd;
+        let o = r.origin + base * d;
+        let outer = major + minor;
+        let oo = o.length_squared();
+        if oo > outer * outer {
+            return None;
+        }
+        let half_chord = ((outer * outer - oo) / dd).sqrt();
+        let min = (t_min - base).max(-half_chord);
+        let max = (t_max - base).min(half_chord);
+        if min > max {
+            return None;
+        }
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) along the ray.
+        let od = o.dot(d);
+        let k = oo + major * major - minor * minor;
+        let four_major_squared = 4.0 * major * major;
+        let coefficients = [
+            dd * dd,
+            4.0 * dd * od,
+            2.0 * dd * k + 4.0 * od * od - four_major_squared * (d.x * d.x + d.y * d.y),
+            4.0 * k * od - 2.0 * four_major_squared * (o.x * d.x + o.y * d.y),
+            k * k - four_major_squared * (o.x * o.x + o.y * o.y),
+        ];
+        let roots = polynomial::quartic(coefficients, min, max);
+        roots
+            .as_slice()
+            .iter()
+            .map(|t| t + base)
+            .find(|t| (t_min..=t_max).contains(t))
+    }
+}
+
+/// An angle in `[0, 1)` turns.
+fn turns(y: Float, x: Float) -> Float {
+    let angle = y.atan2(x);
+    (if angle < 0.0 { angle + TAU } else { angle }) / TAU
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (local_ray, t_min) = self.local_ray(r, t_min);
+        let t = self.intersect(local_ray, t_min, t_max)?;
+
+        // The nearest point of the core circle is the center of the tube's cross-section, so
+        // reprojecting onto a sphere around it puts the point on the surface. That center is
+        // itself a few roundings off, but only by a fraction of the major radius.
+        let point = local_ray.at(t);
+        let distance = (point.x * point.x + point.y * point.y).sqrt();
+        let core = if distance > 0.0 {
+            vec3(point.x, point.y, 0.0) * (self.major_radius / distance)
+        } else {
+            vec3(self.major_radius, 0.0, 0.0)
+        };
+        let (point, point_error) = sphere::surface_point(core, self.minor_radius, point);
+        let point_error = point_error + gamma(3) * core.abs();
+        let normal = (point - core) / self.minor_radius;
+
+        let distance = (point.x * point.x + point.y * point.y).sqrt();
+        Some(
+            HitRecord {
+                point: self.center + self.to_world * point,
+                point_error: float::transformed_error(
+                    self.to_world,
+                    self.center,
+                    point,
+                    point_error,
+                ),
+                mat: Some(self.mat.clone()),
+                t,
+                u: turns(point.y, point.x),
+                v: turns(point.z, distance - self.major_radius),
+                ..Default::default()
+            }
+            .with_face_normal(r, self.to_world * normal),
+        )
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        let (local_ray, t_min) = self.local_ray(r, t_min);
+        self.intersect(local_ray, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        let extent =
+            disk_extent(self.to_world.z_axis, self.major_radius) + Vec3::splat(self.minor_radius);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn hits_match_the_analytic_ones() {
+        let center = vec3(1.0, -2.0, 0.5);
+        let n = vec3(1.0, 2.0, 2.0) / 3.0;
+        let (e1, e2) = n.any_orthonormal_pair();
+        let torus = Torus::new(
+            center,
+            n,
+            2.0,
+            0.5,
+            Rc::new(Lambertian::from_color(Vec3::ONE)),
+        );
+
+        // Grazing rays touch the surface at a double root, which rounding can move by about
+        // the square root of epsilon.
+        let exact = 1e3 * Float::EPSILON;
+        let grazing = 10.0 * Float::EPSILON.sqrt();
+        for (name, origin, direction, expected, tolerance) in [
+            ("axial", 2.0 * e1 + 5.0 * n, -n, Some((4.5, n)), exact),
+            ("axial through the hole", 5.0 * n, -n, None, exact),
+            ("equatorial", 5.0 * e1, -e1, Some((2.5, e1)), exact),
+            (
+                "equatorial from the center",
+                Vec3::ZERO,
+                e1,
+                Some((1.5, -e1)),
+                exact,
+            ),
+            (
+                "long direction",
+                5.0 * e1,
+                -4.0 * e1,
+                Some((0.625, e1)),
+                exact,
+            ),
+            (
+                "grazing the outer equator",
+                2.5 * e1 - 5.0 * e2,
+                e2,
+                Some((5.0, e1)),
+                grazing,
+            ),
+            (
+                "grazing the top",
+                0.5 * n - 5.0 * e1,
+                e1,
+                Some((3.0, n)),
+                grazing,
+            ),
+            (
+                "passing over the top",
+                0.501 * n - 5.0 * e1,
+                e1,
+                None,
+                grazing,
+            ),
+        ] {
+            let r = Ray::new(center + origin, direction, 0.0);
+            let rec = torus.hit(r, 0.0, Float::INFINITY);
+            assert_eq!(
+                torus.occluded(r, 0.0, Float::INFINITY),
+                rec.is_some(),
+                "{name}"
+            );
+            match (rec, expected) {
+                (Some(rec), Some((t, normal))) => {
+                    assert!(
+                        (rec.t - t).abs() < tolerance * t,
+                        "{name}: {} != {t}",
+                        rec.t
+                    );
+                    let outward = if rec.front_face {
+                        rec.normal
+                    } else {
+                        -rec.normal
+                    };
+                    assert!(outward.abs_diff_eq(normal, tolerance), "{name}: {outward}");
+                    assert!(rec.point.abs_diff_eq(r.at(t), tolerance * t), "{name}");
+                }
+                (None, None) => {}
+                (rec, _) => panic!("{name}: {:?}", rec.map(|rec| rec.t)),
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb,
+    float::{gamma, vec2, Float, Vec2, Vec3},
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+};
+
+/// Vertex data shared by all triangles of a mesh.
+pub struct TriangleMesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<Vec2>>,
+    pub indices: Vec<[usize; 3]>,
+    pub mat: Rc<dyn Material>,
+}
+
+impl TriangleMesh {
+    pub fn new(positions: Vec<Vec3>, indices: Vec<[usize; 3]>, mat: Rc<dyn Material>) -> Self {
+        Self {
+            positions,
+            normals: None,
+            uvs: None,
+            indices,
+            mat,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<Vec2>) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    pub fn triangles(self: &Rc<Self>) -> impl Iterator<Item = Triangle> + '_ {
+        (0..self.indices.len()).map(|index| Triangle {
+            mesh: self.clone(),
+            index,
+        })
+    }
+}
+
+pub struct Triangle {
+    pub mesh: Rc<TriangleMesh>,
+    pub index: usize,
+}
+
+impl Triangle {
+    fn vertices(&self) -> [Vec3; 3] {
+        self.mesh.indices[self.index].map(|i| self.mesh.positions[i])
+    }
+
+    fn intersect(&self, r: Ray, t_min: Float, t_max: Float) -> Option<(Float, Float, Float)> {
+        intersect(r, self.vertices(), t_min, t_max)
+    }
+}
+
+/// Returns the ray parameter and the barycentric coordinates of the second and third vertex.
+pub(super) fn intersect(
+    r: Ray,
+    [p0, p1, p2]: [Vec3; 3],
+    t_min: Float,
+    t_max: Float,
+) -> Option<(Float, Float, Float)> {
+    // Möller-Trumbore
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let pvec = r.direction.cross(edge2);
+    let det = edge1.dot(pvec);
+    if det == 0.0 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let tvec = r.origin - p0;
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(edge1);
+    let b2 = r.direction.dot(qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(qvec) * inv_det;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (t, b1, b2) = self.intersect(r, t_min, t_max)?;
+        let [p0, p1, p2] = self.vertices();
+        let edge1 = p1 - p0;
+        let edge2 = p2 - p0;
+
+        let b0 = 1.0 - b1 - b2;
+        let indices = self.mesh.indices[self.index];
+        // Without explicit UVs, use the same parameterization as pbrt.
+        let uv = match &self.mesh.uvs {
+            Some(uvs) => b0 * uvs[indices[0]] + b1 * uvs[indices[1]] + b2 * uvs[indices[2]],
+            None => vec2(b1 + b2, b2),
+        };
+
+        // Interpolating the vertices is more accurate than the ray parameter.
+        let point = b0 * p0 + b1 * p1 + b2 * p2;
+        let point_error = gamma(7) * ((b0 * p0).abs() + (b1 * p1).abs() + (b2 * p2).abs());
+
+        let outward_normal = edge1.cross(edge2).normalize();
+        let mut rec = HitRecord {
+            point,
+            point_error,
+            mat: Some(self.mesh.mat.clone()),
+            t,
+            u: uv.x,
+            v: uv.y,
+            ..Default::default()
+        }
+        .with_face_normal(r, outward_normal);
+
+        if let Some(normals) = &self.mesh.normals {
+            let shading_normal =
+                (b0 * normals[indices[0]] + b1 * normals[indices[1]] + b2 * normals[indices[2]])
+                    .normalize();
+            // Keep the shading normal on the same side as the geometric one.
+            rec.normal = if shading_normal.dot(rec.normal) < 0.0 {
+                -shading_normal
+            } else {
+                shading_normal
+            };
+        }
+
+        Some(rec)
+    }
+
+    fn occluded(&self, r: Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<Aabb> {
+        let [p0, p1, p2] = self.vertices();
+        Some(Aabb::new(p0.min(p1).min(p2), p0.max(p1).max(p2)))
+    }
+}
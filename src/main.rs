@@ -1,86 +1,389 @@
 mod aabb;
 mod camera;
 mod color;
+mod float;
 mod hittable;
 mod material;
 mod math;
 mod noise;
+mod pbrt;
+mod ply;
+mod polynomial;
 mod ray;
+mod scene_file;
 mod test_scenes;
+#[cfg(test)]
+mod testing;
 mod texture;
 
-use std::{fmt::Write, fs};
+use std::{array, env, error::Error, fmt::Write, fs, path::Path, process, time::Instant};
 
-use hittable::BvhNode;
+use camera::Camera;
+use hittable::{
+    Bvh, BvhBuilder, BvhNode, HitRecord, LightList, MediaPath, MediumEvent, PACKET_SIZE,
+};
 use indicatif::ProgressBar;
 use rand::prelude::*;
 use test_scenes::Scene;
 
 use crate::{
-    color::stringify_color, hittable::Hittable, material::MaterialRayInteraction, ray::Ray,
+    color::stringify_color,
+    float::{consts::PI, Float, Vec3},
+    hittable::Hittable,
+    material::MaterialRayInteraction,
+    ray::Ray,
 };
 
 // Screen
-const ASPECT_RATIO: f64 = 16.0 / 9.0;
+const ASPECT_RATIO: Float = 16.0 / 9.0;
 const MAX_DEPTH: i32 = 50;
-const TIME0: f64 = 0.0;
-const TIME1: f64 = 1.0;
+const TIME0: Float = 0.0;
+const TIME1: Float = 1.0;
 const OUTPUT_FILE: &str = "out.ppm";
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+const USAGE: &str =
+    "usage: raytracing [--bvh-stats] [--bench [SAMPLES]] [--frames N] [--ao] [--no-packets] [--bvh-bins N] [--bvh-leaf-size N] [scene]";
+const BENCH_SAMPLES: u32 = 4;
+
+struct Options {
+    scene: Option<String>,
+    bvh_stats: bool,
+    /// Samples per pixel to time each BVH layout with, instead of rendering.
+    bench: Option<u32>,
+    frames: usize,
+    /// Shade with ambient occlusion instead of tracing paths.
+    ambient_occlusion: bool,
+    /// Trace primary rays one by one instead of in packets.
+    no_packets: bool,
+    bvh_builder: BvhBuilder,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        scene: None,
+        bvh_stats: false,
+        bench: None,
+        frames: 1,
+        ambient_occlusion: false,
+        no_packets: false,
+        bvh_builder: BvhBuilder::new(),
+    };
+    let mut args = env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        let mut number = |name: &str| -> Result<usize, String> {
+            let value = args.next().ok_or(format!("{name} needs a value"))?;
+            value
+                .parse()
+                .map_err(|_| format!("invalid value '{value}' for {name}"))
+        };
+        match arg.as_str() {
+            "--bvh-stats" => options.bvh_stats = true,
+            "--ao" => options.ambient_occlusion = true,
+            "--no-packets" => options.no_packets = true,
+            "--bench" => {
+                let samples = args.next_if(|arg| arg.parse::<u32>().is_ok());
+                options.bench = Some(samples.map_or(BENCH_SAMPLES, |s| s.parse().unwrap()));
+            }
+            "--frames" => options.frames = number(&arg)?.max(1),
+            "--bvh-bins" => options.bvh_builder = options.bvh_builder.with_bins(number(&arg)?),
+            "--bvh-leaf-size" => {
+                options.bvh_builder = options.bvh_builder.with_max_leaf_size(number(&arg)?)
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{arg}'")),
+            _ if options.scene.is_none() => options.scene = Some(arg),
+            _ => return Err(format!("unexpected argument '{arg}'")),
+        }
+    }
+    Ok(options)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let options = parse_args().unwrap_or_else(|err| {
+        eprintln!("{err}\n{USAGE}");
+        process::exit(2);
+    });
+
     // World
+    let scene = match &options.scene {
+        Some(path) => load_scene(Path::new(path)).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            process::exit(1);
+        }),
+        None => test_scenes::final_scene(),
+    };
     let Scene {
         world,
+        lights,
+        media,
         cam,
         background_color,
         samples_per_pixel,
         image_width,
         image_height,
-    } = test_scenes::final_scene();
+        max_depth,
+        time0,
+        time1,
+    } = scene;
 
-    let mut buf = String::with_capacity((image_width * image_height) as usize * 12 + 20);
-    let pb = ProgressBar::new(image_height as u64);
-    let mut rng = thread_rng();
+    if options.bvh_stats {
+        let median = BvhNode::from_slice_median(&world.objects, time0, time1);
+        let sah = Bvh::with_builder(&world.objects, time0, time1, &options.bvh_builder);
+        println!("median: {}", median.stats());
+        println!("sah:    {}", sah.stats());
+
+        let bounds: Vec<_> = world
+            .objects
+            .iter()
+            .filter_map(|object| object.bounding_box(time0, time1))
+            .collect();
+        if bounds.is_empty() {
+            // Only unbounded objects, which no BVH holds.
+            return Ok(());
+        }
+        let start = Instant::now();
+        let sequential = options.bvh_builder.with_parallel(false).build(&bounds);
+        let sequential_time = start.elapsed();
+        let start = Instant::now();
+        let parallel = options.bvh_builder.with_parallel(true).build(&bounds);
+        let parallel_time = start.elapsed();
+        println!(
+            "build:  sequential {sequential_time:.2?}, parallel {parallel_time:.2?}, {}",
+            if sequential == parallel {
+                "same tree"
+            } else {
+                "TREES DIFFER"
+            }
+        );
+        return Ok(());
+    }
 
-    let world = BvhNode::from_hittable_list(world, TIME0, TIME1);
+    // Primary rays go through `packets` when given, and through `world` otherwise.
+    let render = |world: &dyn Hittable,
+                  packets: Option<&Bvh>,
+                  cam: &Camera,
+                  samples_per_pixel,
+                  pb: Option<&ProgressBar>| {
+        render(
+            cam,
+            (image_width, image_height),
+            samples_per_pixel,
+            pb,
+            |rays| {
+                let mut hits = match packets {
+                    Some(bvh) => bvh.hit_packet(rays, 0.0, Float::INFINITY),
+                    None => rays.map(|r| world.hit(r, 0.0, Float::INFINITY)),
+                };
+                array::from_fn(|k| {
+                    let rec = hits[k].take();
+                    if options.ambient_occlusion {
+                        ambient_occlusion(rays[k], rec, world)
+                    } else {
+                        shade(
+                            rays[k],
+                            rec,
+                            background_color,
+                            world,
+                            &lights,
+                            MediaPath::new(&media),
+                            max_depth,
+                        )
+                    }
+                })
+            },
+        )
+    };
 
-    write!(&mut buf, "P3\n{image_width} {image_height}\n255\n")?;
+    if let Some(samples_per_pixel) = options.bench {
+        let tree = BvhNode::with_builder(&world.objects, time0, time1, &options.bvh_builder);
+        let flat = Bvh::with_builder(&world.objects, time0, time1, &options.bvh_builder);
+        for (name, world, packets) in [
+            ("BvhNode", &tree as &dyn Hittable, None),
+            ("Bvh", &flat, None),
+            ("packets", &flat, Some(&flat)),
+        ] {
+            let start = Instant::now();
+            render(world, packets, &cam, samples_per_pixel, None);
+            println!("{name:8} {:.2?}", start.elapsed());
+        }
+        return Ok(());
+    }
 
-    for j in (0..image_height).rev() {
-        for i in 0..image_width {
-            let mut pixel_color = color::BLACK;
-            for _ in 0..samples_per_pixel {
-                let u = (i as f64 + rng.gen::<f64>()) / (image_width + 1) as f64;
-                let v = (j as f64 + rng.gen::<f64>()) / (image_height + 1) as f64;
-                let r = cam.get_ray(u, v);
-                pixel_color += ray_color(r, background_color, &world, MAX_DEPTH);
+    let objects = world.objects;
+    let mut world = Bvh::with_builder(&objects, time0, time1, &options.bvh_builder);
+    let mut cam = cam;
+
+    for frame in 0..options.frames {
+        // Each frame is exposed for as long as the scene's shutter interval, one after another.
+        if frame > 0 {
+            let offset = frame as Float * (time1 - time0);
+            let (time0, time1) = (time0 + offset, time1 + offset);
+            cam.set_shutter(time0, time1);
+            let report = world.refit(time0, time1);
+            if report.needs_rebuild() {
+                world = Bvh::with_builder(&objects, time0, time1, &options.bvh_builder);
+                eprintln!("frame {frame}: {report}, rebuilt the BVH");
+            } else {
+                eprintln!("frame {frame}: {report}");
             }
+        }
+
+        let pb = ProgressBar::new(image_height as u64);
+        let packets = (!options.no_packets).then_some(&world);
+        let pixels = render(&world, packets, &cam, samples_per_pixel, Some(&pb));
+
+        let mut buf = String::with_capacity((image_width * image_height) as usize * 12 + 20);
+        write!(&mut buf, "P3\n{image_width} {image_height}\n255\n")?;
+        for pixel_color in pixels {
             write!(
                 &mut buf,
                 "{}",
                 stringify_color(pixel_color, samples_per_pixel)
             )?;
         }
-        pb.set_position((image_height - j) as u64);
+        if options.frames == 1 {
+            fs::write(OUTPUT_FILE, buf)?;
+        } else {
+            fs::write(format!("out_{frame:04}.ppm"), buf)?;
+        }
+        pb.finish_with_message("Done!");
     }
-    fs::write(OUTPUT_FILE, buf)?;
-    pb.finish_with_message("Done!");
     Ok(())
 }
 
+/// Offsets of the pixels in a block, which get one ray each in every packet.
+const PACKET_BLOCK: [(u32, u32); PACKET_SIZE] = [(0, 0), (1, 0), (0, 1), (1, 1)];
+
+/// Traces every pixel, returning the summed sample colors from the top row down. Pixels are
+/// sampled in 2x2 blocks, so that each call to `radiance` gets a packet of neighbouring rays.
+fn render(
+    cam: &Camera,
+    (image_width, image_height): (u32, u32),
+    samples_per_pixel: u32,
+    pb: Option<&ProgressBar>,
+    radiance: impl Fn(&[Ray; PACKET_SIZE]) -> [Vec3; PACKET_SIZE],
+) -> Vec<Vec3> {
+    let mut rng = thread_rng();
+    let mut pixels = vec![color::BLACK; (image_width * image_height) as usize];
+    // Rows are counted from the top here, unlike j below.
+    for row in (0..image_height).step_by(2) {
+        for column in (0..image_width).step_by(2) {
+            // Blocks on the right and bottom edges may stick out of the image; their extra rays
+            // trace the edge pixels again and are thrown away.
+            let block = PACKET_BLOCK.map(|(dx, dy)| {
+                (
+                    (column + dx).min(image_width - 1),
+                    (row + dy).min(image_height - 1),
+                )
+            });
+            let mut colors = [color::BLACK; PACKET_SIZE];
+            for _ in 0..samples_per_pixel {
+                let rays = block.map(|(i, row)| {
+                    let j = image_height - 1 - row;
+                    let u = (i as Float + rng.gen::<Float>()) / (image_width + 1) as Float;
+                    let v = (j as Float + rng.gen::<Float>()) / (image_height + 1) as Float;
+                    cam.get_ray(u, v)
+                });
+                for (color, sample) in colors.iter_mut().zip(radiance(&rays)) {
+                    *color += sample;
+                }
+            }
+            for ((i, row), color) in block.into_iter().zip(colors) {
+                pixels[(row * image_width + i) as usize] = color;
+            }
+        }
+        if let Some(pb) = pb {
+            pb.set_position((row + 2).min(image_height) as u64);
+        }
+    }
+    pixels
+}
+
+fn load_scene(path: &Path) -> Result<Scene, Box<dyn Error>> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("pbrt") => {
+            let (scene, warnings) = pbrt::load(path)?;
+            for warning in warnings {
+                eprintln!("warning: {warning}");
+            }
+            Ok(scene)
+        }
+        _ => Ok(scene_file::load(path)?),
+    }
+}
+
 fn ray_color(
     r: Ray,
-    background_color: glam::DVec3,
+    background_color: Vec3,
     world: &dyn Hittable,
+    lights: &LightList,
+    media: MediaPath,
     depth: i32,
-) -> glam::DVec3 {
+) -> Vec3 {
     if depth <= 0 {
         return color::BLACK;
     }
+    shade(
+        r,
+        world.hit(r, 0.0, Float::INFINITY),
+        background_color,
+        world,
+        lights,
+        media,
+        depth,
+    )
+}
+
+/// The color seen along `r`, given its closest hit in `world`. Diffuse surfaces send half their
+/// rays towards `lights`, which must also be part of `world`, and light is absorbed, scattered
+/// and emitted on the way by the homogeneous media that `media` follows the ray through.
+fn shade(
+    r: Ray,
+    rec: Option<HitRecord>,
+    background_color: Vec3,
+    world: &dyn Hittable,
+    lights: &LightList,
+    mut media: MediaPath,
+    depth: i32,
+) -> Vec3 {
+    let t_max = rec.as_ref().map_or(Float::INFINITY, |rec| rec.t);
+    let weight = match media.travel(r, t_max) {
+        MediumEvent::Passed { weight } => weight,
+        MediumEvent::Collided {
+            emitted,
+            attenuation,
+            scattered_ray,
+        } => {
+            return media.mis_weight() * emitted
+                + attenuation
+                    * ray_color(
+                        scattered_ray,
+                        background_color,
+                        world,
+                        lights,
+                        media,
+                        depth - 1,
+                    );
+        }
+    };
+    weight * shade_surface(r, rec, background_color, world, lights, media, depth)
+}
 
-    if let Some(rec) = world.hit(r, 0.0001, f64::INFINITY) {
+/// The color seen along `r` from its closest hit, or the background, without what the media on
+/// the way do to it.
+fn shade_surface(
+    r: Ray,
+    rec: Option<HitRecord>,
+    background_color: Vec3,
+    world: &dyn Hittable,
+    lights: &LightList,
+    media: MediaPath,
+    depth: i32,
+) -> Vec3 {
+    // Emitted light is weighed against the other channels right away, scattered light once its
+    // path ends.
+    if let Some(rec) = rec {
         if let Some(mat) = &rec.mat {
-            let emitted = mat.emitted(rec.u, rec.v, rec.point);
+            let emitted = media.mis_weight() * mat.emitted(rec.u, rec.v, rec.point);
             return match mat.scatter(r, &rec) {
                 MaterialRayInteraction::Absorbed => emitted,
                 MaterialRayInteraction::Scattered {
@@ -88,11 +391,73 @@ fn ray_color(
                     scattered_ray,
                 } => {
                     emitted
-                        + attenuation * ray_color(scattered_ray, background_color, world, depth - 1)
+                        + attenuation
+                            * ray_color(
+                                scattered_ray,
+                                background_color,
+                                world,
+                                lights,
+                                media,
+                                depth - 1,
+                            )
+                }
+                MaterialRayInteraction::Diffuse {
+                    attenuation,
+                    scattered_ray,
+                } => {
+                    let towards_light = if thread_rng().gen() {
+                        lights.random(rec.point)
+                    } else {
+                        None
+                    };
+                    let scattered_ray = match towards_light {
+                        Some(direction) => rec.spawn_ray(direction, r.time),
+                        None => scattered_ray,
+                    };
+                    let cosine = scattered_ray.direction.normalize().dot(rec.normal);
+                    if cosine <= 0.0 {
+                        // A light sampled from behind the surface.
+                        return emitted;
+                    }
+                    let scattering_pdf = cosine / PI;
+                    // Either strategy may have picked the direction, so weight it by the density
+                    // of the mixture of both.
+                    let pdf = if lights.is_empty() {
+                        scattering_pdf
+                    } else {
+                        0.5 * scattering_pdf
+                            + 0.5 * lights.pdf_value(scattered_ray.origin, scattered_ray.direction)
+                    };
+                    emitted
+                        + attenuation
+                            * (scattering_pdf / pdf)
+                            * ray_color(
+                                scattered_ray,
+                                background_color,
+                                world,
+                                lights,
+                                media,
+                                depth - 1,
+                            )
                 }
             };
         }
     }
     // Background
-    background_color
+    media.mis_weight() * background_color
+}
+
+/// White where the sky is visible from `rec`, the first hit along `r`, in a cosine-weighted
+/// random direction, black where it is blocked.
+fn ambient_occlusion(r: Ray, rec: Option<HitRecord>, world: &dyn Hittable) -> Vec3 {
+    let Some(rec) = rec else {
+        return color::WHITE;
+    };
+    let direction = rec.normal + math::random_unit_vec();
+    let occlusion_ray = rec.spawn_ray(direction, r.time);
+    if world.occluded(occlusion_ray, 0.0, Float::INFINITY) {
+        color::BLACK
+    } else {
+        color::WHITE
+    }
 }
@@ -0,0 +1,39 @@
+use std::rc::Rc;
+
+use crate::{
+    float::Vec3,
+    hittable::HitRecord,
+    material::{Material, MaterialRayInteraction, PhaseFunction},
+    ray::Ray,
+    texture::{SolidColor, Texture},
+};
+
+/// What a medium scatters with, into directions picked by a phase function, such as
+/// [`HenyeyGreenstein`](super::HenyeyGreenstein) for forward-scattering haze.
+pub struct Anisotropic {
+    pub albedo: Rc<dyn Texture>,
+    pub phase: Rc<dyn PhaseFunction>,
+}
+
+impl Anisotropic {
+    pub fn from_texture(albedo: Rc<dyn Texture>, phase: Rc<dyn PhaseFunction>) -> Self {
+        Self { albedo, phase }
+    }
+
+    pub fn from_color(color: Vec3, phase: Rc<dyn PhaseFunction>) -> Self {
+        Self {
+            albedo: Rc::new(SolidColor::from_color(color)),
+            phase,
+        }
+    }
+}
+
+impl Material for Anisotropic {
+    fn scatter(&self, r_in: Ray, rec: &HitRecord) -> MaterialRayInteraction {
+        let direction = self.phase.sample(r_in.direction.normalize());
+        MaterialRayInteraction::Scattered {
+            attenuation: self.albedo.value(rec.u, rec.v, rec.point),
+            scattered_ray: rec.spawn_ray(direction, r_in.time),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     color,
+    float::Float,
     hittable::HitRecord,
     material::{Material, MaterialRayInteraction},
     math::VecExtension,
@@ -9,15 +10,15 @@ use crate::{
 use rand::prelude::*;
 
 pub struct Dielectric {
-    pub ir: f64,
+    pub ir: Float,
 }
 
 impl Dielectric {
-    pub fn new(ir: f64) -> Self {
+    pub fn new(ir: Float) -> Self {
         Self { ir }
     }
 
-    fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
+    fn reflectance(cosine: Float, ref_idx: Float) -> Float {
         // use Shlick's approximation for reflectance
         let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
         r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
@@ -44,7 +45,7 @@ impl Material for Dielectric {
         } else {
             unit_direction.refract(rec.normal, refraction_ratio)
         };
-        let ray = Ray::new(rec.point, direction, r_in.time);
+        let ray = rec.spawn_ray(direction, r_in.time);
         MaterialRayInteraction::Scattered {
             attenuation,
             scattered_ray: ray,
@@ -1,6 +1,7 @@
 use std::rc::Rc;
 
 use crate::{
+    float::{Float, Vec3},
     hittable::HitRecord,
     material::{Material, MaterialRayInteraction},
     ray::Ray,
@@ -12,11 +13,11 @@ pub struct DiffuseLight {
 }
 
 impl DiffuseLight {
-    // pub fn from_texture(texture: Rc<dyn Texture>) -> Self {
-    //     Self { emit: texture }
-    // }
+    pub fn from_texture(texture: Rc<dyn Texture>) -> Self {
+        Self { emit: texture }
+    }
 
-    pub fn from_color(color: glam::DVec3) -> Self {
+    pub fn from_color(color: Vec3) -> Self {
         let emit = Rc::new(SolidColor::from_color(color));
         Self { emit }
     }
@@ -27,7 +28,7 @@ impl Material for DiffuseLight {
         MaterialRayInteraction::Absorbed
     }
 
-    fn emitted(&self, u: f64, v: f64, p: glam::DVec3) -> glam::DVec3 {
+    fn emitted(&self, u: Float, v: Float, p: Vec3) -> Vec3 {
         self.emit.value(u, v, p)
     }
 }
@@ -0,0 +1,490 @@
+//! Scattering from hair fibers, after d'Eon et al. and Chiang et al. as implemented in pbrt.
+//!
+//! A fiber is modeled as a rough dielectric cylinder covered in tilted cuticle scales, with a
+//! pigment that absorbs light passing through it. Light leaves it after `p` segments inside:
+//! reflected off the surface (R), transmitted straight through (TT), reflected once inside
+//! (TRT), and everything that bounces around longer lumped into one last lobe.
+
+use rand::prelude::*;
+
+use crate::{
+    float::{
+        consts::{self, PI, TAU},
+        vec3, Float, Vec3,
+    },
+    hittable::HitRecord,
+    material::{Material, MaterialRayInteraction},
+    ray::Ray,
+};
+
+/// Number of lobes modeled separately. The rest of the light ends up in one more.
+const P_MAX: usize = 3;
+
+/// Absorption per unit of the fiber's radius of the two kinds of melanin, which between them
+/// give natural hair colors from blond (little) over red (mostly pheomelanin) to black (lots of
+/// eumelanin).
+const EUMELANIN_SIGMA_A: Vec3 = vec3(0.419, 0.697, 1.37);
+const PHEOMELANIN_SIGMA_A: Vec3 = vec3(0.187, 0.4, 1.05);
+
+/// A hair fiber, for [`crate::hittable::Curves`] or any other shape that sets a tangent along
+/// its strands and `v` across them.
+pub struct Hair {
+    absorption: Absorption,
+    /// Index of refraction of the fiber.
+    pub eta: Float,
+    /// Roughness along the fiber, which widens the highlights along it.
+    pub beta_m: Float,
+    /// Roughness around the fiber.
+    pub beta_n: Float,
+    /// Tilt of the cuticle scales in radians, which shifts the lobes apart.
+    pub alpha: Float,
+}
+
+enum Absorption {
+    /// Absorption coefficient per unit of the fiber's radius.
+    Coefficient(Vec3),
+    /// The color of the fiber after light has bounced around many fibers.
+    Color(Vec3),
+}
+
+impl Hair {
+    /// A fiber absorbing `sigma_a` per unit of its radius.
+    pub fn new(sigma_a: Vec3) -> Self {
+        Self::with_absorption(Absorption::Coefficient(sigma_a))
+    }
+
+    /// A fiber colored by the given concentrations of melanin, which are around 8 for black
+    /// hair, 1.3 for brown and 0.3 for blond.
+    pub fn from_melanin(eumelanin: Float, pheomelanin: Float) -> Self {
+        Self::new(eumelanin * EUMELANIN_SIGMA_A + pheomelanin * PHEOMELANIN_SIGMA_A)
+    }
+
+    /// A fiber absorbing just enough for a mass of hair to take on `color`.
+    pub fn from_color(color: Vec3) -> Self {
+        Self::with_absorption(Absorption::Color(color))
+    }
+
+    fn with_absorption(absorption: Absorption) -> Self {
+        Self {
+            absorption,
+            eta: 1.55,
+            beta_m: 0.3,
+            beta_n: 0.3,
+            alpha: Float::to_radians(2.0),
+        }
+    }
+
+    /// Sets the roughness along and around the fiber, both from 0 (smooth) to 1.
+    pub fn with_roughness(mut self, longitudinal: Float, azimuthal: Float) -> Self {
+        self.beta_m = longitudinal;
+        self.beta_n = azimuthal;
+        self
+    }
+
+    pub fn with_scale_angle(mut self, degrees: Float) -> Self {
+        self.alpha = degrees.to_radians();
+        self
+    }
+
+    pub fn with_ior(mut self, eta: Float) -> Self {
+        self.eta = eta;
+        self
+    }
+
+    fn sigma_a(&self) -> Vec3 {
+        match self.absorption {
+            Absorption::Coefficient(sigma_a) => sigma_a,
+            Absorption::Color(color) => {
+                // A fit to simulations of many bounces, from Chiang et al.
+                let b = self.beta_n;
+                let scale = 5.969 - 0.215 * b + 2.532 * b.powi(2) - 10.73 * b.powi(3)
+                    + 5.574 * b.powi(4)
+                    + 0.245 * b.powi(5);
+                let ln = vec3(color.x.ln(), color.y.ln(), color.z.ln()) / scale;
+                ln * ln
+            }
+        }
+    }
+}
+
+impl Material for Hair {
+    fn scatter(&self, r_in: Ray, rec: &HitRecord) -> MaterialRayInteraction {
+        // x runs along the fiber, and y and z span its cross section with z towards the ray.
+        let z = rec.normal;
+        let x = (rec.tangent - rec.tangent.dot(z) * z)
+            .try_normalize()
+            .unwrap_or_else(|| z.any_orthonormal_pair().0);
+        let y = z.cross(x);
+
+        let direction = -r_in.direction.normalize();
+        let wo = vec3(direction.dot(x), direction.dot(y), direction.dot(z));
+        let bsdf = HairBsdf::new(self, (2.0 * rec.v - 1.0).clamp(-1.0, 1.0), wo);
+
+        let mut rng = thread_rng();
+        let wi = bsdf.sample(rng.gen(), [rng.gen(), rng.gen()], rng.gen());
+        let (f, pdf) = bsdf.evaluate(wi);
+        if pdf.is_nan() || pdf <= 0.0 {
+            return MaterialRayInteraction::Absorbed;
+        }
+        MaterialRayInteraction::Scattered {
+            attenuation: f / pdf,
+            scattered_ray: rec.spawn_ray(wi.x * x + wi.y * y + wi.z * z, r_in.time),
+        }
+    }
+}
+
+/// A [`Hair`] seen from one direction `wo` at one offset `h` across the fiber, from -1 to 1,
+/// with everything that only depends on those worked out. Directions are in the frame of
+/// [`Hair::scatter`], with angles θ from the normal plane of the fiber and φ around it.
+struct HairBsdf {
+    sin_theta_o: Float,
+    cos_theta_o: Float,
+    phi_o: Float,
+    /// Angles to the normal inside the fiber's cross section, outside and inside it.
+    gamma_o: Float,
+    gamma_t: Float,
+    /// How much of the light ends up in each lobe.
+    attenuation: [Vec3; P_MAX + 1],
+    /// The probability of sampling each lobe, by the brightness of its attenuation.
+    lobe_pdf: [Float; P_MAX + 1],
+    /// Variance of the longitudinal scattering of each lobe.
+    v: [Float; P_MAX + 1],
+    /// Scale of the azimuthal scattering.
+    s: Float,
+    sin_2k_alpha: [Float; 3],
+    cos_2k_alpha: [Float; 3],
+}
+
+impl HairBsdf {
+    fn new(hair: &Hair, h: Float, wo: Vec3) -> Self {
+        let sin_theta_o = wo.x;
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z.atan2(wo.y);
+
+        // Light refracted into the fiber crosses it along a chord, twice the cosine of its
+        // angle to the normal long in the cross section and longer again by its tilt.
+        let eta = hair.eta;
+        let sin_theta_t = sin_theta_o / eta;
+        let cos_theta_t = safe_sqrt(1.0 - sin_theta_t * sin_theta_t);
+        let eta_p = safe_sqrt(eta * eta - sin_theta_o * sin_theta_o) / cos_theta_o;
+        let sin_gamma_t = h / eta_p;
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t * sin_gamma_t);
+        let sigma_a = hair.sigma_a();
+        let path = 2.0 * cos_gamma_t / cos_theta_t;
+        let transmittance = vec3(
+            (-sigma_a.x * path).exp(),
+            (-sigma_a.y * path).exp(),
+            (-sigma_a.z * path).exp(),
+        );
+
+        let cos_gamma_o = safe_sqrt(1.0 - h * h);
+        let f = fresnel(cos_theta_o * cos_gamma_o, eta);
+        let mut attenuation = [Vec3::ZERO; P_MAX + 1];
+        attenuation[0] = Vec3::splat(f);
+        attenuation[1] = (1.0 - f) * (1.0 - f) * transmittance;
+        for p in 2..P_MAX {
+            attenuation[p] = attenuation[p - 1] * transmittance * f;
+        }
+        // The geometric series of all longer paths. Light running along the fiber is all
+        // reflected, and none of it is left for the series, which would come to 0 / 0.
+        let longer = attenuation[P_MAX - 1] * f * transmittance;
+        attenuation[P_MAX] = Vec3::select(
+            longer.cmpeq(Vec3::ZERO),
+            Vec3::ZERO,
+            longer / (Vec3::ONE - transmittance * f),
+        );
+
+        let luminance = attenuation.map(|a| 0.2126 * a.x + 0.7152 * a.y + 0.0722 * a.z);
+        let total: Float = luminance.iter().sum();
+        let lobe_pdf = luminance.map(|l| l / total);
+
+        let beta_m = hair.beta_m;
+        let v0 = (0.726 * beta_m + 0.812 * beta_m.powi(2) + 3.7 * beta_m.powi(20)).powi(2);
+        let beta_n = hair.beta_n;
+        let s =
+            (PI / 8.0).sqrt() * (0.265 * beta_n + 1.194 * beta_n.powi(2) + 5.372 * beta_n.powi(22));
+
+        // The scales tilt R by -2α, TT by α and TRT by 4α.
+        let mut sin_2k_alpha = [hair.alpha.sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [safe_sqrt(1.0 - sin_2k_alpha[0].powi(2)), 0.0, 0.0];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+
+        Self {
+            sin_theta_o,
+            cos_theta_o,
+            phi_o,
+            gamma_o: safe_asin(h),
+            gamma_t: safe_asin(sin_gamma_t),
+            attenuation,
+            lobe_pdf,
+            v: [v0, 0.25 * v0, 4.0 * v0, 4.0 * v0],
+            s,
+            sin_2k_alpha,
+            cos_2k_alpha,
+        }
+    }
+
+    /// The sine and cosine of θo tilted by the scales for lobe `p`.
+    fn tilted(&self, p: usize) -> (Float, Float) {
+        let (sin, cos) = (self.sin_theta_o, self.cos_theta_o);
+        let (sin_tilt, cos_tilt) = match p {
+            0 => (-self.sin_2k_alpha[1], self.cos_2k_alpha[1]),
+            1 => (self.sin_2k_alpha[0], self.cos_2k_alpha[0]),
+            2 => (self.sin_2k_alpha[2], self.cos_2k_alpha[2]),
+            _ => return (sin, cos),
+        };
+        (
+            sin * cos_tilt + cos * sin_tilt,
+            (cos * cos_tilt - sin * sin_tilt).abs(),
+        )
+    }
+
+    /// The scattered light towards `wo` for light from `wi`, times the cosine to the normal
+    /// of the surface, along with the density of sampling `wi`.
+    fn evaluate(&self, wi: Vec3) -> (Vec3, Float) {
+        let sin_theta_i = wi.x;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+        let phi = wi.z.atan2(wi.y) - self.phi_o;
+
+        let mut f = Vec3::ZERO;
+        let mut pdf = 0.0;
+        for p in 0..=P_MAX {
+            let (sin_theta_o, cos_theta_o) = self.tilted(p);
+            let longitudinal = mp(
+                cos_theta_i,
+                cos_theta_o,
+                sin_theta_i,
+                sin_theta_o,
+                self.v[p],
+            );
+            let azimuthal = if p < P_MAX {
+                np(phi, p, self.s, self.gamma_o, self.gamma_t)
+            } else {
+                1.0 / TAU
+            };
+            f += longitudinal * azimuthal * self.attenuation[p];
+            pdf += longitudinal * azimuthal * self.lobe_pdf[p];
+        }
+        (f, pdf)
+    }
+
+    /// Picks a lobe with `u_lobe`, then θi within it with `u_theta` and φi with `u_phi`.
+    fn sample(&self, u_lobe: Float, u_theta: [Float; 2], u_phi: Float) -> Vec3 {
+        let mut p = 0;
+        let mut u = u_lobe;
+        while p < P_MAX && u >= self.lobe_pdf[p] {
+            u -= self.lobe_pdf[p];
+            p += 1;
+        }
+
+        let (sin_theta_o, cos_theta_o) = self.tilted(p);
+        let v = self.v[p];
+        let u0 = u_theta[0].max(1e-5);
+        let cos_theta = 1.0 + v * (u0 + (1.0 - u0) * (-2.0 / v).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta * cos_theta);
+        let cos_phi = (TAU * u_theta[1]).cos();
+        let sin_theta_i = -cos_theta * sin_theta_o + sin_theta * cos_phi * cos_theta_o;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+
+        let delta_phi = if p < P_MAX {
+            phi(p, self.gamma_o, self.gamma_t) + sample_trimmed_logistic(u_phi, self.s)
+        } else {
+            TAU * u_phi
+        };
+        let phi_i = self.phi_o + delta_phi;
+        vec3(
+            sin_theta_i,
+            cos_theta_i * phi_i.cos(),
+            cos_theta_i * phi_i.sin(),
+        )
+    }
+}
+
+/// The longitudinal scattering function: how light spreads along the fiber.
+fn mp(
+    cos_theta_i: Float,
+    cos_theta_o: Float,
+    sin_theta_i: Float,
+    sin_theta_o: Float,
+    v: Float,
+) -> Float {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1 {
+        // The direct formula overflows for small variances.
+        (log_i0(a) - b - 1.0 / v + consts::LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        (-b).exp() * i0(a) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+/// The modified Bessel function of the first kind and order 0.
+fn i0(x: Float) -> Float {
+    let mut value = 0.0;
+    let mut x_2i = 1.0;
+    let mut factorial = 1.0;
+    let mut four_i = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            factorial *= i as Float;
+        }
+        value += x_2i / (four_i * factorial * factorial);
+        x_2i *= x * x;
+        four_i *= 4.0;
+    }
+    value
+}
+
+fn log_i0(x: Float) -> Float {
+    if x > 12.0 {
+        x + 0.5 * (-(TAU.ln()) + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        i0(x).ln()
+    }
+}
+
+/// The azimuthal angle light leaves at after `p` segments inside the fiber.
+fn phi(p: usize, gamma_o: Float, gamma_t: Float) -> Float {
+    let p = p as Float;
+    2.0 * p * gamma_t - 2.0 * gamma_o + p * PI
+}
+
+/// The azimuthal scattering function: a logistic distribution around [`phi`].
+fn np(phi_difference: Float, p: usize, s: Float, gamma_o: Float, gamma_t: Float) -> Float {
+    let mut dphi = phi_difference - phi(p, gamma_o, gamma_t);
+    // Wrap around to [-π, π].
+    dphi -= TAU * (dphi / TAU).round();
+    trimmed_logistic(dphi, s)
+}
+
+fn logistic(x: Float, s: Float) -> Float {
+    let e = (-x.abs() / s).exp();
+    e / (s * (1.0 + e) * (1.0 + e))
+}
+
+fn logistic_cdf(x: Float, s: Float) -> Float {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+/// The logistic distribution restricted to [-π, π].
+fn trimmed_logistic(x: Float, s: Float) -> Float {
+    logistic(x, s) / (logistic_cdf(PI, s) - logistic_cdf(-PI, s))
+}
+
+fn sample_trimmed_logistic(u: Float, s: Float) -> Float {
+    let k = logistic_cdf(PI, s) - logistic_cdf(-PI, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(-PI, s)) - 1.0).ln();
+    x.clamp(-PI, PI)
+}
+
+/// The Fresnel reflectance of unpolarized light entering a dielectric from air.
+fn fresnel(cos_theta_i: Float, eta: Float) -> Float {
+    let cos_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin_t = safe_sqrt(1.0 - cos_i * cos_i) / eta;
+    if sin_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = safe_sqrt(1.0 - sin_t * sin_t);
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+fn safe_sqrt(x: Float) -> Float {
+    x.max(0.0).sqrt()
+}
+
+fn safe_asin(x: Float) -> Float {
+    x.clamp(-1.0, 1.0).asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+
+    fn uniform_sphere(rng: &mut StdRng) -> Vec3 {
+        let z: Float = rng.gen_range(-1.0..1.0);
+        let phi = TAU * rng.gen::<Float>();
+        let r = safe_sqrt(1.0 - z * z);
+        vec3(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    /// Fibers with every roughness from fairly smooth to very rough.
+    fn roughnesses() -> impl Iterator<Item = (Float, Float)> {
+        [0.2, 0.5, 0.9]
+            .into_iter()
+            .flat_map(|beta_m| [0.2, 0.5, 0.9].map(|beta_n| (beta_m, beta_n)))
+    }
+
+    /// A fiber that absorbs nothing scatters all light that reaches it and no more, so the
+    /// scattered light integrated over all directions comes to 1, on average over every
+    /// direction and offset it can be seen from.
+    #[test]
+    #[allow(clippy::useless_conversion)]
+    fn white_furnace() {
+        let mut rng = StdRng::seed_from_u64(45);
+        for (beta_m, beta_n) in roughnesses() {
+            let hair = Hair::new(Vec3::ZERO).with_roughness(beta_m, beta_n);
+            let samples = 200_000;
+            // Without absorption every channel is the same. Summed in double precision, since
+            // single precision loses too much over this many samples.
+            let mut sum = 0.0;
+            for _ in 0..samples {
+                let bsdf = HairBsdf::new(&hair, rng.gen_range(-1.0..1.0), uniform_sphere(&mut rng));
+                sum += f64::from(bsdf.evaluate(uniform_sphere(&mut rng)).0.x);
+            }
+            let average = 4.0 * std::f64::consts::PI * sum / samples as f64;
+            // Up to the noise of the estimate, which is around 1% with this many samples.
+            assert!(
+                (0.97..=1.02).contains(&average),
+                "β_m = {beta_m}, β_n = {beta_n}: {average}"
+            );
+        }
+    }
+
+    /// Every sampled direction has a density, and without absorption the lobes are picked in
+    /// proportion to what they scatter, so the weight of each sample is exactly 1.
+    #[test]
+    fn sampled_directions_have_a_density() {
+        let mut rng = StdRng::seed_from_u64(45);
+        for (beta_m, beta_n) in roughnesses() {
+            for hair in [Hair::new(Vec3::ZERO), Hair::from_melanin(1.3, 0.3)] {
+                let hair = hair.with_roughness(beta_m, beta_n);
+                for _ in 0..10_000 {
+                    let wo = uniform_sphere(&mut rng);
+                    let bsdf = HairBsdf::new(&hair, rng.gen_range(-1.0..1.0), wo);
+                    let wi = bsdf.sample(rng.gen(), [rng.gen(), rng.gen()], rng.gen());
+                    let (f, pdf) = bsdf.evaluate(wi);
+                    assert!(pdf > 0.0 && pdf.is_finite(), "wo = {wo}, wi = {wi}: {pdf}");
+                    if hair.sigma_a() == Vec3::ZERO {
+                        let weight = f / pdf;
+                        assert!(weight.abs_diff_eq(Vec3::ONE, 1e-3), "{weight}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// The density that `evaluate` gives is that of a distribution over directions, which
+    /// integrates to 1 whichever way the fiber is seen from.
+    #[test]
+    fn pdf_integrates_to_one() {
+        let mut rng = StdRng::seed_from_u64(45);
+        let hair = Hair::from_melanin(1.3, 0.3).with_roughness(0.3, 0.3);
+        for _ in 0..10 {
+            let bsdf = HairBsdf::new(&hair, rng.gen_range(-1.0..1.0), uniform_sphere(&mut rng));
+            let samples = 100_000;
+            let integral = (0..samples)
+                .map(|_| bsdf.evaluate(uniform_sphere(&mut rng)).1)
+                .sum::<Float>()
+                * 4.0
+                * PI
+                / samples as Float;
+            assert!((integral - 1.0).abs() < 0.05, "{integral}");
+        }
+    }
+}
@@ -1,13 +1,15 @@
 use std::rc::Rc;
 
 use crate::{
+    float::Vec3,
     hittable::HitRecord,
-    material::{Material, MaterialRayInteraction},
-    math,
+    material::{IsotropicPhase, Material, MaterialRayInteraction, PhaseFunction},
     ray::Ray,
     texture::{SolidColor, Texture},
 };
 
+/// What a medium scatters with, equally in all directions. [`Anisotropic`](super::Anisotropic)
+/// takes any phase function instead.
 pub struct Isotropic {
     pub albedo: Rc<dyn Texture>,
 }
@@ -17,7 +19,7 @@ impl Isotropic {
         Self { albedo }
     }
 
-    pub fn from_color(color: glam::DVec3) -> Self {
+    pub fn from_color(color: Vec3) -> Self {
         Self {
             albedo: Rc::new(SolidColor::from_color(color)),
         }
@@ -26,7 +28,7 @@ impl Isotropic {
 
 impl Material for Isotropic {
     fn scatter(&self, r_in: Ray, rec: &HitRecord) -> MaterialRayInteraction {
-        let scattered_ray = Ray::new(rec.point, math::random_point_in_unit_sphere(), r_in.time);
+        let scattered_ray = rec.spawn_ray(IsotropicPhase.sample(r_in.direction), r_in.time);
         MaterialRayInteraction::Scattered {
             attenuation: self.albedo.value(rec.u, rec.v, rec.point),
             scattered_ray,
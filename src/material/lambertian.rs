@@ -1,6 +1,7 @@
 use std::rc::Rc;
 
 use crate::{
+    float::Vec3,
     hittable::HitRecord,
     material::{Material, MaterialRayInteraction},
     math::{self, VecExtension},
@@ -17,7 +18,7 @@ impl Lambertian {
         Self { albedo: texture }
     }
 
-    pub fn from_color(color: glam::DVec3) -> Self {
+    pub fn from_color(color: Vec3) -> Self {
         Self {
             albedo: Rc::new(SolidColor::from_color(color)),
         }
@@ -32,9 +33,9 @@ impl Material for Lambertian {
         } else {
             scatter_direction
         };
-        let scattered_ray = Ray::new(rec.point, scatter_direction, r_in.time);
-        let attenuation = self.albedo.value(rec.u, rec.v, rec.point);
-        MaterialRayInteraction::Scattered {
+        let scattered_ray = rec.spawn_ray(scatter_direction, r_in.time);
+        let attenuation = rec.tint(self.albedo.value(rec.u, rec.v, rec.point));
+        MaterialRayInteraction::Diffuse {
             attenuation,
             scattered_ray,
         }
@@ -1,4 +1,5 @@
 use crate::{
+    float::{Float, Vec3},
     hittable::HitRecord,
     material::{Material, MaterialRayInteraction},
     math::{self, VecExtension},
@@ -6,12 +7,12 @@ use crate::{
 };
 
 pub struct Metal {
-    pub albedo: glam::DVec3,
-    pub fuzzines: f64,
+    pub albedo: Vec3,
+    pub fuzzines: Float,
 }
 
 impl Metal {
-    pub fn new(albedo: glam::DVec3, fuzzines: f64) -> Self {
+    pub fn new(albedo: Vec3, fuzzines: Float) -> Self {
         Self {
             albedo,
             fuzzines: fuzzines.min(1.0),
@@ -22,12 +23,11 @@ impl Metal {
 impl Material for Metal {
     fn scatter(&self, r_in: Ray, rec: &HitRecord) -> MaterialRayInteraction {
         let reflection_direction = r_in.direction.normalize().reflect(rec.normal);
-        let scattered_ray = Ray::new(
-            rec.point,
+        let scattered_ray = rec.spawn_ray(
             reflection_direction + math::random_point_in_unit_sphere() * self.fuzzines,
             r_in.time,
         );
-        let attenuation = self.albedo;
+        let attenuation = rec.tint(self.albedo);
         if scattered_ray.direction.dot(rec.normal) > 0.0 {
             MaterialRayInteraction::Scattered {
                 attenuation,
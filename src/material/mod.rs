@@ -1,28 +1,46 @@
+mod anisotropic;
 mod dielectric;
 mod diffuse_light;
+mod hair;
 mod isotropic;
 mod lambertian;
 mod metal;
+mod phase;
 
-use crate::{color, hittable::HitRecord, ray::Ray};
+use crate::{
+    color,
+    float::{Float, Vec3},
+    hittable::HitRecord,
+    ray::Ray,
+};
 
 pub enum MaterialRayInteraction {
     Absorbed,
     Scattered {
-        attenuation: glam::DVec3,
+        attenuation: Vec3,
+        scattered_ray: Ray,
+    },
+    /// Scattered by an ideal diffuse surface, whose light falls off with the cosine to the
+    /// normal. `scattered_ray` follows that distribution, but the renderer may aim elsewhere,
+    /// such as at a light, as long as it reweights by the cosine.
+    Diffuse {
+        attenuation: Vec3,
         scattered_ray: Ray,
     },
 }
 
 pub trait Material {
     fn scatter(&self, r_in: Ray, rec: &HitRecord) -> MaterialRayInteraction;
-    fn emitted(&self, _u: f64, _v: f64, _p: glam::DVec3) -> glam::DVec3 {
+    fn emitted(&self, _u: Float, _v: Float, _p: Vec3) -> Vec3 {
         color::BLACK
     }
 }
 
+pub use anisotropic::Anisotropic;
 pub use dielectric::Dielectric;
 pub use diffuse_light::DiffuseLight;
+pub use hair::Hair;
 pub use isotropic::Isotropic;
 pub use lambertian::Lambertian;
 pub use metal::Metal;
+pub use phase::{DoubleHenyeyGreenstein, HenyeyGreenstein, IsotropicPhase, PhaseFunction};
@@ -0,0 +1,135 @@
+//! Phase functions: how likely light traveling through a medium is to scatter into each direction.
+
+use rand::prelude::*;
+
+use crate::{
+    float::{consts::TAU, Float, Vec3},
+    math,
+};
+
+pub trait PhaseFunction {
+    /// Picks the direction light traveling along the unit vector `direction` scatters into, with
+    /// the density of the phase function. Since phase functions integrate to 1, scattered light
+    /// needs no weighting.
+    fn sample(&self, direction: Vec3) -> Vec3;
+}
+
+/// Scatters equally in all directions.
+pub struct IsotropicPhase;
+
+impl PhaseFunction for IsotropicPhase {
+    fn sample(&self, _direction: Vec3) -> Vec3 {
+        math::random_unit_vec()
+    }
+}
+
+/// The Henyey-Greenstein phase function, whose asymmetry `g` is the average cosine between the
+/// directions before and after scattering: positive values scatter forward like clouds and haze,
+/// negative ones back, and 0 is isotropic.
+pub struct HenyeyGreenstein {
+    pub g: Float,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: Float) -> Self {
+        assert!(
+            (-1.0..=1.0).contains(&g),
+            "Henyey-Greenstein asymmetry must be in [-1, 1]"
+        );
+        Self { g }
+    }
+}
+
+impl PhaseFunction for HenyeyGreenstein {
+    fn sample(&self, direction: Vec3) -> Vec3 {
+        let g = self.g;
+        let u: Float = thread_rng().gen();
+        // Inverting the cumulative distribution, which divides by g.
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = TAU * thread_rng().gen::<Float>();
+        let (x, y) = direction.any_orthonormal_pair();
+        sin_theta * (phi.cos() * x + phi.sin() * y) + cos_theta * direction
+    }
+}
+
+/// A blend of a forward and a backward Henyey-Greenstein lobe, with `weight` the share of the
+/// forward one. One lobe can't both scatter most light forward and still send some of it back,
+/// as water droplets in clouds do.
+pub struct DoubleHenyeyGreenstein {
+    pub forward: HenyeyGreenstein,
+    pub backward: HenyeyGreenstein,
+    pub weight: Float,
+}
+
+impl DoubleHenyeyGreenstein {
+    pub fn new(forward: Float, backward: Float, weight: Float) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&weight),
+            "lobe weight must be in [0, 1]"
+        );
+        Self {
+            forward: HenyeyGreenstein::new(forward),
+            backward: HenyeyGreenstein::new(backward),
+            weight,
+        }
+    }
+}
+
+impl PhaseFunction for DoubleHenyeyGreenstein {
+    fn sample(&self, direction: Vec3) -> Vec3 {
+        if thread_rng().gen::<Float>() < self.weight {
+            self.forward.sample(direction)
+        } else {
+            self.backward.sample(direction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::vec3;
+
+    const SAMPLES: usize = 100_000;
+
+    /// The average cosine between `direction` and the directions `phase` scatters it into.
+    fn mean_cosine(phase: &dyn PhaseFunction, direction: Vec3) -> Float {
+        (0..SAMPLES)
+            .map(|_| {
+                let scattered = phase.sample(direction);
+                assert!((scattered.length() - 1.0).abs() < 1e-5, "{scattered}");
+                scattered.dot(direction)
+            })
+            .sum::<Float>()
+            / SAMPLES as Float
+    }
+
+    #[test]
+    fn henyey_greenstein_mean_cosine_is_g() {
+        let direction = vec3(1.0, -2.0, 2.0) / 3.0;
+        for g in [-0.8, 0.0, 0.5, 0.9] {
+            let mean = mean_cosine(&HenyeyGreenstein::new(g), direction);
+            assert!((mean - g).abs() < 0.01, "{g}: {mean}");
+        }
+    }
+
+    #[test]
+    fn double_henyey_greenstein_mean_cosine_blends_the_lobes() {
+        let direction = vec3(0.0, 0.6, -0.8);
+        for (forward, backward, weight) in [(0.9, -0.5, 0.8), (0.6, -0.8, 0.3), (0.5, 0.0, 1.0)] {
+            let phase = DoubleHenyeyGreenstein::new(forward, backward, weight);
+            let expected = weight * forward + (1.0 - weight) * backward;
+            let mean = mean_cosine(&phase, direction);
+            assert!(
+                (mean - expected).abs() < 0.01,
+                "{weight}: {mean} vs {expected}"
+            );
+        }
+    }
+}
@@ -1,9 +1,10 @@
 use rand::prelude::*;
 
+use crate::float::{vec3, Float, Vec3};
 use crate::math;
 
 pub struct Perlin {
-    ranvec: Vec<glam::DVec3>,
+    ranvec: Vec<Vec3>,
     perm_x: Vec<usize>,
     perm_y: Vec<usize>,
     perm_z: Vec<usize>,
@@ -13,7 +14,7 @@ impl Perlin {
     pub const POINT_COUNT: usize = 256;
 
     pub fn new() -> Self {
-        let ranvec: Vec<glam::DVec3> = std::iter::repeat_with(|| math::random_range_vec(-1.0, 1.0))
+        let ranvec: Vec<Vec3> = std::iter::repeat_with(|| math::random_range_vec(-1.0, 1.0))
             .take(Self::POINT_COUNT)
             .collect();
 
@@ -25,7 +26,7 @@ impl Perlin {
         }
     }
 
-    pub fn turb(&self, p: glam::DVec3, depth: i32) -> f64 {
+    pub fn turb(&self, p: Vec3, depth: i32) -> Float {
         let mut acc = 0.0;
         let mut temp_p = p;
         let mut weight = 1.0;
@@ -39,7 +40,7 @@ impl Perlin {
         acc.abs()
     }
 
-    pub fn noise(&self, p: glam::DVec3) -> f64 {
+    pub fn noise(&self, p: Vec3) -> Float {
         let u = p.x - p.x.floor();
         let v = p.y - p.y.floor();
         let w = p.z - p.z.floor();
@@ -47,7 +48,7 @@ impl Perlin {
         let i = p.x.floor() as i32;
         let j = p.y.floor() as i32;
         let k = p.z.floor() as i32;
-        let mut c = [[[glam::DVec3::ZERO; 2]; 2]; 2];
+        let mut c = [[[Vec3::ZERO; 2]; 2]; 2];
 
         #[allow(clippy::needless_range_loop)]
         for di in 0..2 {
@@ -77,7 +78,7 @@ impl Perlin {
         }
     }
 
-    fn perlin_interp(c: [[[glam::DVec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+    fn perlin_interp(c: [[[Vec3; 2]; 2]; 2], u: Float, v: Float, w: Float) -> Float {
         // Hermitian Smoothing
         let uu = u * u * (3.0 - 2.0 * u);
         let vv = v * v * (3.0 - 2.0 * v);
@@ -88,10 +89,10 @@ impl Perlin {
         for i in 0..2 {
             for j in 0..2 {
                 for k in 0..2 {
-                    let weight_v = glam::dvec3(u - i as f64, v - j as f64, w - k as f64);
-                    acc += (i as f64 * uu + (1.0 - i as f64) * (1.0 - uu))
-                        * (j as f64 * vv + (1.0 - j as f64) * (1.0 - vv))
-                        * (k as f64 * ww + (1.0 - k as f64) * (1.0 - ww))
+                    let weight_v = vec3(u - i as Float, v - j as Float, w - k as Float);
+                    acc += (i as Float * uu + (1.0 - i as Float) * (1.0 - uu))
+                        * (j as Float * vv + (1.0 - j as Float) * (1.0 - vv))
+                        * (k as Float * ww + (1.0 - k as Float) * (1.0 - ww))
                         * c[i][j][k].dot(weight_v);
                 }
             }
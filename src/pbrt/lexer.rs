@@ -0,0 +1,93 @@
+use crate::float::Float;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Ident(String),
+    Str(String),
+    Number(Float),
+    LBracket,
+    RBracket,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Splits pbrt source into tokens. Errors carry the 1-based line and column they occurred at.
+pub fn tokenize(src: &str) -> Result<Vec<Token>, (usize, usize, String)> {
+    let mut tokens = Vec::new();
+    let mut chars = src.char_indices().peekable();
+    let mut line = 1;
+    let mut line_start = 0;
+
+    while let Some(&(start, c)) = chars.peek() {
+        let column = src[line_start..start].chars().count() + 1;
+        let kind = match c {
+            '\n' => {
+                chars.next();
+                line += 1;
+                line_start = start + 1;
+                continue;
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '#' => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+                continue;
+            }
+            '[' => {
+                chars.next();
+                TokenKind::LBracket
+            }
+            ']' => {
+                chars.next();
+                TokenKind::RBracket
+            }
+            '"' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => s.push('\n'),
+                            Some((_, 't')) => s.push('\t'),
+                            Some((_, c)) => s.push(c),
+                            None => break,
+                        },
+                        Some((_, '\n')) | None => {
+                            return Err((line, column, "unterminated string".to_owned()))
+                        }
+                        Some((_, c)) => s.push(c),
+                    }
+                }
+                TokenKind::Str(s)
+            }
+            _ => {
+                let mut end = start;
+                while let Some((i, c)) =
+                    chars.next_if(|&(_, c)| !c.is_whitespace() && !matches!(c, '[' | ']' | '"'))
+                {
+                    end = i + c.len_utf8();
+                }
+                let word = &src[start..end];
+                if c.is_ascii_digit() || matches!(c, '-' | '+' | '.') {
+                    let number = word
+                        .parse()
+                        .map_err(|_| (line, column, format!("invalid number '{word}'")))?;
+                    TokenKind::Number(number)
+                } else {
+                    TokenKind::Ident(word.to_owned())
+                }
+            }
+        };
+        tokens.push(Token { kind, line, column });
+    }
+
+    Ok(tokens)
+}
@@ -0,0 +1,1022 @@
+//! An importer for a practical subset of the pbrt-v3 scene format.
+//!
+//! Supported: `LookAt`, `Translate`, `Rotate`, `Scale`, `Transform`, `ConcatTransform`,
+//! `Identity`, `CoordinateSystem`/`CoordSysTransform`, `Camera "perspective"`, `Film`, `Sampler`,
+//! `Integrator` (only `maxdepth`), `WorldBegin`/`WorldEnd`, `AttributeBegin`/`AttributeEnd`,
+//! `TransformBegin`/`TransformEnd`, `Shape "sphere"`/`"disk"`/`"trianglemesh"`/`"plymesh"`,
+//! `Material "matte"`/`"metal"`/`"glass"`, `MakeNamedMaterial`/`NamedMaterial`,
+//! `AreaLightSource "diffuse"`, `LightSource "infinite"` (as the background color) and `Include`.
+//! Anything else is skipped with a [`Warning`], which [`load`] returns along with the scene.
+//!
+//! pbrt uses a left-handed camera space while [`Camera`] is right-handed, so when needed the whole
+//! scene is mirrored about the camera's vertical plane to produce the same image as pbrt.
+
+mod lexer;
+mod params;
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    camera::Camera,
+    color,
+    float::{self, similarity_scale, vec3, Affine3, Float, Mat3, Mat4, Vec3},
+    hittable::{sphere::Sphere, Disk, HittableList, LightList, Transform, TriangleMesh},
+    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal},
+    ply,
+    scene_file::Location,
+    test_scenes::Scene,
+    TIME0, TIME1,
+};
+
+use lexer::{Token, TokenKind};
+use params::{Param, ParamSet, ParamValue};
+
+#[derive(Debug)]
+pub enum PbrtError {
+    Io { path: PathBuf, source: io::Error },
+    Syntax { location: Location, message: String },
+}
+
+impl Display for PbrtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PbrtError::Io { path, source } => {
+                write!(f, "{}: unable to read file: {source}", path.display())
+            }
+            PbrtError::Syntax { location, message } => write!(f, "{location}: {message}"),
+        }
+    }
+}
+
+impl Error for PbrtError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PbrtError::Io { source, .. } => Some(source),
+            PbrtError::Syntax { .. } => None,
+        }
+    }
+}
+
+/// Something in a pbrt file that was skipped or replaced, because this importer doesn't support
+/// it.
+#[derive(Debug)]
+pub struct Warning {
+    pub location: Location,
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Imports the scene in `path`, along with warnings about whatever in it couldn't be imported.
+pub fn load(path: impl AsRef<Path>) -> Result<(Scene, Vec<Warning>), PbrtError> {
+    let mut importer = Importer::new();
+    importer.include(path.as_ref())?;
+    importer.run()?;
+    Ok(importer.finish())
+}
+
+struct Source {
+    path: PathBuf,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+#[derive(Clone)]
+struct GraphicsState {
+    ctm: Mat4,
+    material: Rc<dyn Material>,
+    area_light: Option<Rc<dyn Material>>,
+}
+
+enum Saved {
+    Attributes(GraphicsState),
+    Transform(Mat4),
+}
+
+struct Importer {
+    sources: Vec<Source>,
+    /// Where the last token read was, to report errors at the end of the input.
+    last_location: Option<Location>,
+    state: GraphicsState,
+    stack: Vec<Saved>,
+    named_materials: HashMap<String, Rc<dyn Material>>,
+    coordinate_systems: HashMap<String, Mat4>,
+
+    camera_from_world: Mat4,
+    fov: Float,
+    lens_radius: Float,
+    focal_distance: Float,
+    resolution: (u32, u32),
+    samples_per_pixel: u32,
+    max_depth: Option<i32>,
+    background: Vec3,
+
+    /// Applied on top of every shape transform to fix up handedness, see the module docs.
+    mirror: Mat4,
+    world: HittableList,
+    /// The area lights in `world` that can be sampled directly.
+    lights: LightList,
+    warnings: Vec<Warning>,
+}
+
+impl Importer {
+    fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+            last_location: None,
+            state: GraphicsState {
+                ctm: Mat4::IDENTITY,
+                material: Rc::new(Lambertian::from_color(Vec3::splat(0.5))),
+                area_light: None,
+            },
+            stack: Vec::new(),
+            named_materials: HashMap::new(),
+            coordinate_systems: HashMap::new(),
+            camera_from_world: Mat4::IDENTITY,
+            fov: 90.0,
+            lens_radius: 0.0,
+            focal_distance: 1e6,
+            resolution: (640, 480),
+            samples_per_pixel: 16,
+            max_depth: None,
+            background: color::BLACK,
+            mirror: Mat4::IDENTITY,
+            world: HittableList::default(),
+            lights: LightList::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn include(&mut self, path: &Path) -> Result<(), PbrtError> {
+        let src = fs::read_to_string(path).map_err(|source| PbrtError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        let tokens =
+            lexer::tokenize(&src).map_err(|(line, column, message)| PbrtError::Syntax {
+                location: Location {
+                    file: path.to_owned(),
+                    line,
+                    column,
+                },
+                message,
+            })?;
+        self.sources.push(Source {
+            path: path.to_owned(),
+            tokens,
+            pos: 0,
+        });
+        Ok(())
+    }
+
+    /// Resolves a path from the current file the way pbrt does: relative to its directory.
+    fn resolve(&self, path: &str) -> PathBuf {
+        let dir = self
+            .sources
+            .last()
+            .and_then(|s| s.path.parent())
+            .unwrap_or(Path::new(""));
+        dir.join(path)
+    }
+
+    fn peek(&mut self) -> Option<&TokenKind> {
+        while self.sources.last().is_some_and(|s| s.pos >= s.tokens.len()) {
+            self.sources.pop();
+        }
+        let source = self.sources.last()?;
+        Some(&source.tokens[source.pos].kind)
+    }
+
+    fn next(&mut self) -> Option<(TokenKind, Location)> {
+        self.peek()?;
+        let source = self.sources.last_mut()?;
+        let token = source.tokens[source.pos].clone();
+        source.pos += 1;
+        let location = Location {
+            file: source.path.clone(),
+            line: token.line,
+            column: token.column,
+        };
+        self.last_location = Some(location.clone());
+        Some((token.kind, location))
+    }
+
+    /// The location of the next token, or of the last one at the end of the input.
+    fn location(&mut self) -> Location {
+        self.peek();
+        match self.sources.last() {
+            Some(source) => {
+                let token = &source.tokens[source.pos];
+                Location {
+                    file: source.path.clone(),
+                    line: token.line,
+                    column: token.column,
+                }
+            }
+            None => self.last_location.clone().unwrap_or(Location {
+                file: PathBuf::new(),
+                line: 0,
+                column: 0,
+            }),
+        }
+    }
+
+    fn error<T>(&mut self, message: impl Into<String>) -> Result<T, PbrtError> {
+        Err(PbrtError::Syntax {
+            location: self.location(),
+            message: message.into(),
+        })
+    }
+
+    fn expect_number(&mut self) -> Result<Float, PbrtError> {
+        match self.peek() {
+            Some(TokenKind::Number(n)) => {
+                let n = *n;
+                self.next();
+                Ok(n)
+            }
+            _ => self.error("expected a number"),
+        }
+    }
+
+    /// Reads `n` numbers, optionally wrapped in brackets.
+    fn expect_numbers<const N: usize>(&mut self) -> Result<[Float; N], PbrtError> {
+        let bracketed = self.peek() == Some(&TokenKind::LBracket);
+        if bracketed {
+            self.next();
+        }
+        let mut numbers = [0.0; N];
+        for n in &mut numbers {
+            *n = self.expect_number()?;
+        }
+        if bracketed {
+            match self.next() {
+                Some((TokenKind::RBracket, _)) => {}
+                _ => return self.error(format!("expected ']' after {N} numbers")),
+            }
+        }
+        Ok(numbers)
+    }
+
+    fn expect_string(&mut self) -> Result<String, PbrtError> {
+        match self.peek() {
+            Some(TokenKind::Str(s)) => {
+                let s = s.clone();
+                self.next();
+                Ok(s)
+            }
+            _ => self.error("expected a quoted string"),
+        }
+    }
+
+    fn params(&mut self) -> Result<ParamSet, PbrtError> {
+        let mut params = ParamSet::default();
+        while let Some(TokenKind::Str(decl)) = self.peek() {
+            let decl = decl.clone();
+            let mut words = decl.split_whitespace();
+            let (Some(ty), Some(name), None) = (words.next(), words.next(), words.next()) else {
+                return self.error(format!(
+                    "expected a \"type name\" parameter, got \"{decl}\""
+                ));
+            };
+            let (ty, name) = (ty.to_owned(), name.to_owned());
+            let (_, location) = self.next().unwrap();
+
+            let mut values = Vec::new();
+            let bracketed = self.peek() == Some(&TokenKind::LBracket);
+            if bracketed {
+                self.next();
+            }
+            loop {
+                match self.peek() {
+                    Some(TokenKind::RBracket) if bracketed => {
+                        self.next();
+                        break;
+                    }
+                    Some(TokenKind::Number(_) | TokenKind::Str(_)) => {
+                        values.push(self.next().unwrap().0)
+                    }
+                    Some(TokenKind::Ident(word)) if word == "true" || word == "false" => {
+                        values.push(TokenKind::Str(word.clone()));
+                        self.next();
+                    }
+                    _ => return self.error(format!("invalid value for parameter \"{decl}\"")),
+                }
+                if !bracketed {
+                    break;
+                }
+            }
+
+            let value = if values.iter().all(|v| matches!(v, TokenKind::Number(_))) {
+                ParamValue::Numbers(
+                    values
+                        .into_iter()
+                        .filter_map(|v| match v {
+                            TokenKind::Number(n) => Some(n),
+                            _ => None,
+                        })
+                        .collect(),
+                )
+            } else if values.iter().all(|v| matches!(v, TokenKind::Str(_))) {
+                ParamValue::Strings(
+                    values
+                        .into_iter()
+                        .filter_map(|v| match v {
+                            TokenKind::Str(s) => Some(s),
+                            _ => None,
+                        })
+                        .collect(),
+                )
+            } else {
+                return syntax(
+                    &location,
+                    &format!("mixed value types for parameter \"{decl}\""),
+                );
+            };
+            params.add(Param::new(ty, name, value));
+        }
+        Ok(params)
+    }
+
+    fn run(&mut self) -> Result<(), PbrtError> {
+        while let Some((token, location)) = self.next() {
+            let TokenKind::Ident(directive) = token else {
+                return Err(PbrtError::Syntax {
+                    location,
+                    message: "expected a directive".to_owned(),
+                });
+            };
+            self.directive(&directive, &location)?;
+        }
+        Ok(())
+    }
+
+    fn directive(&mut self, directive: &str, location: &Location) -> Result<(), PbrtError> {
+        match directive {
+            "Identity" => self.state.ctm = Mat4::IDENTITY,
+            "Translate" => {
+                let [x, y, z] = self.expect_numbers()?;
+                self.state.ctm *= Mat4::from_translation(vec3(x, y, z));
+            }
+            "Scale" => {
+                let [x, y, z] = self.expect_numbers()?;
+                self.state.ctm *= Mat4::from_scale(vec3(x, y, z));
+            }
+            "Rotate" => {
+                let [angle, x, y, z] = self.expect_numbers()?;
+                let axis = vec3(x, y, z).normalize();
+                self.state.ctm *= Mat4::from_axis_angle(axis, angle.to_radians());
+            }
+            "LookAt" => {
+                let [ex, ey, ez, lx, ly, lz, ux, uy, uz] = self.expect_numbers()?;
+                let eye = vec3(ex, ey, ez);
+                let dir = (vec3(lx, ly, lz) - eye).normalize();
+                let right = vec3(ux, uy, uz).normalize().cross(dir).normalize();
+                let up = dir.cross(right);
+                let world_from_camera = Mat4::from_cols(
+                    right.extend(0.0),
+                    up.extend(0.0),
+                    dir.extend(0.0),
+                    eye.extend(1.0),
+                );
+                self.state.ctm *= world_from_camera.inverse();
+            }
+            "Transform" => {
+                let m: [Float; 16] = self.expect_numbers()?;
+                self.state.ctm = Mat4::from_cols_array(&m);
+            }
+            "ConcatTransform" => {
+                let m: [Float; 16] = self.expect_numbers()?;
+                self.state.ctm *= Mat4::from_cols_array(&m);
+            }
+            "CoordinateSystem" => {
+                let name = self.expect_string()?;
+                self.coordinate_systems.insert(name, self.state.ctm);
+            }
+            "CoordSysTransform" => {
+                let name = self.expect_string()?;
+                match self.coordinate_systems.get(&name) {
+                    Some(ctm) => self.state.ctm = *ctm,
+                    None => self.warn(location, &format!("unknown coordinate system \"{name}\"")),
+                }
+            }
+            "Camera" => {
+                let ty = self.expect_string()?;
+                let params = self.params()?;
+                self.camera_from_world = self.state.ctm;
+                self.coordinate_systems
+                    .insert("camera".to_owned(), self.state.ctm.inverse());
+                if ty == "perspective" {
+                    self.fov = params.float("fov", 90.0);
+                    self.lens_radius = params.float("lensradius", 0.0);
+                    self.focal_distance = params.float("focaldistance", 1e6);
+                } else {
+                    self.warn(
+                        location,
+                        &format!("unsupported camera \"{ty}\", using perspective"),
+                    );
+                }
+                self.warn_unused(location, &params);
+            }
+            "Film" => {
+                self.expect_string()?;
+                let params = self.params()?;
+                self.resolution = (
+                    params.int("xresolution", 640).max(1) as u32,
+                    params.int("yresolution", 480).max(1) as u32,
+                );
+                self.warn_unused(location, &params);
+            }
+            "Sampler" => {
+                self.expect_string()?;
+                let params = self.params()?;
+                self.samples_per_pixel = params.int("pixelsamples", 16).max(1) as u32;
+                self.warn_unused(location, &params);
+            }
+            "Integrator" => {
+                self.expect_string()?;
+                let params = self.params()?;
+                if let Some(depth) = params.numbers("maxdepth", &["integer"]) {
+                    self.max_depth = depth.first().map(|&d| d as i32);
+                }
+                self.warn_unused(location, &params);
+            }
+            "PixelFilter" | "Accelerator" | "ColorSpace" | "Option" => {
+                self.expect_string()?;
+                self.params()?;
+            }
+            "WorldBegin" => {
+                self.state.ctm = Mat4::IDENTITY;
+                self.coordinate_systems
+                    .insert("world".to_owned(), Mat4::IDENTITY);
+                self.mirror = self.handedness_mirror();
+            }
+            "WorldEnd" => {}
+            "AttributeBegin" => self.stack.push(Saved::Attributes(self.state.clone())),
+            "AttributeEnd" => match self.stack.pop() {
+                Some(Saved::Attributes(state)) => self.state = state,
+                _ => return mismatched(location, "AttributeEnd", "AttributeBegin"),
+            },
+            "TransformBegin" => self.stack.push(Saved::Transform(self.state.ctm)),
+            "TransformEnd" => match self.stack.pop() {
+                Some(Saved::Transform(ctm)) => self.state.ctm = ctm,
+                _ => return mismatched(location, "TransformEnd", "TransformBegin"),
+            },
+            "Material" => {
+                let ty = self.expect_string()?;
+                let params = self.params()?;
+                self.state.material = self.material(&ty, &params, location);
+                self.warn_unused(location, &params);
+            }
+            "MakeNamedMaterial" => {
+                let name = self.expect_string()?;
+                let params = self.params()?;
+                let ty = params.string("type").unwrap_or("matte").to_owned();
+                let material = self.material(&ty, &params, location);
+                self.named_materials.insert(name, material);
+                self.warn_unused(location, &params);
+            }
+            "NamedMaterial" => {
+                let name = self.expect_string()?;
+                match self.named_materials.get(&name) {
+                    Some(material) => self.state.material = material.clone(),
+                    None => {
+                        return Err(PbrtError::Syntax {
+                            location: location.clone(),
+                            message: format!("unknown named material \"{name}\""),
+                        })
+                    }
+                }
+            }
+            "AreaLightSource" => {
+                let ty = self.expect_string()?;
+                let params = self.params()?;
+                if ty == "diffuse" {
+                    let emit = params.rgb("L").unwrap_or(color::WHITE) * params.float("scale", 1.0);
+                    self.state.area_light = Some(Rc::new(DiffuseLight::from_color(emit)));
+                } else {
+                    self.warn(location, &format!("unsupported area light \"{ty}\""));
+                }
+                self.warn_unused(location, &params);
+            }
+            "LightSource" => {
+                let ty = self.expect_string()?;
+                let params = self.params()?;
+                if ty == "infinite" {
+                    self.background =
+                        params.rgb("L").unwrap_or(color::WHITE) * params.float("scale", 1.0);
+                } else {
+                    self.warn(location, &format!("unsupported light source \"{ty}\""));
+                }
+                self.warn_unused(location, &params);
+            }
+            "Shape" => {
+                let ty = self.expect_string()?;
+                let params = self.params()?;
+                self.shape(&ty, &params, location)?;
+                self.warn_unused(location, &params);
+            }
+            "Include" => {
+                let path = self.expect_string()?;
+                let path = self.resolve(&path);
+                self.include(&path)?;
+            }
+            _ => {
+                self.warn(location, &format!("unsupported directive \"{directive}\""));
+                // Skip the directive's arguments.
+                while matches!(
+                    self.peek(),
+                    Some(TokenKind::Str(_) | TokenKind::Number(_) | TokenKind::LBracket)
+                ) {
+                    if self.peek() == Some(&TokenKind::LBracket) {
+                        while !matches!(self.next(), Some((TokenKind::RBracket, _)) | None) {}
+                    } else {
+                        self.next();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the reflection needed to make pbrt's camera space match [`Camera`]'s.
+    fn handedness_mirror(&self) -> Mat4 {
+        let world_from_camera = self.camera_from_world.inverse();
+        let linear = Mat3::from_mat4(world_from_camera);
+        if linear.determinant() < 0.0 {
+            // The scene already flips handedness itself, e.g. with `Scale -1 1 1`.
+            return Mat4::IDENTITY;
+        }
+        let origin = world_from_camera.transform_point3(Vec3::ZERO);
+        let right = world_from_camera.transform_vector3(Vec3::X).normalize();
+        let reflect = Mat3::IDENTITY - 2.0 * outer(right, right);
+        let mut mirror = Mat4::from_mat3(reflect);
+        mirror.w_axis = (2.0 * origin.dot(right) * right).extend(1.0);
+        mirror
+    }
+
+    fn shape(&mut self, ty: &str, params: &ParamSet, location: &Location) -> Result<(), PbrtError> {
+        let world_from_object = self.mirror * self.state.ctm;
+        let material = self
+            .state
+            .area_light
+            .clone()
+            .unwrap_or_else(|| self.state.material.clone());
+
+        let affine = Affine3::from_mat4(world_from_object);
+        match ty {
+            "sphere" => {
+                let radius = params.float("radius", 1.0);
+                if let Some(scale) = similarity_scale(affine) {
+                    // Rotations and uniform scales keep a sphere a sphere.
+                    let center = float::affine_parts(affine).1;
+                    let sphere = Rc::new(Sphere::new(center, radius * scale, material));
+                    if self.state.area_light.is_some() {
+                        self.lights.add(sphere.clone());
+                    }
+                    self.world.add(sphere);
+                } else {
+                    let sphere = Rc::new(Sphere::new(Vec3::ZERO, radius, material));
+                    self.world.add(Rc::new(Transform::new(sphere, affine)));
+                }
+            }
+            "disk" => {
+                let height = params.float("height", 0.0);
+                let radius = params.float("radius", 1.0);
+                let inner_radius = params.float("innerradius", 0.0);
+                if let Some(scale) = similarity_scale(affine) {
+                    let disk = Rc::new(
+                        Disk::new(
+                            affine.transform_point3(vec3(0.0, 0.0, height)),
+                            affine.transform_vector3(Vec3::Z),
+                            radius * scale,
+                            material,
+                        )
+                        .with_inner_radius(inner_radius * scale),
+                    );
+                    if self.state.area_light.is_some() {
+                        self.lights.add(disk.clone());
+                    }
+                    self.world.add(disk);
+                } else {
+                    // Not sampled as a light, since only the untransformed disk knows its area.
+                    let disk = Disk::new(vec3(0.0, 0.0, height), Vec3::Z, radius, material)
+                        .with_inner_radius(inner_radius);
+                    self.world
+                        .add(Rc::new(Transform::new(Rc::new(disk), affine)));
+                }
+            }
+            "trianglemesh" | "plymesh" => {
+                let (positions, indices, normals, uvs) = if ty == "plymesh" {
+                    let Some(filename) = params.string("filename") else {
+                        return syntax(location, "plymesh requires a \"string filename\"");
+                    };
+                    let path = self.resolve(filename);
+                    let mesh = ply::read(&path).map_err(|err| PbrtError::Syntax {
+                        location: location.clone(),
+                        message: format!("{}: {err}", path.display()),
+                    })?;
+                    (mesh.positions, mesh.indices, mesh.normals, mesh.uvs)
+                } else {
+                    let Some(positions) = params.vec3s("P", &["point", "point3"]) else {
+                        return syntax(location, "trianglemesh requires a \"point P\" parameter");
+                    };
+                    let indices = match params.ints("indices") {
+                        Some(indices) => indices,
+                        None if positions.len() == 3 => vec![0, 1, 2],
+                        None => {
+                            return syntax(
+                                location,
+                                "trianglemesh requires an \"integer indices\" parameter",
+                            )
+                        }
+                    };
+                    if indices.len() % 3 != 0 {
+                        return syntax(location, "number of indices is not a multiple of 3");
+                    }
+                    if let Some(bad) = indices.iter().find(|&&i| i >= positions.len()) {
+                        return syntax(location, &format!("index {bad} is out of range"));
+                    }
+                    let indices = indices
+                        .chunks_exact(3)
+                        .map(|c| [c[0], c[1], c[2]])
+                        .collect();
+                    let normals = params.vec3s("N", &["normal", "normal3"]);
+                    let uvs = params
+                        .vec2s("uv", &["float", "point2"])
+                        .or_else(|| params.vec2s("st", &["float", "point2"]));
+                    (positions, indices, normals, uvs)
+                };
+
+                let normal_from_object = Mat3::from_mat4(world_from_object).inverse().transpose();
+                let positions = positions
+                    .into_iter()
+                    .map(|p| world_from_object.transform_point3(p))
+                    .collect();
+                let mut mesh = TriangleMesh::new(positions, indices, material);
+                if let Some(normals) = normals.filter(|n| n.len() == mesh.positions.len()) {
+                    mesh = mesh.with_normals(
+                        normals
+                            .into_iter()
+                            .map(|n| (normal_from_object * n).normalize())
+                            .collect(),
+                    );
+                }
+                if let Some(uvs) = uvs.filter(|uv| uv.len() == mesh.positions.len()) {
+                    mesh = mesh.with_uvs(uvs);
+                }
+                let mesh = Rc::new(mesh);
+                for triangle in mesh.triangles() {
+                    self.world.add(Rc::new(triangle));
+                }
+            }
+            _ => self.warn(location, &format!("unsupported shape \"{ty}\"")),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> (Scene, Vec<Warning>) {
+        let world_from_camera = self.mirror * self.camera_from_world.inverse();
+        let look_from = world_from_camera.transform_point3(Vec3::ZERO);
+        let look_at = world_from_camera.transform_point3(Vec3::Z);
+        let vup = world_from_camera.transform_vector3(Vec3::Y);
+
+        let (width, height) = self.resolution;
+        let aspect_ratio = width as Float / height as Float;
+        // pbrt's fov spans the shorter image axis, ours always spans the vertical one.
+        let vfov = if aspect_ratio < 1.0 {
+            2.0 * ((self.fov.to_radians() / 2.0).tan() / aspect_ratio)
+                .atan()
+                .to_degrees()
+        } else {
+            self.fov
+        };
+
+        // Ray directions are scaled by the focus distance, so keep pbrt's huge default out of
+        // pinhole cameras to keep ray parameters in a sane range.
+        let focus_dist = if self.lens_radius > 0.0 {
+            self.focal_distance
+        } else {
+            1.0
+        };
+        let cam = Camera::new(
+            look_from,
+            look_at,
+            vup,
+            vfov,
+            aspect_ratio,
+            2.0 * self.lens_radius,
+            focus_dist,
+            TIME0,
+            TIME1,
+        );
+
+        let mut scene = Scene::new(self.world, cam)
+            .with_lights(self.lights)
+            .with_background_color(self.background)
+            .with_image_width(width, aspect_ratio)
+            .with_samples_per_pixel(self.samples_per_pixel);
+        if let Some(max_depth) = self.max_depth {
+            scene = scene.with_max_depth(max_depth);
+        }
+        (scene, self.warnings)
+    }
+
+    fn material(&mut self, ty: &str, params: &ParamSet, location: &Location) -> Rc<dyn Material> {
+        match ty {
+            "matte" => Rc::new(Lambertian::from_color(
+                params.rgb("Kd").unwrap_or(Vec3::splat(0.5)),
+            )),
+            "metal" => {
+                // Reflectance at normal incidence from the complex index of refraction, defaulting
+                // to copper like pbrt does.
+                let eta = params.rgb("eta").unwrap_or(vec3(0.2004, 0.9240, 1.1022));
+                let k = params.rgb("k").unwrap_or(vec3(3.9129, 2.4528, 2.1421));
+                let albedo = ((eta - 1.0).powf(2.0) + k * k) / ((eta + 1.0).powf(2.0) + k * k);
+                let roughness = params
+                    .numbers("uroughness", &["float"])
+                    .or_else(|| params.numbers("roughness", &["float"]))
+                    .and_then(|r| r.first().copied())
+                    .unwrap_or(0.01);
+                // Mark the other roughness parameters as understood.
+                params.numbers("vroughness", &["float"]);
+                params.numbers("remaproughness", &["bool"]);
+                Rc::new(Metal::new(albedo, roughness))
+            }
+            "glass" => {
+                let eta = params
+                    .numbers("eta", &["float"])
+                    .or_else(|| params.numbers("index", &["float"]))
+                    .and_then(|eta| eta.first().copied())
+                    .unwrap_or(1.5);
+                Rc::new(Dielectric::new(eta))
+            }
+            _ => {
+                self.warn(
+                    location,
+                    &format!("unsupported material \"{ty}\", using matte"),
+                );
+                Rc::new(Lambertian::from_color(Vec3::splat(0.5)))
+            }
+        }
+    }
+
+    fn warn(&mut self, location: &Location, message: &str) {
+        self.warnings.push(Warning {
+            location: location.clone(),
+            message: message.to_owned(),
+        });
+    }
+
+    fn warn_unused(&mut self, location: &Location, params: &ParamSet) {
+        for param in params.unused() {
+            self.warn(
+                location,
+                &format!(
+                    "ignoring unsupported parameter \"{} {}\"",
+                    param.ty, param.name
+                ),
+            );
+        }
+    }
+}
+
+fn outer(a: Vec3, b: Vec3) -> Mat3 {
+    Mat3::from_cols(a * b.x, a * b.y, a * b.z)
+}
+
+fn syntax<T>(location: &Location, message: &str) -> Result<T, PbrtError> {
+    Err(PbrtError::Syntax {
+        location: location.clone(),
+        message: message.to_owned(),
+    })
+}
+
+fn mismatched<T>(location: &Location, end: &str, begin: &str) -> Result<T, PbrtError> {
+    syntax(location, &format!("{end} without a matching {begin}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::Hittable, ray::Ray};
+
+    /// Writes `files` to a directory of their own and imports the first one. Locations in the
+    /// warnings or error are made relative to that directory.
+    fn import(name: &str, files: &[(&str, &str)]) -> Result<(Scene, Vec<String>), String> {
+        let dir =
+            std::env::temp_dir().join(format!("raytracing-pbrt-{}-{name}", std::process::id()));
+        for (file, src) in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, src).unwrap();
+        }
+        let relative = |location: &Location, message: &str| {
+            let file = location.file.strip_prefix(&dir).unwrap_or(&location.file);
+            format!(
+                "{}:{}:{}: {message}",
+                file.display(),
+                location.line,
+                location.column
+            )
+        };
+        let result = match load(dir.join(files[0].0)) {
+            Ok((scene, warnings)) => Ok((
+                scene,
+                warnings
+                    .iter()
+                    .map(|w| relative(&w.location, &w.message))
+                    .collect(),
+            )),
+            Err(PbrtError::Syntax { location, message }) => Err(relative(&location, &message)),
+            Err(err) => Err(err.to_string()),
+        };
+        fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    /// Where a ray from `(x, y, -5)` along +z first hits `scene`.
+    fn hit_from(scene: &Scene, x: Float, y: Float) -> Option<Float> {
+        let r = Ray::new(vec3(x, y, -5.0), Vec3::Z, 0.0);
+        Some(scene.world.hit(r, 0.0, Float::INFINITY)?.t)
+    }
+
+    /// A camera that flips handedness itself keeps the importer from mirroring the scene, so
+    /// that shapes end up where the file puts them.
+    const WORLD: &str = "Scale -1 1 1\nCamera \"perspective\"\nWorldBegin\n";
+
+    #[test]
+    fn includes_are_read_relative_to_the_including_file() {
+        let main = format!(
+            "{WORLD}Include \"parts/lights.pbrt\"\nShape \"sphere\"\nFancyDirective 1 [ 2 ]\n"
+        );
+        let (scene, warnings) = import(
+            "include",
+            &[
+                ("main.pbrt", &main),
+                (
+                    "parts/lights.pbrt",
+                    "Translate 3 0 0\nInclude \"light.pbrt\"\n",
+                ),
+                (
+                    "parts/light.pbrt",
+                    "AreaLightSource \"diffuse\" \"float power\" 2\n\
+                     Shape \"disk\" \"float radius\" 3\n",
+                ),
+            ],
+        )
+        .unwrap();
+
+        // Included files share the graphics state with the file including them, so the sphere
+        // is moved and emits light too.
+        assert_eq!(scene.world.objects.len(), 2);
+        assert_eq!(scene.lights.lights.len(), 2);
+        assert_eq!(hit_from(&scene, 3.0, 0.0), Some(4.0));
+        assert_eq!(hit_from(&scene, 3.0, 2.0), Some(5.0));
+        assert_eq!(hit_from(&scene, 0.0, 0.0), Some(5.0));
+        assert_eq!(hit_from(&scene, 0.0, -3.5), None);
+        assert_eq!(
+            warnings,
+            [
+                "parts/light.pbrt:1:1: ignoring unsupported parameter \"float power\"",
+                "main.pbrt:6:1: unsupported directive \"FancyDirective\"",
+            ]
+        );
+
+        let missing = import(
+            "missing-include",
+            &[("main.pbrt", "Include \"nowhere.pbrt\"\n")],
+        );
+        assert!(
+            missing
+                .as_ref()
+                .is_err_and(|err| err.contains("nowhere.pbrt: unable to read file")),
+            "{:?}",
+            missing.map(|(_, warnings)| warnings)
+        );
+    }
+
+    #[test]
+    fn attribute_and_transform_blocks_restore_what_they_save() {
+        let src = format!(
+            "{WORLD}\
+            AttributeBegin
+              AreaLightSource \"diffuse\" \"rgb L\" [ 4 4 4 ]
+              Translate 10 0 0
+              TransformBegin
+                Translate 0 10 0
+                Shape \"sphere\"
+              TransformEnd
+              Shape \"sphere\"
+            AttributeEnd
+            Shape \"sphere\" \"float radius\" 2
+            "
+        );
+        let (scene, warnings) = import("nesting", &[("main.pbrt", &src)]).unwrap();
+        assert!(warnings.is_empty(), "{warnings:?}");
+
+        assert_eq!(hit_from(&scene, 10.0, 10.0), Some(4.0));
+        assert_eq!(hit_from(&scene, 10.0, 0.0), Some(4.0));
+        assert_eq!(hit_from(&scene, 0.0, 0.0), Some(3.0));
+        assert_eq!(hit_from(&scene, 5.0, 5.0), None);
+        // The area light ends with the attribute block, but not with the transform block.
+        assert_eq!(scene.lights.lights.len(), 2);
+
+        for (name, src, expected) in [
+            (
+                "attribute-end",
+                "TransformBegin\nAttributeEnd\n",
+                "main.pbrt:2:1: AttributeEnd without a matching AttributeBegin",
+            ),
+            (
+                "transform-end",
+                "AttributeBegin\nTransformBegin\nTransformEnd\nTransformEnd\n",
+                "main.pbrt:4:1: TransformEnd without a matching TransformBegin",
+            ),
+        ] {
+            let result = import(name, &[("main.pbrt", src)]);
+            assert_eq!(result.err().as_deref(), Some(expected), "{name}");
+        }
+    }
+
+    #[test]
+    fn trianglemesh_parameter_lists() {
+        let src = format!(
+            "{WORLD}\
+            Shape \"trianglemesh\" \"point P\" [ -1 -1 0  1 -1 0  1 1 0  -1 1 0 ]
+              \"integer indices\" [ 0 1 2  0 2 3 ]
+              \"normal N\" [ 0 0 -1  0 0 -1  0 0 -1  0 0 -1 ]
+              \"float uv\" [ 0 0  1 0  1 1  0 1 ]
+            # A single triangle needs no indices, and values needn't be bracketed.
+            Shape \"trianglemesh\" \"point P\" [ 4 0 1  6 0 1  4 2 1 ] \"float alpha\" 1
+            "
+        );
+        let (scene, warnings) = import("mesh", &[("main.pbrt", &src)]).unwrap();
+        assert_eq!(
+            warnings,
+            ["main.pbrt:9:13: ignoring unsupported parameter \"float alpha\""]
+        );
+        assert_eq!(scene.world.objects.len(), 3);
+
+        let r = Ray::new(vec3(0.5, -0.5, -5.0), Vec3::Z, 0.0);
+        let rec = scene.world.hit(r, 0.0, Float::INFINITY).unwrap();
+        assert_eq!(rec.t, 5.0);
+        assert!((rec.u - 0.75).abs() < 1e-4 && (rec.v - 0.25).abs() < 1e-4);
+        assert!(rec.normal.abs_diff_eq(-Vec3::Z, 1e-4), "{}", rec.normal);
+        assert_eq!(hit_from(&scene, 4.5, 0.5), Some(6.0));
+        assert_eq!(hit_from(&scene, 5.5, 1.5), None);
+
+        let mesh = |params: &str| format!("Shape \"trianglemesh\" {params}\n");
+        for (name, src, expected) in [
+            (
+                "no-points",
+                mesh("\"integer indices\" [ 0 1 2 ]"),
+                "main.pbrt:1:1: trianglemesh requires a \"point P\" parameter",
+            ),
+            (
+                "no-indices",
+                mesh("\"point P\" [ 0 0 0  1 0 0  1 1 0  0 1 0 ]"),
+                "main.pbrt:1:1: trianglemesh requires an \"integer indices\" parameter",
+            ),
+            (
+                "partial-triangle",
+                mesh("\"point P\" [ 0 0 0  1 0 0  1 1 0 ] \"integer indices\" [ 0 1 2 0 ]"),
+                "main.pbrt:1:1: number of indices is not a multiple of 3",
+            ),
+            (
+                "out-of-range",
+                mesh("\"point P\" [ 0 0 0  1 0 0  1 1 0 ] \"integer indices\" [ 0 1 3 ]"),
+                "main.pbrt:1:1: index 3 is out of range",
+            ),
+            (
+                "mixed-values",
+                mesh("\"point P\" [ 0 0 \"0\" ]"),
+                "main.pbrt:1:22: mixed value types for parameter \"point P\"",
+            ),
+            (
+                "bad-declaration",
+                mesh("\"P\" [ 0 0 0 ]"),
+                "main.pbrt:1:22: expected a \"type name\" parameter, got \"P\"",
+            ),
+            (
+                "unclosed",
+                mesh("\"point P\" [ 0 0 0"),
+                "main.pbrt:1:38: invalid value for parameter \"point P\"",
+            ),
+        ] {
+            let result = import(name, &[("main.pbrt", &src)]);
+            assert_eq!(result.err().as_deref(), Some(expected), "{name}");
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::cell::Cell;
+
+use crate::float::{vec2, vec3, Float, Vec2, Vec3};
+
+#[derive(Debug, Clone)]
+pub enum ParamValue {
+    Numbers(Vec<Float>),
+    Strings(Vec<String>),
+}
+
+/// One `"type name" value` entry of a directive's parameter list.
+#[derive(Debug)]
+pub struct Param {
+    pub ty: String,
+    pub name: String,
+    pub value: ParamValue,
+    used: Cell<bool>,
+}
+
+impl Param {
+    pub fn new(ty: String, name: String, value: ParamValue) -> Self {
+        Self {
+            ty,
+            name,
+            value,
+            used: Cell::new(false),
+        }
+    }
+}
+
+/// The parameter list of a directive. Lookups mark parameters as used, so that anything the
+/// importer doesn't understand can be reported afterwards.
+#[derive(Debug, Default)]
+pub struct ParamSet {
+    params: Vec<Param>,
+}
+
+impl ParamSet {
+    pub fn add(&mut self, param: Param) {
+        self.params.push(param);
+    }
+
+    fn find(&self, name: &str, types: &[&str]) -> Option<&Param> {
+        let param = self
+            .params
+            .iter()
+            .rev()
+            .find(|p| p.name == name && types.contains(&p.ty.as_str()))?;
+        param.used.set(true);
+        Some(param)
+    }
+
+    pub fn numbers(&self, name: &str, types: &[&str]) -> Option<&[Float]> {
+        match &self.find(name, types)?.value {
+            ParamValue::Numbers(numbers) => Some(numbers),
+            ParamValue::Strings(_) => None,
+        }
+    }
+
+    pub fn float(&self, name: &str, default: Float) -> Float {
+        self.numbers(name, &["float"])
+            .and_then(|n| n.first().copied())
+            .unwrap_or(default)
+    }
+
+    pub fn int(&self, name: &str, default: i64) -> i64 {
+        self.numbers(name, &["integer"])
+            .and_then(|n| n.first().map(|&n| n as i64))
+            .unwrap_or(default)
+    }
+
+    pub fn ints(&self, name: &str) -> Option<Vec<usize>> {
+        Some(
+            self.numbers(name, &["integer"])?
+                .iter()
+                .map(|&n| n as usize)
+                .collect(),
+        )
+    }
+
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match &self.find(name, &["string"])?.value {
+            ParamValue::Strings(strings) => strings.first().map(String::as_str),
+            ParamValue::Numbers(_) => None,
+        }
+    }
+
+    pub fn rgb(&self, name: &str) -> Option<Vec3> {
+        match self.numbers(name, &["rgb", "color"])? {
+            [r, g, b, ..] => Some(vec3(*r, *g, *b)),
+            _ => None,
+        }
+    }
+
+    pub fn vec3s(&self, name: &str, types: &[&str]) -> Option<Vec<Vec3>> {
+        Some(
+            self.numbers(name, types)?
+                .chunks_exact(3)
+                .map(|c| vec3(c[0], c[1], c[2]))
+                .collect(),
+        )
+    }
+
+    pub fn vec2s(&self, name: &str, types: &[&str]) -> Option<Vec<Vec2>> {
+        Some(
+            self.numbers(name, types)?
+                .chunks_exact(2)
+                .map(|c| vec2(c[0], c[1]))
+                .collect(),
+        )
+    }
+
+    pub fn unused(&self) -> impl Iterator<Item = &Param> {
+        self.params.iter().filter(|p| !p.used.get())
+    }
+}
@@ -0,0 +1,304 @@
+//! A reader for PLY files in ASCII or binary encoding, for the meshes referenced by
+//! `Shape "plymesh"` and for point clouds.
+
+use std::{fs, path::Path};
+
+use crate::float::{Float, Vec2, Vec3};
+
+/// The vertices of a PLY file and its faces as triangles. A file with no faces is a point cloud,
+/// whose vertices may also have a radius and a color.
+pub struct PlyMesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<Vec2>>,
+    pub radii: Option<Vec<Float>>,
+    /// In `[0, 1]`, scaled down from the full range of integer color properties.
+    pub colors: Option<Vec<Vec3>>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self, String> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(format!("unknown property type '{name}'")),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// What a color stored as this type is divided by to bring it into `[0, 1]`.
+    fn color_scale(self) -> f64 {
+        match self {
+            Scalar::U8 => u8::MAX as f64,
+            Scalar::U16 => u16::MAX as f64,
+            Scalar::U32 => u32::MAX as f64,
+            Scalar::I8 => i8::MAX as f64,
+            Scalar::I16 => i16::MAX as f64,
+            Scalar::I32 => i32::MAX as f64,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+enum Property {
+    Scalar {
+        name: String,
+        ty: Scalar,
+    },
+    List {
+        name: String,
+        count: Scalar,
+        item: Scalar,
+    },
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Reads scalar values from the body of a PLY file.
+struct Body<'a> {
+    format: Format,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Body<'_> {
+    fn read(&mut self, ty: Scalar) -> Result<f64, String> {
+        if self.format == Format::Ascii {
+            while self.data.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+                self.pos += 1;
+            }
+            let start = self.pos;
+            while self
+                .data
+                .get(self.pos)
+                .is_some_and(|b| !b.is_ascii_whitespace())
+            {
+                self.pos += 1;
+            }
+            let word = std::str::from_utf8(&self.data[start..self.pos]).unwrap_or_default();
+            return word
+                .parse()
+                .map_err(|_| format!("invalid number '{word}' in body"));
+        }
+
+        let size = ty.size();
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + size)
+            .ok_or("unexpected end of file")?;
+        self.pos += size;
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(bytes);
+        if self.format == Format::BinaryBigEndian {
+            buf[..size].reverse();
+        }
+        Ok(match ty {
+            Scalar::I8 => buf[0] as i8 as f64,
+            Scalar::U8 => buf[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(buf),
+        })
+    }
+}
+
+pub fn read(path: &Path) -> Result<PlyMesh, String> {
+    let data = fs::read(path).map_err(|err| err.to_string())?;
+
+    let header_end = data
+        .windows(b"end_header".len())
+        .position(|w| w == b"end_header")
+        .ok_or("missing 'end_header'")?;
+    let body_start = data[header_end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |i| header_end + i + 1);
+    let header = String::from_utf8_lossy(&data[..header_end]);
+
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err("not a PLY file".to_owned());
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", f, _] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(format!("unknown format '{f}'")),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format!("invalid element count '{count}'"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or("property outside of an element")?
+                .properties
+                .push(Property::List {
+                    name: name.to_string(),
+                    count: Scalar::parse(count)?,
+                    item: Scalar::parse(item)?,
+                }),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or("property outside of an element")?
+                .properties
+                .push(Property::Scalar {
+                    name: name.to_string(),
+                    ty: Scalar::parse(ty)?,
+                }),
+            _ => {} // comments, obj_info, blank lines
+        }
+    }
+
+    let mut body = Body {
+        format: format.ok_or("missing 'format' line")?,
+        data: &data[body_start..],
+        pos: 0,
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut radii = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+
+    for element in &elements {
+        for _ in 0..element.count {
+            let mut p = Vec3::ZERO;
+            let mut n = Vec3::ZERO;
+            let mut uv = Vec2::ZERO;
+            let mut radius = 0.0;
+            let mut color = Vec3::ZERO;
+            for property in &element.properties {
+                match property {
+                    Property::Scalar { name, ty } => {
+                        let value = body.read(*ty)?;
+                        let x = value as Float;
+                        match name.as_str() {
+                            "x" => p.x = x,
+                            "y" => p.y = x,
+                            "z" => p.z = x,
+                            "nx" => n.x = x,
+                            "ny" => n.y = x,
+                            "nz" => n.z = x,
+                            "u" | "s" | "texture_u" | "texture_s" => uv.x = x,
+                            "v" | "t" | "texture_v" | "texture_t" => uv.y = x,
+                            "radius" => radius = x,
+                            "red" | "diffuse_red" => color.x = (value / ty.color_scale()) as Float,
+                            "green" | "diffuse_green" => {
+                                color.y = (value / ty.color_scale()) as Float
+                            }
+                            "blue" | "diffuse_blue" => {
+                                color.z = (value / ty.color_scale()) as Float
+                            }
+                            _ => {}
+                        }
+                    }
+                    Property::List { name, count, item } => {
+                        let count = body.read(*count)? as usize;
+                        let mut face = Vec::with_capacity(count);
+                        for _ in 0..count {
+                            face.push(body.read(*item)? as usize);
+                        }
+                        if element.name == "face"
+                            && matches!(name.as_str(), "vertex_indices" | "vertex_index")
+                        {
+                            // Fan-triangulate quads and other convex polygons.
+                            for i in 1..face.len().saturating_sub(1) {
+                                indices.push([face[0], face[i], face[i + 1]]);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if element.name == "vertex" {
+                positions.push(p);
+                normals.push(n);
+                uvs.push(uv);
+                radii.push(radius);
+                colors.push(color);
+            }
+        }
+    }
+
+    if let Some(bad) = indices.iter().flatten().find(|&&i| i >= positions.len()) {
+        return Err(format!(
+            "face refers to vertex {bad}, but there are only {} vertices",
+            positions.len()
+        ));
+    }
+
+    let has = |name: &str| {
+        elements.iter().any(|e| {
+            e.name == "vertex"
+                && e.properties
+                    .iter()
+                    .any(|p| matches!(p, Property::Scalar { name: n, .. } if n == name))
+        })
+    };
+    let has_uvs = ["u", "s", "texture_u", "texture_s"].iter().any(|n| has(n));
+    let has_colors = ["red", "diffuse_red"].iter().any(|n| has(n));
+
+    Ok(PlyMesh {
+        positions,
+        normals: has("nx").then_some(normals),
+        uvs: has_uvs.then_some(uvs),
+        radii: has("radius").then_some(radii),
+        colors: has_colors.then_some(colors),
+        indices,
+    })
+}
@@ -0,0 +1,244 @@
+//! Real roots of low-degree polynomials, for intersecting rays with implicit surfaces.
+//!
+//! Coefficients are given from the highest degree down. Cubics and quartics are solved by
+//! isolating each root between the roots of the derivative, where the polynomial is monotonic,
+//! and then narrowing it down with safeguarded Newton steps. Unlike the closed-form solutions
+//! this doesn't lose roots to cancellation, and every root is as accurate as the coefficients.
+//! A double root at a turning point, where the polynomial only touches 0, is found as long as
+//! the polynomial there is within its rounding error of 0, such as for a ray that grazes a
+//! surface exactly.
+
+use crate::float::{gamma, Float};
+
+/// Up to four real roots, in ascending order.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Roots {
+    values: [Float; 4],
+    len: usize,
+}
+
+impl Roots {
+    fn push(&mut self, root: Float) {
+        self.values[self.len] = root;
+        self.len += 1;
+    }
+
+    pub fn as_slice(&self) -> &[Float] {
+        &self.values[..self.len]
+    }
+}
+
+/// The real roots of `a t^2 + b t + c`, smallest first. Computed without the cancellation of the
+/// textbook formula, which matters for rays that start far from a small shape.
+pub fn quadratic(a: Float, b: Float, c: Float) -> Option<(Float, Float)> {
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 || a == 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let q = if b < 0.0 {
+        -0.5 * (b - root)
+    } else {
+        -0.5 * (b + root)
+    };
+    let (t0, t1) = (q / a, c / q);
+    Some(if t0 <= t1 { (t0, t1) } else { (t1, t0) })
+}
+
+/// The real roots of a cubic within `[min, max]`.
+pub fn cubic(coefficients: [Float; 4], min: Float, max: Float) -> Roots {
+    let [a, b, c, _] = coefficients;
+    let turning_points = if a == 0.0 {
+        // Really a quadratic, which turns once at most.
+        Some((-c / (2.0 * b), Float::NAN))
+    } else {
+        quadratic(3.0 * a, 2.0 * b, c)
+    };
+    let mut critical = Roots::default();
+    if let Some((t0, t1)) = turning_points {
+        for t in [t0, t1] {
+            if min < t && t < max && critical.as_slice().last() != Some(&t) {
+                critical.push(t);
+            }
+        }
+    }
+    isolate(&coefficients, critical.as_slice(), min, max)
+}
+
+/// The real roots of a quartic within `[min, max]`. Both bounds must be finite.
+pub fn quartic(coefficients: [Float; 5], min: Float, max: Float) -> Roots {
+    let [a, b, c, d, _] = coefficients;
+    let critical = cubic([4.0 * a, 3.0 * b, 2.0 * c, d], min, max);
+    isolate(&coefficients, critical.as_slice(), min, max)
+}
+
+fn evaluate(coefficients: &[Float], t: Float) -> Float {
+    coefficients.iter().fold(0.0, |acc, &c| acc * t + c)
+}
+
+/// Bounds the rounding error of [`evaluate`] at `t`.
+fn evaluation_error(coefficients: &[Float], t: Float) -> Float {
+    let degree = coefficients.len() as u32 - 1;
+    gamma(2 * degree)
+        * coefficients
+            .iter()
+            .fold(0.0, |acc, &c| acc * t.abs() + c.abs())
+}
+
+/// Finds the roots of `coefficients` in `[min, max]`, given the sorted roots of its derivative
+/// in between, which split the range into stretches holding at most one root each.
+fn isolate(coefficients: &[Float], critical: &[Float], min: Float, max: Float) -> Roots {
+    let mut roots = Roots::default();
+    let mut lo = min;
+    let mut f_lo = evaluate(coefficients, lo);
+    for (hi, turning) in critical.iter().map(|&t| (t, true)).chain([(max, false)]) {
+        let mut f_hi = evaluate(coefficients, hi);
+        // A root where the polynomial turns doesn't change its sign.
+        if turning && f_hi.abs() <= evaluation_error(coefficients, hi) {
+            f_hi = 0.0;
+        }
+        if f_lo == 0.0 {
+            roots.push(lo);
+        } else if f_lo.signum() != f_hi.signum() && f_hi != 0.0 {
+            roots.push(refine(coefficients, lo, hi, f_lo));
+        }
+        (lo, f_lo) = (hi, f_hi);
+    }
+    if f_lo == 0.0 {
+        roots.push(lo);
+    }
+    roots
+}
+
+/// Narrows down the single root in `[lo, hi]`, where the polynomial changes sign, with Newton
+/// steps that fall back to bisection whenever they would leave the bracket.
+fn refine(coefficients: &[Float], mut lo: Float, mut hi: Float, f_lo: Float) -> Float {
+    let degree = coefficients.len() - 1;
+    let derivative = |t: Float| {
+        coefficients[..degree]
+            .iter()
+            .enumerate()
+            .fold(0.0, |acc, (i, &c)| acc * t + (degree - i) as Float * c)
+    };
+    let lo_negative = f_lo < 0.0;
+    let mut t = 0.5 * (lo + hi);
+    // Bisection alone halves the bracket each step, so this many always suffice.
+    for _ in 0..2 * Float::MANTISSA_DIGITS {
+        let f = evaluate(coefficients, t);
+        if f == 0.0 {
+            return t;
+        }
+        if (f < 0.0) == lo_negative {
+            lo = t;
+        } else {
+            hi = t;
+        }
+        let newton = t - f / derivative(t);
+        let next = if lo < newton && newton < hi {
+            newton
+        } else {
+            0.5 * (lo + hi)
+        };
+        if next == t || hi - lo <= Float::EPSILON * t.abs() {
+            return next;
+        }
+        t = next;
+    }
+    t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The coefficients of the monic polynomial with `roots`, highest degree first.
+    fn from_roots<const N: usize>(roots: &[Float]) -> [Float; N] {
+        let mut coefficients = [0.0; N];
+        coefficients[0] = 1.0;
+        for (degree, &root) in roots.iter().enumerate() {
+            for i in (1..=degree + 1).rev() {
+                coefficients[i] -= root * coefficients[i - 1];
+            }
+        }
+        coefficients
+    }
+
+    fn assert_roots(found: Roots, expected: &[Float], tolerance: Float) {
+        let found = found.as_slice();
+        assert_eq!(found.len(), expected.len(), "{found:?} != {expected:?}");
+        for (&found, &expected) in found.iter().zip(expected) {
+            assert!(
+                (found - expected).abs() <= tolerance * expected.abs().max(1.0),
+                "{found} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn quartic_roots() {
+        let tolerance = 1e3 * Float::EPSILON;
+        for roots in [
+            [-3.0, -1.0, 0.5, 2.0],
+            // Of widely different magnitude.
+            [-1e3, 1e-3, 1.0, 1e2],
+        ] {
+            let coefficients = from_roots::<5>(&roots);
+            assert_roots(quartic(coefficients, -1e4, 1e4), &roots, tolerance);
+        }
+
+        // Rounding the coefficients moves three clustered roots by about epsilon over the square
+        // of their spacing, and merges them once the polynomial between them is within rounding
+        // error of 0, which takes them being about the cube root of epsilon apart.
+        let gap = Float::EPSILON.powf(1.0 / 6.0);
+        let roots = [1.0 - gap, 1.0, 1.0 + gap, 3.0];
+        let coefficients = from_roots::<5>(&roots);
+        let clustered_tolerance = 100.0 * Float::EPSILON.powf(2.0 / 3.0);
+        assert_roots(
+            quartic(coefficients, -10.0, 10.0),
+            &roots,
+            clustered_tolerance,
+        );
+
+        // Only the roots in range, including one right at its start.
+        let coefficients = from_roots::<5>(&[-3.0, -1.0, 0.5, 2.0]);
+        assert_roots(quartic(coefficients, -1.0, 1.0), &[-1.0, 0.5], tolerance);
+
+        // A double root is found once, including where the polynomial only touches 0.
+        // The double root is a tenth, which rounding moves off the turning point.
+        let double_root_tolerance = 10.0 * Float::EPSILON.sqrt();
+        let coefficients = from_roots::<5>(&[-2.0, 0.1, 0.1, 3.0]);
+        let roots = quartic(coefficients, -10.0, 10.0);
+        assert_roots(roots, &[-2.0, 0.1, 3.0], double_root_tolerance);
+        let touching = [1.0, -0.2, 1.01, -0.2, 0.01]; // (t - 0.1)^2 (t^2 + 1)
+        assert_roots(
+            quartic(touching, -10.0, 10.0),
+            &[0.1],
+            double_root_tolerance,
+        );
+        let missing = [1.0, -0.2, 1.01, -0.2, 0.01 + 1e-3];
+        assert_roots(quartic(missing, -10.0, 10.0), &[], tolerance);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        let tolerance = 1e3 * Float::EPSILON;
+        let coefficients = from_roots::<4>(&[-2.0, 0.5, 4.0]);
+        assert_roots(
+            cubic(coefficients, -10.0, 10.0),
+            &[-2.0, 0.5, 4.0],
+            tolerance,
+        );
+        let coefficients = from_roots::<4>(&[-1.0, 2.0, 2.0]);
+        assert_roots(cubic(coefficients, -10.0, 10.0), &[-1.0, 2.0], tolerance);
+
+        // With a leading coefficient of 0 or close to it, the cubic is a quadratic within the
+        // range, with its third root far outside.
+        let quadratic = [1.0, -3.0, 2.0];
+        for a in [0.0, 1e-9, -1e-9] {
+            let coefficients = [a, quadratic[0], quadratic[1], quadratic[2]];
+            assert_roots(cubic(coefficients, -10.0, 10.0), &[1.0, 2.0], 1e-7);
+        }
+        assert_roots(cubic([0.0, 1.0, -2.0, 1.0], -10.0, 10.0), &[1.0], tolerance);
+        assert_roots(cubic([0.0, 0.0, 2.0, -1.0], -10.0, 10.0), &[0.5], tolerance);
+    }
+}
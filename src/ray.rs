@@ -1,20 +1,56 @@
+use crate::float::{Float, Vec3};
+
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub struct Ray {
-    pub origin: glam::DVec3,
-    pub direction: glam::DVec3,
-    pub time: f64,
+    pub origin: Vec3,
+    pub direction: Vec3,
+    pub time: Float,
+    /// Componentwise `1 / direction`, infinite along axes the ray is parallel to.
+    pub inv_direction: Vec3,
+    /// 1 for axes along which the ray points in the negative direction, 0 otherwise.
+    pub sign: [usize; 3],
 }
 
 impl Ray {
-    pub fn new(origin: glam::DVec3, direction: glam::DVec3, time: f64) -> Self {
+    pub fn new(origin: Vec3, direction: Vec3, time: Float) -> Self {
+        let inv_direction = Vec3::ONE / direction;
         Self {
             origin,
             direction,
             time,
+            inv_direction,
+            // Checking the inverse keeps the sign of zero components, so -0.0 counts as negative.
+            sign: [
+                (inv_direction.x < 0.0) as usize,
+                (inv_direction.y < 0.0) as usize,
+                (inv_direction.z < 0.0) as usize,
+            ],
         }
     }
 
-    pub fn at(&self, t: f64) -> glam::DVec3 {
+    pub fn at(&self, t: Float) -> Vec3 {
         self.origin + self.direction * t
     }
+
+    /// How far along the ray, in units of `t`, it gets out of `origin_error`, the box that
+    /// rounding in a change of coordinates left its origin somewhere in. A ray spawned just
+    /// outside a surface may have been moved back through it, so closer hits can't be trusted.
+    pub fn error_clearance(&self, origin_error: Vec3) -> Float {
+        let length_squared = self.direction.length_squared();
+        if length_squared > 0.0 {
+            self.direction.abs().dot(origin_error) / length_squared
+        } else {
+            0.0
+        }
+    }
+
+    /// `t_min` raised past [`Ray::error_clearance`], unless it reaches behind the origin on
+    /// purpose, as when following the whole line through a closed shape to tell its inside.
+    pub fn clear_t_min(&self, t_min: Float, origin_error: Vec3) -> Float {
+        if t_min < 0.0 {
+            t_min
+        } else {
+            t_min.max(self.error_clearance(origin_error))
+        }
+    }
 }
@@ -0,0 +1,510 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::{
+    float::{Float, Vec3},
+    ASPECT_RATIO, MAX_DEPTH, TIME0, TIME1,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDesc {
+    pub camera: CameraDesc,
+    #[serde(default)]
+    pub render: RenderDesc,
+    #[serde(default)]
+    pub textures: HashMap<String, TextureDesc>,
+    #[serde(default)]
+    pub materials: HashMap<String, MaterialDesc>,
+    /// Objects that are built once and placed any number of times by `instance` objects.
+    #[serde(default)]
+    pub prototypes: HashMap<String, ObjectDesc>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDesc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDesc {
+    pub look_from: Vec3,
+    pub look_at: Vec3,
+    #[serde(default = "default_vup")]
+    pub vup: Vec3,
+    pub vfov: Float,
+    #[serde(default)]
+    pub aperture: Float,
+    #[serde(default = "default_focus_dist")]
+    pub focus_dist: Float,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderDesc {
+    pub image_width: u32,
+    pub aspect_ratio: Float,
+    pub samples_per_pixel: u32,
+    pub max_depth: i32,
+    pub background: Vec3,
+    pub time0: Float,
+    pub time1: Float,
+}
+
+impl Default for RenderDesc {
+    fn default() -> Self {
+        Self {
+            image_width: 400,
+            aspect_ratio: ASPECT_RATIO,
+            samples_per_pixel: 100,
+            max_depth: MAX_DEPTH,
+            background: crate::color::DEEP_SKY_BLUE,
+            time0: TIME0,
+            time1: TIME1,
+        }
+    }
+}
+
+/// Either an inline RGB color or the name of a texture from the `[textures]` table.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ColorSource {
+    Color(Vec3),
+    Texture(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TextureDesc {
+    Solid {
+        color: Vec3,
+    },
+    Checker {
+        odd: ColorSource,
+        even: ColorSource,
+    },
+    Noise {
+        #[serde(default = "default_scale")]
+        scale: Float,
+    },
+    Image {
+        path: String,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDesc {
+    Lambertian {
+        albedo: ColorSource,
+    },
+    Metal {
+        albedo: Vec3,
+        fuzz: Float,
+    },
+    Dielectric {
+        ir: Float,
+    },
+    DiffuseLight {
+        emit: ColorSource,
+    },
+    Isotropic {
+        albedo: ColorSource,
+    },
+    /// A hair fiber, colored by at most one of `color`, `sigma_a` or the melanin
+    /// concentrations. Without any it is brown.
+    Hair {
+        color: Option<Vec3>,
+        sigma_a: Option<Vec3>,
+        eumelanin: Option<Float>,
+        pheomelanin: Option<Float>,
+        #[serde(default = "default_hair_roughness")]
+        longitudinal_roughness: Float,
+        #[serde(default = "default_hair_roughness")]
+        azimuthal_roughness: Float,
+        #[serde(default = "default_scale_angle")]
+        scale_angle: Float,
+        #[serde(default = "default_hair_ior")]
+        ior: Float,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObjectDesc {
+    Sphere {
+        center: Vec3,
+        radius: Float,
+        material: String,
+    },
+    MovingSphere {
+        center0: Vec3,
+        center1: Vec3,
+        #[serde(default)]
+        time0: Float,
+        #[serde(default = "default_time1")]
+        time1: Float,
+        radius: Float,
+        material: String,
+    },
+    /// Spheres around the points of an XYZ, CSV or PLY file, with `radius` for those that
+    /// don't have their own.
+    SphereCloud {
+        path: String,
+        radius: Float,
+        material: String,
+    },
+    XyRect {
+        x0: Float,
+        x1: Float,
+        y0: Float,
+        y1: Float,
+        k: Float,
+        material: String,
+    },
+    XzRect {
+        x0: Float,
+        x1: Float,
+        z0: Float,
+        z1: Float,
+        k: Float,
+        material: String,
+    },
+    YzRect {
+        y0: Float,
+        y1: Float,
+        z0: Float,
+        z1: Float,
+        k: Float,
+        material: String,
+    },
+    Quad {
+        q: Vec3,
+        u: Vec3,
+        v: Vec3,
+        material: String,
+    },
+    Disk {
+        center: Vec3,
+        normal: Vec3,
+        radius: Float,
+        #[serde(default)]
+        inner_radius: Float,
+        material: String,
+    },
+    Plane {
+        point: Vec3,
+        normal: Vec3,
+        material: String,
+    },
+    Cylinder {
+        start: Vec3,
+        end: Vec3,
+        radius: Float,
+        #[serde(default = "default_true")]
+        caps: bool,
+        #[serde(default = "default_sweep")]
+        sweep: Float,
+        material: String,
+    },
+    Cone {
+        start: Vec3,
+        end: Vec3,
+        start_radius: Float,
+        #[serde(default)]
+        end_radius: Float,
+        #[serde(default = "default_true")]
+        caps: bool,
+        #[serde(default = "default_sweep")]
+        sweep: Float,
+        material: String,
+    },
+    Capsule {
+        start: Vec3,
+        end: Vec3,
+        radius: Float,
+        #[serde(default = "default_sweep")]
+        sweep: Float,
+        material: String,
+    },
+    Torus {
+        center: Vec3,
+        normal: Vec3,
+        major_radius: Float,
+        minor_radius: Float,
+        material: String,
+    },
+    Box {
+        min: Vec3,
+        max: Vec3,
+        material: String,
+    },
+    Heightfield {
+        heights: HeightsDesc,
+        min: Vec3,
+        max: Vec3,
+        material: String,
+    },
+    Sdf {
+        shape: SdfDesc,
+        min: Vec3,
+        max: Vec3,
+        #[serde(default = "default_max_steps")]
+        max_steps: u32,
+        #[serde(default = "default_epsilon")]
+        epsilon: Float,
+        material: String,
+    },
+    Curve {
+        points: [Vec3; 4],
+        widths: [Float; 2],
+        #[serde(default)]
+        kind: CurveKindDesc,
+        /// The normals at both ends of a ribbon.
+        normals: Option<[Vec3; 2]>,
+        material: String,
+    },
+    Hair {
+        path: String,
+        #[serde(default)]
+        kind: CurveKindDesc,
+        material: String,
+    },
+    Fur {
+        center: Vec3,
+        radius: Float,
+        count: usize,
+        length: Float,
+        width: Float,
+        #[serde(default)]
+        droop: Float,
+        #[serde(default)]
+        seed: u64,
+        #[serde(default)]
+        kind: CurveKindDesc,
+        material: String,
+    },
+    Group {
+        #[serde(default)]
+        bvh: bool,
+        objects: Vec<ObjectDesc>,
+    },
+    Translate {
+        offset: Vec3,
+        object: Box<ObjectDesc>,
+    },
+    RotateY {
+        angle: Float,
+        object: Box<ObjectDesc>,
+    },
+    /// A copy of a prototype, scaled, then rotated about x, y and z in that order (in degrees)
+    /// and then moved by `offset`. It can use one `material` for everything, or swap the
+    /// prototype's materials for others by name in `materials`.
+    Instance {
+        prototype: String,
+        #[serde(default)]
+        offset: Vec3,
+        #[serde(default)]
+        rotate: Vec3,
+        #[serde(default = "default_instance_scale")]
+        scale: Vec3,
+        material: Option<String>,
+        #[serde(default)]
+        materials: HashMap<String, String>,
+    },
+    Union {
+        left: Box<ObjectDesc>,
+        right: Box<ObjectDesc>,
+    },
+    Intersection {
+        left: Box<ObjectDesc>,
+        right: Box<ObjectDesc>,
+    },
+    Difference {
+        left: Box<ObjectDesc>,
+        right: Box<ObjectDesc>,
+    },
+    ConstantMedium {
+        density: Float,
+        albedo: ColorSource,
+        /// How the medium scatters, isotropically if not given.
+        phase: Option<PhaseDesc>,
+        boundary: Box<ObjectDesc>,
+    },
+    HeterogeneousMedium {
+        density: DensityDesc,
+        albedo: ColorSource,
+        phase: Option<PhaseDesc>,
+        boundary: Box<ObjectDesc>,
+    },
+    /// Only allowed directly in `objects`, since rays go through it rather than hit it.
+    HomogeneousMedium {
+        sigma_a: Vec3,
+        sigma_s: Vec3,
+        #[serde(default)]
+        emission: Vec3,
+        phase: Option<PhaseDesc>,
+        boundary: Box<ObjectDesc>,
+    },
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CurveKindDesc {
+    Flat,
+    #[default]
+    Tube,
+    Ribbon,
+}
+
+/// Where the heights of a heightfield come from.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum HeightsDesc {
+    /// The brightness of an image.
+    Image { path: String },
+    /// Perlin turbulence, sampled on a grid of `resolution` points along x and z.
+    Noise {
+        #[serde(default = "default_resolution")]
+        resolution: [usize; 2],
+        #[serde(default = "default_scale")]
+        scale: Float,
+    },
+}
+
+/// The phase function of a medium.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PhaseDesc {
+    Isotropic,
+    /// Forward scattering for positive `g`, backward for negative.
+    HenyeyGreenstein {
+        g: Float,
+    },
+    /// A `forward` and a `backward` Henyey-Greenstein lobe, with `weight` the share of the
+    /// forward one.
+    DoubleHenyeyGreenstein {
+        forward: Float,
+        backward: Float,
+        weight: Float,
+    },
+}
+
+/// Where the density of a heterogeneous medium comes from.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DensityDesc {
+    /// Voxels read from a raw or text file, scaled by `density`, filling the box from `min` to
+    /// `max`.
+    Grid {
+        path: String,
+        resolution: [usize; 3],
+        #[serde(default = "default_scale")]
+        density: Float,
+        min: Vec3,
+        max: Vec3,
+    },
+    /// Perlin turbulence with `scale` noise cells per unit, up to `density`.
+    Noise {
+        density: Float,
+        #[serde(default = "default_scale")]
+        scale: Float,
+    },
+}
+
+/// A signed distance function, built up from shapes centered on the origin.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SdfDesc {
+    Sphere {
+        radius: Float,
+    },
+    Box {
+        half_extents: Vec3,
+    },
+    RoundedBox {
+        half_extents: Vec3,
+        radius: Float,
+    },
+    Torus {
+        major_radius: Float,
+        minor_radius: Float,
+    },
+    Capsule {
+        start: Vec3,
+        end: Vec3,
+        radius: Float,
+    },
+    SmoothUnion {
+        left: Box<SdfDesc>,
+        right: Box<SdfDesc>,
+        k: Float,
+    },
+    Subtraction {
+        left: Box<SdfDesc>,
+        right: Box<SdfDesc>,
+    },
+    Repeat {
+        period: Vec3,
+        shape: Box<SdfDesc>,
+    },
+    Twist {
+        degrees_per_unit: Float,
+        shape: Box<SdfDesc>,
+    },
+    Translate {
+        offset: Vec3,
+        shape: Box<SdfDesc>,
+    },
+}
+
+fn default_vup() -> Vec3 {
+    Vec3::Y
+}
+
+fn default_focus_dist() -> Float {
+    10.0
+}
+
+fn default_scale() -> Float {
+    1.0
+}
+
+fn default_time1() -> Float {
+    1.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_sweep() -> Float {
+    360.0
+}
+
+fn default_resolution() -> [usize; 2] {
+    [256, 256]
+}
+
+fn default_hair_roughness() -> Float {
+    0.3
+}
+
+fn default_scale_angle() -> Float {
+    2.0
+}
+
+fn default_hair_ior() -> Float {
+    1.55
+}
+
+fn default_instance_scale() -> Vec3 {
+    Vec3::ONE
+}
+
+fn default_max_steps() -> u32 {
+    256
+}
+
+fn default_epsilon() -> Float {
+    1e-4
+}
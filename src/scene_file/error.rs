@@ -0,0 +1,289 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+/// Everything that can go wrong while turning a scene file into a [`Scene`](crate::test_scenes::Scene).
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        location: Location,
+        message: String,
+    },
+    /// The file parsed, but the validation pass found problems with its contents.
+    Invalid(Vec<Diagnostic>),
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => {
+                write!(f, "{}: unable to read scene file: {source}", path.display())
+            }
+            SceneError::Parse { location, message } => write!(f, "{location}: {message}"),
+            SceneError::Invalid(diagnostics) => {
+                for (i, diagnostic) in diagnostics.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{diagnostic}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A position in a scene file, 1-based like the ones printed by editors and compilers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub fn from_offset(file: &Path, src: &str, offset: usize) -> Self {
+        let before = &src[..offset.min(src.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            file: file.to_owned(),
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    pub fn from_span(file: &Path, src: &str, span: Option<Range<usize>>) -> Self {
+        Self::from_offset(file, src, span.map_or(0, |span| span.start))
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+/// A single problem found by the validation pass.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub location: Location,
+    pub key: KeyPath,
+    pub problem: Problem,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.location, self.key, self.problem)
+    }
+}
+
+#[derive(Debug)]
+pub enum Problem {
+    UnknownMaterial(String),
+    UnknownPrototype(String),
+    PrototypeCycle(String),
+    UnknownTexture(String),
+    TextureCycle(String),
+    MissingTextureFile(PathBuf),
+    UnreadableTextureFile {
+        path: PathBuf,
+        reason: String,
+    },
+    MissingHeightmapFile(PathBuf),
+    UnreadableHeightmapFile {
+        path: PathBuf,
+        reason: String,
+    },
+    MissingHairFile(PathBuf),
+    UnreadableHairFile {
+        path: PathBuf,
+        reason: String,
+    },
+    MissingPointFile(PathBuf),
+    UnreadablePointFile {
+        path: PathBuf,
+        reason: String,
+    },
+    MissingDensityFile(PathBuf),
+    UnreadableDensityFile {
+        path: PathBuf,
+        reason: String,
+    },
+    MissingNormals,
+    UnexpectedNormals,
+    ZeroRadius,
+    TooFewSamples,
+    NotFinite,
+    NotPositive,
+    Negative,
+    NotFraction,
+    NotUnitInterval,
+    ZeroVector,
+    ZeroScale,
+    InvalidSweep,
+    InvalidAsymmetry,
+    NestedMedium,
+    Degenerate {
+        min: &'static str,
+        max: &'static str,
+    },
+    Coincident {
+        a: &'static str,
+        b: &'static str,
+    },
+    Parallel {
+        a: &'static str,
+        b: &'static str,
+    },
+    Conflicting {
+        a: &'static str,
+        b: &'static str,
+    },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::UnknownMaterial(name) => write!(f, "unknown material '{name}'"),
+            Problem::UnknownPrototype(name) => write!(f, "unknown prototype '{name}'"),
+            Problem::PrototypeCycle(name) => {
+                write!(f, "prototype '{name}' is part of an instance cycle")
+            }
+            Problem::UnknownTexture(name) => write!(f, "unknown texture '{name}'"),
+            Problem::TextureCycle(name) => {
+                write!(f, "texture '{name}' is part of a reference cycle")
+            }
+            Problem::MissingTextureFile(path) => {
+                write!(f, "texture file '{}' does not exist", path.display())
+            }
+            Problem::UnreadableTextureFile { path, reason } => {
+                write!(
+                    f,
+                    "unable to load texture file '{}': {reason}",
+                    path.display()
+                )
+            }
+            Problem::MissingHeightmapFile(path) => {
+                write!(f, "heightmap file '{}' does not exist", path.display())
+            }
+            Problem::UnreadableHeightmapFile { path, reason } => {
+                write!(
+                    f,
+                    "unable to load heightmap file '{}': {reason}",
+                    path.display()
+                )
+            }
+            Problem::MissingHairFile(path) => {
+                write!(f, "hair file '{}' does not exist", path.display())
+            }
+            Problem::UnreadableHairFile { path, reason } => {
+                write!(f, "unable to load hair file '{}': {reason}", path.display())
+            }
+            Problem::MissingPointFile(path) => {
+                write!(f, "point file '{}' does not exist", path.display())
+            }
+            Problem::UnreadablePointFile { path, reason } => {
+                write!(
+                    f,
+                    "unable to load point file '{}': {reason}",
+                    path.display()
+                )
+            }
+            Problem::MissingDensityFile(path) => {
+                write!(f, "density file '{}' does not exist", path.display())
+            }
+            Problem::UnreadableDensityFile { path, reason } => {
+                write!(
+                    f,
+                    "unable to load density file '{}': {reason}",
+                    path.display()
+                )
+            }
+            Problem::MissingNormals => write!(f, "ribbons need `normals` at both ends"),
+            Problem::UnexpectedNormals => write!(f, "only ribbons have `normals`"),
+            Problem::TooFewSamples => write!(f, "need at least 2 samples along each axis"),
+            Problem::ZeroRadius => write!(f, "radius must not be zero"),
+            Problem::NotFinite => write!(f, "value must be a finite number"),
+            Problem::NotPositive => write!(f, "value must be greater than zero"),
+            Problem::Negative => write!(f, "value must not be negative"),
+            Problem::NotFraction => {
+                write!(f, "value must be greater than zero and at most 1")
+            }
+            Problem::NotUnitInterval => write!(f, "value must be between 0 and 1"),
+            Problem::ZeroVector => write!(f, "vector must not be zero"),
+            Problem::ZeroScale => write!(f, "scale must not be zero along any axis"),
+            Problem::InvalidSweep => {
+                write!(f, "sweep must be greater than 0 and at most 360 degrees")
+            }
+            Problem::InvalidAsymmetry => write!(f, "asymmetry must be between -1 and 1"),
+            Problem::NestedMedium => write!(f, "homogeneous media must be top-level objects"),
+            Problem::Degenerate { min, max } => write!(f, "`{min}` must be less than `{max}`"),
+            Problem::Coincident { a, b } => write!(f, "`{a}` and `{b}` must differ"),
+            Problem::Parallel { a, b } => write!(f, "`{a}` and `{b}` must not be parallel"),
+            Problem::Conflicting { a, b } => write!(f, "`{a}` and `{b}` can't both be given"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySegment {
+    Key(String),
+    Index(usize),
+}
+
+/// The chain of keys and array indices leading to a value, e.g. `objects[3].material`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyPath(pub Vec<KeySegment>);
+
+impl KeyPath {
+    /// Finds the span of the value this path points to, falling back to the closest enclosing
+    /// table or array when the value itself is missing from the document.
+    pub fn span_in(&self, doc: &toml_edit::ImDocument<&str>) -> Option<Range<usize>> {
+        let mut item = doc.as_item();
+        let mut span = item.span();
+        for segment in &self.0 {
+            let next = match segment {
+                KeySegment::Key(key) => item.get(key),
+                KeySegment::Index(i) => item.get(*i),
+            };
+            match next {
+                Some(next) => {
+                    item = next;
+                    span = item.span().or(span);
+                }
+                None => break,
+            }
+        }
+        span
+    }
+}
+
+impl Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                KeySegment::Key(key) if i == 0 => write!(f, "{key}")?,
+                KeySegment::Key(key) => write!(f, ".{key}")?,
+                KeySegment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1356 @@
+//! Loading of declarative scene descriptions from TOML files.
+//!
+//! A scene file mirrors the structure of [`Scene`]: a `[camera]` table, optional `[render]`
+//! settings, named `[textures]` and `[materials]`, and a list of `[[objects]]`. Objects refer to
+//! materials (and materials to textures) by name. See the `scenes/` directory for examples.
+
+mod description;
+mod error;
+mod validate;
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    aabb::Aabb,
+    camera::Camera,
+    float::{similarity_scale, vec3, Affine3, Mat3, Vec3},
+    hittable::{
+        load_strands,
+        sdf::{self, Sdf, SdfHittable},
+        sphere::{MovingSphere, PointCloud, Sphere, SphereCloud},
+        Bvh, Capsule, Cone, ConstantMedium, Csg, Curve, CurveKind, Curves, Cylinder, DensityField,
+        Disk, Fur, GeometricBox, HeightMap, Heightfield, HeterogeneousMedium, Hittable,
+        HittableList, HomogeneousMedium, Instance, LightList, MaterialVariants, NoiseDensity,
+        Plane, Quad, RotateY, Strand, Torus, Translate, VoxelGrid, XYRect, XZRect, YZRect,
+    },
+    material::{
+        Anisotropic, Dielectric, DiffuseLight, DoubleHenyeyGreenstein, Hair, HenyeyGreenstein,
+        Isotropic, IsotropicPhase, Lambertian, Material, Metal, PhaseFunction,
+    },
+    noise::Perlin,
+    test_scenes::Scene,
+    texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture},
+};
+
+use description::{
+    ColorSource, CurveKindDesc, DensityDesc, HeightsDesc, MaterialDesc, ObjectDesc, PhaseDesc,
+    SceneDesc, SdfDesc, TextureDesc,
+};
+use error::{KeyPath, KeySegment};
+
+pub use error::{Diagnostic, Location, Problem, SceneError};
+
+/// Reads, validates and builds the scene described by the file at `path`.
+///
+/// All problems are reported before anything expensive happens, so a broken scene file fails
+/// immediately instead of partway through a render.
+pub fn load(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let src = fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    load_source(path, &src)
+}
+
+/// Like [`load`], for `src` read from the file at `path` already. Diagnostics point into `src`,
+/// and files the scene refers to are found relative to `path`.
+fn load_source(path: &Path, src: &str) -> Result<Scene, SceneError> {
+    let desc: SceneDesc = toml::from_str(src).map_err(|err| SceneError::Parse {
+        location: Location::from_span(path, src, err.span()),
+        message: err.message().to_owned(),
+    })?;
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    let mut problems = validate::validate(&desc, &base_dir);
+    let scene = if problems.is_empty() {
+        SceneBuilder::new(&desc, base_dir)
+            .build()
+            .map_err(|mut build_problems| problems.append(&mut build_problems))
+            .ok()
+    } else {
+        None
+    };
+
+    match scene {
+        Some(scene) => Ok(scene),
+        None => Err(SceneError::Invalid(diagnostics(path, src, problems))),
+    }
+}
+
+fn diagnostics(path: &Path, src: &str, problems: Vec<(KeyPath, Problem)>) -> Vec<Diagnostic> {
+    // The document already parsed once, so this can only fail on a toml_edit/toml mismatch, in
+    // which case every diagnostic falls back to the start of the file.
+    let doc = toml_edit::ImDocument::parse(src).ok();
+    let mut diagnostics: Vec<_> = problems
+        .into_iter()
+        .map(|(key, problem)| Diagnostic {
+            location: Location::from_span(path, src, doc.as_ref().and_then(|d| key.span_in(d))),
+            key,
+            problem,
+        })
+        .collect();
+    diagnostics.sort_by_key(|d| (d.location.line, d.location.column));
+    diagnostics
+}
+
+/// Turns a validated [`SceneDesc`] into a [`Scene`].
+///
+/// Name lookups cannot fail here since [`validate::validate`] already checked every reference.
+struct SceneBuilder<'a> {
+    desc: &'a SceneDesc,
+    base_dir: PathBuf,
+    textures: HashMap<&'a str, Rc<dyn Texture>>,
+    materials: HashMap<&'a str, Rc<dyn Material>>,
+    /// Prototypes are built the first time they are instanced and shared from then on.
+    prototypes: HashMap<&'a str, Rc<dyn Hittable>>,
+    /// Heightmap images by their path in the scene file.
+    heightmaps: HashMap<&'a str, HeightMap>,
+    /// Strands from hair files by their path in the scene file.
+    strands: HashMap<&'a str, Vec<Strand>>,
+    /// Points for sphere clouds by their path in the scene file.
+    point_clouds: HashMap<&'a str, PointCloud>,
+    /// Density grids of heterogeneous media by their path in the scene file.
+    density_grids: HashMap<&'a str, VoxelGrid>,
+}
+
+impl<'a> SceneBuilder<'a> {
+    fn new(desc: &'a SceneDesc, base_dir: PathBuf) -> Self {
+        Self {
+            desc,
+            base_dir,
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            prototypes: HashMap::new(),
+            heightmaps: HashMap::new(),
+            strands: HashMap::new(),
+            point_clouds: HashMap::new(),
+            density_grids: HashMap::new(),
+        }
+    }
+
+    fn build(mut self) -> Result<Scene, Vec<(KeyPath, Problem)>> {
+        let desc = self.desc;
+        self.load_images()?;
+        self.load_files()?;
+
+        let render = &desc.render;
+        let cam = Camera::new(
+            desc.camera.look_from,
+            desc.camera.look_at,
+            desc.camera.vup,
+            desc.camera.vfov,
+            render.aspect_ratio,
+            desc.camera.aperture,
+            desc.camera.focus_dist,
+            render.time0,
+            render.time1,
+        );
+
+        let mut world = HittableList::default();
+        let mut lights = LightList::default();
+        let mut media = Vec::new();
+        for object in &desc.objects {
+            // Homogeneous media aren't objects, and validation only allows them here.
+            if let ObjectDesc::HomogeneousMedium {
+                sigma_a,
+                sigma_s,
+                emission,
+                phase,
+                boundary,
+            } = object
+            {
+                let medium = HomogeneousMedium::new(self.object(boundary), *sigma_a, *sigma_s)
+                    .with_emission(*emission);
+                media.push(match phase {
+                    Some(phase) => medium.with_phase(phase_function(phase)),
+                    None => medium,
+                });
+                continue;
+            }
+            self.collect_lights(object, Affine3::IDENTITY, &mut Vec::new(), &mut lights);
+            world.add(self.object(object));
+        }
+
+        Ok(Scene::new(world, cam)
+            .with_lights(lights)
+            .with_media(media)
+            .with_background_color(render.background)
+            .with_image_width(render.image_width, render.aspect_ratio)
+            .with_samples_per_pixel(render.samples_per_pixel)
+            .with_max_depth(render.max_depth)
+            .with_time(render.time0, render.time1))
+    }
+
+    /// Loads every image texture up front, since image files are the only part of a scene that
+    /// can still turn out to be broken after validation.
+    fn load_images(&mut self) -> Result<(), Vec<(KeyPath, Problem)>> {
+        let mut names: Vec<_> = self.desc.textures.keys().collect();
+        names.sort();
+
+        let mut problems = Vec::new();
+        for name in names {
+            if let TextureDesc::Image { path } = &self.desc.textures[name] {
+                let path = self.base_dir.join(path);
+                match ImageTexture::load(&path.to_string_lossy()) {
+                    Ok(image) => {
+                        self.textures.insert(name, Rc::new(image));
+                    }
+                    Err(reason) => problems.push((
+                        KeyPath(vec![
+                            KeySegment::Key("textures".to_owned()),
+                            KeySegment::Key(name.clone()),
+                            KeySegment::Key("path".to_owned()),
+                        ]),
+                        Problem::UnreadableTextureFile { path, reason },
+                    )),
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Loads the heightmap images, hair files, point files and density grids of all objects up
+    /// front, like
+    /// [`Self::load_images`], wherever the objects are nested.
+    fn load_files(&mut self) -> Result<(), Vec<(KeyPath, Problem)>> {
+        let mut problems = Vec::new();
+        let mut names: Vec<_> = self.desc.prototypes.keys().collect();
+        names.sort();
+        let mut path = vec![KeySegment::Key("prototypes".to_owned())];
+        for name in names {
+            path.push(KeySegment::Key(name.clone()));
+            self.load_files_in(&self.desc.prototypes[name], &mut path, &mut problems);
+            path.pop();
+        }
+
+        let mut path = vec![KeySegment::Key("objects".to_owned())];
+        for (index, object) in self.desc.objects.iter().enumerate() {
+            path.push(KeySegment::Index(index));
+            self.load_files_in(object, &mut path, &mut problems);
+            path.pop();
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    fn load_files_in(
+        &mut self,
+        desc: &'a ObjectDesc,
+        path: &mut Vec<KeySegment>,
+        problems: &mut Vec<(KeyPath, Problem)>,
+    ) {
+        let children: Vec<(&str, &'a ObjectDesc)> = match desc {
+            ObjectDesc::Heightfield {
+                heights: HeightsDesc::Image { path: file },
+                ..
+            } => {
+                let full_path = self.base_dir.join(file);
+                match HeightMap::load(&full_path.to_string_lossy()) {
+                    Ok(map) => {
+                        self.heightmaps.insert(file, map);
+                    }
+                    Err(reason) => {
+                        let mut path = path.clone();
+                        path.push(KeySegment::Key("heights".to_owned()));
+                        path.push(KeySegment::Key("path".to_owned()));
+                        problems.push((
+                            KeyPath(path),
+                            Problem::UnreadableHeightmapFile {
+                                path: full_path,
+                                reason,
+                            },
+                        ));
+                    }
+                }
+                return;
+            }
+            ObjectDesc::Hair { path: file, .. } => {
+                let full_path = self.base_dir.join(file);
+                match load_strands(&full_path.to_string_lossy()) {
+                    Ok(strands) => {
+                        self.strands.insert(file, strands);
+                    }
+                    Err(reason) => {
+                        let mut path = path.clone();
+                        path.push(KeySegment::Key("path".to_owned()));
+                        problems.push((
+                            KeyPath(path),
+                            Problem::UnreadableHairFile {
+                                path: full_path,
+                                reason,
+                            },
+                        ));
+                    }
+                }
+                return;
+            }
+            ObjectDesc::SphereCloud { path: file, .. } => {
+                let full_path = self.base_dir.join(file);
+                match PointCloud::load(&full_path.to_string_lossy()) {
+                    Ok(cloud) => {
+                        self.point_clouds.insert(file, cloud);
+                    }
+                    Err(reason) => {
+                        let mut path = path.clone();
+                        path.push(KeySegment::Key("path".to_owned()));
+                        problems.push((
+                            KeyPath(path),
+                            Problem::UnreadablePointFile {
+                                path: full_path,
+                                reason,
+                            },
+                        ));
+                    }
+                }
+                return;
+            }
+            ObjectDesc::Group { objects, .. } => {
+                path.push(KeySegment::Key("objects".to_owned()));
+                for (index, object) in objects.iter().enumerate() {
+                    path.push(KeySegment::Index(index));
+                    self.load_files_in(object, path, problems);
+                    path.pop();
+                }
+                path.pop();
+                return;
+            }
+            ObjectDesc::Translate { object, .. } | ObjectDesc::RotateY { object, .. } => {
+                vec![("object", object)]
+            }
+            ObjectDesc::ConstantMedium { boundary, .. }
+            | ObjectDesc::HomogeneousMedium { boundary, .. } => vec![("boundary", boundary)],
+            ObjectDesc::HeterogeneousMedium {
+                density, boundary, ..
+            } => {
+                if let DensityDesc::Grid {
+                    path: file,
+                    resolution,
+                    ..
+                } = density
+                {
+                    let full_path = self.base_dir.join(file);
+                    match VoxelGrid::load(&full_path.to_string_lossy(), *resolution) {
+                        Ok(grid) => {
+                            self.density_grids.insert(file, grid);
+                        }
+                        Err(reason) => {
+                            let mut path = path.clone();
+                            path.push(KeySegment::Key("density".to_owned()));
+                            path.push(KeySegment::Key("path".to_owned()));
+                            problems.push((
+                                KeyPath(path),
+                                Problem::UnreadableDensityFile {
+                                    path: full_path,
+                                    reason,
+                                },
+                            ));
+                        }
+                    }
+                }
+                vec![("boundary", boundary)]
+            }
+            ObjectDesc::Union { left, right }
+            | ObjectDesc::Intersection { left, right }
+            | ObjectDesc::Difference { left, right } => vec![("left", left), ("right", right)],
+            _ => return,
+        };
+        for (key, child) in children {
+            path.push(KeySegment::Key(key.to_owned()));
+            self.load_files_in(child, path, problems);
+            path.pop();
+        }
+    }
+
+    fn texture(&mut self, name: &'a str) -> Rc<dyn Texture> {
+        if let Some(texture) = self.textures.get(name) {
+            return texture.clone();
+        }
+
+        let texture: Rc<dyn Texture> = match &self.desc.textures[name] {
+            TextureDesc::Solid { color } => Rc::new(SolidColor::from_color(*color)),
+            TextureDesc::Checker { odd, even } => Rc::new(CheckerTexture::new(
+                self.color_source(odd),
+                self.color_source(even),
+            )),
+            TextureDesc::Noise { scale } => Rc::new(NoiseTexture::new().with_scale(*scale)),
+            TextureDesc::Image { .. } => unreachable!("image textures are loaded up front"),
+        };
+        self.textures.insert(name, texture.clone());
+        texture
+    }
+
+    fn color_source(&mut self, src: &'a ColorSource) -> Rc<dyn Texture> {
+        match src {
+            ColorSource::Color(color) => Rc::new(SolidColor::from_color(*color)),
+            ColorSource::Texture(name) => self.texture(name),
+        }
+    }
+
+    fn material(&mut self, name: &'a str) -> Rc<dyn Material> {
+        if let Some(mat) = self.materials.get(name) {
+            return mat.clone();
+        }
+
+        let mat: Rc<dyn Material> = match &self.desc.materials[name] {
+            MaterialDesc::Lambertian { albedo } => {
+                Rc::new(Lambertian::from_texture(self.color_source(albedo)))
+            }
+            MaterialDesc::Metal { albedo, fuzz } => Rc::new(Metal::new(*albedo, *fuzz)),
+            MaterialDesc::Dielectric { ir } => Rc::new(Dielectric::new(*ir)),
+            MaterialDesc::DiffuseLight { emit } => {
+                Rc::new(DiffuseLight::from_texture(self.color_source(emit)))
+            }
+            MaterialDesc::Isotropic { albedo } => {
+                Rc::new(Isotropic::from_texture(self.color_source(albedo)))
+            }
+            MaterialDesc::Hair {
+                color,
+                sigma_a,
+                eumelanin,
+                pheomelanin,
+                longitudinal_roughness,
+                azimuthal_roughness,
+                scale_angle,
+                ior,
+            } => {
+                let hair = match (color, sigma_a) {
+                    (Some(color), _) => Hair::from_color(*color),
+                    (_, Some(sigma_a)) => Hair::new(*sigma_a),
+                    // Brown, unless any melanin is given.
+                    _ if eumelanin.is_none() && pheomelanin.is_none() => {
+                        Hair::from_melanin(1.3, 0.0)
+                    }
+                    _ => Hair::from_melanin(
+                        eumelanin.unwrap_or_default(),
+                        pheomelanin.unwrap_or_default(),
+                    ),
+                };
+                Rc::new(
+                    hair.with_roughness(*longitudinal_roughness, *azimuthal_roughness)
+                        .with_scale_angle(*scale_angle)
+                        .with_ior(*ior),
+                )
+            }
+        };
+        self.materials.insert(name, mat.clone());
+        mat
+    }
+
+    fn prototype(&mut self, name: &'a str) -> Rc<dyn Hittable> {
+        if let Some(prototype) = self.prototypes.get(name) {
+            return prototype.clone();
+        }
+
+        let prototype = self.object(&self.desc.prototypes[name]);
+        self.prototypes.insert(name, prototype.clone());
+        prototype
+    }
+
+    /// Adds the emitters in `desc` that can be sampled directly to `lights`, however deep in
+    /// groups, transforms and instances they are. `to_world` places `desc` in the world, and
+    /// `overrides` are the material overrides of the instances it is in, innermost last.
+    ///
+    /// Quads and rectangles are sampled under any transform, disks and spheres only under those
+    /// that keep them round. Other emitters, and lights inside media or CSG, are still only
+    /// reached by chance.
+    fn collect_lights(
+        &mut self,
+        desc: &'a ObjectDesc,
+        to_world: Affine3,
+        overrides: &mut Vec<MaterialOverride<'a>>,
+        lights: &mut LightList,
+    ) {
+        let mut add_quad = |builder: &mut Self, q: Vec3, u: Vec3, v: Vec3, material| {
+            if let Some(mat) = builder.emitter(material, overrides) {
+                lights.add(Rc::new(Quad::new(
+                    to_world.transform_point3(q),
+                    to_world.transform_vector3(u),
+                    to_world.transform_vector3(v),
+                    mat,
+                )));
+            }
+        };
+        match desc {
+            ObjectDesc::Quad { q, u, v, material } => add_quad(self, *q, *u, *v, material),
+            ObjectDesc::XyRect {
+                x0,
+                x1,
+                y0,
+                y1,
+                k,
+                material,
+            } => add_quad(
+                self,
+                vec3(*x0, *y0, *k),
+                vec3(x1 - x0, 0.0, 0.0),
+                vec3(0.0, y1 - y0, 0.0),
+                material,
+            ),
+            ObjectDesc::XzRect {
+                x0,
+                x1,
+                z0,
+                z1,
+                k,
+                material,
+            } => add_quad(
+                self,
+                vec3(*x0, *k, *z0),
+                vec3(x1 - x0, 0.0, 0.0),
+                vec3(0.0, 0.0, z1 - z0),
+                material,
+            ),
+            ObjectDesc::YzRect {
+                y0,
+                y1,
+                z0,
+                z1,
+                k,
+                material,
+            } => add_quad(
+                self,
+                vec3(*k, *y0, *z0),
+                vec3(0.0, y1 - y0, 0.0),
+                vec3(0.0, 0.0, z1 - z0),
+                material,
+            ),
+            ObjectDesc::Disk {
+                center,
+                normal,
+                radius,
+                inner_radius,
+                material,
+            } => {
+                if let (Some(scale), Some(mat)) = (
+                    similarity_scale(to_world),
+                    self.emitter(material, overrides),
+                ) {
+                    lights.add(Rc::new(
+                        Disk::new(
+                            to_world.transform_point3(*center),
+                            to_world.transform_vector3(*normal),
+                            radius * scale,
+                            mat,
+                        )
+                        .with_inner_radius(inner_radius * scale),
+                    ));
+                }
+            }
+            ObjectDesc::Sphere {
+                center,
+                radius,
+                material,
+            } => {
+                if let (Some(scale), Some(mat)) = (
+                    similarity_scale(to_world),
+                    self.emitter(material, overrides),
+                ) {
+                    lights.add(Rc::new(Sphere::new(
+                        to_world.transform_point3(*center),
+                        radius * scale,
+                        mat,
+                    )));
+                }
+            }
+            ObjectDesc::Group { objects, .. } => {
+                for object in objects {
+                    self.collect_lights(object, to_world, overrides, lights);
+                }
+            }
+            ObjectDesc::Translate { offset, object } => {
+                let to_world = to_world * Affine3::from_translation(*offset);
+                self.collect_lights(object, to_world, overrides, lights);
+            }
+            ObjectDesc::RotateY { angle, object } => {
+                let to_world = to_world * Affine3::from_rotation_y(angle.to_radians());
+                self.collect_lights(object, to_world, overrides, lights);
+            }
+            ObjectDesc::Instance {
+                prototype,
+                offset,
+                rotate,
+                scale,
+                material,
+                materials,
+            } => {
+                let to_world = to_world * instance_to_world(*offset, *rotate, *scale);
+                overrides.push(MaterialOverride {
+                    material: material.as_deref(),
+                    materials,
+                });
+                let prototype = &self.desc.prototypes[prototype.as_str()];
+                self.collect_lights(prototype, to_world, overrides, lights);
+                overrides.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// The material that `material` turns into inside instances with the given `overrides`, if
+    /// it emits light.
+    fn emitter(
+        &mut self,
+        material: &'a str,
+        overrides: &[MaterialOverride<'a>],
+    ) -> Option<Rc<dyn Material>> {
+        let material = overrides
+            .iter()
+            .rev()
+            .fold(material, |material, with| with.apply(material));
+        matches!(
+            self.desc.materials[material],
+            MaterialDesc::DiffuseLight { .. }
+        )
+        .then(|| self.material(material))
+    }
+
+    fn object(&mut self, desc: &'a ObjectDesc) -> Rc<dyn Hittable> {
+        let (time0, time1) = (self.desc.render.time0, self.desc.render.time1);
+        let object: Rc<dyn Hittable> = match desc {
+            ObjectDesc::Sphere {
+                center,
+                radius,
+                material,
+            } => Rc::new(Sphere::new(*center, *radius, self.material(material))),
+            ObjectDesc::SphereCloud {
+                path,
+                radius,
+                material,
+            } => {
+                let mat = self.material(material);
+                Rc::new(SphereCloud::new(
+                    &self.point_clouds[path.as_str()],
+                    *radius,
+                    mat,
+                ))
+            }
+            ObjectDesc::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material,
+            } => Rc::new(MovingSphere::new(
+                *center0,
+                *center1,
+                *time0,
+                *time1,
+                *radius,
+                self.material(material),
+            )),
+            ObjectDesc::XyRect {
+                x0,
+                x1,
+                y0,
+                y1,
+                k,
+                material,
+            } => Rc::new(XYRect::new(*x0, *x1, *y0, *y1, *k, self.material(material))),
+            ObjectDesc::XzRect {
+                x0,
+                x1,
+                z0,
+                z1,
+                k,
+                material,
+            } => Rc::new(XZRect::new(*x0, *x1, *z0, *z1, *k, self.material(material))),
+            ObjectDesc::YzRect {
+                y0,
+                y1,
+                z0,
+                z1,
+                k,
+                material,
+            } => Rc::new(YZRect::new(*y0, *y1, *z0, *z1, *k, self.material(material))),
+            ObjectDesc::Quad { q, u, v, material } => {
+                Rc::new(Quad::new(*q, *u, *v, self.material(material)))
+            }
+            ObjectDesc::Disk {
+                center,
+                normal,
+                radius,
+                inner_radius,
+                material,
+            } => Rc::new(
+                Disk::new(*center, *normal, *radius, self.material(material))
+                    .with_inner_radius(*inner_radius),
+            ),
+            ObjectDesc::Plane {
+                point,
+                normal,
+                material,
+            } => Rc::new(Plane::new(*point, *normal, self.material(material))),
+            ObjectDesc::Cylinder {
+                start,
+                end,
+                radius,
+                caps,
+                sweep,
+                material,
+            } => Rc::new(
+                Cylinder::new(*start, *end, *radius, self.material(material))
+                    .with_caps(*caps)
+                    .with_sweep(*sweep),
+            ),
+            ObjectDesc::Cone {
+                start,
+                end,
+                start_radius,
+                end_radius,
+                caps,
+                sweep,
+                material,
+            } => Rc::new(
+                Cone::new(
+                    *start,
+                    *end,
+                    *start_radius,
+                    *end_radius,
+                    self.material(material),
+                )
+                .with_caps(*caps)
+                .with_sweep(*sweep),
+            ),
+            ObjectDesc::Capsule {
+                start,
+                end,
+                radius,
+                sweep,
+                material,
+            } => Rc::new(
+                Capsule::new(*start, *end, *radius, self.material(material)).with_sweep(*sweep),
+            ),
+            ObjectDesc::Torus {
+                center,
+                normal,
+                major_radius,
+                minor_radius,
+                material,
+            } => Rc::new(Torus::new(
+                *center,
+                *normal,
+                *major_radius,
+                *minor_radius,
+                self.material(material),
+            )),
+            ObjectDesc::Box { min, max, material } => {
+                Rc::new(GeometricBox::new(*min, *max, self.material(material)))
+            }
+            ObjectDesc::Heightfield {
+                heights,
+                min,
+                max,
+                material,
+            } => {
+                let mat = self.material(material);
+                let noise_map;
+                let map = match heights {
+                    HeightsDesc::Image { path } => &self.heightmaps[path.as_str()],
+                    HeightsDesc::Noise { resolution, scale } => {
+                        let [width, depth] = *resolution;
+                        noise_map = HeightMap::from_noise(&Perlin::new(), width, depth, *scale);
+                        &noise_map
+                    }
+                };
+                Rc::new(Heightfield::new(map, *min, *max, mat))
+            }
+            ObjectDesc::Sdf {
+                shape,
+                min,
+                max,
+                max_steps,
+                epsilon,
+                material,
+            } => Rc::new(
+                SdfHittable::new(
+                    build_sdf(shape),
+                    Aabb::new(*min, *max),
+                    self.material(material),
+                )
+                .with_max_steps(*max_steps)
+                .with_epsilon(*epsilon),
+            ),
+            ObjectDesc::Curve {
+                points,
+                widths,
+                kind,
+                normals,
+                material,
+            } => Rc::new(Curves::new(
+                vec![Curve::new(*points, *widths)],
+                curve_kind(*kind, normals.map(|normals| normals.map(|n| n.normalize()))),
+                self.material(material),
+            )),
+            ObjectDesc::Hair {
+                path,
+                kind,
+                material,
+            } => {
+                let mat = self.material(material);
+                let curves = self.strands[path.as_str()]
+                    .iter()
+                    .flat_map(Strand::curves)
+                    .collect();
+                Rc::new(Curves::new(curves, curve_kind(*kind, None), mat))
+            }
+            ObjectDesc::Fur {
+                center,
+                radius,
+                count,
+                length,
+                width,
+                droop,
+                seed,
+                kind,
+                material,
+            } => {
+                let strands = Fur::new(*center, *radius, *count, *length, *width)
+                    .with_droop(*droop)
+                    .with_seed(*seed)
+                    .strands();
+                let curves = strands.iter().flat_map(Strand::curves).collect();
+                Rc::new(Curves::new(
+                    curves,
+                    curve_kind(*kind, None),
+                    self.material(material),
+                ))
+            }
+            ObjectDesc::Group { bvh, objects } => {
+                let mut list = HittableList::default();
+                for object in objects {
+                    list.add(self.object(object));
+                }
+                if *bvh {
+                    Rc::new(Bvh::from_hittable_list(list, time0, time1))
+                } else {
+                    Rc::new(list)
+                }
+            }
+            ObjectDesc::Translate { offset, object } => {
+                Rc::new(Translate::new(self.object(object), *offset))
+            }
+            ObjectDesc::RotateY { angle, object } => {
+                Rc::new(RotateY::new(self.object(object), *angle))
+            }
+            ObjectDesc::Instance {
+                prototype,
+                offset,
+                rotate,
+                scale,
+                material,
+                materials,
+            } => {
+                let object_to_world = instance_to_world(*offset, *rotate, *scale);
+                let instance = Instance::new(self.prototype(prototype), object_to_world);
+                let instance = if let Some(material) = material {
+                    instance.with_material(self.material(material))
+                } else if !materials.is_empty() {
+                    let mut variants = MaterialVariants::new();
+                    for (original, replacement) in materials {
+                        variants =
+                            variants.with(self.material(original), self.material(replacement));
+                    }
+                    instance.with_variants(Rc::new(variants))
+                } else {
+                    instance
+                };
+                Rc::new(instance)
+            }
+            ObjectDesc::Union { left, right } => {
+                Rc::new(Csg::union(self.object(left), self.object(right)))
+            }
+            ObjectDesc::Intersection { left, right } => {
+                Rc::new(Csg::intersection(self.object(left), self.object(right)))
+            }
+            ObjectDesc::Difference { left, right } => {
+                Rc::new(Csg::difference(self.object(left), self.object(right)))
+            }
+            ObjectDesc::ConstantMedium {
+                density,
+                albedo,
+                phase,
+                boundary,
+            } => Rc::new(ConstantMedium::new(
+                self.object(boundary),
+                *density,
+                self.phase(albedo, phase.as_ref()),
+            )),
+            ObjectDesc::HeterogeneousMedium {
+                density,
+                albedo,
+                phase,
+                boundary,
+            } => {
+                let boundary = self.object(boundary);
+                let density: Rc<dyn DensityField> = match density {
+                    DensityDesc::Grid {
+                        path,
+                        density,
+                        min,
+                        max,
+                        ..
+                    } => Rc::new(
+                        self.density_grids[path.as_str()]
+                            .clone()
+                            .with_bounds(Aabb::new(*min, *max))
+                            .scaled(*density),
+                    ),
+                    DensityDesc::Noise { density, scale } => {
+                        Rc::new(NoiseDensity::new(*density).with_scale(*scale))
+                    }
+                };
+                Rc::new(HeterogeneousMedium::new(
+                    boundary,
+                    density,
+                    self.phase(albedo, phase.as_ref()),
+                ))
+            }
+            ObjectDesc::HomogeneousMedium { .. } => {
+                unreachable!("homogeneous media are only allowed at the top level")
+            }
+        };
+        object
+    }
+
+    /// The material a medium scatters with, isotropic unless given another `phase`.
+    fn phase(&mut self, albedo: &'a ColorSource, phase: Option<&PhaseDesc>) -> Rc<dyn Material> {
+        let albedo = self.color_source(albedo);
+        match phase {
+            None | Some(PhaseDesc::Isotropic) => Rc::new(Isotropic::from_texture(albedo)),
+            Some(phase) => Rc::new(Anisotropic::from_texture(albedo, phase_function(phase))),
+        }
+    }
+}
+
+/// How an instance replaces the materials of its prototype: all of them with `material`, or each
+/// of those named in `materials` with another.
+struct MaterialOverride<'a> {
+    material: Option<&'a str>,
+    materials: &'a HashMap<String, String>,
+}
+
+impl<'a> MaterialOverride<'a> {
+    fn apply(&self, material: &'a str) -> &'a str {
+        self.material.unwrap_or_else(|| {
+            self.materials
+                .get(material)
+                .map_or(material, String::as_str)
+        })
+    }
+}
+
+/// Places an instance's prototype in the world: scaled, then rotated about the x, y and z axes
+/// in that order, by angles in degrees, then moved by `offset`.
+fn instance_to_world(offset: Vec3, rotate: Vec3, scale: Vec3) -> Affine3 {
+    let rotation = Mat3::from_rotation_z(rotate.z.to_radians())
+        * Mat3::from_rotation_y(rotate.y.to_radians())
+        * Mat3::from_rotation_x(rotate.x.to_radians());
+    Affine3::from_mat3_translation(rotation * Mat3::from_diagonal(scale), offset)
+}
+
+fn phase_function(phase: &PhaseDesc) -> Rc<dyn PhaseFunction> {
+    match phase {
+        PhaseDesc::Isotropic => Rc::new(IsotropicPhase),
+        PhaseDesc::HenyeyGreenstein { g } => Rc::new(HenyeyGreenstein::new(*g)),
+        PhaseDesc::DoubleHenyeyGreenstein {
+            forward,
+            backward,
+            weight,
+        } => Rc::new(DoubleHenyeyGreenstein::new(*forward, *backward, *weight)),
+    }
+}
+
+/// Validation made sure that only single ribbons come with `normals`.
+fn curve_kind(kind: CurveKindDesc, normals: Option<[Vec3; 2]>) -> CurveKind {
+    match kind {
+        CurveKindDesc::Flat => CurveKind::Flat,
+        CurveKindDesc::Tube => CurveKind::Tube,
+        CurveKindDesc::Ribbon => CurveKind::Ribbon(vec![normals.expect("ribbons have normals")]),
+    }
+}
+
+fn build_sdf(desc: &SdfDesc) -> Rc<dyn Sdf> {
+    match desc {
+        SdfDesc::Sphere { radius } => Rc::new(sdf::Sphere { radius: *radius }),
+        SdfDesc::Box { half_extents } => Rc::new(sdf::Cuboid {
+            half_extents: *half_extents,
+        }),
+        SdfDesc::RoundedBox {
+            half_extents,
+            radius,
+        } => Rc::new(sdf::RoundedCuboid {
+            half_extents: *half_extents,
+            radius: *radius,
+        }),
+        SdfDesc::Torus {
+            major_radius,
+            minor_radius,
+        } => Rc::new(sdf::Torus {
+            major_radius: *major_radius,
+            minor_radius: *minor_radius,
+        }),
+        SdfDesc::Capsule { start, end, radius } => Rc::new(sdf::Capsule {
+            start: *start,
+            end: *end,
+            radius: *radius,
+        }),
+        SdfDesc::SmoothUnion { left, right, k } => Rc::new(sdf::SmoothUnion {
+            left: build_sdf(left),
+            right: build_sdf(right),
+            k: *k,
+        }),
+        SdfDesc::Subtraction { left, right } => Rc::new(sdf::Subtraction {
+            left: build_sdf(left),
+            right: build_sdf(right),
+        }),
+        SdfDesc::Repeat { period, shape } => Rc::new(sdf::Repeat {
+            shape: build_sdf(shape),
+            period: *period,
+        }),
+        SdfDesc::Twist {
+            degrees_per_unit,
+            shape,
+        } => Rc::new(sdf::Twist {
+            shape: build_sdf(shape),
+            rate: degrees_per_unit.to_radians(),
+        }),
+        SdfDesc::Translate { offset, shape } => Rc::new(sdf::Translate {
+            shape: build_sdf(shape),
+            offset: *offset,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{float::Float, ray::Ray};
+
+    /// Builds a scene from the text of a scene file, which must be valid. Files it refers to are
+    /// found relative to the root of the repository.
+    fn build(src: &str) -> Scene {
+        load_source(Path::new("test.toml"), src).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// What is wrong with a scene file that must not load, as `line:column: message` for a
+    /// syntax error or `line:column: key: problem` for each problem with its contents.
+    fn errors(src: &str) -> Vec<String> {
+        let location = |l: &Location| format!("{}:{}", l.line, l.column);
+        match load_source(Path::new("test.toml"), src) {
+            Ok(_) => panic!("the scene loaded"),
+            Err(SceneError::Parse {
+                location: l,
+                message,
+            }) => {
+                vec![format!("{}: {message}", location(&l))]
+            }
+            Err(SceneError::Invalid(diagnostics)) => diagnostics
+                .iter()
+                .map(|d| format!("{}: {}: {}", location(&d.location), d.key, d.problem))
+                .collect(),
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    const CAMERA: &str = "
+[camera]
+look_from = [0.0, 0.0, 0.0]
+look_at = [0.0, 0.0, -1.0]
+vfov = 40.0
+
+[materials.white]
+type = \"lambertian\"
+albedo = [0.7, 0.7, 0.7]
+";
+
+    #[test]
+    fn small_scene_parses_and_builds() {
+        let scene = build(&format!(
+            r#"{CAMERA}
+[render]
+image_width = 40
+aspect_ratio = 2.0
+samples_per_pixel = 3
+max_depth = 4
+background = [0.1, 0.2, 0.3]
+
+[textures.checks]
+type = "checker"
+odd = [0.0, 0.0, 0.0]
+even = "noise"
+
+[textures.noise]
+type = "noise"
+scale = 4.0
+
+[materials.checkered]
+type = "lambertian"
+albedo = "checks"
+
+[materials.glass]
+type = "dielectric"
+ir = 1.5
+
+[materials.light]
+type = "diffuse_light"
+emit = [4.0, 4.0, 4.0]
+
+[prototypes.ball]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "glass"
+
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, -5.0]
+radius = 1.0
+material = "checkered"
+
+[[objects]]
+type = "group"
+bvh = true
+objects = [
+    {{ type = "quad", q = [-1.0, 3.0, -6.0], u = [2.0, 0.0, 0.0], v = [0.0, 0.0, 2.0], material = "light" }},
+    {{ type = "instance", prototype = "ball", offset = [3.0, 0.0, -5.0], material = "white" }},
+]
+
+[[objects]]
+type = "homogeneous_medium"
+sigma_a = [0.1, 0.1, 0.1]
+sigma_s = [0.2, 0.2, 0.2]
+boundary = {{ type = "sphere", center = [0.0, 0.0, 0.0], radius = 100.0, material = "white" }}
+"#
+        ));
+        assert_eq!((scene.image_width, scene.image_height), (40, 20));
+        assert_eq!((scene.samples_per_pixel, scene.max_depth), (3, 4));
+        assert_eq!(scene.background_color, vec3(0.1, 0.2, 0.3));
+        // The medium isn't an object in the world.
+        assert_eq!(scene.world.objects.len(), 2);
+        assert_eq!(scene.media.len(), 1);
+        assert_eq!(scene.lights.lights.len(), 1);
+
+        let hit = |origin: Vec3| {
+            let r = Ray::new(origin, -Vec3::Z, 0.0);
+            scene.world.hit(r, 0.0, Float::INFINITY).map(|rec| rec.t)
+        };
+        assert_eq!(hit(Vec3::ZERO), Some(4.0));
+        assert_eq!(hit(vec3(3.0, 0.0, 0.0)), Some(4.0));
+        assert_eq!(hit(vec3(-3.0, 0.0, 0.0)), None);
+    }
+
+    /// The examples all load, apart from the earth's texture, which isn't part of the
+    /// repository.
+    #[test]
+    fn example_scenes_load() {
+        let mut paths: Vec<_> = std::fs::read_dir("scenes")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        paths.sort();
+        assert!(paths.len() >= 10);
+        let missing_earth = |d: &Diagnostic| match &d.problem {
+            Problem::MissingTextureFile(file) => file.ends_with("earthmap.jpg"),
+            _ => false,
+        };
+        for path in paths {
+            match load(&path) {
+                Ok(_) => {}
+                Err(SceneError::Invalid(diagnostics)) if diagnostics.iter().all(missing_earth) => {}
+                Err(err) => panic!("{err}"),
+            }
+        }
+    }
+
+    /// Files that would otherwise break assumptions the builder makes are reported instead.
+    #[test]
+    fn malformed_scenes_are_reported() {
+        let cases = [
+            (
+                "[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0]",
+                "11:1: invalid length 2",
+            ),
+            (
+                "[[objects]]\ntype = \"teapot\"",
+                "12:8: unknown variant `teapot`",
+            ),
+            (
+                // Only top-level media are followed through the scene.
+                r#"[[objects]]
+type = "group"
+objects = [
+    { type = "homogeneous_medium", sigma_a = [0.1, 0.1, 0.1], sigma_s = [0.1, 0.1, 0.1], boundary = { type = "sphere", center = [0.0, 0.0, 0.0], radius = 1.0, material = "white" } },
+]"#,
+                "14:5: objects[0].objects[0]: homogeneous media must be top-level objects",
+            ),
+            (
+                r#"[prototypes.fog]
+type = "homogeneous_medium"
+sigma_a = [0.1, 0.1, 0.1]
+sigma_s = [0.1, 0.1, 0.1]
+boundary = { type = "sphere", center = [0.0, 0.0, 0.0], radius = 1.0, material = "white" }"#,
+                "11:1: prototypes.fog: homogeneous media must be top-level objects",
+            ),
+            (
+                r#"[[objects]]
+type = "curve"
+points = [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 2.0, 0.0], [0.0, 3.0, 0.0]]
+widths = [0.1, 0.1]
+kind = "ribbon"
+material = "white""#,
+                "15:8: objects[0].kind: ribbons need `normals` at both ends",
+            ),
+            (
+                r#"[[objects]]
+type = "fur"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+count = 10
+length = 0.1
+width = 0.01
+kind = "ribbon"
+material = "white""#,
+                "18:8: objects[0].kind: ribbons need `normals` at both ends",
+            ),
+            (
+                // The file exists, but isn't an image.
+                r#"[textures.photo]
+type = "image"
+path = "Cargo.toml"
+
+[materials.photo]
+type = "lambertian"
+albedo = "photo""#,
+                "13:8: textures.photo.path: unable to load texture file 'Cargo.toml'",
+            ),
+            (
+                r#"[prototypes.a]
+type = "instance"
+prototype = "b"
+
+[prototypes.b]
+type = "instance"
+prototype = "a""#,
+                "15:1: prototypes.b: prototype 'b' is part of an instance cycle",
+            ),
+            (
+                r#"[[objects]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "chalk""#,
+                "15:12: objects[0].material: unknown material 'chalk'",
+            ),
+        ];
+        for (objects, expected) in cases {
+            let errors = errors(&format!("{CAMERA}\n{objects}"));
+            assert!(
+                errors
+                    .first()
+                    .is_some_and(|error| error.starts_with(expected)),
+                "{objects}\n{errors:#?}"
+            );
+        }
+    }
+
+    #[test]
+    fn nested_lights_are_sampled_where_they_end_up() {
+        let scene = build(
+            r#"
+            [camera]
+            look_from = [0.0, 0.0, 0.0]
+            look_at = [0.0, 0.0, -1.0]
+            vfov = 40.0
+
+            [materials.white]
+            type = "lambertian"
+            albedo = [0.7, 0.7, 0.7]
+
+            [materials.light]
+            type = "diffuse_light"
+            emit = [4.0, 4.0, 4.0]
+
+            [prototypes.lamp]
+            type = "group"
+            objects = [
+                { type = "sphere", center = [0.0, 1.0, 0.0], radius = 0.5, material = "white" },
+                { type = "quad", q = [-0.5, 3.0, -0.5], u = [1.0, 0.0, 0.0], v = [0.0, 0.0, 1.0], material = "light" },
+            ]
+
+            [[objects]]
+            type = "group"
+            objects = [
+                { type = "translate", offset = [0.0, 5.0, 0.0], object = { type = "xz_rect", x0 = -1.0, x1 = 1.0, z0 = -1.0, z1 = 1.0, k = 0.0, material = "light" } },
+            ]
+
+            [[objects]]
+            type = "rotate_y"
+            angle = 90.0
+            object = { type = "disk", center = [2.0, 0.0, 0.0], normal = [0.0, 1.0, 0.0], radius = 1.0, material = "light" }
+
+            # The sphere turns into a light, and both it and the quad are scaled up.
+            [[objects]]
+            type = "instance"
+            prototype = "lamp"
+            offset = [10.0, 0.0, 0.0]
+            scale = [2.0, 2.0, 2.0]
+            materials = { white = "light" }
+
+            # The sphere would be stretched into an ellipsoid, which can't be sampled.
+            [[objects]]
+            type = "instance"
+            prototype = "lamp"
+            offset = [-10.0, 0.0, 0.0]
+            scale = [1.0, 2.0, 1.0]
+            materials = { white = "light" }
+
+            # Nothing glows.
+            [[objects]]
+            type = "instance"
+            prototype = "lamp"
+            offset = [0.0, 0.0, 20.0]
+            material = "white"
+
+            [[objects]]
+            type = "sphere"
+            center = [0.0, -5.0, 0.0]
+            radius = 1.0
+            material = "white"
+            "#,
+        );
+        let lights = &scene.lights;
+        assert_eq!(lights.lights.len(), 5);
+
+        let reaches = |origin: Vec3, direction: Vec3| lights.pdf_value(origin, direction) > 0.0;
+        // The rectangle, moved up.
+        assert!(reaches(Vec3::ZERO, Vec3::Y));
+        // The disk, turned from +x to -z.
+        assert!(reaches(vec3(0.0, 3.0, -2.0), -Vec3::Y));
+        assert!(!reaches(vec3(2.0, 3.0, 0.0), -Vec3::Y));
+        // The scaled sphere and both quads, but not the stretched sphere.
+        assert!(reaches(vec3(0.0, 2.0, 0.0), Vec3::X));
+        assert!(reaches(vec3(10.0, 0.0, 0.0), Vec3::Y));
+        assert!(reaches(vec3(-10.0, 0.0, 0.0), Vec3::Y));
+        assert!(!reaches(vec3(0.0, 2.0, 0.0), -Vec3::X));
+        // Nothing where everything was overridden to be white, or in the white sphere.
+        assert!(!reaches(vec3(0.0, 0.0, 20.0), Vec3::Y));
+        assert!(!reaches(Vec3::ZERO, -Vec3::Y));
+    }
+}
@@ -0,0 +1,1056 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::float::{Float, Vec3};
+use crate::scene_file::{
+    description::{
+        CameraDesc, ColorSource, CurveKindDesc, DensityDesc, HeightsDesc, MaterialDesc, ObjectDesc,
+        PhaseDesc, RenderDesc, SceneDesc, SdfDesc, TextureDesc,
+    },
+    error::{KeyPath, KeySegment, Problem},
+};
+
+/// Checks a parsed scene description for everything that would otherwise panic, fail silently or
+/// produce garbage while building or rendering the scene.
+///
+/// Returns every problem found, each paired with the path of the offending value.
+pub fn validate(desc: &SceneDesc, base_dir: &Path) -> Vec<(KeyPath, Problem)> {
+    let mut validator = Validator {
+        desc,
+        base_dir,
+        path: Vec::new(),
+        problems: Vec::new(),
+    };
+    validator.scene();
+    validator.problems
+}
+
+struct Validator<'a> {
+    desc: &'a SceneDesc,
+    base_dir: &'a Path,
+    path: Vec<KeySegment>,
+    problems: Vec<(KeyPath, Problem)>,
+}
+
+impl<'a> Validator<'a> {
+    fn scene(&mut self) {
+        let desc = self.desc;
+        self.key("camera", |v| v.camera(&desc.camera));
+        self.key("render", |v| v.render(&desc.render));
+
+        let mut names: Vec<_> = desc.textures.keys().collect();
+        names.sort();
+        self.key("textures", |v| {
+            for name in &names {
+                v.key(name, |v| v.texture(&desc.textures[*name]));
+            }
+        });
+        self.texture_cycles(&names);
+
+        let mut names: Vec<_> = desc.materials.keys().collect();
+        names.sort();
+        self.key("materials", |v| {
+            for name in names {
+                v.key(name, |v| v.material(&desc.materials[name]));
+            }
+        });
+
+        let mut names: Vec<_> = desc.prototypes.keys().collect();
+        names.sort();
+        self.key("prototypes", |v| {
+            for name in &names {
+                v.key(name, |v| v.object(&desc.prototypes[*name]));
+            }
+        });
+        self.prototype_cycles(&names);
+
+        self.key("objects", |v| {
+            for (i, object) in desc.objects.iter().enumerate() {
+                v.index(i, |v| v.object(object));
+            }
+        });
+    }
+
+    fn camera(&mut self, camera: &CameraDesc) {
+        self.finite_vec("look_from", camera.look_from);
+        self.finite_vec("look_at", camera.look_at);
+        self.finite_vec("vup", camera.vup);
+        self.positive("vfov", camera.vfov);
+        self.finite("aperture", camera.aperture);
+        self.positive("focus_dist", camera.focus_dist);
+        if camera.look_from == camera.look_at {
+            self.report_at(
+                "look_at",
+                Problem::Coincident {
+                    a: "look_from",
+                    b: "look_at",
+                },
+            );
+        }
+    }
+
+    fn render(&mut self, render: &RenderDesc) {
+        self.positive("image_width", render.image_width as Float);
+        self.positive("aspect_ratio", render.aspect_ratio);
+        self.positive("samples_per_pixel", render.samples_per_pixel as Float);
+        self.positive("max_depth", render.max_depth as Float);
+        self.finite_vec("background", render.background);
+        self.finite("time0", render.time0);
+        self.finite("time1", render.time1);
+    }
+
+    fn texture(&mut self, texture: &TextureDesc) {
+        match texture {
+            TextureDesc::Solid { color } => {
+                self.finite_vec("color", *color);
+            }
+            TextureDesc::Checker { odd, even } => {
+                self.color_source("odd", odd);
+                self.color_source("even", even);
+            }
+            TextureDesc::Noise { scale } => {
+                self.finite("scale", *scale);
+            }
+            TextureDesc::Image { path } => {
+                let full_path = self.base_dir.join(path);
+                if !full_path.is_file() {
+                    self.report_at("path", Problem::MissingTextureFile(full_path));
+                }
+            }
+        }
+    }
+
+    /// Reports textures that (indirectly) use themselves, which would recurse forever.
+    fn texture_cycles(&mut self, names: &[&'a String]) {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Visiting,
+            Done,
+        }
+
+        fn visit<'a>(
+            desc: &'a SceneDesc,
+            name: &'a str,
+            states: &mut HashMap<&'a str, State>,
+            cycles: &mut Vec<(&'a str, &'static str)>,
+        ) {
+            let Some(TextureDesc::Checker { odd, even }) = desc.textures.get(name) else {
+                return;
+            };
+            states.insert(name, State::Visiting);
+            for (key, src) in [("odd", odd), ("even", even)] {
+                if let ColorSource::Texture(child) = src {
+                    match states.get(child.as_str()) {
+                        Some(State::Visiting) => cycles.push((name, key)),
+                        Some(State::Done) => {}
+                        None => visit(desc, child, states, cycles),
+                    }
+                }
+            }
+            states.insert(name, State::Done);
+        }
+
+        let mut states = HashMap::new();
+        let mut cycles = Vec::new();
+        for name in names {
+            if !states.contains_key(name.as_str()) {
+                visit(self.desc, name, &mut states, &mut cycles);
+            }
+        }
+
+        for (name, key) in cycles {
+            self.key("textures", |v| {
+                v.key(name, |v| {
+                    v.report_at(key, Problem::TextureCycle(name.to_owned()))
+                })
+            });
+        }
+    }
+
+    /// Reports prototypes that (indirectly) contain instances of themselves, which would never
+    /// finish building.
+    fn prototype_cycles(&mut self, names: &[&'a String]) {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Visiting,
+            Done,
+        }
+
+        /// Collects the prototypes that `desc` has instances of, wherever they are nested.
+        fn instanced<'a>(desc: &'a ObjectDesc, prototypes: &mut Vec<&'a str>) {
+            match desc {
+                ObjectDesc::Instance { prototype, .. } => prototypes.push(prototype),
+                ObjectDesc::Group { objects, .. } => {
+                    for object in objects {
+                        instanced(object, prototypes);
+                    }
+                }
+                ObjectDesc::Translate { object, .. } | ObjectDesc::RotateY { object, .. } => {
+                    instanced(object, prototypes)
+                }
+                ObjectDesc::ConstantMedium { boundary, .. }
+                | ObjectDesc::HeterogeneousMedium { boundary, .. }
+                | ObjectDesc::HomogeneousMedium { boundary, .. } => instanced(boundary, prototypes),
+                ObjectDesc::Union { left, right }
+                | ObjectDesc::Intersection { left, right }
+                | ObjectDesc::Difference { left, right } => {
+                    instanced(left, prototypes);
+                    instanced(right, prototypes);
+                }
+                _ => {}
+            }
+        }
+
+        fn visit<'a>(
+            desc: &'a SceneDesc,
+            name: &'a str,
+            states: &mut HashMap<&'a str, State>,
+            cycles: &mut Vec<&'a str>,
+        ) {
+            let Some(prototype) = desc.prototypes.get(name) else {
+                return;
+            };
+            states.insert(name, State::Visiting);
+            let mut children = Vec::new();
+            instanced(prototype, &mut children);
+            for child in children {
+                match states.get(child) {
+                    Some(State::Visiting) => cycles.push(name),
+                    Some(State::Done) => {}
+                    None => visit(desc, child, states, cycles),
+                }
+            }
+            states.insert(name, State::Done);
+        }
+
+        let mut states = HashMap::new();
+        let mut cycles = Vec::new();
+        for name in names {
+            if !states.contains_key(name.as_str()) {
+                visit(self.desc, name, &mut states, &mut cycles);
+            }
+        }
+
+        cycles.dedup();
+        for name in cycles {
+            self.key("prototypes", |v| {
+                v.report_at(name, Problem::PrototypeCycle(name.to_owned()))
+            });
+        }
+    }
+
+    fn material(&mut self, material: &MaterialDesc) {
+        match material {
+            MaterialDesc::Lambertian { albedo } | MaterialDesc::Isotropic { albedo } => {
+                self.color_source("albedo", albedo)
+            }
+            MaterialDesc::Metal { albedo, fuzz } => {
+                self.finite_vec("albedo", *albedo);
+                self.unit_interval("fuzz", *fuzz);
+            }
+            MaterialDesc::Dielectric { ir } => self.positive("ir", *ir),
+            MaterialDesc::DiffuseLight { emit } => self.color_source("emit", emit),
+            MaterialDesc::Hair {
+                color,
+                sigma_a,
+                eumelanin,
+                pheomelanin,
+                longitudinal_roughness,
+                azimuthal_roughness,
+                scale_angle,
+                ior,
+            } => {
+                // Only one way of coloring the fiber may be given.
+                let sources = [
+                    ("color", color.is_some()),
+                    ("sigma_a", sigma_a.is_some()),
+                    ("eumelanin", eumelanin.is_some()),
+                    ("pheomelanin", pheomelanin.is_some()),
+                ];
+                let mut given = sources
+                    .iter()
+                    .filter(|(_, given)| *given)
+                    .map(|(key, _)| *key);
+                if let Some(first) = given.next() {
+                    for other in given {
+                        let melanin = ["eumelanin", "pheomelanin"];
+                        if !(melanin.contains(&first) && melanin.contains(&other)) {
+                            self.report_at(other, Problem::Conflicting { a: first, b: other });
+                        }
+                    }
+                }
+
+                if let Some(color) = color {
+                    if self.finite_vec("color", *color)
+                        && !(color.min_element() > 0.0 && color.max_element() <= 1.0)
+                    {
+                        self.report_at("color", Problem::NotFraction);
+                    }
+                }
+                if let Some(sigma_a) = sigma_a {
+                    if self.finite_vec("sigma_a", *sigma_a) && sigma_a.min_element() < 0.0 {
+                        self.report_at("sigma_a", Problem::Negative);
+                    }
+                }
+                if let Some(eumelanin) = eumelanin {
+                    self.non_negative("eumelanin", *eumelanin);
+                }
+                if let Some(pheomelanin) = pheomelanin {
+                    self.non_negative("pheomelanin", *pheomelanin);
+                }
+                self.fraction("longitudinal_roughness", *longitudinal_roughness);
+                self.fraction("azimuthal_roughness", *azimuthal_roughness);
+                self.finite("scale_angle", *scale_angle);
+                self.positive("ior", *ior);
+            }
+        }
+    }
+
+    fn object(&mut self, object: &ObjectDesc) {
+        match object {
+            ObjectDesc::Sphere {
+                center,
+                radius,
+                material,
+            } => {
+                self.finite_vec("center", *center);
+                self.radius(*radius);
+                self.material_ref(material);
+            }
+            ObjectDesc::SphereCloud {
+                path,
+                radius,
+                material,
+            } => {
+                let full_path = self.base_dir.join(path);
+                if !full_path.is_file() {
+                    self.report_at("path", Problem::MissingPointFile(full_path));
+                }
+                self.positive("radius", *radius);
+                self.material_ref(material);
+            }
+            ObjectDesc::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material,
+            } => {
+                self.finite_vec("center0", *center0);
+                self.finite_vec("center1", *center1);
+                self.finite("time0", *time0);
+                self.finite("time1", *time1);
+                if time0 == time1 {
+                    self.report_at(
+                        "time1",
+                        Problem::Coincident {
+                            a: "time0",
+                            b: "time1",
+                        },
+                    );
+                }
+                self.radius(*radius);
+                self.material_ref(material);
+            }
+            ObjectDesc::XyRect {
+                x0,
+                x1,
+                y0,
+                y1,
+                k,
+                material,
+            } => {
+                self.interval(("x0", *x0), ("x1", *x1));
+                self.interval(("y0", *y0), ("y1", *y1));
+                self.finite("k", *k);
+                self.material_ref(material);
+            }
+            ObjectDesc::XzRect {
+                x0,
+                x1,
+                z0,
+                z1,
+                k,
+                material,
+            } => {
+                self.interval(("x0", *x0), ("x1", *x1));
+                self.interval(("z0", *z0), ("z1", *z1));
+                self.finite("k", *k);
+                self.material_ref(material);
+            }
+            ObjectDesc::YzRect {
+                y0,
+                y1,
+                z0,
+                z1,
+                k,
+                material,
+            } => {
+                self.interval(("y0", *y0), ("y1", *y1));
+                self.interval(("z0", *z0), ("z1", *z1));
+                self.finite("k", *k);
+                self.material_ref(material);
+            }
+            ObjectDesc::Quad { q, u, v, material } => {
+                self.finite_vec("q", *q);
+                let finite = self.finite_vec("u", *u) & self.finite_vec("v", *v);
+                if finite && u.cross(*v) == Vec3::ZERO {
+                    self.report_at("v", Problem::Parallel { a: "u", b: "v" });
+                }
+                self.material_ref(material);
+            }
+            ObjectDesc::Disk {
+                center,
+                normal,
+                radius,
+                inner_radius,
+                material,
+            } => {
+                self.finite_vec("center", *center);
+                self.normal(*normal);
+                self.positive("radius", *radius);
+                if self.non_negative("inner_radius", *inner_radius) && inner_radius >= radius {
+                    self.report_at(
+                        "inner_radius",
+                        Problem::Degenerate {
+                            min: "inner_radius",
+                            max: "radius",
+                        },
+                    );
+                }
+                self.material_ref(material);
+            }
+            ObjectDesc::Plane {
+                point,
+                normal,
+                material,
+            } => {
+                self.finite_vec("point", *point);
+                self.normal(*normal);
+                self.material_ref(material);
+            }
+            ObjectDesc::Cylinder {
+                start,
+                end,
+                radius,
+                sweep,
+                material,
+                ..
+            }
+            | ObjectDesc::Capsule {
+                start,
+                end,
+                radius,
+                sweep,
+                material,
+            } => {
+                self.axis(*start, *end);
+                self.positive("radius", *radius);
+                self.sweep(*sweep);
+                self.material_ref(material);
+            }
+            ObjectDesc::Cone {
+                start,
+                end,
+                start_radius,
+                end_radius,
+                sweep,
+                material,
+                ..
+            } => {
+                self.axis(*start, *end);
+                let start_valid = self.non_negative("start_radius", *start_radius);
+                let end_valid = self.non_negative("end_radius", *end_radius);
+                if start_valid && end_valid && *start_radius == 0.0 && *end_radius == 0.0 {
+                    self.report_at("start_radius", Problem::ZeroRadius);
+                }
+                self.sweep(*sweep);
+                self.material_ref(material);
+            }
+            ObjectDesc::Torus {
+                center,
+                normal,
+                major_radius,
+                minor_radius,
+                material,
+            } => {
+                self.finite_vec("center", *center);
+                self.normal(*normal);
+                self.positive("major_radius", *major_radius);
+                self.positive("minor_radius", *minor_radius);
+                self.material_ref(material);
+            }
+            ObjectDesc::Box { min, max, material } => {
+                self.bounds(*min, *max);
+                self.material_ref(material);
+            }
+            ObjectDesc::Heightfield {
+                heights,
+                min,
+                max,
+                material,
+            } => {
+                self.key("heights", |v| v.heights(heights));
+                self.bounds(*min, *max);
+                self.material_ref(material);
+            }
+            ObjectDesc::Sdf {
+                shape,
+                min,
+                max,
+                max_steps,
+                epsilon,
+                material,
+            } => {
+                self.key("shape", |v| v.sdf(shape));
+                self.bounds(*min, *max);
+                if *max_steps == 0 {
+                    self.report_at("max_steps", Problem::NotPositive);
+                }
+                self.positive("epsilon", *epsilon);
+                self.material_ref(material);
+            }
+            ObjectDesc::Curve {
+                points,
+                widths,
+                kind,
+                normals,
+                material,
+            } => {
+                if !points.iter().all(|p| p.is_finite()) {
+                    self.report_at("points", Problem::NotFinite);
+                }
+                if !widths.iter().all(|w| w.is_finite()) {
+                    self.report_at("widths", Problem::NotFinite);
+                } else if widths.iter().any(|&w| w <= 0.0) {
+                    self.report_at("widths", Problem::NotPositive);
+                }
+                match (kind, normals) {
+                    (CurveKindDesc::Ribbon, None) => {
+                        self.report_at("kind", Problem::MissingNormals)
+                    }
+                    (CurveKindDesc::Ribbon, Some(normals)) => {
+                        if !normals.iter().all(|n| n.is_finite()) {
+                            self.report_at("normals", Problem::NotFinite);
+                        } else if normals.contains(&Vec3::ZERO) {
+                            self.report_at("normals", Problem::ZeroVector);
+                        }
+                    }
+                    (_, Some(_)) => self.report_at("normals", Problem::UnexpectedNormals),
+                    (_, None) => {}
+                }
+                self.material_ref(material);
+            }
+            ObjectDesc::Hair {
+                path,
+                kind,
+                material,
+            } => {
+                let full_path = self.base_dir.join(path);
+                if !full_path.is_file() {
+                    self.report_at("path", Problem::MissingHairFile(full_path));
+                }
+                self.strand_kind(*kind);
+                self.material_ref(material);
+            }
+            ObjectDesc::Fur {
+                center,
+                radius,
+                count,
+                length,
+                width,
+                droop,
+                kind,
+                material,
+                ..
+            } => {
+                self.finite_vec("center", *center);
+                self.positive("radius", *radius);
+                if *count == 0 {
+                    self.report_at("count", Problem::NotPositive);
+                }
+                self.positive("length", *length);
+                self.positive("width", *width);
+                self.finite("droop", *droop);
+                self.strand_kind(*kind);
+                self.material_ref(material);
+            }
+            ObjectDesc::Group { objects, .. } => {
+                self.key("objects", |v| {
+                    for (i, object) in objects.iter().enumerate() {
+                        v.index(i, |v| v.object(object));
+                    }
+                });
+            }
+            ObjectDesc::Translate { offset, object } => {
+                self.finite_vec("offset", *offset);
+                self.key("object", |v| v.object(object))
+            }
+            ObjectDesc::RotateY { angle, object } => {
+                self.finite("angle", *angle);
+                self.key("object", |v| v.object(object))
+            }
+            ObjectDesc::Instance {
+                prototype,
+                offset,
+                rotate,
+                scale,
+                material,
+                materials,
+            } => {
+                if !self.desc.prototypes.contains_key(prototype) {
+                    self.report_at("prototype", Problem::UnknownPrototype(prototype.to_owned()));
+                }
+                self.finite_vec("offset", *offset);
+                self.finite_vec("rotate", *rotate);
+                if self.finite_vec("scale", *scale) && scale.cmpeq(Vec3::ZERO).any() {
+                    self.report_at("scale", Problem::ZeroScale);
+                }
+                if let Some(material) = material {
+                    self.material_ref(material);
+                    if !materials.is_empty() {
+                        self.report_at(
+                            "materials",
+                            Problem::Conflicting {
+                                a: "material",
+                                b: "materials",
+                            },
+                        );
+                    }
+                }
+
+                let mut originals: Vec<_> = materials.keys().collect();
+                originals.sort();
+                self.key("materials", |v| {
+                    for original in originals {
+                        for name in [original, &materials[original]] {
+                            if !v.desc.materials.contains_key(name) {
+                                v.report_at(original, Problem::UnknownMaterial(name.to_owned()));
+                            }
+                        }
+                    }
+                });
+            }
+            ObjectDesc::Union { left, right }
+            | ObjectDesc::Intersection { left, right }
+            | ObjectDesc::Difference { left, right } => {
+                self.key("left", |v| v.object(left));
+                self.key("right", |v| v.object(right))
+            }
+            ObjectDesc::ConstantMedium {
+                density,
+                albedo,
+                phase,
+                boundary,
+            } => {
+                self.positive("density", *density);
+                self.color_source("albedo", albedo);
+                if let Some(phase) = phase {
+                    self.key("phase", |v| v.phase(phase));
+                }
+                self.key("boundary", |v| v.object(boundary))
+            }
+            ObjectDesc::HeterogeneousMedium {
+                density,
+                albedo,
+                phase,
+                boundary,
+            } => {
+                self.key("density", |v| v.density(density));
+                self.color_source("albedo", albedo);
+                if let Some(phase) = phase {
+                    self.key("phase", |v| v.phase(phase));
+                }
+                self.key("boundary", |v| v.object(boundary))
+            }
+            ObjectDesc::HomogeneousMedium {
+                sigma_a,
+                sigma_s,
+                emission,
+                phase,
+                boundary,
+            } => {
+                // Anywhere but directly in `objects`, such as in a group or a prototype.
+                let top_level = matches!(
+                    &self.path[..],
+                    [KeySegment::Key(key), KeySegment::Index(_)] if key == "objects"
+                );
+                if !top_level {
+                    self.report(Problem::NestedMedium);
+                }
+                self.non_negative_vec("sigma_a", *sigma_a);
+                self.non_negative_vec("sigma_s", *sigma_s);
+                self.non_negative_vec("emission", *emission);
+                if let Some(phase) = phase {
+                    self.key("phase", |v| v.phase(phase));
+                }
+                self.key("boundary", |v| v.object(boundary))
+            }
+        }
+    }
+
+    fn phase(&mut self, phase: &PhaseDesc) {
+        match phase {
+            PhaseDesc::Isotropic => {}
+            PhaseDesc::HenyeyGreenstein { g } => self.asymmetry("g", *g),
+            PhaseDesc::DoubleHenyeyGreenstein {
+                forward,
+                backward,
+                weight,
+            } => {
+                self.asymmetry("forward", *forward);
+                self.asymmetry("backward", *backward);
+                self.unit_interval("weight", *weight);
+            }
+        }
+    }
+
+    fn asymmetry(&mut self, key: &str, g: Float) {
+        if self.finite(key, g) && !(-1.0..=1.0).contains(&g) {
+            self.report_at(key, Problem::InvalidAsymmetry);
+        }
+    }
+
+    fn density(&mut self, density: &DensityDesc) {
+        match density {
+            DensityDesc::Grid {
+                path,
+                resolution,
+                density,
+                min,
+                max,
+            } => {
+                let full_path = self.base_dir.join(path);
+                if !full_path.is_file() {
+                    self.report_at("path", Problem::MissingDensityFile(full_path));
+                }
+                if resolution.contains(&0) {
+                    self.report_at("resolution", Problem::NotPositive);
+                }
+                self.non_negative("density", *density);
+                self.bounds(*min, *max);
+            }
+            DensityDesc::Noise { density, scale } => {
+                self.non_negative("density", *density);
+                self.positive("scale", *scale);
+            }
+        }
+    }
+
+    fn heights(&mut self, heights: &HeightsDesc) {
+        match heights {
+            HeightsDesc::Image { path } => {
+                let full_path = self.base_dir.join(path);
+                if !full_path.is_file() {
+                    self.report_at("path", Problem::MissingHeightmapFile(full_path));
+                }
+            }
+            HeightsDesc::Noise { resolution, scale } => {
+                if resolution.iter().any(|&n| n < 2) {
+                    self.report_at("resolution", Problem::TooFewSamples);
+                }
+                self.positive("scale", *scale);
+            }
+        }
+    }
+
+    fn sdf(&mut self, shape: &SdfDesc) {
+        match shape {
+            SdfDesc::Sphere { radius } => self.positive("radius", *radius),
+            SdfDesc::Box { half_extents } => self.positive_vec("half_extents", *half_extents),
+            SdfDesc::RoundedBox {
+                half_extents,
+                radius,
+            } => {
+                self.positive_vec("half_extents", *half_extents);
+                if self.non_negative("radius", *radius) && *radius >= half_extents.min_element() {
+                    self.report_at(
+                        "radius",
+                        Problem::Degenerate {
+                            min: "radius",
+                            max: "half_extents",
+                        },
+                    );
+                }
+            }
+            SdfDesc::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                self.positive("major_radius", *major_radius);
+                self.positive("minor_radius", *minor_radius);
+            }
+            SdfDesc::Capsule { start, end, radius } => {
+                self.axis(*start, *end);
+                self.positive("radius", *radius);
+            }
+            SdfDesc::SmoothUnion { left, right, k } => {
+                self.key("left", |v| v.sdf(left));
+                self.key("right", |v| v.sdf(right));
+                self.positive("k", *k);
+            }
+            SdfDesc::Subtraction { left, right } => {
+                self.key("left", |v| v.sdf(left));
+                self.key("right", |v| v.sdf(right));
+            }
+            SdfDesc::Repeat { period, shape } => {
+                self.positive_vec("period", *period);
+                self.key("shape", |v| v.sdf(shape));
+            }
+            SdfDesc::Twist {
+                degrees_per_unit,
+                shape,
+            } => {
+                self.finite("degrees_per_unit", *degrees_per_unit);
+                self.key("shape", |v| v.sdf(shape));
+            }
+            SdfDesc::Translate { offset, shape } => {
+                self.finite_vec("offset", *offset);
+                self.key("shape", |v| v.sdf(shape));
+            }
+        }
+    }
+
+    /// Strands have no normals to turn into ribbons.
+    fn strand_kind(&mut self, kind: CurveKindDesc) {
+        if kind == CurveKindDesc::Ribbon {
+            self.report_at("kind", Problem::MissingNormals);
+        }
+    }
+
+    fn material_ref(&mut self, name: &str) {
+        if !self.desc.materials.contains_key(name) {
+            self.report_at("material", Problem::UnknownMaterial(name.to_owned()));
+        }
+    }
+
+    fn color_source(&mut self, key: &str, src: &ColorSource) {
+        match src {
+            ColorSource::Color(color) => {
+                self.finite_vec(key, *color);
+            }
+            ColorSource::Texture(name) => {
+                if !self.desc.textures.contains_key(name) {
+                    self.report_at(key, Problem::UnknownTexture(name.clone()));
+                }
+            }
+        }
+    }
+
+    fn radius(&mut self, radius: Float) {
+        if self.finite("radius", radius) && radius == 0.0 {
+            self.report_at("radius", Problem::ZeroRadius);
+        }
+    }
+
+    /// Checks the ends of the axis of a round shape.
+    fn axis(&mut self, start: Vec3, end: Vec3) {
+        let finite = self.finite_vec("start", start) & self.finite_vec("end", end);
+        if finite && start == end {
+            self.report_at(
+                "end",
+                Problem::Coincident {
+                    a: "start",
+                    b: "end",
+                },
+            );
+        }
+    }
+
+    fn sweep(&mut self, sweep: Float) {
+        if self.finite("sweep", sweep) && !(sweep > 0.0 && sweep <= 360.0) {
+            self.report_at("sweep", Problem::InvalidSweep);
+        }
+    }
+
+    /// Checks that `min < max` along every axis.
+    fn bounds(&mut self, min: Vec3, max: Vec3) {
+        let finite = self.finite_vec("min", min) & self.finite_vec("max", max);
+        if finite && !min.cmplt(max).all() {
+            self.report_at(
+                "max",
+                Problem::Degenerate {
+                    min: "min",
+                    max: "max",
+                },
+            );
+        }
+    }
+
+    fn normal(&mut self, normal: Vec3) {
+        if self.finite_vec("normal", normal) && normal == Vec3::ZERO {
+            self.report_at("normal", Problem::ZeroVector);
+        }
+    }
+
+    /// Checks that `min < max`, which rules out both empty and inverted ranges.
+    fn interval(
+        &mut self,
+        (min_key, min): (&'static str, Float),
+        (max_key, max): (&'static str, Float),
+    ) {
+        let finite = self.finite(min_key, min) & self.finite(max_key, max);
+        if finite && min >= max {
+            self.report_at(
+                max_key,
+                Problem::Degenerate {
+                    min: min_key,
+                    max: max_key,
+                },
+            );
+        }
+    }
+
+    fn finite(&mut self, key: &str, x: Float) -> bool {
+        let finite = x.is_finite();
+        if !finite {
+            self.report_at(key, Problem::NotFinite);
+        }
+        finite
+    }
+
+    fn finite_vec(&mut self, key: &str, v: Vec3) -> bool {
+        let finite = v.is_finite();
+        if !finite {
+            self.report_at(key, Problem::NotFinite);
+        }
+        finite
+    }
+
+    fn positive(&mut self, key: &str, x: Float) {
+        if self.finite(key, x) && x <= 0.0 {
+            self.report_at(key, Problem::NotPositive);
+        }
+    }
+
+    fn positive_vec(&mut self, key: &str, v: Vec3) {
+        if self.finite_vec(key, v) && v.min_element() <= 0.0 {
+            self.report_at(key, Problem::NotPositive);
+        }
+    }
+
+    fn fraction(&mut self, key: &str, x: Float) {
+        if self.finite(key, x) && !(x > 0.0 && x <= 1.0) {
+            self.report_at(key, Problem::NotFraction);
+        }
+    }
+
+    fn unit_interval(&mut self, key: &str, x: Float) {
+        if self.finite(key, x) && !(0.0..=1.0).contains(&x) {
+            self.report_at(key, Problem::NotUnitInterval);
+        }
+    }
+
+    fn non_negative(&mut self, key: &str, x: Float) -> bool {
+        let finite = self.finite(key, x);
+        if finite && x < 0.0 {
+            self.report_at(key, Problem::Negative);
+        }
+        finite && x >= 0.0
+    }
+
+    fn non_negative_vec(&mut self, key: &str, v: Vec3) {
+        if self.finite_vec(key, v) && v.min_element() < 0.0 {
+            self.report_at(key, Problem::Negative);
+        }
+    }
+
+    fn key<T>(&mut self, key: &str, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.path.push(KeySegment::Key(key.to_owned()));
+        let result = f(self);
+        self.path.pop();
+        result
+    }
+
+    fn index<T>(&mut self, index: usize, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.path.push(KeySegment::Index(index));
+        let result = f(self);
+        self.path.pop();
+        result
+    }
+
+    fn report(&mut self, problem: Problem) {
+        self.problems.push((KeyPath(self.path.clone()), problem));
+    }
+
+    fn report_at(&mut self, key: &str, problem: Problem) {
+        self.key(key, |v| v.report(problem));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The key paths and messages of the problems with a scene file.
+    fn problems(src: &str) -> Vec<String> {
+        let desc: SceneDesc = toml::from_str(src).unwrap();
+        validate(&desc, Path::new("."))
+            .into_iter()
+            .map(|(key, problem)| format!("{key}: {problem}"))
+            .collect()
+    }
+
+    const CAMERA: &str = "
+        [camera]
+        look_from = [0.0, 0.0, 0.0]
+        look_at = [0.0, 0.0, -1.0]
+        vfov = 40.0
+    ";
+
+    #[test]
+    fn render_settings_must_be_positive() {
+        let src = format!(
+            "{CAMERA}
+            [render]
+            image_width = 0
+            samples_per_pixel = 0
+            max_depth = -1"
+        );
+        assert_eq!(
+            problems(&src),
+            [
+                "render.image_width: value must be greater than zero",
+                "render.samples_per_pixel: value must be greater than zero",
+                "render.max_depth: value must be greater than zero",
+            ]
+        );
+
+        let src = format!(
+            "{CAMERA}
+            [render]
+            max_depth = 0"
+        );
+        assert_eq!(
+            problems(&src),
+            ["render.max_depth: value must be greater than zero"]
+        );
+        assert!(problems(CAMERA).is_empty());
+    }
+
+    #[test]
+    fn metal_fuzz_must_be_between_0_and_1() {
+        let metal = |fuzz| {
+            format!(
+                "{CAMERA}
+                [materials.steel]
+                type = \"metal\"
+                albedo = [0.8, 0.8, 0.8]
+                fuzz = {fuzz}"
+            )
+        };
+        for fuzz in ["0.0", "0.3", "1.0"] {
+            assert!(problems(&metal(fuzz)).is_empty(), "fuzz = {fuzz}");
+        }
+        for fuzz in ["-0.1", "1.5"] {
+            assert_eq!(
+                problems(&metal(fuzz)),
+                ["materials.steel.fuzz: value must be between 0 and 1"]
+            );
+        }
+        assert_eq!(
+            problems(&metal("nan")),
+            ["materials.steel.fuzz: value must be a finite number"]
+        );
+    }
+}
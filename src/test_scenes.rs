@@ -5,24 +5,35 @@ use rand::prelude::*;
 use crate::{
     camera::Camera,
     color,
+    float::{consts, vec3, Affine3, Float, Mat3, Vec3},
     hittable::{
-        sphere::{MovingSphere, Sphere},
-        BvhNode, ConstantMedium, GeometricBox, HittableList, RotateY, Translate, XYRect, XZRect,
-        YZRect,
+        sphere::{MovingSphere, PointCloud, Sphere, SphereCloud},
+        Bvh, Cone, ConstantMedium, Cylinder, GeometricBox, HeterogeneousMedium, Hittable,
+        HittableList, HomogeneousMedium, Instance, LightList, MaterialVariants, NoiseDensity,
+        Plane, Quad, RotateY, Translate, XYRect, XZRect, YZRect,
+    },
+    material::{
+        Anisotropic, Dielectric, DiffuseLight, HenyeyGreenstein, Lambertian, Material, Metal,
     },
-    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal},
     math,
     texture::{CheckerTexture, ImageTexture, NoiseTexture},
-    ASPECT_RATIO, TIME0, TIME1,
+    ASPECT_RATIO, MAX_DEPTH, TIME0, TIME1,
 };
 
 pub struct Scene {
     pub world: HittableList,
+    /// Emitters in `world` that diffuse surfaces aim rays at directly.
+    pub lights: LightList,
+    /// Media that every ray goes through, which aren't part of `world`.
+    pub media: Vec<HomogeneousMedium>,
     pub cam: Camera,
-    pub background_color: glam::DVec3,
+    pub background_color: Vec3,
     pub samples_per_pixel: u32,
     pub image_width: u32,
     pub image_height: u32,
+    pub max_depth: i32,
+    pub time0: Float,
+    pub time1: Float,
 }
 
 impl Scene {
@@ -31,15 +42,30 @@ impl Scene {
         let image_width = 400;
         Self {
             world,
+            lights: LightList::default(),
+            media: Vec::new(),
             cam,
             background_color: color::DEEP_SKY_BLUE,
             samples_per_pixel: 100,
             image_width,
-            image_height: (image_width as f64 / aspect_ratio) as u32,
+            image_height: (image_width as Float / aspect_ratio) as u32,
+            max_depth: MAX_DEPTH,
+            time0: TIME0,
+            time1: TIME1,
         }
     }
 
-    pub fn with_background_color(mut self, background_color: glam::DVec3) -> Self {
+    pub fn with_lights(mut self, lights: LightList) -> Self {
+        self.lights = lights;
+        self
+    }
+
+    pub fn with_media(mut self, media: Vec<HomogeneousMedium>) -> Self {
+        self.media = media;
+        self
+    }
+
+    pub fn with_background_color(mut self, background_color: Vec3) -> Self {
         self.background_color = background_color;
         self
     }
@@ -49,18 +75,29 @@ impl Scene {
         self
     }
 
-    pub fn with_image_width(mut self, width: u32, aspect_ratio: f64) -> Self {
+    pub fn with_image_width(mut self, width: u32, aspect_ratio: Float) -> Self {
         self.image_width = width;
-        self.image_height = (width as f64 / aspect_ratio) as u32;
+        self.image_height = (width as Float / aspect_ratio) as u32;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: i32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_time(mut self, time0: Float, time1: Float) -> Self {
+        self.time0 = time0;
+        self.time1 = time1;
         self
     }
 }
 
 #[allow(dead_code)]
 pub fn simple_scene() -> Scene {
-    let lookfrom = glam::DVec3::ZERO;
-    let lookat = glam::dvec3(0.0, 0.0, 1.0);
-    let vup = glam::dvec3(0.0, 1.0, 0.0);
+    let lookfrom = Vec3::ZERO;
+    let lookat = vec3(0.0, 0.0, 1.0);
+    let vup = vec3(0.0, 1.0, 0.0);
     let vfov = 40.0;
     let aperture = 0.1;
     let focus_dist = 1.0;
@@ -78,14 +115,14 @@ pub fn simple_scene() -> Scene {
 
     let mut world = HittableList::default();
     world.add(Rc::new(Sphere::new(
-        glam::dvec3(-0.5, 0.0, 1.0),
+        vec3(-0.5, 0.0, 1.0),
         0.1,
-        Rc::new(Lambertian::from_color(glam::dvec3(0.7, 0.3, 0.3))),
+        Rc::new(Lambertian::from_color(vec3(0.7, 0.3, 0.3))),
     )));
     world.add(Rc::new(Sphere::new(
-        glam::dvec3(0.5, 0.0, 1.0),
+        vec3(0.5, 0.0, 1.0),
         0.1,
-        Rc::new(Lambertian::from_color(glam::dvec3(0.3, 0.7, 0.3))),
+        Rc::new(Lambertian::from_color(vec3(0.3, 0.7, 0.3))),
     )));
 
     Scene::new(world, cam)
@@ -93,9 +130,9 @@ pub fn simple_scene() -> Scene {
 
 #[allow(dead_code)]
 pub fn random_scene() -> Scene {
-    let lookfrom = glam::dvec3(13.0, 2.0, 3.0);
-    let lookat = glam::dvec3(0.0, 0.0, 0.0);
-    let vup = glam::dvec3(0.0, 1.0, 0.0);
+    let lookfrom = vec3(13.0, 2.0, 3.0);
+    let lookat = vec3(0.0, 0.0, 0.0);
+    let vup = vec3(0.0, 1.0, 0.0);
     let vfov = 20.0;
     let aperture = 0.1;
     let dist_to_focus = 10.0;
@@ -113,28 +150,27 @@ pub fn random_scene() -> Scene {
     let mut world = HittableList::default();
     let mut rng = thread_rng();
 
-    let checker_texture =
-        CheckerTexture::from_colors(glam::dvec3(0.2, 0.3, 0.1), glam::dvec3(0.9, 0.9, 0.9));
+    let checker_texture = CheckerTexture::from_colors(vec3(0.2, 0.3, 0.1), vec3(0.9, 0.9, 0.9));
     let ground_mat = Rc::new(Lambertian::from_texture(Rc::new(checker_texture)));
     world.add(Rc::new(Sphere::new(
-        glam::dvec3(0.0, -1000.0, 0.0),
+        vec3(0.0, -1000.0, 0.0),
         1000.0,
         ground_mat,
     )));
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat = rng.gen::<f64>();
-            let center = glam::dvec3(
-                a as f64 + 0.9 * rng.gen::<f64>(),
+            let choose_mat = rng.gen::<Float>();
+            let center = vec3(
+                a as Float + 0.9 * rng.gen::<Float>(),
                 0.2,
-                b as f64 + 0.9 * rng.gen::<f64>(),
+                b as Float + 0.9 * rng.gen::<Float>(),
             );
-            if center.distance(glam::dvec3(4.0, 0.2, 0.0)) > 0.9 {
+            if center.distance(vec3(4.0, 0.2, 0.0)) > 0.9 {
                 let sphere_mat: Rc<dyn Material>;
                 if choose_mat < 0.8 {
                     // diffuse
-                    let center2 = center + glam::dvec3(0.0, rng.gen_range(0.0..0.5), 0.0);
-                    let albedo = rng.gen::<glam::DVec3>() * rng.gen::<glam::DVec3>();
+                    let center2 = center + vec3(0.0, rng.gen_range(0.0..0.5), 0.0);
+                    let albedo = rng.gen::<Vec3>() * rng.gen::<Vec3>();
                     sphere_mat = Rc::new(Lambertian::from_color(albedo));
                     world.add(Rc::new(MovingSphere::new(
                         center, center2, 0.0, 1.0, 0.2, sphere_mat,
@@ -155,22 +191,22 @@ pub fn random_scene() -> Scene {
     }
 
     let mat1 = Rc::new(Dielectric::new(1.5));
-    world.add(Rc::new(Sphere::new(glam::dvec3(0.0, 1.0, 0.0), 1.0, mat1)));
+    world.add(Rc::new(Sphere::new(vec3(0.0, 1.0, 0.0), 1.0, mat1)));
 
-    let mat2 = Rc::new(Lambertian::from_color(glam::dvec3(0.4, 0.2, 0.1)));
-    world.add(Rc::new(Sphere::new(glam::dvec3(-4.0, 1.0, 0.0), 1.0, mat2)));
+    let mat2 = Rc::new(Lambertian::from_color(vec3(0.4, 0.2, 0.1)));
+    world.add(Rc::new(Sphere::new(vec3(-4.0, 1.0, 0.0), 1.0, mat2)));
 
-    let mat3 = Rc::new(Metal::new(glam::dvec3(0.7, 0.6, 0.5), 0.0));
-    world.add(Rc::new(Sphere::new(glam::dvec3(4.0, 1.0, 0.0), 1.0, mat3)));
+    let mat3 = Rc::new(Metal::new(vec3(0.7, 0.6, 0.5), 0.0));
+    world.add(Rc::new(Sphere::new(vec3(4.0, 1.0, 0.0), 1.0, mat3)));
 
     Scene::new(world, cam)
 }
 
 #[allow(dead_code)]
 pub fn two_spheres() -> Scene {
-    let lookfrom = glam::dvec3(13.0, 2.0, 3.0);
-    let lookat = glam::dvec3(0.0, 0.0, 0.0);
-    let vup = glam::dvec3(0.0, 1.0, 0.0);
+    let lookfrom = vec3(13.0, 2.0, 3.0);
+    let lookat = vec3(0.0, 0.0, 0.0);
+    let vup = vec3(0.0, 1.0, 0.0);
     let vfov = 20.0;
     let aperture = 0.1;
     let dist_to_focus = 10.0;
@@ -189,17 +225,17 @@ pub fn two_spheres() -> Scene {
     let mut world = HittableList::default();
 
     let checker = Rc::new(CheckerTexture::from_colors(
-        glam::dvec3(0.2, 0.3, 0.1),
-        glam::dvec3(0.9, 0.9, 0.9),
+        vec3(0.2, 0.3, 0.1),
+        vec3(0.9, 0.9, 0.9),
     ));
 
     world.add(Rc::new(Sphere::new(
-        glam::dvec3(0.0, -10.0, 0.0),
+        vec3(0.0, -10.0, 0.0),
         10.0,
         Rc::new(Lambertian::from_texture(checker.clone())),
     )));
     world.add(Rc::new(Sphere::new(
-        glam::dvec3(0.0, 10.0, 0.0),
+        vec3(0.0, 10.0, 0.0),
         10.0,
         Rc::new(Lambertian::from_texture(checker)),
     )));
@@ -209,9 +245,9 @@ pub fn two_spheres() -> Scene {
 
 #[allow(dead_code)]
 pub fn two_perlin_spheres() -> Scene {
-    let lookfrom = glam::dvec3(13.0, 2.0, 3.0);
-    let lookat = glam::dvec3(0.0, 0.0, 0.0);
-    let vup = glam::dvec3(0.0, 1.0, 0.0);
+    let lookfrom = vec3(13.0, 2.0, 3.0);
+    let lookat = vec3(0.0, 0.0, 0.0);
+    let vup = vec3(0.0, 1.0, 0.0);
     let vfov = 20.0;
     let aperture = 0.1;
     let dist_to_focus = 10.0;
@@ -231,12 +267,12 @@ pub fn two_perlin_spheres() -> Scene {
 
     let pertext = Rc::new(NoiseTexture::new().with_scale(4.0));
     world.add(Rc::new(Sphere::new(
-        glam::dvec3(0.0, -1000.0, 0.0),
+        vec3(0.0, -1000.0, 0.0),
         1000.0,
         Rc::new(Lambertian::from_texture(pertext.clone())),
     )));
     world.add(Rc::new(Sphere::new(
-        glam::dvec3(0.0, 2.0, 0.0),
+        vec3(0.0, 2.0, 0.0),
         2.0,
         Rc::new(Lambertian::from_texture(pertext)),
     )));
@@ -246,9 +282,9 @@ pub fn two_perlin_spheres() -> Scene {
 
 #[allow(dead_code)]
 pub fn earth() -> Scene {
-    let lookfrom = glam::dvec3(13.0, 2.0, 3.0);
-    let lookat = glam::dvec3(0.0, 0.0, 0.0);
-    let vup = glam::dvec3(0.0, 1.0, 0.0);
+    let lookfrom = vec3(13.0, 2.0, 3.0);
+    let lookat = vec3(0.0, 0.0, 0.0);
+    let vup = vec3(0.0, 1.0, 0.0);
     let vfov = 20.0;
     let aperture = 0.1;
     let dist_to_focus = 10.0;
@@ -266,7 +302,7 @@ pub fn earth() -> Scene {
 
     let earth_texture = Rc::new(ImageTexture::new("earthmap.jpg"));
     let earth_mat = Rc::new(Lambertian::from_texture(earth_texture));
-    let globe = Rc::new(Sphere::new(glam::DVec3::ZERO, 2.0, earth_mat));
+    let globe = Rc::new(Sphere::new(Vec3::ZERO, 2.0, earth_mat));
     let world = HittableList::new(globe);
 
     Scene::new(world, cam)
@@ -281,9 +317,9 @@ pub fn black_screen() -> Scene {
 
 #[allow(dead_code)]
 pub fn simple_light() -> Scene {
-    let lookfrom = glam::dvec3(26.0, 3.0, 6.0);
-    let lookat = glam::dvec3(0.0, 2.0, 0.0);
-    let vup = glam::dvec3(0.0, 1.0, 0.0);
+    let lookfrom = vec3(26.0, 3.0, 6.0);
+    let lookat = vec3(0.0, 2.0, 0.0);
+    let vup = vec3(0.0, 1.0, 0.0);
     let vfov = 20.0;
     let aperture = 0.1;
     let dist_to_focus = 100.0;
@@ -303,19 +339,19 @@ pub fn simple_light() -> Scene {
 
     let pertext = Rc::new(NoiseTexture::new().with_scale(4.0));
     world.add(Rc::new(Sphere::new(
-        glam::dvec3(0.0, -1000.0, 0.0),
+        vec3(0.0, -1000.0, 0.0),
         1000.0,
         Rc::new(Lambertian::from_texture(pertext.clone())),
     )));
     world.add(Rc::new(Sphere::new(
-        glam::dvec3(0.0, 2.0, 0.0),
+        vec3(0.0, 2.0, 0.0),
         2.0,
         Rc::new(Lambertian::from_texture(pertext)),
     )));
 
-    let difflight = Rc::new(DiffuseLight::from_color(glam::DVec3::splat(4.0)));
+    let difflight = Rc::new(DiffuseLight::from_color(Vec3::splat(4.0)));
     let rect_light = XYRect::new(3.0, 5.0, 1.0, 3.0, -2.0, difflight.clone());
-    let sphere_light = Sphere::new(glam::dvec3(0.0, 7.0, 0.0), 2.0, difflight);
+    let sphere_light = Sphere::new(vec3(0.0, 7.0, 0.0), 2.0, difflight);
     world.add(Rc::new(rect_light));
     world.add(Rc::new(sphere_light));
 
@@ -330,9 +366,9 @@ pub fn cornel_box() -> Scene {
     let image_width = 600;
     let samples_per_pixel = 200;
     let background_color = color::BLACK;
-    let lookfrom = glam::dvec3(278.0, 278.0, -800.0);
-    let lookat = glam::dvec3(278.0, 278.0, 0.0);
-    let vup = glam::dvec3(0.0, 1.0, 0.0);
+    let lookfrom = vec3(278.0, 278.0, -800.0);
+    let lookat = vec3(278.0, 278.0, 0.0);
+    let vup = vec3(0.0, 1.0, 0.0);
     let vfov = 40.0;
     let aperture = 0.0;
     let dist_to_focus = 10.0;
@@ -350,18 +386,23 @@ pub fn cornel_box() -> Scene {
 
     let mut world = HittableList::default();
 
-    let red = Rc::new(Lambertian::from_color(glam::dvec3(0.65, 0.05, 0.05)));
-    let white = Rc::new(Lambertian::from_color(glam::DVec3::splat(0.73)));
-    let green = Rc::new(Lambertian::from_color(glam::dvec3(0.12, 0.45, 0.15)));
-    let light = Rc::new(DiffuseLight::from_color(glam::DVec3::splat(15.0)));
+    let red = Rc::new(Lambertian::from_color(vec3(0.65, 0.05, 0.05)));
+    let white = Rc::new(Lambertian::from_color(Vec3::splat(0.73)));
+    let green = Rc::new(Lambertian::from_color(vec3(0.12, 0.45, 0.15)));
+    let light = Rc::new(DiffuseLight::from_color(Vec3::splat(15.0)));
 
     world.add(Rc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green)));
 
     world.add(Rc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red)));
 
-    world.add(Rc::new(XZRect::new(
-        213.0, 343.0, 227.0, 332.0, 554.0, light,
-    )));
+    // The light faces down, into the box, and is sampled directly by the walls.
+    let light = Rc::new(Quad::new(
+        vec3(213.0, 554.0, 227.0),
+        vec3(130.0, 0.0, 0.0),
+        vec3(0.0, 0.0, 105.0),
+        light,
+    ));
+    world.add(light.clone());
 
     world.add(Rc::new(XZRect::new(
         0.0,
@@ -391,24 +432,21 @@ pub fn cornel_box() -> Scene {
     )));
 
     let box1 = Rc::new(GeometricBox::new(
-        glam::DVec3::ZERO,
-        glam::dvec3(165.0, 330.0, 165.0),
+        Vec3::ZERO,
+        vec3(165.0, 330.0, 165.0),
         white.clone(),
     ));
     let box1 = Rc::new(RotateY::new(box1, 15.0));
-    let box1 = Rc::new(Translate::new(box1, glam::dvec3(265.0, 0.0, 295.0)));
+    let box1 = Rc::new(Translate::new(box1, vec3(265.0, 0.0, 295.0)));
     world.add(box1);
 
-    let box2 = Rc::new(GeometricBox::new(
-        glam::DVec3::ZERO,
-        glam::DVec3::splat(165.0),
-        white,
-    ));
+    let box2 = Rc::new(GeometricBox::new(Vec3::ZERO, Vec3::splat(165.0), white));
     let box2 = Rc::new(RotateY::new(box2, -18.0));
-    let box2 = Rc::new(Translate::new(box2, glam::dvec3(130.0, 0.0, 65.0)));
+    let box2 = Rc::new(Translate::new(box2, vec3(130.0, 0.0, 65.0)));
     world.add(box2);
 
     Scene::new(world, cam)
+        .with_lights(LightList::new(light))
         .with_background_color(background_color)
         .with_image_width(image_width, aspect_ratio)
         .with_samples_per_pixel(samples_per_pixel)
@@ -420,9 +458,9 @@ pub fn cornel_smoke() -> Scene {
     let aspect_ratio = 1.0;
     let image_width = 600;
     let samples_per_pixel = 200;
-    let lookfrom = glam::dvec3(278.0, 278.0, -800.0);
-    let lookat = glam::dvec3(278.0, 278.0, 0.0);
-    let vup = glam::DVec3::Y;
+    let lookfrom = vec3(278.0, 278.0, -800.0);
+    let lookat = vec3(278.0, 278.0, 0.0);
+    let vup = Vec3::Y;
     let vfov = 40.0;
     let aperture = 0.0;
     let dist_to_focus = 10.0;
@@ -440,10 +478,10 @@ pub fn cornel_smoke() -> Scene {
 
     let mut world = HittableList::default();
 
-    let red = Rc::new(Lambertian::from_color(glam::dvec3(0.65, 0.05, 0.05)));
-    let white = Rc::new(Lambertian::from_color(glam::dvec3(0.73, 0.73, 0.73)));
-    let green = Rc::new(Lambertian::from_color(glam::dvec3(0.12, 0.45, 0.15)));
-    let light = Rc::new(DiffuseLight::from_color(glam::dvec3(7.0, 7.0, 7.0)));
+    let red = Rc::new(Lambertian::from_color(vec3(0.65, 0.05, 0.05)));
+    let white = Rc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
+    let green = Rc::new(Lambertian::from_color(vec3(0.12, 0.45, 0.15)));
+    let light = Rc::new(DiffuseLight::from_color(vec3(7.0, 7.0, 7.0)));
 
     world.add(Rc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green)));
     world.add(Rc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red)));
@@ -476,30 +514,30 @@ pub fn cornel_smoke() -> Scene {
     )));
 
     let box1 = Rc::new(GeometricBox::new(
-        glam::dvec3(0.0, 0.0, 0.0),
-        glam::dvec3(165.0, 330.0, 165.0),
+        vec3(0.0, 0.0, 0.0),
+        vec3(165.0, 330.0, 165.0),
         white.clone(),
     ));
     let box1 = Rc::new(RotateY::new(box1, 15.0));
-    let box1 = Rc::new(Translate::new(box1, glam::dvec3(265.0, 0.0, 295.0)));
+    let box1 = Rc::new(Translate::new(box1, vec3(265.0, 0.0, 295.0)));
 
     let box2 = Rc::new(GeometricBox::new(
-        glam::dvec3(0.0, 0.0, 0.0),
-        glam::dvec3(165.0, 165.0, 165.0),
+        vec3(0.0, 0.0, 0.0),
+        vec3(165.0, 165.0, 165.0),
         white,
     ));
     let box2 = Rc::new(RotateY::new(box2, -18.0));
-    let box2 = Rc::new(Translate::new(box2, glam::dvec3(130.0, 0.0, 65.0)));
+    let box2 = Rc::new(Translate::new(box2, vec3(130.0, 0.0, 65.0)));
 
     world.add(Rc::new(ConstantMedium::from_color(
         box1,
         0.01,
-        glam::dvec3(0.0, 0.0, 0.0),
+        vec3(0.0, 0.0, 0.0),
     )));
     world.add(Rc::new(ConstantMedium::from_color(
         box2,
         0.01,
-        glam::dvec3(1.0, 1.0, 1.0),
+        vec3(1.0, 1.0, 1.0),
     )));
 
     Scene::new(world, cam)
@@ -508,15 +546,98 @@ pub fn cornel_smoke() -> Scene {
         .with_samples_per_pixel(samples_per_pixel)
 }
 
+#[allow(dead_code)]
+pub fn cornel_clouds() -> Scene {
+    let background_color = color::BLACK;
+    let aspect_ratio = 1.0;
+    let image_width = 600;
+    let samples_per_pixel = 200;
+    let lookfrom = vec3(278.0, 278.0, -800.0);
+    let lookat = vec3(278.0, 278.0, 0.0);
+    let vup = Vec3::Y;
+    let vfov = 40.0;
+    let aperture = 0.0;
+    let dist_to_focus = 10.0;
+    let cam = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        vfov,
+        aspect_ratio,
+        aperture,
+        dist_to_focus,
+        TIME0,
+        TIME1,
+    );
+
+    let mut world = HittableList::default();
+
+    let red = Rc::new(Lambertian::from_color(vec3(0.65, 0.05, 0.05)));
+    let white = Rc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
+    let green = Rc::new(Lambertian::from_color(vec3(0.12, 0.45, 0.15)));
+    let light = Rc::new(DiffuseLight::from_color(vec3(7.0, 7.0, 7.0)));
+
+    world.add(Rc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green)));
+    world.add(Rc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red)));
+    world.add(Rc::new(XZRect::new(
+        113.0, 443.0, 127.0, 432.0, 554.0, light,
+    )));
+    world.add(Rc::new(XZRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+    world.add(Rc::new(XZRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        0.0,
+        white.clone(),
+    )));
+    world.add(Rc::new(XYRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+
+    let boundary = Rc::new(Sphere::new(
+        vec3(278.0, 278.0, 278.0),
+        200.0,
+        Rc::new(Dielectric::new(1.5)),
+    ));
+    let density = Rc::new(NoiseDensity::new(0.05).with_scale(0.01));
+    // Like real clouds, scatter most light onward rather than back.
+    let phase = Rc::new(HenyeyGreenstein::new(0.6));
+    world.add(Rc::new(HeterogeneousMedium::new(
+        boundary,
+        density,
+        Rc::new(Anisotropic::from_color(vec3(1.0, 1.0, 1.0), phase)),
+    )));
+
+    Scene::new(world, cam)
+        .with_background_color(background_color)
+        .with_image_width(image_width, aspect_ratio)
+        .with_samples_per_pixel(samples_per_pixel)
+}
+
+/// The box heights and sphere cluster are random, but seeded, so that benchmarks always see the
+/// same scene.
 #[allow(dead_code)]
 pub fn final_scene() -> Scene {
     let aspect_ratio = 1.0;
     let image_width = 800;
     let samples_per_pixel = 1000;
-    let background_color = glam::dvec3(0.0, 0.0, 0.0);
-    let lookfrom = glam::dvec3(478.0, 278.0, -600.0);
-    let lookat = glam::dvec3(278.0, 278.0, 0.0);
-    let vup = glam::DVec3::Y;
+    let background_color = vec3(0.0, 0.0, 0.0);
+    let lookfrom = vec3(478.0, 278.0, -600.0);
+    let lookat = vec3(278.0, 278.0, 0.0);
+    let vup = Vec3::Y;
     let vfov = 40.0;
     let aperture = 0.0;
     let dist_to_focus = 10.0;
@@ -532,25 +653,25 @@ pub fn final_scene() -> Scene {
         TIME1,
     );
 
-    let mut rng = thread_rng();
+    let mut rng = StdRng::seed_from_u64(2020);
 
-    let ground = Rc::new(Lambertian::from_color(glam::dvec3(0.48, 0.83, 0.53)));
+    let ground = Rc::new(Lambertian::from_color(vec3(0.48, 0.83, 0.53)));
 
     let mut boxes1 = HittableList::default();
     const BOXES_PER_SIDE: u32 = 20;
     for i in 0..BOXES_PER_SIDE {
         for j in 0..BOXES_PER_SIDE {
             let w = 100.0;
-            let x0 = -1000.0 + i as f64 * w;
-            let z0 = -1000.0 + j as f64 * w;
+            let x0 = -1000.0 + i as Float * w;
+            let z0 = -1000.0 + j as Float * w;
             let y0 = 0.0;
             let x1 = x0 + w;
             let y1 = rng.gen_range(1.0..101.0);
             let z1 = z0 + w;
 
             boxes1.add(Rc::new(GeometricBox::new(
-                glam::dvec3(x0, y0, z0),
-                glam::dvec3(x1, y1, z1),
+                vec3(x0, y0, z0),
+                vec3(x1, y1, z1),
                 ground.clone(),
             )));
         }
@@ -558,16 +679,16 @@ pub fn final_scene() -> Scene {
 
     let mut world = HittableList::default();
 
-    world.add(Rc::new(BvhNode::from_hittable_list(boxes1, TIME0, TIME1)));
+    world.add(Rc::new(Bvh::from_hittable_list(boxes1, TIME0, TIME1)));
 
-    let light = Rc::new(DiffuseLight::from_color(glam::dvec3(7.0, 7.0, 7.0)));
+    let light = Rc::new(DiffuseLight::from_color(vec3(7.0, 7.0, 7.0)));
     world.add(Rc::new(XZRect::new(
         123.0, 423.0, 147.0, 412.0, 554.0, light,
     )));
 
-    let center1 = glam::dvec3(400.0, 400.0, 200.0);
-    let center2 = center1 + glam::dvec3(30.0, 0.0, 0.0);
-    let moving_sphere_material = Rc::new(Lambertian::from_color(glam::dvec3(0.7, 0.3, 0.1)));
+    let center1 = vec3(400.0, 400.0, 200.0);
+    let center2 = center1 + vec3(30.0, 0.0, 0.0);
+    let moving_sphere_material = Rc::new(Lambertian::from_color(vec3(0.7, 0.3, 0.1)));
     world.add(Rc::new(MovingSphere::new(
         center1,
         center2,
@@ -578,18 +699,18 @@ pub fn final_scene() -> Scene {
     )));
 
     world.add(Rc::new(Sphere::new(
-        glam::dvec3(260.0, 150.0, 45.0),
+        vec3(260.0, 150.0, 45.0),
         50.0,
         Rc::new(Dielectric::new(1.5)),
     )));
     world.add(Rc::new(Sphere::new(
-        glam::dvec3(0.0, 150.0, 145.0),
+        vec3(0.0, 150.0, 145.0),
         50.0,
-        Rc::new(Metal::new(glam::dvec3(0.8, 0.8, 0.9), 1.0)),
+        Rc::new(Metal::new(vec3(0.8, 0.8, 0.9), 1.0)),
     )));
 
     let boundary = Rc::new(Sphere::new(
-        glam::dvec3(360.0, 150.0, 145.0),
+        vec3(360.0, 150.0, 145.0),
         70.0,
         Rc::new(Dielectric::new(1.5)),
     ));
@@ -597,51 +718,45 @@ pub fn final_scene() -> Scene {
     world.add(Rc::new(ConstantMedium::from_color(
         boundary,
         0.2,
-        glam::dvec3(0.2, 0.4, 0.9),
+        vec3(0.2, 0.4, 0.9),
     )));
     let boundary = Rc::new(Sphere::new(
-        glam::dvec3(0.0, 0.0, 0.0),
+        vec3(0.0, 0.0, 0.0),
         5000.0,
         Rc::new(Dielectric::new(1.5)),
     ));
     world.add(Rc::new(ConstantMedium::from_color(
         boundary,
         0.0001,
-        glam::dvec3(1.0, 1.0, 1.0),
+        vec3(1.0, 1.0, 1.0),
     )));
 
     let emat = Rc::new(Lambertian::from_texture(Rc::new(ImageTexture::new(
         "earthmap.jpg",
     ))));
-    world.add(Rc::new(Sphere::new(
-        glam::dvec3(400.0, 200.0, 400.0),
-        100.0,
-        emat,
-    )));
+    world.add(Rc::new(Sphere::new(vec3(400.0, 200.0, 400.0), 100.0, emat)));
     let pertext = Rc::new(NoiseTexture::new().with_scale(0.1));
     world.add(Rc::new(Sphere::new(
-        glam::dvec3(220.0, 280.0, 300.0),
+        vec3(220.0, 280.0, 300.0),
         80.0,
         Rc::new(Lambertian::from_texture(pertext)),
     )));
 
-    let mut boxes2 = HittableList::default();
-    let white = Rc::new(Lambertian::from_color(glam::dvec3(0.73, 0.73, 0.73)));
-    const NS: u32 = 1000;
-    for _ in 0..NS {
-        boxes2.add(Rc::new(Sphere::new(
-            math::random_range_vec(0.0, 165.0),
-            10.0,
-            white.clone(),
-        )));
-    }
+    let white = Rc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
+    const NS: usize = 1000;
+    let points = PointCloud {
+        centers: (0..NS)
+            .map(|_| vec3(rng.gen(), rng.gen(), rng.gen()) * 165.0)
+            .collect(),
+        ..Default::default()
+    };
 
     world.add(Rc::new(Translate::new(
         Rc::new(RotateY::new(
-            Rc::new(BvhNode::from_hittable_list(boxes2, 0.0, 1.0)),
+            Rc::new(SphereCloud::new(&points, 10.0, white)),
             15.0,
         )),
-        glam::dvec3(-100.0, 270.0, 395.0),
+        vec3(-100.0, 270.0, 395.0),
     )));
 
     Scene::new(world, cam)
@@ -649,3 +764,87 @@ pub fn final_scene() -> Scene {
         .with_image_width(image_width, aspect_ratio)
         .with_samples_per_pixel(samples_per_pixel)
 }
+
+/// 10,000 trees, all instances of one prototype, with leaves in a handful of colors.
+#[allow(dead_code)]
+pub fn forest() -> Scene {
+    let cam = Camera::new(
+        vec3(0.0, 12.0, -110.0),
+        vec3(0.0, 0.0, -60.0),
+        vec3(0.0, 1.0, 0.0),
+        40.0,
+        ASPECT_RATIO,
+        0.0,
+        10.0,
+        TIME0,
+        TIME1,
+    );
+    let mut world = HittableList::default();
+    let mut rng = thread_rng();
+
+    let ground = Rc::new(Lambertian::from_color(vec3(0.35, 0.3, 0.2)));
+    world.add(Rc::new(Plane::new(Vec3::ZERO, Vec3::Y, ground)));
+
+    let bark: Rc<dyn Material> = Rc::new(Lambertian::from_color(vec3(0.3, 0.2, 0.1)));
+    let leaves: Rc<dyn Material> = Rc::new(Lambertian::from_color(vec3(0.1, 0.4, 0.1)));
+    let mut tree = HittableList::default();
+    tree.add(Rc::new(Cylinder::new(
+        Vec3::ZERO,
+        vec3(0.0, 1.0, 0.0),
+        0.12,
+        bark.clone(),
+    )));
+    tree.add(Rc::new(Cone::new(
+        vec3(0.0, 0.8, 0.0),
+        vec3(0.0, 2.6, 0.0),
+        0.9,
+        0.0,
+        leaves.clone(),
+    )));
+    tree.add(Rc::new(Cone::new(
+        vec3(0.0, 1.8, 0.0),
+        vec3(0.0, 3.4, 0.0),
+        0.6,
+        0.0,
+        leaves.clone(),
+    )));
+    let tree: Rc<dyn Hittable> = Rc::new(Bvh::from_hittable_list(tree, TIME0, TIME1));
+
+    // Instances share these tables, so each one costs the same whatever its colors.
+    let variants: Vec<_> = [
+        vec3(0.1, 0.4, 0.1),
+        vec3(0.2, 0.5, 0.1),
+        vec3(0.05, 0.3, 0.15),
+        vec3(0.6, 0.4, 0.05),
+        vec3(0.6, 0.15, 0.05),
+    ]
+    .into_iter()
+    .map(|color| {
+        Rc::new(
+            MaterialVariants::new().with(leaves.clone(), Rc::new(Lambertian::from_color(color))),
+        )
+    })
+    .collect();
+
+    let mut trees = HittableList::default();
+    for x in -50..50 {
+        for z in -50..50 {
+            let offset = vec3(
+                2.0 * (x as Float + rng.gen::<Float>()),
+                0.0,
+                2.0 * (z as Float + rng.gen::<Float>()),
+            );
+            let object_to_world = Affine3::from_mat3_translation(
+                Mat3::from_rotation_y(rng.gen_range(0.0..consts::TAU)) * rng.gen_range(0.7..1.3),
+                offset,
+            );
+            let leaves = variants.choose(&mut rng).unwrap().clone();
+            trees.add(Rc::new(
+                Instance::new(tree.clone(), object_to_world).with_variants(leaves),
+            ));
+        }
+    }
+    world.add(Rc::new(Bvh::from_hittable_list(trees, TIME0, TIME1)));
+
+    Scene::new(world, cam).with_background_color(vec3(0.7, 0.8, 1.0))
+}
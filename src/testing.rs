@@ -0,0 +1,54 @@
+//! Helpers shared by the unit tests.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+thread_local! {
+    static RETAINED: Cell<isize> = const { Cell::new(0) };
+}
+
+/// The system allocator, counting the bytes each thread holds on to, so that a test can bound
+/// the memory of what it builds however many other tests run alongside it.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // The counter may already be gone while a thread shuts down.
+        let _ = RETAINED.try_with(|retained| retained.set(retained.get() + layout.size() as isize));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = RETAINED.try_with(|retained| retained.set(retained.get() - layout.size() as isize));
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Calls `f`, returning its result along with how many bytes it left allocated on this thread.
+pub fn retained<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = RETAINED.with(Cell::get);
+    let result = f();
+    let after = RETAINED.with(Cell::get);
+    (result, (after - before).max(0) as usize)
+}
+
+/// The fastest of `runs` calls to `f`, which is less disturbed by whatever else the machine is
+/// doing than the mean.
+pub fn fastest<T>(runs: usize, mut f: impl FnMut() -> T) -> (T, Duration) {
+    let mut best = None;
+    for _ in 0..runs {
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+        if best.as_ref().is_none_or(|(_, time)| elapsed < *time) {
+            best = Some((result, elapsed));
+        }
+    }
+    best.expect("at least one run")
+}
@@ -1,5 +1,6 @@
 use std::rc::Rc;
 
+use crate::float::{Float, Vec3};
 use crate::texture::{SolidColor, Texture};
 
 pub struct CheckerTexture {
@@ -8,7 +9,6 @@ pub struct CheckerTexture {
 }
 
 impl CheckerTexture {
-    #[allow(dead_code)]
     pub fn new(odd_texture: Rc<dyn Texture>, even_texture: Rc<dyn Texture>) -> Self {
         Self {
             odd_texture,
@@ -16,7 +16,7 @@ impl CheckerTexture {
         }
     }
 
-    pub fn from_colors(a: glam::DVec3, b: glam::DVec3) -> Self {
+    pub fn from_colors(a: Vec3, b: Vec3) -> Self {
         Self {
             odd_texture: Rc::new(SolidColor::from_color(a)),
             even_texture: Rc::new(SolidColor::from_color(b)),
@@ -25,7 +25,7 @@ impl CheckerTexture {
 }
 
 impl Texture for CheckerTexture {
-    fn value(&self, u: f64, v: f64, p: glam::DVec3) -> glam::DVec3 {
+    fn value(&self, u: Float, v: Float, p: Vec3) -> Vec3 {
         let sines = (p.x * 10.0).sin() * (p.y * 10.0).sin() * (p.z * 10.0).sin();
         if sines < 0.0 {
             self.odd_texture.value(u, v, p)
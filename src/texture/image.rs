@@ -1,7 +1,17 @@
 use stb_image::image::{Image, LoadResult};
 
+use crate::float::{vec3, Float, Vec3};
 use crate::texture::Texture;
 
+/// Reads an image file with 8 bits per channel.
+pub fn load_image(filename: &str) -> Result<Image<u8>, String> {
+    match stb_image::image::load(filename) {
+        LoadResult::ImageU8(image) => Ok(image),
+        LoadResult::Error(err) => Err(err),
+        LoadResult::ImageF32(_) => Err("Images with float-based colors unimplemented".to_owned()),
+    }
+}
+
 pub struct ImageTexture {
     image: Option<Image<u8>>,
     bytes_per_scanline: usize,
@@ -10,26 +20,20 @@ pub struct ImageTexture {
 impl ImageTexture {
     pub const BYTES_PER_PIXEL: usize = 3;
 
+    /// Loads `filename`, falling back to an empty (cyan) texture if it can't be read.
     pub fn new(filename: &str) -> Self {
-        let image = stb_image::image::load(filename);
-        let image = match image {
-            LoadResult::ImageU8(image) => image,
-            LoadResult::Error(err) => {
-                eprintln!("ERROR: Unable to load image '{}': {}", filename, err);
-                return Self::empty();
-            }
-            LoadResult::ImageF32(_) => {
-                eprintln!(
-                    "ERROR: Unable to load image '{}': Images with float-based colors unimplemented", filename
-                );
-                return Self::empty();
-            }
-        };
+        Self::load(filename).unwrap_or_else(|err| {
+            eprintln!("ERROR: Unable to load image '{}': {}", filename, err);
+            Self::empty()
+        })
+    }
 
-        Self {
+    pub fn load(filename: &str) -> Result<Self, String> {
+        let image = load_image(filename)?;
+        Ok(Self {
             bytes_per_scanline: Self::BYTES_PER_PIXEL * image.width,
             image: Some(image),
-        }
+        })
     }
 
     pub fn empty() -> Self {
@@ -41,28 +45,28 @@ impl ImageTexture {
 }
 
 impl Texture for ImageTexture {
-    fn value(&self, u: f64, v: f64, _p: glam::DVec3) -> glam::DVec3 {
+    fn value(&self, u: Float, v: Float, _p: Vec3) -> Vec3 {
         let image = match &self.image {
             Some(image) => image,
-            None => return glam::dvec3(0.0, 1.0, 1.0),
+            None => return vec3(0.0, 1.0, 1.0),
         };
 
         let u = u.clamp(0.0, 1.0);
         let v = 1.0 - v.clamp(0.0, 1.0); // flip Y coordinates
 
-        let i = (u * image.width as f64) as usize;
-        let j = (v * image.height as f64) as usize;
+        let i = (u * image.width as Float) as usize;
+        let j = (v * image.height as Float) as usize;
 
         let i = i.min(image.width);
         let j = j.min(image.height);
 
-        const COLOR_SCALE: f64 = 1.0 / 255.0;
+        const COLOR_SCALE: Float = 1.0 / 255.0;
         let pixel = j * self.bytes_per_scanline + i * Self::BYTES_PER_PIXEL;
 
-        glam::dvec3(
-            image.data[pixel] as f64 * COLOR_SCALE,
-            image.data[pixel + 1] as f64 * COLOR_SCALE,
-            image.data[pixel + 2] as f64 * COLOR_SCALE,
+        vec3(
+            image.data[pixel] as Float * COLOR_SCALE,
+            image.data[pixel + 1] as Float * COLOR_SCALE,
+            image.data[pixel + 2] as Float * COLOR_SCALE,
         )
     }
 }
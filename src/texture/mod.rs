@@ -3,11 +3,13 @@ mod image;
 mod noise;
 mod solid_color;
 
+use crate::float::{Float, Vec3};
+
 pub trait Texture {
-    fn value(&self, u: f64, v: f64, p: glam::DVec3) -> glam::DVec3;
+    fn value(&self, u: Float, v: Float, p: Vec3) -> Vec3;
 }
 
 pub use checker::CheckerTexture;
-pub use image::ImageTexture;
+pub use image::{load_image, ImageTexture};
 pub use noise::NoiseTexture;
 pub use solid_color::SolidColor;
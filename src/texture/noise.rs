@@ -1,10 +1,14 @@
-use crate::{color, noise::Perlin};
+use crate::{
+    color,
+    float::{Float, Vec3},
+    noise::Perlin,
+};
 
 use crate::texture::Texture;
 
 pub struct NoiseTexture {
     pub noise: Perlin,
-    pub scale: f64,
+    pub scale: Float,
 }
 
 impl NoiseTexture {
@@ -15,14 +19,14 @@ impl NoiseTexture {
         }
     }
 
-    pub fn with_scale(mut self, scale: f64) -> Self {
+    pub fn with_scale(mut self, scale: Float) -> Self {
         self.scale = scale;
         self
     }
 }
 
 impl Texture for NoiseTexture {
-    fn value(&self, _u: f64, _v: f64, p: glam::DVec3) -> glam::DVec3 {
-        color::WHITE * 0.5 * (1.0 + f64::sin(self.scale * p.z + 10.0 * self.noise.turb(p, 7)))
+    fn value(&self, _u: Float, _v: Float, p: Vec3) -> Vec3 {
+        color::WHITE * 0.5 * (1.0 + Float::sin(self.scale * p.z + 10.0 * self.noise.turb(p, 7)))
     }
 }
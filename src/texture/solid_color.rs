@@ -1,24 +1,25 @@
+use crate::float::{vec3, Float, Vec3};
 use crate::texture::Texture;
 
 pub struct SolidColor {
-    color_value: glam::DVec3,
+    color_value: Vec3,
 }
 
 impl SolidColor {
     #[allow(dead_code)]
-    pub fn new(r: f64, g: f64, b: f64) -> SolidColor {
+    pub fn new(r: Float, g: Float, b: Float) -> SolidColor {
         SolidColor {
-            color_value: glam::dvec3(r, g, b),
+            color_value: vec3(r, g, b),
         }
     }
 
-    pub fn from_color(color_value: glam::DVec3) -> Self {
+    pub fn from_color(color_value: Vec3) -> Self {
         Self { color_value }
     }
 }
 
 impl Texture for SolidColor {
-    fn value(&self, _u: f64, _v: f64, _p: glam::DVec3) -> glam::DVec3 {
+    fn value(&self, _u: Float, _v: Float, _p: Vec3) -> Vec3 {
         self.color_value
     }
 }